@@ -0,0 +1,85 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use egui::{epaint::Primitive, pos2, vec2, Context, FullOutput, Mesh, RawInput, Rect};
+use egui_d3d11::bench::{process_deltas, BufferPool, GpuMesh, TextureAllocator, WarpTarget};
+use windows::Win32::Graphics::Direct3D11::ID3D11Device;
+
+/// A handful of busy windows, roughly what a cheat menu or a debug overlay draws.
+fn frame(ctx: &Context) -> FullOutput {
+    let input = RawInput {
+        screen_rect: Some(Rect::from_min_size(pos2(0., 0.), vec2(1920., 1080.))),
+        ..Default::default()
+    };
+
+    ctx.run(input, |ctx| {
+        for i in 0..8 {
+            egui::Window::new(format!("Window {i}")).show(ctx, |ui| {
+                for j in 0..20 {
+                    ui.label(format!("Label number {j}"));
+                }
+                ui.add(egui::Slider::new(&mut 0.5f32, 0.0..=1.0));
+                ui.checkbox(&mut true, "Checkbox");
+            });
+        }
+    })
+}
+
+fn meshes(ctx: &Context, output: FullOutput) -> Vec<(Mesh, Rect)> {
+    ctx.tessellate(output.shapes)
+        .into_iter()
+        .filter_map(|prim| match prim.primitive {
+            Primitive::Mesh(mesh) => Some((mesh, prim.clip_rect)),
+            Primitive::Callback(_) => None,
+        })
+        .collect()
+}
+
+fn hot_path(c: &mut Criterion) {
+    let ctx = Context::default();
+    let first = frame(&ctx);
+    let textures = first.textures_delta.clone();
+    let meshes = meshes(&ctx, frame(&ctx));
+
+    c.bench_function("from_mesh", |b| {
+        b.iter_batched(
+            || meshes.clone(),
+            |meshes| {
+                meshes
+                    .into_iter()
+                    .filter_map(|(mesh, clip)| GpuMesh::from_mesh(mesh, clip, 1.))
+                    .count()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let target = WarpTarget::new(1920, 1080);
+    let device: ID3D11Device = unsafe { target.swap.GetDevice() }.unwrap();
+
+    let gpu_meshes = meshes
+        .iter()
+        .cloned()
+        .filter_map(|(mesh, clip)| GpuMesh::from_mesh(mesh, clip, 1.))
+        .collect::<Vec<_>>();
+
+    let mut pool = BufferPool::default();
+    let mut ranges = vec![];
+    c.bench_function("buffer_upload", |b| {
+        b.iter(|| {
+            pool.upload(&device, &target.context, &gpu_meshes, &mut ranges)
+                .unwrap()
+        })
+    });
+
+    c.bench_function("texture_delta", |b| {
+        b.iter_batched(
+            || (TextureAllocator::default(), textures.clone()),
+            |(mut alloc, delta)| {
+                process_deltas(&mut alloc, &device, &target.context, delta).unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, hot_path);
+criterion_main!(benches);
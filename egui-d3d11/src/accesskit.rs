@@ -0,0 +1,71 @@
+use accesskit_windows::{Adapter, UiaInitMarker};
+use egui::{
+    accesskit::{ActionHandler, ActionRequest, TreeUpdate},
+    Event,
+};
+use std::sync::{Arc, Mutex};
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+
+#[derive(Default, Clone)]
+struct ActionQueue(Arc<Mutex<Vec<ActionRequest>>>);
+
+impl ActionHandler for ActionQueue {
+    fn do_action(&self, request: ActionRequest) {
+        if let Ok(mut queue) = self.0.lock() {
+            queue.push(request);
+        }
+    }
+}
+
+/// Exposes egui's accessibility tree to UI Automation through the hooked window.
+pub struct AccessKitAdapter {
+    hwnd: HWND,
+    adapter: Option<Adapter>,
+    actions: ActionQueue,
+    uia: UiaInitMarker,
+}
+
+impl AccessKitAdapter {
+    /// Has to be called outside of `WM_GETOBJECT`, forces UI Automation to initialize.
+    pub fn new(hwnd: HWND) -> Self {
+        Self {
+            hwnd,
+            adapter: None,
+            actions: ActionQueue::default(),
+            uia: UiaInitMarker::new(),
+        }
+    }
+
+    /// Pushes the tree produced by the last frame, the adapter is created with the first one.
+    pub fn update(&mut self, update: TreeUpdate) {
+        match &self.adapter {
+            Some(adapter) => adapter.update(update).raise(),
+            None => {
+                self.adapter = Some(Adapter::new(
+                    accesskit_windows::HWND(self.hwnd.0),
+                    update,
+                    Box::new(self.actions.clone()),
+                    self.uia,
+                ));
+            }
+        }
+    }
+
+    /// Actions requested by screen readers since the last frame, as egui events.
+    pub fn take_events(&self) -> Vec<Event> {
+        match self.actions.0.lock() {
+            Ok(mut queue) => queue.drain(..).map(Event::AccessKitActionRequest).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Returns `None` until the first frame was drawn, the message should be passed on then.
+    pub fn handle_wm_getobject(&self, wparam: WPARAM, lparam: LPARAM) -> Option<LRESULT> {
+        let result = self.adapter.as_ref()?.handle_wm_getobject(
+            accesskit_windows::WPARAM(wparam.0),
+            accesskit_windows::LPARAM(lparam.0),
+        )?;
+
+        Some(LRESULT(result.into().0))
+    }
+}
@@ -1,109 +1,231 @@
+#[cfg(feature = "accesskit")]
+use crate::accesskit::AccessKitAdapter;
+#[cfg(feature = "d3d12")]
+use crate::d3d12::D3D12Bridge;
+#[cfg(feature = "frame-debug")]
+use crate::frame_debug::FrameDebug;
+#[cfg(feature = "persistence")]
+use crate::persistence::Persistence;
+#[cfg(feature = "settings")]
+use crate::settings::OverlaySettings;
+#[cfg(feature = "replay")]
+use crate::replay::{RecordedFrame, Recorder};
 use crate::{
     backup::BackupState,
-    input::{InputCollector, InputResult},
-    mesh::{create_index_buffer, create_vertex_buffer, GpuMesh, GpuVertex},
+    capabilities::Capabilities,
+    capture::{read_texture, read_texture_region, CapturedFrame},
+    compat::{self, UiOutput},
+    config::{
+        AddressMode, BackupMode, BufferFailure, ColorBlending, Config, KeyPassthrough,
+        LineQuality, ScreenSizeSource, ScreenTransform, SubmitPoint, TextureSampling,
+        WatchdogAction,
+    },
+    constants::{ConstantBuffer, ShaderConstants},
+    dds::DdsImage,
+    depth::DepthTarget,
+    error::{Error, ErrorContext, InitError},
+    format::{channel_bits, is_supported_backbuffer},
+    hdr::ColorSpace,
+    hotkey::{HotkeyId, Hotkeys},
+    input::{
+        scale_input, transform_input, InputCategory, InputCollector, InputMessage, InputQueue,
+        InputResult,
+    },
+    layer::{draw_hook, DrawHookId, DrawHooks, LayerId, Layers},
+    lock::DefaultRawMutex,
+    mesh::{
+        batch_instances, scissor_rect, BufferPool, BufferStats, FrameScratch, GpuMesh,
+        MeshInstances,
+    },
+    mirror::BackbufferMirror,
+    pipeline::{Pass, Pipeline},
+    platform::{paint_software_cursor, PlatformWorker},
+    scaled::ScaledTarget,
+    screenshot::RegionCapture,
     shader::CompiledShaders,
-    texture::TextureAllocator,
+    target::{FrameTarget, RenderTargets},
+    texture::{
+        max_texture_side, DeferredUploads, FontAtlasStats, GpuMemoryUsage, SamplerOverride,
+        TextureAllocator,
+    },
+    timings::{FrameClock, FrameStage, FrameTimings, StageTimer, TimingHistory},
+    watchdog::{lock_timeout, Watched},
+};
+#[cfg(feature = "dcomp")]
+use crate::{config::AlphaMode, dcomp::CompositionLayer};
+use egui::{
+    ecolor,
+    epaint::{ClippedPrimitive, ClippedShape, Fonts, TessellationOptions},
+    Color32, Context, Key, Modifiers, Pos2, RawInput, Rect, Rgba, Shape, Stroke, TextureId,
+    TextureOptions, TexturesDelta, Vec2,
 };
-use clipboard::{windows_clipboard::WindowsClipboardContext, ClipboardProvider};
-use egui::{epaint::Primitive, Context};
 use once_cell::sync::OnceCell;
-use std::{mem::size_of, ops::DerefMut};
+#[cfg(feature = "replay")]
+use std::collections::VecDeque;
+use std::{
+    ffi::c_void,
+    mem::size_of,
+    ops::DerefMut,
+    panic::{catch_unwind, AssertUnwindSafe, Location},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+#[cfg(feature = "d3d12")]
+use windows::Win32::Graphics::Direct3D12::ID3D12CommandQueue;
+#[cfg(feature = "accesskit")]
+use windows::Win32::UI::WindowsAndMessaging::WM_GETOBJECT;
 use windows::{
-    core::HRESULT,
+    core::{Vtable, HRESULT},
     Win32::{
-        Foundation::{HWND, LPARAM, RECT, WPARAM},
+        Foundation::{BOOL, HWND, LPARAM, LRESULT, RECT, WPARAM},
         Graphics::{
-            Direct3D::D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
             Direct3D11::{
-                ID3D11BlendState, ID3D11Device, ID3D11DeviceContext, ID3D11InputLayout,
-                ID3D11RasterizerState, ID3D11RenderTargetView, ID3D11SamplerState, ID3D11Texture2D,
-                D3D11_APPEND_ALIGNED_ELEMENT, D3D11_BLEND_DESC, D3D11_BLEND_INV_SRC_ALPHA,
-                D3D11_BLEND_ONE, D3D11_BLEND_OP_ADD, D3D11_BLEND_SRC_ALPHA,
-                D3D11_COLOR_WRITE_ENABLE_ALL, D3D11_COMPARISON_ALWAYS, D3D11_CULL_NONE,
-                D3D11_FILL_SOLID, D3D11_FILTER_MIN_MAG_MIP_LINEAR, D3D11_INPUT_ELEMENT_DESC,
-                D3D11_INPUT_PER_VERTEX_DATA, D3D11_RASTERIZER_DESC, D3D11_RENDER_TARGET_BLEND_DESC,
-                D3D11_SAMPLER_DESC, D3D11_TEXTURE_ADDRESS_BORDER, D3D11_VIEWPORT,
+                ID3D11Device, ID3D11DeviceContext, ID3D11RenderTargetView,
+                ID3D11ShaderResourceView, ID3D11Texture2D, D3D11_TEXTURE2D_DESC,
             },
             Dxgi::{
-                Common::{
-                    DXGI_FORMAT_R32G32B32A32_FLOAT, DXGI_FORMAT_R32G32_FLOAT, DXGI_FORMAT_R32_UINT,
-                },
-                IDXGISwapChain, DXGI_SWAP_CHAIN_DESC,
+                Common::DXGI_COLOR_SPACE_TYPE, IDXGISwapChain, DXGI_OUTPUT_DESC,
+                DXGI_SWAP_CHAIN_DESC, DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT,
             },
         },
-        UI::WindowsAndMessaging::GetClientRect,
+        UI::WindowsAndMessaging::{
+            CallWindowProcW, GetAncestor, IsIconic, IsWindow, SetWindowDisplayAffinity, GA_ROOT,
+            WDA_EXCLUDEFROMCAPTURE, WDA_NONE, WNDPROC,
+        },
     },
 };
 
+type DrawCallback = Box<dyn FnMut(&ID3D11Device, &ID3D11DeviceContext) + 'static>;
+type TextureRestorer = Box<
+    dyn FnMut(&ID3D11Device, TextureId) -> Option<(ID3D11Texture2D, ID3D11ShaderResourceView)>
+        + 'static,
+>;
+
 #[allow(clippy::type_complexity)]
 struct AppData<T> {
-    render_view: Option<ID3D11RenderTargetView>,
+    device: ID3D11Device,
+    render_views: RenderTargets,
+    backend: Backend,
     ui: Box<dyn FnMut(&Context, &mut T) + 'static>,
+    layers: Layers<T>,
+    draw_hooks: DrawHooks,
+    timings: TimingHistory,
+    region_capture: RegionCapture,
+    hotkeys: Hotkeys<T>,
     tex_alloc: TextureAllocator,
-    input_layout: ID3D11InputLayout,
-    input_collector: InputCollector,
+    buffers: BufferPool,
+    scratch: FrameScratch,
+    deferred: Option<DeferredUploads>,
+    // last primitives drawn with the current font atlas, see `Config::staged_atlas`
+    atlas_frame: Vec<ClippedPrimitive>,
+    evicted: Vec<TextureId>,
+    mirror: Option<BackbufferMirror>,
+    scaled: Option<ScaledTarget>,
+    depth: Option<DepthTarget>,
+    #[cfg(feature = "dcomp")]
+    composition: Option<CompositionLayer>,
+    pipeline: Pipeline,
+    input: Arc<InputQueue>,
     shaders: CompiledShaders,
+    constants: ConstantBuffer,
+    detected_color_space: ColorSpace,
+    swap_chain_color_space: Option<ColorSpace>,
+    // of the last frame, changes without `resize_buffers` are followed in `present`
+    buffer_size: Option<(f32, f32)>,
+    // presents in a row that couldn't acquire the buffer, see `Config::buffer_failure`
+    buffer_failures: u32,
+    max_texture_side: usize,
+    capabilities: Capabilities,
+    config: Config,
+    frame_callback: Option<Box<dyn FnMut(&mut T) + 'static>>,
+    pre_draw: Option<DrawCallback>,
+    post_draw: Option<DrawCallback>,
+    texture_restorer: Option<TextureRestorer>,
+    #[cfg(feature = "persistence")]
+    persistence: Option<Persistence>,
+    #[cfg(feature = "settings")]
+    settings: OverlaySettings,
+    #[cfg(feature = "frame-debug")]
+    last_frame: FrameDebug,
+    #[cfg(feature = "replay")]
+    recorder: Option<Recorder>,
+    #[cfg(feature = "replay")]
+    replay: VecDeque<RecordedFrame>,
+    ui_cache: Option<UiCache>,
+    platform_worker: PlatformWorker,
+    // set by the context from any thread, see `Config::max_ui_fps`
+    repaint_requested: Arc<AtomicBool>,
     backup: BackupState,
     ctx: Context,
-    state: T,
 }
 
-#[cfg(feature = "parking-lot")]
-use parking_lot::{Mutex, MutexGuard};
-#[cfg(feature = "spin-lock")]
-use spin::lock_api::{Mutex, MutexGuard};
+use lock_api::{MappedMutexGuard, Mutex, MutexGuard, RawMutex};
+
+/// Guard returned by [`DirectX11App::lock_state`] and friends, the UI can't run while it's alive.
+pub type StateGuard<'a, T, R = DefaultRawMutex> = MappedMutexGuard<'a, R, T>;
+
+/// Guard returned by [`DirectX11App::lock_textures`].
+pub type TexturesGuard<'a, R = DefaultRawMutex> = MappedMutexGuard<'a, R, TextureAllocator>;
 
-use lock_api::MappedMutexGuard;
+/// [`TextureId`] under which the copy of the game's backbuffer is registered.
+/// See [`DirectX11App::set_backbuffer_texture`].
+pub const BACKBUFFER_TEXTURE_ID: TextureId = TextureId::User(u64::MAX);
 
 /// Heart and soul of this integration.
 /// Main methods you are going to use are:
 /// * [`Self::present`] - Should be called inside of hook or before present.
 /// * [`Self::resize_buffers`] - Should be called **INSTEAD** of swapchain's `ResizeBuffers`.
 /// * [`Self::wnd_proc`] - Should be called on each `WndProc`.
-pub struct DirectX11App<T = ()> {
-    data: Mutex<Option<AppData<T>>>,
+///
+/// # Locking
+/// The app is split into independently locked parts, so the window's message thread never
+/// waits for a frame and games that `SendMessage` from inside of present can't deadlock:
+/// * render data - everything `present` and `resize_buffers` need, held for the whole frame.
+/// * user state - `T`, held by `present` only while the UI and the frame callback run.
+/// * input - held by `wnd_proc` for a single message.
+///
+/// They are always taken in that order and `wnd_proc`, [`Self::is_visible`],
+/// [`Self::set_visible`] and the accessibility requests take no lock but the input's.
+/// [`Self::paint_meshes`] only takes the lock of its own queue, so it can be called from the UI.
+///
+/// `R` is the raw mutex of these locks, any [`RawMutex`] of `lock_api` works.
+/// [`DefaultRawMutex`] follows the `parking-lot` and `spin-lock` features.
+pub struct DirectX11App<T = (), R = DefaultRawMutex> {
+    data: Watched<R, Option<AppData<T>>>,
+    state: Watched<R, Option<T>>,
+    input: Watched<R, Option<InputCollector>>,
+    // Locks of the app's own, they are never held across a call into the user's code.
+    watchdog: Mutex<R, Option<(Duration, WatchdogAction)>>,
+    #[cfg(feature = "accesskit")]
+    accesskit: Mutex<R, Option<AccessKitAdapter>>,
+    visible: AtomicBool,
+    error_handler: Mutex<R, Option<fn(Error)>>,
+    meshes: Mutex<R, Vec<GpuMesh>>,
     hwnd: OnceCell<HWND>,
+    /// Shared with the input collector, read without taking [`Self::input`].
+    queue: OnceCell<Arc<InputQueue>>,
+    context: OnceCell<Context>,
 }
 
-impl<T> DirectX11App<T> {
-    const INPUT_ELEMENTS_DESC: [D3D11_INPUT_ELEMENT_DESC; 3] = [
-        D3D11_INPUT_ELEMENT_DESC {
-            SemanticName: pc_str!("POSITION"),
-            SemanticIndex: 0,
-            Format: DXGI_FORMAT_R32G32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: 0,
-            InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
-            InstanceDataStepRate: 0,
-        },
-        D3D11_INPUT_ELEMENT_DESC {
-            SemanticName: pc_str!("TEXCOORD"),
-            SemanticIndex: 0,
-            Format: DXGI_FORMAT_R32G32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: D3D11_APPEND_ALIGNED_ELEMENT,
-            InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
-            InstanceDataStepRate: 0,
-        },
-        D3D11_INPUT_ELEMENT_DESC {
-            SemanticName: pc_str!("COLOR"),
-            SemanticIndex: 0,
-            Format: DXGI_FORMAT_R32G32B32A32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: D3D11_APPEND_ALIGNED_ELEMENT,
-            InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
-            InstanceDataStepRate: 0,
-        },
-    ];
-}
-
-impl<T> DirectX11App<T> {
+impl<T, R: RawMutex> DirectX11App<T, R> {
     /// Creates new [`DirectX11App`] in const context. You are supposed to create a single static item to store the application state.
     pub const fn new() -> Self {
         Self {
-            data: Mutex::new(None),
+            data: Watched::new("render data", None),
+            state: Watched::new("user state", None),
+            input: Watched::new("input", None),
+            watchdog: Mutex::new(None),
+            #[cfg(feature = "accesskit")]
+            accesskit: Mutex::new(None),
+            visible: AtomicBool::new(true),
+            error_handler: Mutex::new(None),
+            meshes: Mutex::new(Vec::new()),
             hwnd: OnceCell::new(),
+            queue: OnceCell::new(),
+            context: OnceCell::new(),
         }
     }
 
@@ -113,7 +235,65 @@ impl<T> DirectX11App<T> {
         self.hwnd.get().is_some()
     }
 
+    /// Window the app draws on and takes input from, `None` before init. Lets hosts with
+    /// several apps, e.g. one on the game's window and one on a tool window, route their
+    /// `present` and window procedure calls to the app of the right window.
+    pub fn window(&self) -> Option<HWND> {
+        self.hwnd.get().copied()
+    }
+
+    /// Window the input comes from, the one the app draws on unless [`Config::input_window`]
+    /// is set. `None` before init.
+    pub fn input_window(&self) -> Option<HWND> {
+        self.lock_blocking(&self.input)
+            .as_ref()
+            .map(InputCollector::input_window)
+    }
+
+    /// What the renderer detected about the device and the swapchain at init, refreshed when
+    /// the buffers are resized or the game recreates its device. For host code that depends on
+    /// the environment and for bug reports.
+    pub fn capabilities(&self) -> Capabilities {
+        self.lock_data().capabilities
+    }
+
+    /// Position of the latest mouse message in pixels of the area the UI is laid out on, the
+    /// space egui's positions are in before `pixels_per_point`. Unlike egui's pointer it's
+    /// updated by every message instead of once per frame, for crosshairs and the like drawn in
+    /// [`Self::set_pre_draw`] or [`Self::set_post_draw`] on high refresh rate displays. Takes
+    /// no lock, so it can be called from those callbacks and any other thread. `None` before
+    /// init, until the pointer moved or after the window lost focus.
+    pub fn cursor_pos(&self) -> Option<Pos2> {
+        self.queue.get().and_then(|queue| queue.cursor_pos())
+    }
+
+    /// Releases every D3D11 object of the app and the mouse if egui captured it, e.g. before
+    /// the DLL is unloaded. Waits for a `present` or `resize_buffers` that is still running and
+    /// returns the state. Nothing but this may be called afterwards, so the window procedure
+    /// has to be restored first, see [`crate::entry::HookEntry::eject`].
+    pub fn shutdown(&self) -> Option<T> {
+        let data = self.lock_blocking(&self.data).take();
+        let state = self.lock_blocking(&self.state).take();
+        let input = self.lock_blocking(&self.input).take();
+        #[cfg(feature = "accesskit")]
+        self.accesskit.lock().take();
+        self.meshes.lock().clear();
+
+        if let Some(request) = input.and_then(|mut input| input.release_capture()) {
+            request.apply();
+        }
+        #[cfg(feature = "cursor-hooks")]
+        crate::cursor::set_frozen(self as *const Self as usize, false);
+
+        if data.is_some() {
+            log_msg!(info, "Released the app's resources");
+        }
+        state
+    }
+
     /// Initializes application and state. You should call this only once!
+    /// `context` may be a clone of one the host already uses, see [`Config::context`].
+    #[inline]
     pub fn init_with_state_context(
         &self,
         swap: &IDXGISwapChain,
@@ -121,64 +301,320 @@ impl<T> DirectX11App<T> {
         state: T,
         context: Context,
     ) {
+        self.init_swap_chain(swap, Backend::D3D11, ui, state, context, Config::default())
+    }
+
+    /// Initializes application and state with runtime options. You should call this only once!
+    #[inline]
+    pub fn init_with_config(
+        &self,
+        swap: &IDXGISwapChain,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+        config: Config,
+    ) {
+        self.init_swap_chain(swap, Backend::D3D11, ui, state, Context::default(), config)
+    }
+
+    /// Initializes application and state for a D3D12 swapchain, the UI is drawn with a D3D11 device
+    /// layered on top of `queue`, the direct queue the game presents with. You should call this only once!
+    #[cfg(feature = "d3d12")]
+    pub fn init_d3d12(
+        &self,
+        swap: &IDXGISwapChain,
+        queue: &ID3D12CommandQueue,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+        config: Config,
+    ) {
+        let bridge = expect!(D3D12Bridge::new(queue), "Failed to create D3D11On12 device");
+        self.init_swap_chain(
+            swap,
+            Backend::D3D12(bridge),
+            ui,
+            state,
+            Context::default(),
+            config,
+        )
+    }
+
+    /// Initializes application and state for drawing with [`Self::paint_to`], for engines that
+    /// present through paths without an `IDXGISwapChain`. `hwnd` is the window input comes from.
+    /// You should call this only once!
+    pub fn init_with_device(
+        &self,
+        device: &ID3D11Device,
+        hwnd: HWND,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+        config: Config,
+    ) {
+        let surface = Surface {
+            hwnd,
+            device: device.clone(),
+            render_views: RenderTargets::default(),
+            // the real target is only known once something is painted
+            srgb: config.wanted_srgb_view().unwrap_or(false),
+            color_space: ColorSpace::Srgb,
+            samples: 1,
+            swap_desc: None,
+        };
+
+        log_msg!(info, "Initialized for {:?} without a swapchain", hwnd);
+        self.init_impl(
+            surface,
+            Backend::D3D11,
+            ui,
+            state,
+            Context::default(),
+            config,
+        )
+    }
+
+    fn init_swap_chain(
+        &self,
+        swap: &IDXGISwapChain,
+        backend: Backend,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+        context: Context,
+        config: Config,
+    ) {
+        if let Err(e) = self.try_init_swap_chain(swap, backend, ui, state, context, config) {
+            panic_msg!("{}", e);
+        }
+    }
+
+    fn try_init_swap_chain(
+        &self,
+        swap: &IDXGISwapChain,
+        mut backend: Backend,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+        context: Context,
+        config: Config,
+    ) -> Result<(), InitError> {
         unsafe {
             if self.hwnd.get().is_some() {
-                panic_msg!("You must call init only once");
+                return Err(InitError::AlreadyInitialized);
             }
 
             let mut swap_desc: DXGI_SWAP_CHAIN_DESC = Default::default();
-
-            expect!(
-                swap.GetDesc(&mut swap_desc),
-                "Failed to get swapchain's descriptor"
-            );
+            swap.GetDesc(&mut swap_desc).context("Failed to get swapchain's descriptor")?;
 
             let hwnd = swap_desc.OutputWindow;
-            if hwnd.0 == -1 {
-                panic_msg!("Invalid output window descriptor");
+            if hwnd.0 == 0 || hwnd.0 == -1 || !IsWindow(hwnd).as_bool() {
+                return Err(InitError::InvalidWindow(hwnd));
             }
-            let _ = self.hwnd.set(hwnd);
 
-            let dev: ID3D11Device = expect!(swap.GetDevice(), "Failed to get swapchain's device");
+            let format = swap_desc.BufferDesc.Format;
+            if !is_supported_backbuffer(format) {
+                return Err(InitError::UnsupportedFormat(format));
+            }
 
-            let backbuffer: ID3D11Texture2D =
-                expect!(swap.GetBuffer(0), "Failed to get swapchain's backbuffer");
+            let (dev, ctx, backbuffer) = backend.device_and_buffer(swap)?;
+            dev.GetDeviceRemovedReason().map_err(InitError::DeviceRemoved)?;
+
+            let mut render_views = RenderTargets::default();
+            let srgb_view = config.wanted_srgb_view();
+            let srgb = render_views.get(&dev, &ctx, &backbuffer, srgb_view)?.srgb;
+
+            let surface = Surface {
+                hwnd,
+                device: dev,
+                render_views,
+                srgb,
+                color_space: ColorSpace::detect(swap, format),
+                samples: swap_desc.SampleDesc.Count,
+                swap_desc: Some(swap_desc),
+            };
+
+            log_msg!(
+                info,
+                "Initialized for {:?}, backbuffer {}x{} {:?} with {} samples, {:?} output",
+                hwnd,
+                swap_desc.BufferDesc.Width,
+                swap_desc.BufferDesc.Height,
+                format,
+                swap_desc.SampleDesc.Count,
+                config.color_space.unwrap_or(surface.color_space)
+            );
+            let waitable = DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT.0 as u32;
+            if swap_desc.Flags & waitable != 0 && config.submit_point == SubmitPoint::Present
+            {
+                log_msg!(
+                    info,
+                    "Swapchain has a frame latency waitable object, submit the UI manually if \
+                     the game stalls"
+                );
+            }
 
-            let mut render_view: Option<ID3D11RenderTargetView> = None;
+            self.try_init_impl(surface, backend, ui, state, context, config)
+        }
+    }
 
-            expect!(
-                dev.CreateRenderTargetView(&backbuffer, None, Some(&mut render_view)),
-                "Failed to create new render target view"
-            );
+    fn init_impl(
+        &self,
+        surface: Surface,
+        backend: Backend,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+        context: Context,
+        config: Config,
+    ) {
+        if let Err(e) = self.try_init_impl(surface, backend, ui, state, context, config) {
+            panic_msg!("{}", e);
+        }
+    }
 
-            let shaders = CompiledShaders::new(&dev);
+    fn try_init_impl(
+        &self,
+        surface: Surface,
+        backend: Backend,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+        context: Context,
+        config: Config,
+    ) -> Result<(), InitError> {
+        if self.hwnd.get().is_some() {
+            return Err(InitError::AlreadyInitialized);
+        }
 
-            let mut input_layout: Option<ID3D11InputLayout> = None;
+        #[cfg(feature = "settings")]
+        let (config, settings) = load_settings(config);
+
+        let Surface {
+            hwnd,
+            device: dev,
+            render_views,
+            srgb,
+            color_space: detected_color_space,
+            samples,
+            swap_desc,
+        } = surface;
+
+        // everything fallible happens before the app is marked as initialized
+        let shaders = CompiledShaders::new(
+            &dev,
+            &config,
+            config.color_space.unwrap_or(detected_color_space),
+            srgb,
+        )?;
+        let pipeline = Pipeline::new(&dev, &shaders, samples > 1, &config)?;
+
+        if self.hwnd.set(hwnd).is_err() {
+            return Err(InitError::AlreadyInitialized);
+        }
 
-            expect!(
-                dev.CreateInputLayout(
-                    &Self::INPUT_ELEMENTS_DESC,
-                    shaders.bytecode(),
-                    Some(&mut input_layout)
-                ),
-                "Failed to create input layout"
-            );
+        if config.exclude_from_capture {
+            if let Err(e) = unsafe { exclude_from_capture(hwnd, true) } {
+                log_msg!(warn, "{}", e);
+            }
+        }
 
-            // this can only happen if the expect above fails
-            let input_layout = expect!(input_layout, "Failed to create input layout");
+        let context = config.context.clone().unwrap_or(context);
+        #[cfg(feature = "image-loaders")]
+        crate::images::install(&context);
 
-            *self.data.lock() = Some(AppData {
-                input_collector: InputCollector::new(hwnd),
-                tex_alloc: TextureAllocator::default(),
-                backup: BackupState::default(),
-                ui: Box::new(ui),
-                ctx: context,
-                input_layout,
-                render_view,
-                shaders,
-                state,
-            });
+        if let Some(fonts) = config.fonts.clone() {
+            context.set_fonts(fonts);
+        }
+
+        #[cfg(feature = "accesskit")]
+        context.enable_accesskit();
+
+        #[cfg(feature = "persistence")]
+        let persistence = config.persistence.clone().map(|(path, interval)| {
+            let persistence = Persistence::new(path, interval);
+            persistence.load(&context);
+            persistence
+        });
+
+        let input = Arc::new(InputQueue::new(hwnd));
+        let mut collector = InputCollector::new(input.clone());
+        collector.configure(&config);
+        *self.input.lock() = Some(collector);
+        // can't be set yet, `hwnd` already guards against a second init
+        let _ = self.queue.set(input.clone());
+        *self.state.lock() = Some(state);
+        #[cfg(feature = "accesskit")]
+        {
+            *self.accesskit.lock() = Some(AccessKitAdapter::new(hwnd));
+        }
+        let repaint_requested = Arc::new(AtomicBool::new(false));
+        if config.max_ui_fps.is_some() {
+            watch_repaints(&context, Some(&repaint_requested));
+        }
+
+        let mut capabilities = Capabilities {
+            srgb,
+            color_space: detected_color_space,
+            samples,
+            d3d12: !matches!(backend, Backend::D3D11),
+            ..Capabilities::new(&dev)
+        };
+        if let Some(desc) = &swap_desc {
+            capabilities.follow_swap_chain(desc);
         }
+        log_msg!(debug, "{:?}", capabilities);
+
+        self.visible.store(config.visible, Ordering::Relaxed);
+        *self.watchdog.lock() = config.watchdog;
+        *self.data.lock() = Some(AppData {
+            tex_alloc: TextureAllocator::default(),
+            device: dev.clone(),
+            buffers: BufferPool::default(),
+            scratch: FrameScratch::default(),
+            deferred: None,
+            atlas_frame: vec![],
+            evicted: vec![],
+            input,
+            mirror: None,
+            backup: BackupState::default(),
+            constants: ConstantBuffer::new(&dev, ShaderConstants::default()),
+            max_texture_side: max_texture_side(&dev),
+            capabilities,
+            detected_color_space,
+            swap_chain_color_space: None,
+            buffer_size: None,
+            buffer_failures: 0,
+            scaled: None,
+            depth: None,
+            #[cfg(feature = "dcomp")]
+            composition: None,
+            config,
+            frame_callback: None,
+            pre_draw: None,
+            post_draw: None,
+            texture_restorer: None,
+            #[cfg(feature = "persistence")]
+            persistence,
+            #[cfg(feature = "settings")]
+            settings,
+            #[cfg(feature = "frame-debug")]
+            last_frame: FrameDebug::default(),
+            #[cfg(feature = "replay")]
+            recorder: None,
+            #[cfg(feature = "replay")]
+            replay: VecDeque::new(),
+            ui_cache: None,
+            platform_worker: PlatformWorker::default(),
+            repaint_requested,
+            ui: Box::new(ui),
+            layers: Layers::default(),
+            draw_hooks: DrawHooks::default(),
+            timings: TimingHistory::default(),
+            region_capture: RegionCapture::default(),
+            hotkeys: Hotkeys::default(),
+            ctx: context.clone(),
+            render_views,
+            backend,
+            pipeline,
+            shaders,
+        });
+
+        let _ = self.context.set(context);
+        Ok(())
     }
 
     /// Initializes application and state. Sets egui's context to default value. You should call this only once!
@@ -192,6 +628,26 @@ impl<T> DirectX11App<T> {
         self.init_with_state_context(swap, ui, state, Context::default())
     }
 
+    /// Same as [`Self::init_with_state`] but reports why the swapchain can't be used yet instead
+    /// of panicking: a missing or destroyed output window, an unsupported backbuffer format or a
+    /// removed device. Nothing is kept on failure, so loaders can call it again on the next
+    /// `Present` of games that set up D3D lazily.
+    pub fn try_init_with_state(
+        &self,
+        swap: &IDXGISwapChain,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+    ) -> Result<(), InitError> {
+        self.try_init_swap_chain(
+            swap,
+            Backend::D3D11,
+            ui,
+            state,
+            Context::default(),
+            Config::default(),
+        )
+    }
+
     /// Initializes application and state while allowing you to mutate the initial state of the egui's context. You should call this only once!
     #[inline]
     pub fn init_with_mutate(
@@ -207,305 +663,2304 @@ impl<T> DirectX11App<T> {
         self.init_with_state_context(swap, ui, state, ctx);
     }
 
-    #[cfg(feature = "parking-lot")]
-    pub fn lock_state(&self) -> MappedMutexGuard<'_, parking_lot::RawMutex, T> {
-        MutexGuard::map(self.data.lock(), |app| &mut app.as_mut().unwrap().state)
+    /// egui's context, available right after init. Doesn't wait for the current frame,
+    /// so background threads can use it to `request_repaint` or change the style.
+    pub fn context(&self) -> Context {
+        expect!(self.context.get(), "You need to call init first").clone()
     }
 
-    #[cfg(feature = "spin-lock")]
-    pub fn lock_state(&self) -> MappedMutexGuard<'_, spin::mutex::Mutex<()>, T> {
-        MutexGuard::map(self.data.lock(), |app| &mut app.as_mut().unwrap().state)
+    /// Only waits for the UI closure of a frame, never for its drawing.
+    #[track_caller]
+    pub fn lock_state(&self) -> StateGuard<'_, T, R> {
+        MutexGuard::map(self.lock_blocking(&self.state), |state| {
+            expect!(state.as_mut(), "You need to call init first")
+        })
     }
 
-    fn lock_data(&self) -> impl DerefMut<Target = AppData<T>> + '_ {
-        MutexGuard::map(self.data.lock(), |app| {
-            expect!(app.as_mut(), "You need to call init first")
-        })
+    /// Same as [`Self::lock_state`], but returns `None` instead of blocking if the state is in use,
+    /// e.g. by the UI of `present` running on another thread, or isn't initialized yet.
+    pub fn try_lock_state(&self) -> Option<StateGuard<'_, T, R>> {
+        Self::map_state(self.state.try_lock()?)
     }
-}
 
-impl<T: Default> DirectX11App<T> {
-    /// Initializes application and sets the state to its default value. You should call this only once!
-    #[inline]
-    pub fn init_default(&self, swap: &IDXGISwapChain, ui: impl FnMut(&Context, &mut T) + 'static) {
-        self.init_with_state_context(swap, ui, T::default(), Context::default());
+    /// Same as [`Self::try_lock_state`], but waits up to `timeout` for the app to become free.
+    pub fn lock_state_timeout(&self, timeout: Duration) -> Option<StateGuard<'_, T, R>> {
+        Self::map_state(lock_timeout(&self.state, timeout)?)
     }
-}
 
-impl<T> DirectX11App<T> {
-    /// Present call. Should be called once per original present call, before or inside of hook.
-    #[allow(clippy::cast_ref_to_mut)]
-    pub fn present(&self, swap_chain: &IDXGISwapChain) {
-        unsafe {
-            let this = &mut *self.lock_data();
+    /// Runs `f` on the state and releases it right after, see [`Self::lock_state`].
+    #[track_caller]
+    pub fn with_state<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        f(&mut self.lock_state())
+    }
 
-            let (dev, ctx) = &get_device_and_context(swap_chain);
+    /// Swaps the whole state, e.g. after reloading a config, and returns the old one.
+    #[track_caller]
+    pub fn replace_state(&self, state: T) -> T {
+        std::mem::replace(&mut *self.lock_state(), state)
+    }
 
-            this.backup.save(ctx);
+    fn map_state(guard: MutexGuard<'_, R, Option<T>>) -> Option<StateGuard<'_, T, R>> {
+        MutexGuard::try_map(guard, |state| state.as_mut()).ok()
+    }
 
-            let screen = self.get_screen_size();
+    /// Enables or disables copying of the backbuffer into a texture each frame.
+    /// While enabled, the game's frame (without the UI) can be drawn with [`BACKBUFFER_TEXTURE_ID`],
+    /// e.g. to show a scaled picture-in-picture view or a magnifier.
+    pub fn set_backbuffer_texture(&self, enabled: bool) {
+        let this = &mut *self.lock_data();
 
-            if cfg!(feature = "clear") {
-                // Use let_chains here once stabilized, didn't wanna add a nightly feature to the crate
-                if let Some(render_view) = &this.render_view {
-                    ctx.ClearRenderTargetView(render_view, [0.39, 0.58, 0.92, 1.].as_ptr());
-                }
-            }
+        if enabled {
+            this.mirror.get_or_insert_with(BackbufferMirror::default);
+        } else if let Some(mut mirror) = this.mirror.take() {
+            mirror.release(&mut this.tex_alloc);
+        }
+    }
 
-            let output = this.ctx.run(this.input_collector.collect_input(), |ctx| {
-                // Dont look here, it should be fine until someone tries to do something horrible.
-                (this.ui)(ctx, &mut this.state);
-            });
+    /// Uploads a BC1-BC7 compressed DDS file without decompressing it,
+    /// the returned id can be passed to `egui::Image` like any other texture.
+    pub fn load_dds(&self, data: &[u8]) -> Result<TextureId, Error> {
+        let image = DdsImage::parse(data)?;
+        let this = &mut *self.lock_data();
 
-            if !output.textures_delta.is_empty() {
-                this.tex_alloc
-                    .process_deltas(dev, ctx, output.textures_delta);
-            }
+        this.tex_alloc.register_compressed(&this.device, &image)
+    }
 
-            if !output.platform_output.copied_text.is_empty() {
-                let _ = WindowsClipboardContext.set_contents(output.platform_output.copied_text);
-            }
+    /// Sets how `id` is sampled outside of its bounds, e.g. [`AddressMode::Wrap`] for tiled textures.
+    /// `None` goes back to the mode from [`Config::sampler`]. Returns `false` if `id` isn't allocated.
+    pub fn set_texture_address(&self, id: TextureId, address: Option<AddressMode>) -> bool {
+        self.lock_data().tex_alloc.set_address(id, address)
+    }
 
-            if output.shapes.is_empty() {
-                this.backup.restore(ctx);
-                return;
-            }
+    /// Samples the mip levels of `id` within the range of `sampling`, optionally with anisotropic
+    /// filtering. Textures sample only their first mip level by default, mipmapped ones loaded
+    /// with [`Self::load_dds`] or [`TextureAllocator::register`] need this to use the rest.
+    /// `None` goes back to the default. Returns `false` if `id` isn't allocated.
+    pub fn set_texture_sampling(&self, id: TextureId, sampling: Option<TextureSampling>) -> bool {
+        self.lock_data().tex_alloc.set_sampling(id, sampling)
+    }
 
-            let primitives = this
-                .ctx
-                .tessellate(output.shapes)
-                .into_iter()
-                .filter_map(|prim| {
-                    if let Primitive::Mesh(mesh) = prim.primitive {
-                        GpuMesh::from_mesh(screen, mesh, prim.clip_rect)
-                    } else {
-                        panic!("Paint callbacks are not yet supported")
-                    }
-                })
-                .collect::<Vec<_>>();
-
-            self.set_blend_state(dev, ctx);
-            self.set_raster_options(dev, ctx);
-            self.set_sampler_state(dev, ctx);
-
-            ctx.RSSetViewports(Some(&[self.get_viewport()]));
-            ctx.OMSetRenderTargets(
-                Some(&[expect!(
-                    this.render_view.clone(),
-                    "Failed to set render targets"
-                )]),
-                None,
-            );
-            ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-            ctx.IASetInputLayout(&this.input_layout);
+    /// Gives access to every texture the UI can draw. `present` waits while the guard is held.
+    #[track_caller]
+    pub fn lock_textures(&self) -> TexturesGuard<'_, R> {
+        MutexGuard::map(self.lock_blocking(&self.data), |app| {
+            &mut expect!(app.as_mut(), "You need to call init first").tex_alloc
+        })
+    }
 
-            for mesh in primitives {
-                let idx = create_index_buffer(dev, &mesh);
-                let vtx = create_vertex_buffer(dev, &mesh);
+    /// Frees a texture created with [`Self::load_dds`], returns `false` if `id` wasn't allocated.
+    pub fn free_texture(&self, id: TextureId) -> bool {
+        self.lock_data().tex_alloc.free(id)
+    }
 
-                let texture = this.tex_alloc.get_by_id(mesh.texture_id);
+    /// Returns a copy of the current runtime options.
+    pub fn config(&self) -> Config {
+        let visible = self.is_visible();
+        self.lock_data().config.clone().visible(visible)
+    }
 
-                ctx.RSSetScissorRects(Some(&[RECT {
-                    left: mesh.clip.left() as _,
-                    top: mesh.clip.top() as _,
-                    right: mesh.clip.right() as _,
-                    bottom: mesh.clip.bottom() as _,
-                }]));
+    /// Number of input events dropped so far because frames weren't presented fast enough to consume them.
+    pub fn dropped_input_events(&self) -> usize {
+        self.lock_data().input.dropped()
+    }
 
-                if let Some(texture) = texture {
-                    ctx.PSSetShaderResources(0, Some(&[texture]));
-                }
+    /// GPU memory allocated for the UI's textures and buffers.
+    pub fn gpu_memory_usage(&self) -> GpuMemoryUsage {
+        let this = self.lock_data();
 
-                ctx.IASetVertexBuffers(
-                    0,
-                    1,
-                    Some(&Some(vtx)),
-                    Some(&(size_of::<GpuVertex>() as _)),
-                    Some(&0),
-                );
-                ctx.IASetIndexBuffer(&idx, DXGI_FORMAT_R32_UINT, 0);
-                ctx.VSSetShader(&this.shaders.vertex, None);
-                ctx.PSSetShader(&this.shaders.pixel, None);
+        GpuMemoryUsage {
+            textures: this.tex_alloc.bytes(),
+            buffers: this.buffers.bytes() + size_of::<ShaderConstants>(),
+        }
+    }
+
+    /// Size and fill ratio of egui's font atlas and how often it was uploaded as a whole, e.g. to
+    /// see whether new glyphs or another `pixels_per_point` made egui rebuild it.
+    /// `None` before the first frame created the fonts.
+    pub fn font_atlas_stats(&self) -> Option<FontAtlasStats> {
+        let this = self.lock_data();
+        let (size, fill_ratio) = this.ctx.fonts_mut(|fonts| {
+            let fonts = fonts.as_ref()?;
+            Some((fonts.font_image_size(), fonts.font_atlas_fill_ratio()))
+        })?;
+
+        Some(FontAtlasStats {
+            size,
+            fill_ratio,
+            uploads: this.tex_alloc.atlas_uploads(),
+            staging: this.tex_alloc.is_staging_atlas(),
+        })
+    }
 
-                ctx.DrawIndexed(mesh.indices.len() as _, 0, 0);
+    /// Throws away egui's font atlas and lays out the current fonts again, the new atlas is
+    /// uploaded with the next frame. For tools that swap fonts or `pixels_per_point` at runtime
+    /// and want a fresh atlas right away, egui itself only rebuilds it with the next frame's
+    /// `pixels_per_point` or once it's almost full.
+    /// Does nothing before the first frame, it creates the fonts anyway.
+    pub fn rebuild_fonts(&self) {
+        // never in the middle of a frame that lays out text with the old ones
+        let this = self.lock_data();
+
+        this.ctx.fonts_mut(|fonts| {
+            if let Some(fonts) = fonts.as_mut() {
+                let definitions = fonts.lock().fonts.definitions().clone();
+                *fonts = Fonts::new(
+                    fonts.pixels_per_point(),
+                    fonts.max_texture_side(),
+                    definitions,
+                );
             }
+        });
+    }
 
-            this.backup.restore(ctx);
-        }
+    /// Textures freed since the last call because the UI exceeded its memory budget, see
+    /// [`crate::BudgetAction::EvictUserTextures`], or because they couldn't be restored after
+    /// the game recreated its device, see [`Self::set_texture_restorer`].
+    pub fn take_evicted_textures(&self) -> Vec<TextureId> {
+        std::mem::take(&mut self.lock_data().evicted)
     }
 
-    /// Call when resizing buffers.
-    /// Do not call the original function before it, instead call it inside of the `original` closure.
-    /// # Behavior
-    /// In `origin` closure make sure to call the original `ResizeBuffers`.
-    pub fn resize_buffers(
-        &self,
-        swap_chain: &IDXGISwapChain,
-        original: impl FnOnce() -> HRESULT,
-    ) -> HRESULT {
-        unsafe {
-            let this = &mut *self.lock_data();
-            drop(this.render_view.take());
+    /// Primitives, scissor rects and texture bindings the last drawn frame submitted, for tools
+    /// and tests. Frames that are skipped because the UI is hidden keep the previous one.
+    #[cfg(feature = "frame-debug")]
+    pub fn last_frame_debug(&self) -> FrameDebug {
+        self.lock_data().last_frame.clone()
+    }
 
-            let result = original();
+    /// Sizes of the vertex and index buffers the UI is drawn from and how often they had to grow.
+    pub fn buffer_stats(&self) -> BufferStats {
+        self.lock_data().buffers.stats()
+    }
 
-            let backbuffer: ID3D11Texture2D = expect!(
-                swap_chain.GetBuffer(0),
-                "Failed to get swapchain's backbuffer"
-            );
+    /// Stage timings of the last frames the UI was drawn in, oldest first, see
+    /// [`Config::frame_timings`].
+    pub fn frame_timings(&self) -> Vec<FrameTimings> {
+        self.lock_data().timings.frames().copied().collect()
+    }
 
-            let device: ID3D11Device =
-                expect!(swap_chain.GetDevice(), "Failed to get swapchain's device");
+    /// Number of frames that took longer than the threshold of [`Config::frame_timings`].
+    pub fn slow_frames(&self) -> usize {
+        self.lock_data().timings.slow_frames()
+    }
 
-            let mut new_view: Option<ID3D11RenderTargetView> = None;
+    /// Shows or hides the window with the frame timings, see [`Config::profiler`].
+    pub fn set_profiler_visible(&self, visible: bool) {
+        self.lock_data().config.profiler = visible;
+    }
 
-            expect!(
-                device.CreateRenderTargetView(&backbuffer, None, Some(&mut new_view)),
-                "Failed to create render target view"
-            );
+    /// Number of consecutive clicks of the last pressed mouse button, counted with the system's
+    /// double-click time and rectangle. egui's own `double_clicked` uses fixed timings.
+    pub fn click_count(&self) -> u32 {
+        self.lock_data().input.clicks()
+    }
 
-            this.render_view = new_view;
-            result
+    /// Replaces all runtime options at once.
+    pub fn set_config(&self, config: Config) {
+        let this = &mut *self.lock_data();
+
+        self.lock_input().configure(&config);
+        self.visible.store(config.visible, Ordering::Relaxed);
+        *self.watchdog.lock() = config.watchdog;
+        if this.config.exclude_from_capture != config.exclude_from_capture {
+            let hwnd = *expect!(self.hwnd.get(), "You need to call init first");
+            if let Err(e) = unsafe { exclude_from_capture(hwnd, config.exclude_from_capture) } {
+                log_msg!(warn, "{}", e);
+            }
         }
+        this.config = config;
     }
 
-    /// Call on each `WndProc` occurence.
-    /// Returns `true` if message was recognized and dispatched by input handler,
-    /// `false` otherwise.
-    #[inline]
-    pub fn wnd_proc(&self, umsg: u32, wparam: WPARAM, lparam: LPARAM) -> InputResult {
-        self.lock_data()
-            .input_collector
-            .process(umsg, wparam.0, lparam.0)
+    /// Hides the window from screen capture or shows it again, see [`Config::exclude_from_capture`].
+    pub fn set_exclude_from_capture(&self, exclude: bool) -> Result<(), Error> {
+        let hwnd = *expect!(self.hwnd.get(), "You need to call init first");
+        unsafe { exclude_from_capture(hwnd, exclude)? };
+        self.lock_data().config.exclude_from_capture = exclude;
+        Ok(())
     }
-}
 
-impl<T> DirectX11App<T> {
-    #[inline]
-    fn get_screen_size(&self) -> (f32, f32) {
-        let mut rect = RECT::default();
-        unsafe {
-            GetClientRect(
-                *expect!(self.hwnd.get(), "You need to call init first"),
-                &mut rect,
-            );
+    /// Draws the UI into a transparent DirectComposition layer above the window instead of the
+    /// game's backbuffer, out of reach of the game's post-processing. Only for D3D11 swapchains,
+    /// the layer always blends premultiplied and in sRGB.
+    #[cfg(feature = "dcomp")]
+    pub fn enable_composition(&self) -> Result<(), Error> {
+        let hwnd = *expect!(self.hwnd.get(), "You need to call init first");
+        let mut this = self.lock_data();
+        if this.composition.is_some() {
+            return Ok(());
         }
-        (
-            (rect.right - rect.left) as f32,
-            (rect.bottom - rect.top) as f32,
-        )
+        if !matches!(this.backend, Backend::D3D11) {
+            return Err(Error::CompositionUnsupported);
+        }
+
+        let size = this
+            .input
+            .client_size()
+            .map_or((1, 1), |size| (size.x as u32, size.y as u32));
+        this.composition = Some(unsafe { CompositionLayer::new(&this.device, hwnd, size)? });
+        Ok(())
     }
 
-    #[inline]
-    fn get_viewport(&self) -> D3D11_VIEWPORT {
-        let (w, h) = self.get_screen_size();
-        D3D11_VIEWPORT {
-            TopLeftX: 0.,
-            TopLeftY: 0.,
-            Width: w,
-            Height: h,
-            MinDepth: 0.,
-            MaxDepth: 1.,
-        }
-    }
-
-    fn set_blend_state(&self, dev: &ID3D11Device, ctx: &ID3D11DeviceContext) {
-        let mut targets: [D3D11_RENDER_TARGET_BLEND_DESC; 8] = Default::default();
-        targets[0].BlendEnable = true.into();
-        targets[0].SrcBlend = D3D11_BLEND_SRC_ALPHA;
-        targets[0].DestBlend = D3D11_BLEND_INV_SRC_ALPHA;
-        targets[0].BlendOp = D3D11_BLEND_OP_ADD;
-        targets[0].SrcBlendAlpha = D3D11_BLEND_ONE;
-        targets[0].DestBlendAlpha = D3D11_BLEND_INV_SRC_ALPHA;
-        targets[0].BlendOpAlpha = D3D11_BLEND_OP_ADD;
-        targets[0].RenderTargetWriteMask = D3D11_COLOR_WRITE_ENABLE_ALL.0 as _;
-
-        let blend_desc = D3D11_BLEND_DESC {
-            AlphaToCoverageEnable: false.into(),
-            IndependentBlendEnable: false.into(),
-            RenderTarget: targets,
-        };
+    /// Goes back to drawing into the game's backbuffer, see [`Self::enable_composition`].
+    #[cfg(feature = "dcomp")]
+    pub fn disable_composition(&self) {
+        self.lock_data().composition = None;
+    }
 
-        unsafe {
-            let mut blend_state: Option<ID3D11BlendState> = None;
+    /// Sets the color the backbuffer is cleared with before drawing the UI, `None` disables clearing.
+    /// Useful when the app is used as a standalone renderer.
+    pub fn set_clear_color(&self, color: Option<Rgba>) {
+        self.lock_data().config.clear_color = color;
+    }
 
-            expect!(
-                dev.CreateBlendState(&blend_desc, Some(&mut blend_state)),
-                "Failed to create blend state"
-            );
+    /// Scales the UI on top of the scale of the monitor, see [`Config::ui_scale`].
+    pub fn set_ui_scale(&self, scale: f32) {
+        self.lock_data().config.ui_scale = scale;
+    }
+
+    /// Runs the UI at most `fps` times a second, for games running far above the refresh rate.
+    /// Frames in between draw the primitives of the last run again, input, animations and
+    /// repaint requests always run it. `0` runs it every frame, which is the default.
+    /// Replaces the repaint callback of the context, see [`Config::max_ui_fps`].
+    pub fn set_max_ui_fps(&self, fps: u32) {
+        let mut this = self.lock_data();
+        this.config.max_ui_fps = (fps > 0).then_some(fps);
+        this.ui_cache = None;
+        // the old callback would keep flagging repaints for a limit that is gone
+        watch_repaints(&this.ctx, (fps > 0).then_some(&this.repaint_requested));
+    }
 
-            let blend_state = expect!(blend_state, "Failed to create blend state");
+    /// Overrides the color space the UI is rendered in.
+    /// `None` detects it from the swapchain, which is the default.
+    pub fn set_color_space(&self, color_space: Option<ColorSpace>) {
+        self.lock_data().config.color_space = color_space;
+    }
 
-            ctx.OMSetBlendState(
-                &blend_state,
-                Some([0f32, 0f32, 0f32, 0f32].as_ptr()),
-                0xffffffff,
-            );
+    /// Tells the app the color space the game set with `IDXGISwapChain3::SetColorSpace1`, call
+    /// it from a hook of that function. DXGI has no way to query it, without it the color space
+    /// is guessed from the backbuffer format and the HDR state of the output, which gets HDR10
+    /// games on SDR outputs and SDR games in 10 bit backbuffers on HDR ones wrong.
+    ///
+    /// Color spaces the UI can't be rendered in go back to guessing. [`Self::set_color_space`]
+    /// still takes precedence.
+    pub fn set_swap_chain_color_space(&self, color_space: DXGI_COLOR_SPACE_TYPE) {
+        let mut this = self.lock_data();
+        this.swap_chain_color_space = ColorSpace::from_dxgi(color_space);
+
+        match this.swap_chain_color_space {
+            Some(reported) => {
+                log_msg!(info, "Swapchain color space set to {:?}", reported);
+                this.detected_color_space = reported;
+            }
+            None => {
+                log_msg!(warn, "Unsupported swapchain color space {:?}", color_space);
+            }
         }
     }
 
-    fn set_raster_options(&self, dev: &ID3D11Device, ctx: &ID3D11DeviceContext) {
-        let raster_desc = D3D11_RASTERIZER_DESC {
-            FillMode: D3D11_FILL_SOLID,
-            CullMode: D3D11_CULL_NONE,
-            FrontCounterClockwise: false.into(),
-            DepthBias: false.into(),
-            DepthBiasClamp: 0.,
-            SlopeScaledDepthBias: 0.,
-            DepthClipEnable: false.into(),
-            ScissorEnable: true.into(),
-            MultisampleEnable: false.into(),
-            AntialiasedLineEnable: false.into(),
-        };
+    /// Color space the UI is currently rendered in, the one of [`Self::set_color_space`] or the
+    /// one detected from the swapchain.
+    pub fn color_space(&self) -> ColorSpace {
+        let this = self.lock_data();
+        this.config.color_space.unwrap_or(this.detected_color_space)
+    }
 
-        unsafe {
-            let mut options: Option<ID3D11RasterizerState> = None;
+    /// Maps window coordinates onto the backbuffer, for games rendering at a non-native resolution.
+    /// `None`, the default, leaves the size to [`ScreenSizeSource`].
+    pub fn set_screen_transform(&self, transform: Option<ScreenTransform>) {
+        self.lock_data().config.transform = transform;
+    }
 
-            expect!(
-                dev.CreateRasterizerState(&raster_desc, Some(&mut options)),
-                "Failed to create rasterizer state"
-            );
-            if let Some(options) = options {
-                ctx.RSSetState(&options);
-            }
+    /// Switches between laying out the UI on the swapchain's buffers or the window's client area.
+    pub fn set_screen_size_source(&self, source: ScreenSizeSource) {
+        self.lock_data().config.screen_size = source;
+    }
+
+    /// Sets how bright egui's white is in nits when rendering into HDR backbuffers.
+    pub fn set_sdr_white_level(&self, nits: f32) {
+        self.lock_data().config.sdr_white_level = nits;
+    }
+
+    /// Enables or disables dithering of the output, see [`Config::dither`].
+    pub fn set_dither(&self, enabled: bool) {
+        self.lock_data().config.dither = enabled;
+    }
+
+    /// Changes the gamma of the UI, see [`Config::gamma`].
+    pub fn set_gamma(&self, gamma: f32) {
+        self.lock_data().config.gamma = gamma;
+    }
+
+    /// Changes the brightness multiplier of the UI, see [`Config::gamma`].
+    pub fn set_brightness(&self, brightness: f32) {
+        self.lock_data().config.brightness = brightness;
+    }
+
+    /// Changes the resolution the UI is drawn at, see [`Config::render_scale`].
+    pub fn set_render_scale(&self, scale: f32) {
+        self.lock_data().config.render_scale = scale.clamp(0.25, 1.);
+    }
+
+    /// Enables or disables the wireframe view, see [`Config::wireframe`].
+    pub fn set_wireframe(&self, enabled: bool) {
+        self.lock_data().config.wireframe = enabled;
+    }
+
+    /// Enables or disables the clip rectangle outlines, see [`Config::clip_rects`].
+    pub fn set_clip_rects(&self, enabled: bool) {
+        self.lock_data().config.clip_rects = enabled;
+    }
+
+    /// Enables or disables the overdraw heat map, see [`Config::overdraw`].
+    pub fn set_overdraw(&self, enabled: bool) {
+        self.lock_data().config.overdraw = enabled;
+    }
+
+    /// Replaces the tessellation options, see [`Config::tessellation`].
+    pub fn set_tessellation(&self, options: Option<TessellationOptions>) {
+        self.lock_data().config.tessellation = options;
+    }
+
+    /// Changes how smooth thin lines are drawn, see [`Config::line_quality`].
+    pub fn set_line_quality(&self, quality: LineQuality) {
+        let this = &mut *self.lock_data();
+        this.config.line_quality = quality;
+
+        // nothing overrides egui's options anymore, undo what the last quality changed
+        if quality == LineQuality::Default && this.config.tessellation.is_none() {
+            this.ctx
+                .tessellation_options_mut(|current| *current = TessellationOptions::default());
         }
     }
 
-    fn set_sampler_state(&self, dev: &ID3D11Device, ctx: &ID3D11DeviceContext) {
-        let desc = D3D11_SAMPLER_DESC {
-            Filter: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
-            AddressU: D3D11_TEXTURE_ADDRESS_BORDER,
-            AddressV: D3D11_TEXTURE_ADDRESS_BORDER,
-            AddressW: D3D11_TEXTURE_ADDRESS_BORDER,
-            MipLODBias: 0.,
-            ComparisonFunc: D3D11_COMPARISON_ALWAYS,
-            MinLOD: 0.,
-            MaxLOD: 0.,
-            BorderColor: [1., 1., 1., 1.],
-            ..Default::default()
-        };
+    /// Switches between the Windows clipboard and one that never leaves the process.
+    /// Copy and paste keep working inside egui either way.
+    pub fn set_system_clipboard(&self, enabled: bool) {
+        let this = &mut *self.lock_data();
 
-        unsafe {
-            let mut sampler: Option<ID3D11SamplerState> = None;
+        this.config = std::mem::take(&mut this.config).system_clipboard(enabled);
+        self.lock_input().configure(&this.config);
+    }
 
-            expect!(
-                dev.CreateSamplerState(&desc, Some(&mut sampler)),
-                "Failed to create sampler"
-            );
+    /// Replaces the keys that reach the game while egui wants the keyboard,
+    /// see [`Config::key_passthrough`].
+    pub fn set_key_passthrough(&self, keys: KeyPassthrough) {
+        let this = &mut *self.lock_data();
 
-            if let Some(sampler) = sampler {
-                ctx.PSSetSamplers(0, Some(&[sampler]));
-            }
+        this.config.key_passthrough = keys;
+        self.lock_input().configure(&this.config);
+    }
+
+    /// Enables or disables capturing the mouse during drags, see [`Config::mouse_capture`].
+    pub fn set_mouse_capture(&self, enabled: bool) {
+        let this = &mut *self.lock_data();
+
+        this.config.mouse_capture = enabled;
+        self.lock_input().configure(&this.config);
+    }
+
+    /// Checks if the UI is currently drawn.
+    pub fn is_visible(&self) -> bool {
+        self.visible.load(Ordering::Relaxed)
+    }
+
+    /// Shows or hides the UI. While hidden, `present` doesn't touch the pipeline at all.
+    pub fn set_visible(&self, visible: bool) {
+        self.visible.store(visible, Ordering::Relaxed);
+
+        #[cfg(feature = "cursor-hooks")]
+        if !visible {
+            crate::cursor::set_frozen(self as *const Self as usize, false);
         }
     }
-}
 
-unsafe fn get_device_and_context(swap: &IDXGISwapChain) -> (ID3D11Device, ID3D11DeviceContext) {
-    let device: ID3D11Device = expect!(swap.GetDevice(), "Failed to get swapchain's device");
-    let ctx = device.GetImmediateContext();
+    /// Replaces the main UI closure, all resources and layers are kept.
+    pub fn set_ui(&self, ui: impl FnMut(&Context, &mut T) + 'static) {
+        self.lock_data().ui = Box::new(ui);
+    }
 
-    (
-        device,
-        expect!(ctx, "Failed to get device's immediate context"),
-    )
+    /// Adds a UI closure that runs every frame after the main one and the layers added before it.
+    /// Lets plugins contribute their own windows at runtime.
+    pub fn add_layer(
+        &self,
+        name: impl Into<String>,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+    ) -> LayerId {
+        self.lock_data().layers.add(name, ui)
+    }
+
+    /// Removes a layer, returns `false` if it was already removed.
+    pub fn remove_layer(&self, id: LayerId) -> bool {
+        self.lock_data().layers.remove(id)
+    }
+
+    /// Adds D3D11 drawing between egui's layers, it runs while the UI is drawn above every layer
+    /// of a lower order than `order` and below every one of a higher order. Within `order` it's
+    /// sorted like a layer that isn't an area, e.g. [`egui::Order::PanelResizeLine`] puts it
+    /// above the panels and the background and below every window.
+    ///
+    /// The hook gets the render target bound, with the depth buffer of
+    /// [`Config::callback_depth`] if there is one, and sets up the rest of the state it draws
+    /// with itself. While [`Config::render_scale`] is below 1 the UI is composited after all
+    /// hooks ran, so they end up below all of it.
+    pub fn add_draw_hook(
+        &self,
+        order: egui::Order,
+        hook: impl FnMut(&ID3D11Device, &ID3D11DeviceContext) + 'static,
+    ) -> DrawHookId {
+        self.lock_data().draw_hooks.add(order, hook)
+    }
+
+    /// Removes a draw hook, returns `false` if it was already removed.
+    pub fn remove_draw_hook(&self, id: DrawHookId) -> bool {
+        self.lock_data().draw_hooks.remove(id)
+    }
+
+    /// Runs `action` at the start of the next frame whenever `key` is pressed with exactly
+    /// `modifiers`, whether or not the UI is visible or has the keyboard focus.
+    pub fn register_hotkey(
+        &self,
+        modifiers: Modifiers,
+        key: Key,
+        action: impl FnMut(&mut T) + 'static,
+    ) -> HotkeyId {
+        let mut this = self.lock_data();
+        let id = this.hotkeys.add(modifiers, key, action);
+        self.lock_input().set_hotkeys(this.hotkeys.bindings());
+        id
+    }
+
+    /// Removes a hotkey, returns `false` if it was already removed.
+    pub fn unregister_hotkey(&self, id: HotkeyId) -> bool {
+        let mut this = self.lock_data();
+        let removed = this.hotkeys.remove(id);
+        self.lock_input().set_hotkeys(this.hotkeys.bindings());
+        removed
+    }
+
+    /// Lists the layers with their names in the order they are drawn.
+    pub fn layers(&self) -> Vec<(LayerId, String)> {
+        self.lock_data()
+            .layers
+            .names()
+            .map(|(id, name)| (id, name.to_owned()))
+            .collect()
+    }
+
+    /// Sets a callback that runs at the start of every frame, before the UI closure.
+    /// Meant for sampling the game's state, it's skipped while the UI is hidden.
+    pub fn set_frame_callback(&self, callback: impl FnMut(&mut T) + 'static) {
+        self.lock_data().frame_callback = Some(Box::new(callback));
+    }
+
+    /// Sets a callback that runs in `present` right before the UI is drawn, after the game's state was saved.
+    /// Content drawn here ends up below the UI. With [`Config::callback_depth`] it runs with the
+    /// render target and the app's depth buffer bound.
+    pub fn set_pre_draw(
+        &self,
+        callback: impl FnMut(&ID3D11Device, &ID3D11DeviceContext) + 'static,
+    ) {
+        self.lock_data().pre_draw = Some(Box::new(callback));
+    }
+
+    /// Sets a callback that runs in `present` right after the UI is drawn, before the game's state is restored.
+    /// Content drawn here ends up on top of the UI.
+    pub fn set_post_draw(
+        &self,
+        callback: impl FnMut(&ID3D11Device, &ID3D11DeviceContext) + 'static,
+    ) {
+        self.lock_data().post_draw = Some(Box::new(callback));
+    }
+
+    /// Sets the function that recreates user textures when the game recreated its device, e.g.
+    /// after a device loss. It gets the new device and the id of a texture the app has no copy
+    /// of, the returned texture is drawn under the same id from then on. Textures it returns
+    /// `None` for are freed and reported by [`Self::take_evicted_textures`].
+    /// egui's own textures are restored from [`Config::retain_textures`].
+    pub fn set_texture_restorer(
+        &self,
+        restorer: impl FnMut(
+                &ID3D11Device,
+                TextureId,
+            ) -> Option<(ID3D11Texture2D, ID3D11ShaderResourceView)>
+            + 'static,
+    ) {
+        self.lock_data().texture_restorer = Some(Box::new(restorer));
+    }
+
+    /// Sets the function that is called when drawing or resizing fails, instead of panicking.
+    /// The frame the error happened in is skipped and the game's pipeline state is restored.
+    /// Panics of the UI are caught and reported as [`Error::Panic`] too.
+    /// The app isn't locked while the handler runs, so it can be used from inside of it.
+    pub fn set_error_handler(&self, handler: fn(Error)) {
+        *self.error_handler.lock() = Some(handler);
+    }
+
+    /// Writes egui's memory into the file set with [`Config::persistence`].
+    /// Should be called on shutdown, does nothing if persistence isn't configured.
+    #[cfg(feature = "persistence")]
+    pub fn save_memory(&self) -> std::io::Result<()> {
+        let this = &mut *self.lock_data();
+
+        match this.persistence.as_mut() {
+            Some(persistence) => persistence.save(&this.ctx),
+            None => Ok(()),
+        }
+    }
+
+    /// The current options as they would be saved by [`Self::save_settings`].
+    #[cfg(feature = "settings")]
+    pub fn settings(&self) -> OverlaySettings {
+        let config = self.config();
+        OverlaySettings {
+            hotkeys: self.lock_data().settings.hotkeys.clone(),
+            ..OverlaySettings::from_config(&config)
+        }
+    }
+
+    /// Applies `settings` to the runtime options, they're saved by [`Self::save_settings`].
+    #[cfg(feature = "settings")]
+    pub fn set_settings(&self, settings: OverlaySettings) {
+        self.set_config(settings.apply(self.config()));
+        self.lock_data().settings = settings;
+    }
+
+    /// Writes the current options into the file set with [`Config::settings`].
+    /// Should be called on shutdown, does nothing if no file is configured.
+    #[cfg(feature = "settings")]
+    pub fn save_settings(&self) -> std::io::Result<()> {
+        let settings = self.settings();
+
+        match self.lock_data().config.settings.as_deref() {
+            Some(path) => settings.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the draw data of the next `count` frames into `path`,
+    /// load it with [`crate::replay::load_recording`] and draw it with [`Self::replay`].
+    #[cfg(feature = "replay")]
+    pub fn capture_frames(&self, count: usize, path: impl Into<std::path::PathBuf>) {
+        self.lock_data().recorder = (count > 0).then(|| Recorder::new(path.into(), count));
+    }
+
+    /// Draws the recorded `frames` instead of the UI, one per [`Self::present`] call.
+    /// Works best on a backbuffer of the recorded size, e.g. with the WARP test harness.
+    #[cfg(feature = "replay")]
+    pub fn replay(&self, frames: Vec<RecordedFrame>) {
+        self.lock_data().replay.extend(frames);
+    }
+
+    fn report(&self, error: Error) {
+        let handler = *self.error_handler.lock();
+
+        match handler {
+            Some(handler) => {
+                log_msg!(error, "{}", error);
+                handler(error);
+            }
+            None => panic_msg!("{}", error),
+        }
+    }
+
+    #[track_caller]
+    fn lock_data(&self) -> impl DerefMut<Target = AppData<T>> + '_ {
+        Self::map_data(self.lock_blocking(&self.data))
+    }
+
+    fn map_data(
+        guard: MutexGuard<'_, R, Option<AppData<T>>>,
+    ) -> impl DerefMut<Target = AppData<T>> + '_ {
+        MutexGuard::map(guard, |app| {
+            expect!(app.as_mut(), "You need to call init first")
+        })
+    }
+
+    /// Can be taken while holding `data`, never the other way around.
+    #[track_caller]
+    fn lock_input(&self) -> impl DerefMut<Target = InputCollector> + '_ {
+        MutexGuard::map(self.lock_blocking(&self.input), |input| {
+            expect!(input.as_mut(), "You need to call init first")
+        })
+    }
+
+    /// Takes `lock`, through the watchdog if one is configured.
+    /// Returns `None` if the watchdog wants the frame of a `skippable` call to be skipped.
+    #[track_caller]
+    fn lock_watched<'a, V>(
+        &self,
+        lock: &'a Watched<R, V>,
+        skippable: bool,
+    ) -> Option<MutexGuard<'a, R, V>> {
+        let location = Location::caller();
+        let watchdog = *self.watchdog.lock();
+
+        let guard = match watchdog {
+            Some((threshold, action)) => match lock_timeout(lock, threshold) {
+                Some(guard) => guard,
+                None => {
+                    self.report(Error::LockTimeout(lock.name(), location, lock.holder()));
+
+                    if skippable && action == WatchdogAction::SkipFrame {
+                        return None;
+                    }
+                    lock.lock()
+                }
+            },
+            None => lock.lock(),
+        };
+
+        Some(lock.taken_at(guard, location))
+    }
+
+    #[track_caller]
+    fn lock_blocking<'a, V>(&self, lock: &'a Watched<R, V>) -> MutexGuard<'a, R, V> {
+        match self.lock_watched(lock, false) {
+            Some(guard) => guard,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<T: Default, R: RawMutex> DirectX11App<T, R> {
+    /// Initializes application and sets the state to its default value. You should call this only once!
+    #[inline]
+    pub fn init_default(&self, swap: &IDXGISwapChain, ui: impl FnMut(&Context, &mut T) + 'static) {
+        self.init_with_state_context(swap, ui, T::default(), Context::default());
+    }
+}
+
+impl<T, R: RawMutex> DirectX11App<T, R> {
+    /// Present call. Should be called once per original present call, before or inside of hook.
+    /// Does nothing with [`SubmitPoint::Manual`], see [`Self::submit`].
+    pub fn present(&self, swap_chain: &IDXGISwapChain) {
+        self.present_at(swap_chain, None, SubmitPoint::Present);
+    }
+
+    /// Draws the UI into the current buffer of `swap_chain` if it's submitted with
+    /// [`SubmitPoint::Manual`], does nothing otherwise. Call it once per frame after the game
+    /// drew into the buffer and before it presents, at the point the frame's GPU work should
+    /// include the UI, e.g. ahead of the game's own blocking waits on the GPU.
+    pub fn submit(&self, swap_chain: &IDXGISwapChain) {
+        self.present_at(swap_chain, None, SubmitPoint::Manual);
+    }
+
+    /// Like [`Self::present`], but confines the UI to `size` pixels of the backbuffer starting
+    /// at `origin` instead of laying it out on the window's client area, e.g. for games that
+    /// draw into a part of their buffer. Input is moved into the area as well.
+    pub fn present_with_size(
+        &self,
+        swap_chain: &IDXGISwapChain,
+        origin: (u32, u32),
+        size: (u32, u32),
+    ) {
+        self.present_at(swap_chain, Some((origin, size)), SubmitPoint::Present);
+    }
+
+    fn present_at(
+        &self,
+        swap_chain: &IDXGISwapChain,
+        region: Option<((u32, u32), (u32, u32))>,
+        point: SubmitPoint,
+    ) {
+        let this = match self.lock_watched(&self.data, true) {
+            Some(guard) => guard,
+            None => return,
+        };
+        let mut this = Self::map_data(this);
+        if this.config.submit_point != point {
+            return;
+        }
+        let result = unsafe { self.present_impl(&mut this, swap_chain, region) };
+
+        if let Err(e) = result {
+            self.report(e);
+        }
+    }
+
+    /// Draws the UI into `render_view` instead of a swapchain's buffer, for apps initialized with
+    /// [`Self::init_with_device`]. `size` is the area of the target the UI is laid out on.
+    /// Should be called once per frame, on the thread that owns the device's immediate context.
+    pub fn paint_to(&self, render_view: &ID3D11RenderTargetView, size: (u32, u32)) {
+        let this = match self.lock_watched(&self.data, true) {
+            Some(guard) => guard,
+            None => return,
+        };
+        let result = unsafe { self.paint_impl(&mut Self::map_data(this), render_view, size) };
+
+        if let Err(e) = result {
+            self.report(e);
+        }
+    }
+
+    unsafe fn paint_impl(
+        &self,
+        this: &mut AppData<T>,
+        render_view: &ID3D11RenderTargetView,
+        size: (u32, u32),
+    ) -> Result<(), Error> {
+        let clock = FrameClock::start();
+        let result = self.paint_frame(this, render_view, size);
+        // frames the UI isn't drawn in don't take them
+        self.meshes.lock().clear();
+        this.timings
+            .push(clock.finish(), this.config.frame_timings, this.config.slow_frame);
+        result
+    }
+
+    unsafe fn paint_frame(
+        &self,
+        this: &mut AppData<T>,
+        render_view: &ID3D11RenderTargetView,
+        size: (u32, u32),
+    ) -> Result<(), Error> {
+        profile_span!("present");
+
+        let Some(input) = self.begin_frame(this) else {
+            return Ok(());
+        };
+
+        let dev = this.device.clone();
+        let ctx = dev
+            .GetImmediateContext()
+            .context("Failed to get device's immediate context")?;
+        let target = FrameTarget::from_view(render_view, size)?;
+
+        self.draw_frame(this, &dev, &ctx, &target, input)
+    }
+
+    unsafe fn present_impl(
+        &self,
+        this: &mut AppData<T>,
+        swap_chain: &IDXGISwapChain,
+        region: Option<((u32, u32), (u32, u32))>,
+    ) -> Result<(), Error> {
+        let clock = FrameClock::start();
+        let result = self.present_frame(this, swap_chain, region);
+        // frames the UI isn't drawn in don't take them
+        self.meshes.lock().clear();
+        if this.config.resize_fallback {
+            // the game may resize its buffers before the next frame without telling us
+            this.render_views.clear();
+            this.backend.release_buffers();
+        }
+        this.timings
+            .push(clock.finish(), this.config.frame_timings, this.config.slow_frame);
+        result
+    }
+
+    unsafe fn present_frame(
+        &self,
+        this: &mut AppData<T>,
+        swap_chain: &IDXGISwapChain,
+        region: Option<((u32, u32), (u32, u32))>,
+    ) -> Result<(), Error> {
+        profile_span!("present");
+
+        #[cfg(feature = "dcomp")]
+        if this.composition.is_some() {
+            return self.present_composition(this, swap_chain);
+        }
+
+        let Some(input) = self.begin_frame(this) else {
+            return Ok(());
+        };
+
+        let (dev, ctx, backbuffer) = &match this.backend.device_and_buffer(swap_chain) {
+            Ok(buffer) => buffer,
+            Err(e) => return Self::buffer_failed(this, e),
+        };
+        if dev.as_raw() != this.device.as_raw() {
+            Self::recreate_device(this, swap_chain, dev, backbuffer)?;
+        }
+        this.backend.acquire(backbuffer);
+        let size = backbuffer_size(backbuffer);
+        if this.buffer_size.replace(size).is_some_and(|old| old != size) {
+            Self::follow_resize(this, swap_chain, backbuffer);
+        }
+        this.input.set_display_scale(fullscreen_scale(swap_chain, size));
+
+        // Flip model chains may hand out another buffer after `ResizeBuffers` or a device
+        // change without going through our hook, the view has to follow it.
+        let wanted_srgb = this.config.wanted_srgb_view();
+        let result = match this.render_views.get(dev, ctx, backbuffer, wanted_srgb) {
+            Ok(render_view) => {
+                let target = FrameTarget {
+                    view: render_view.view.clone(),
+                    srgb: render_view.srgb,
+                    format: render_view.format,
+                    texture: backbuffer.clone(),
+                    size: match region {
+                        Some((_, (width, height))) => (width as f32, height as f32),
+                        None => backbuffer_size(backbuffer),
+                    },
+                    origin: region.map(|((x, y), _)| (x as f32, y as f32)),
+                };
+                this.buffer_failures = 0;
+                self.draw_frame(this, dev, ctx, &target, input)
+            }
+            Err(e) => Self::buffer_failed(this, e),
+        };
+
+        this.backend.release(backbuffer);
+
+        result
+    }
+
+    /// Applies [`Config::buffer_failure`] to a backbuffer or view that couldn't be acquired,
+    /// `Ok` skips the frame without reporting it.
+    fn buffer_failed(this: &mut AppData<T>, error: Error) -> Result<(), Error> {
+        this.buffer_failures += 1;
+        let skip = match this.config.buffer_failure {
+            BufferFailure::Report => false,
+            BufferFailure::SkipFrame => true,
+            BufferFailure::Retry(presents) => this.buffer_failures < presents,
+        };
+
+        if skip {
+            log_msg!(warn, "Skipped frame: {}", error);
+            return Ok(());
+        }
+        this.buffer_failures = 0;
+        Err(error)
+    }
+
+    /// Moves everything the app allocated to `dev` after the game recreated its device and
+    /// swapchain, textures keep their ids.
+    unsafe fn recreate_device(
+        this: &mut AppData<T>,
+        swap_chain: &IDXGISwapChain,
+        dev: &ID3D11Device,
+        backbuffer: &ID3D11Texture2D,
+    ) -> Result<(), Error> {
+        log_msg!(warn, "The swapchain's device changed, recreating resources");
+
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        backbuffer.GetDesc(&mut desc);
+        this.detected_color_space = this
+            .swap_chain_color_space
+            .unwrap_or_else(|| ColorSpace::detect(swap_chain, desc.Format));
+
+        let color_space = this.config.color_space.unwrap_or(this.detected_color_space);
+        let shaders = CompiledShaders::new(dev, &this.config, color_space, this.shaders.linear)?;
+        let multisample = desc.SampleDesc.Count > 1;
+        this.pipeline = Pipeline::new(dev, &shaders, multisample, &this.config)?;
+        this.shaders = shaders;
+        this.constants = ConstantBuffer::new(dev, ShaderConstants::default());
+        this.buffers = BufferPool::default();
+        this.max_texture_side = max_texture_side(dev);
+        this.capabilities.follow_device(dev);
+        this.capabilities.color_space = this.detected_color_space;
+        let mut swap_desc = DXGI_SWAP_CHAIN_DESC::default();
+        if swap_chain.GetDesc(&mut swap_desc).is_ok() {
+            this.capabilities.follow_swap_chain(&swap_desc);
+        }
+
+        this.render_views.clear();
+        this.deferred = None;
+        this.scaled = None;
+        this.depth = None;
+        if let Some(mirror) = this.mirror.as_mut() {
+            *mirror = BackbufferMirror::default();
+        }
+
+        let restorer = &mut this.texture_restorer;
+        let lost = this.tex_alloc.recreate(dev, |tid| {
+            let restore = restorer.as_mut()?;
+            catch_unwind(AssertUnwindSafe(|| restore(dev, tid)))
+                .ok()
+                .flatten()
+        });
+        this.evicted.extend(lost);
+        this.device = dev.clone();
+
+        Ok(())
+    }
+
+    /// Catches up with buffers resized without [`Self::resize_buffers`], the views and the
+    /// viewport follow the buffer anyway.
+    unsafe fn follow_resize(
+        this: &mut AppData<T>,
+        swap_chain: &IDXGISwapChain,
+        backbuffer: &ID3D11Texture2D,
+    ) {
+        log_msg!(info, "Backbuffer resized to {:?} outside of resize_buffers", this.buffer_size);
+
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        backbuffer.GetDesc(&mut desc);
+        this.detected_color_space = this
+            .swap_chain_color_space
+            .unwrap_or_else(|| ColorSpace::detect(swap_chain, desc.Format));
+
+        // views of replaced buffers would keep them alive
+        this.render_views.clear();
+        this.depth = None;
+        this.input.refresh_client_size();
+    }
+
+    /// Draws into the composition layer instead of `swap_chain`, which only gives the size.
+    /// The layer is cleared and presented even while hidden, it would keep the last UI otherwise.
+    #[cfg(feature = "dcomp")]
+    unsafe fn present_composition(
+        &self,
+        this: &mut AppData<T>,
+        swap_chain: &IDXGISwapChain,
+    ) -> Result<(), Error> {
+        let input = self.begin_frame(this);
+
+        let size = match this.input.client_size() {
+            Some(size) => (size.x as u32, size.y as u32),
+            None => {
+                let mut desc = DXGI_SWAP_CHAIN_DESC::default();
+                swap_chain
+                    .GetDesc(&mut desc)
+                    .context("Failed to get swapchain's description")?;
+                (desc.BufferDesc.Width, desc.BufferDesc.Height)
+            }
+        };
+
+        let dev = this.device.clone();
+        let ctx = dev
+            .GetImmediateContext()
+            .context("Failed to get device's immediate context")?;
+
+        let layer = this
+            .composition
+            .as_mut()
+            .context("Composition was disabled")?;
+        layer.resize(size)?;
+        let buffer = layer.buffer()?;
+        let target = layer
+            .render_views
+            .get(&dev, &ctx, &buffer, Some(false))
+            .map(|render_view| FrameTarget {
+                view: render_view.view.clone(),
+                srgb: false,
+                format: render_view.format,
+                texture: buffer.clone(),
+                size: backbuffer_size(&buffer),
+                origin: None,
+            })?;
+        ctx.ClearRenderTargetView(&target.view, [0.; 4].as_ptr());
+
+        let result = match input {
+            Some(input) => {
+                let alpha_mode =
+                    std::mem::replace(&mut this.config.alpha_mode, AlphaMode::Premultiplied);
+                let color_space =
+                    std::mem::replace(&mut this.detected_color_space, ColorSpace::Srgb);
+                let forced_space = this.config.color_space.take();
+
+                let result = self.draw_frame(this, &dev, &ctx, &target, input);
+
+                this.config.alpha_mode = alpha_mode;
+                this.detected_color_space = color_space;
+                this.config.color_space = forced_space;
+                result
+            }
+            None => Ok(()),
+        };
+
+        let layer = this
+            .composition
+            .as_ref()
+            .context("Composition was disabled")?;
+        result.and(layer.present())
+    }
+
+    /// Handles what happens every frame even without drawing, returns the input if the UI
+    /// should be drawn.
+    unsafe fn begin_frame(&self, this: &mut AppData<T>) -> Option<RawInput> {
+        // Collected even while hidden, events would pile up otherwise.
+        if this.input.take_toggled() {
+            self.visible.fetch_xor(true, Ordering::Relaxed);
+        }
+        while let Some(id) = this.input.take_hotkey() {
+            this.hotkeys.run(id, &mut self.lock_state());
+        }
+        let input = {
+            profile_span!("collect_input");
+            let _stage = StageTimer::enter(FrameStage::Input);
+            this.input.collect_input()
+        };
+
+        if !self.is_visible() {
+            this.input.platform.hide_cursor(false);
+            return None;
+        }
+
+        // Nothing can be seen, the UI continues where it left off once the window is restored.
+        if IsIconic(*expect!(self.hwnd.get(), "You need to call init first")).as_bool() {
+            return None;
+        }
+
+        if let Some(frame_callback) = this.frame_callback.as_mut() {
+            frame_callback(&mut self.lock_state());
+        }
+
+        Some(input)
+    }
+
+    /// Runs the UI and draws it into `target` with the game's state saved around it.
+    unsafe fn draw_frame(
+        &self,
+        this: &mut AppData<T>,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        target: &FrameTarget,
+        input: RawInput,
+    ) -> Result<(), Error> {
+        // the buffer only holds the game's frame yet
+        if let Some(region) = this.region_capture.take_selection() {
+            match read_texture_region(dev, ctx, &target.texture, region) {
+                Ok(frame) => this.region_capture.finish(frame),
+                Err(e) => self.report(e),
+            }
+        }
+
+        let Some(mut output) = self.run_ui(this, dev, ctx, target, input)? else {
+            return Ok(());
+        };
+        output.meshes = std::mem::take(&mut *self.meshes.lock());
+
+        #[cfg(feature = "frame-debug")]
+        {
+            this.last_frame = FrameDebug::new(output.screen, output.ppp);
+            this.last_frame.primitives = output.primitives.clone();
+        }
+
+        // Nothing would change on screen, the game's state isn't even saved.
+        let gpu_work = this.config.clear_color.is_some()
+            || this.pre_draw.is_some()
+            || this.post_draw.is_some()
+            || this.tex_alloc.has_pending_uploads()
+            || this
+                .deferred
+                .as_ref()
+                .is_some_and(DeferredUploads::is_recorded);
+        if output.is_empty() && !gpu_work {
+            return Ok(());
+        }
+
+        let backup = this.config.backup == BackupMode::Full;
+        if backup {
+            let _stage = StageTimer::enter(FrameStage::Backup);
+            this.backup.save(ctx);
+        }
+
+        let result = match this.config.callback_depth {
+            Some(format)
+                if this.pre_draw.is_some()
+                    || this.post_draw.is_some()
+                    || !this.draw_hooks.is_empty() =>
+            {
+                DepthTarget::prepare(&mut this.depth, dev, ctx, target, format)
+            }
+            _ => Ok(()),
+        };
+
+        // the game's state is restored even if a callback panics
+        let result = match (result, this.pre_draw.as_mut()) {
+            (Ok(()), Some(pre_draw)) => {
+                if let Some(depth) = &this.depth {
+                    depth.bind(ctx, target);
+                }
+                catch_unwind(AssertUnwindSafe(|| pre_draw(dev, ctx))).map_err(Error::from_panic)
+            }
+            (result, _) => result,
+        };
+
+        let result = result.and_then(|()| Self::render(this, dev, ctx, target, output));
+
+        if let Some(budget) = this.config.memory_budget {
+            let buffers = this.buffers.bytes() + size_of::<ShaderConstants>();
+            let evicted = this.tex_alloc.enforce_budget(budget, buffers);
+            this.evicted.extend(evicted);
+        }
+
+        let result = match (result, this.post_draw.as_mut()) {
+            (Ok(()), Some(post_draw)) => {
+                if let Some(depth) = &this.depth {
+                    depth.bind(ctx, target);
+                }
+                catch_unwind(AssertUnwindSafe(|| post_draw(dev, ctx))).map_err(Error::from_panic)
+            }
+            (result, _) => result,
+        };
+
+        if backup {
+            let _stage = StageTimer::enter(FrameStage::Backup);
+            this.backup.restore(ctx);
+        }
+
+        result
+    }
+
+    /// Runs the UI for `target` without touching the pipeline, `None` if there is no area to
+    /// lay it out on.
+    #[cfg_attr(not(feature = "replay"), allow(unused_variables))]
+    unsafe fn run_ui(
+        &self,
+        this: &mut AppData<T>,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        target: &FrameTarget,
+        mut input: RawInput,
+    ) -> Result<Option<FrameOutput>, Error> {
+        let (screen, transform) = match (target.origin, this.config.transform) {
+            // input is mapped onto the whole buffer first, then moved into the area
+            (Some(origin), transform) => {
+                let transform = transform
+                    .or_else(|| {
+                        let buffer = backbuffer_size(&target.texture).into();
+                        let client = this.input.input_size(buffer);
+                        this.config.screen_size.resolve(client, buffer).1
+                    })
+                    .unwrap_or(ScreenTransform::IDENTITY);
+
+                (target.size.into(), Some(transform.shifted(origin.into())))
+            }
+            (None, Some(transform)) => (target.size.into(), Some(transform)),
+            (None, None) => {
+                let buffer = target.size.into();
+                let client = this.input.input_size(buffer);
+                this.config.screen_size.resolve(client, buffer)
+            }
+        };
+
+        this.input.set_cursor_transform(transform);
+        if let Some(transform) = &transform {
+            transform_input(&mut input, transform);
+        }
+        input.screen_rect = Some(Rect::from_min_size(Pos2::ZERO, screen));
+        let screen = (screen.x, screen.y);
+
+        // Zero sized viewports are rejected by some drivers and there would be no pixels anyway.
+        if screen.0 < 1. || screen.1 < 1. {
+            return Ok(None);
+        }
+
+        let ppp = this.input.pixels_per_point() * this.config.ui_scale;
+        scale_input(&mut input, ppp);
+
+        #[cfg(feature = "replay")]
+        if let Some(frame) = this.replay.pop_front() {
+            return Ok(Some(FrameOutput {
+                screen: (frame.screen_size[0], frame.screen_size[1]),
+                ppp: frame.pixels_per_point,
+                textures_delta: frame.textures_delta,
+                primitives: RecordedFrame::primitives(frame.meshes),
+                meshes: vec![],
+            }));
+        }
+
+        if let Some(cache) = &this.ui_cache {
+            let repaint = this.repaint_requested.load(Ordering::Relaxed);
+            if let Some(output) = cache.reuse(&input, screen, ppp, this.config.max_ui_fps, repaint)
+            {
+                return Ok(Some(output));
+            }
+        }
+        this.repaint_requested.store(false, Ordering::Relaxed);
+
+        input.max_texture_side = Some(this.max_texture_side);
+
+        // the UI runs while the allocator is locked, so the inspector gets a copy
+        #[cfg(feature = "debug-ui")]
+        crate::debug_ui::publish_textures(&this.ctx, this.tex_alloc.textures());
+
+        #[cfg(feature = "accesskit")]
+        if let Some(accesskit) = self.accesskit.lock().as_ref() {
+            input.events.extend(accesskit.take_events());
+        }
+
+        let software_cursor = this.config.software_cursor;
+        let profiler = this.config.profiler;
+        let origin = target.origin.unwrap_or_default();
+        let mut output: UiOutput = {
+            // held only while the UI runs, not while it's drawn
+            let state = &mut *self.lock_state();
+            // A panic must not unwind into the game, the frame is skipped and the UI runs again
+            // on the next one.
+            catch_unwind(AssertUnwindSafe(|| {
+                this.ctx.run(input, |ctx| {
+                    profile_span!("run_ui");
+                    let _stage = StageTimer::enter(FrameStage::Ui);
+
+                    (this.ui)(ctx, state);
+                    this.layers.run(ctx, state);
+                    this.draw_hooks.paint_markers(ctx);
+                    if profiler {
+                        this.timings.show(ctx);
+                    }
+                    this.region_capture.show(ctx, origin);
+
+                    if software_cursor && (ctx.wants_pointer_input() || ctx.is_pointer_over_area())
+                    {
+                        paint_software_cursor(ctx);
+                    }
+                })
+            }))
+            .map_err(Error::from_panic)?
+            .into()
+        };
+
+        this.input.platform.hide_cursor(
+            software_cursor && (this.ctx.wants_pointer_input() || this.ctx.is_pointer_over_area()),
+        );
+
+        #[cfg(feature = "cursor-hooks")]
+        crate::cursor::set_frozen(self as *const Self as usize, this.ctx.wants_pointer_input());
+
+        #[cfg(feature = "persistence")]
+        if let Some(persistence) = this.persistence.as_mut() {
+            persistence.save_if_due(&this.ctx);
+        }
+
+        #[cfg(feature = "accesskit")]
+        if let Some(update) = output.platform_output.accesskit_update.take() {
+            if let Some(accesskit) = self.accesskit.lock().as_mut() {
+                accesskit.update(update);
+            }
+        }
+
+        this.input
+            .platform
+            .update(&output.platform_output, transform.as_ref(), ppp);
+
+        this.platform_worker
+            .apply(&mut output.platform_output, &this.config.clipboard);
+
+        let primitives = if output.shapes.is_empty() {
+            vec![]
+        } else {
+            let quality = this.config.line_quality;
+            if this.config.tessellation.is_some() || quality != LineQuality::Default {
+                let mut options = this.config.tessellation.unwrap_or_default();
+                quality.apply(&mut options);
+                this.ctx
+                    .tessellation_options_mut(|current| *current = options);
+            }
+
+            let mut shapes = output.shapes;
+            let clip_rects = this.config.clip_rects;
+            let ctx = &this.ctx;
+            catch_unwind(AssertUnwindSafe(|| {
+                if clip_rects {
+                    let outlines = clip_rect_outlines(&shapes);
+                    shapes.extend(outlines);
+                }
+
+                profile_span!("tessellate");
+                let _stage = StageTimer::enter(FrameStage::Tessellate);
+                #[cfg(feature = "rayon")]
+                let primitives = crate::parallel::tessellate(ctx, shapes);
+                #[cfg(not(feature = "rayon"))]
+                let primitives = ctx.tessellate(shapes);
+                primitives
+            }))
+            .map_err(Error::from_panic)?
+        };
+
+        this.ui_cache = this.config.max_ui_fps.map(|_| UiCache {
+            ran_at: Instant::now(),
+            screen,
+            ppp,
+            primitives: primitives.clone(),
+            repaint_after: output.repaint_after,
+        });
+
+        #[allow(unused_mut)]
+        let mut textures_delta = output.textures_delta;
+
+        #[cfg(feature = "replay")]
+        if let Some(recorder) = this.recorder.as_mut() {
+            if recorder.is_starting() {
+                let mut snapshot = this.tex_alloc.snapshot(dev, ctx);
+                snapshot.set.append(&mut textures_delta.set);
+                textures_delta.set = snapshot.set;
+            }
+
+            let frame = RecordedFrame::new(
+                [screen.0, screen.1],
+                ppp,
+                textures_delta.clone(),
+                &primitives,
+            );
+            if recorder.record(frame) {
+                this.recorder = None;
+            }
+        }
+
+        Ok(Some(FrameOutput {
+            screen,
+            ppp,
+            textures_delta,
+            primitives,
+            meshes: vec![],
+        }))
+    }
+
+    /// Draws what [`Self::run_ui`] produced, after what has to happen every drawn frame.
+    unsafe fn render(
+        this: &mut AppData<T>,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        target: &FrameTarget,
+        output: FrameOutput,
+    ) -> Result<(), Error> {
+        if let Some(mirror) = this.mirror.as_mut() {
+            mirror.update(dev, ctx, &target.texture, &mut this.tex_alloc)?;
+        }
+
+        match (this.config.deferred_uploads, this.deferred.as_mut()) {
+            (true, None) => this.deferred = Some(DeferredUploads::new(dev)?),
+            (true, Some(deferred)) => deferred.execute(ctx),
+            (false, Some(deferred)) => {
+                deferred.execute(ctx);
+                this.deferred = None;
+            }
+            (false, None) => {}
+        }
+
+        let color_space = this.config.color_space.unwrap_or(this.detected_color_space);
+
+        if let Some(color) = this.config.clear_color {
+            let color = match color_space {
+                // the view does the encoding
+                ColorSpace::Srgb if target.srgb => color.to_array(),
+                _ => color_space.encode(color, this.config.sdr_white_level),
+            };
+
+            ctx.ClearRenderTargetView(&target.view, color.as_ptr());
+        }
+
+        Self::draw(this, dev, ctx, target, output)
+    }
+
+    /// Uploads the output's textures and draws its primitives into the render target.
+    unsafe fn draw(
+        this: &mut AppData<T>,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        target: &FrameTarget,
+        output: FrameOutput,
+    ) -> Result<(), Error> {
+        let FrameOutput {
+            screen,
+            ppp,
+            textures_delta,
+            primitives,
+            meshes: painted,
+        } = output;
+        let (render_view, srgb, format) = (&target.view, target.srgb, target.format);
+        let uploads = match this.deferred.as_ref() {
+            Some(deferred) => &deferred.context,
+            None => ctx,
+        };
+
+        this.tex_alloc.set_retain_pixels(this.config.retain_textures);
+        this.tex_alloc.set_atlas_budget(this.config.staged_atlas);
+        let upload = StageTimer::enter(FrameStage::Upload);
+        let mut recorded = !textures_delta.is_empty();
+        if recorded {
+            profile_span!("upload_textures");
+            this.tex_alloc
+                .process_deltas(dev, uploads, textures_delta)?;
+        }
+        recorded |= this
+            .tex_alloc
+            .flush_uploads(uploads, this.config.upload_budget);
+        // on the immediate context, so the new atlas isn't swapped in before its last rows
+        // were copied by deferred uploads
+        this.tex_alloc.flush_atlas(dev, ctx)?;
+        drop(upload);
+
+        // the old atlas is drawn until the new one is complete, with primitives made for it
+        let primitives = if this.tex_alloc.is_staging_atlas() {
+            this.atlas_frame.clone()
+        } else {
+            match this.config.staged_atlas {
+                Some(_) => this.atlas_frame.clone_from(&primitives),
+                None => this.atlas_frame.clear(),
+            }
+            primitives
+        };
+
+        if let Some(deferred) = this.deferred.as_mut() {
+            if recorded {
+                deferred.finish()?;
+            }
+        }
+
+        if primitives.is_empty() && painted.is_empty() {
+            return Ok(());
+        }
+
+        let color_space = this.config.color_space.unwrap_or(this.detected_color_space);
+
+        let linear_blending = this.config.color_blending == Some(ColorBlending::Linear);
+        if this.shaders.linear_blending() != linear_blending {
+            log_msg!(info, "Switching to linear blending: {}", linear_blending);
+            this.shaders.set_linear_blending(dev, linear_blending)?;
+        }
+
+        if this.shaders.color_space != color_space || this.shaders.linear != srgb {
+            log_msg!(
+                info,
+                "Switching output to {:?}, sRGB view: {}",
+                color_space,
+                srgb
+            );
+            this.shaders.set_color_space(dev, color_space, srgb)?;
+        }
+
+        let constants = ShaderConstants {
+            screen_size: [screen.0, screen.1],
+            sdr_white_level: this.config.sdr_white_level,
+            // one step of the target's precision, 10 bit targets need less noise
+            dither: if this.config.dither {
+                1. / ((1u32 << channel_bits(format).0) - 1) as f32
+            } else {
+                0.
+            },
+            // every covering triangle adds a bit of red, then yellow
+            tint: if this.config.overdraw {
+                OVERDRAW_TINT
+            } else {
+                [0.; 4]
+            },
+            gamma_exponent: 1. / this.config.gamma.max(f32::EPSILON),
+            brightness: this.config.brightness,
+            _padding: [0.; 2],
+        };
+        this.pipeline.update(dev, &this.config, format)?;
+
+        let scale = this.config.render_scale;
+        if scale < 1. {
+            this.shaders.prepare_gamma_pixel(dev)?;
+            let size = ScaledTarget::size_for(screen, scale);
+            if this.scaled.as_ref().map(|target| target.size) != Some(size) {
+                this.scaled = Some(ScaledTarget::new(dev, size)?);
+            }
+        } else {
+            this.scaled = None;
+        }
+
+        // the scaled target gets the colors as egui produced them, the composite pass adjusts them
+        let ui_constants = match &this.scaled {
+            Some(_) => ShaderConstants {
+                dither: 0.,
+                gamma_exponent: 1.,
+                brightness: 1.,
+                ..constants
+            },
+            None => constants,
+        };
+        this.constants.update(ctx, ui_constants);
+
+        // the area on the render target, the scaled target is always drawn from its corner
+        let area = Rect::from_min_size(target.origin.map_or(Pos2::ZERO, Pos2::from), screen.into());
+        let clip_scale = if this.scaled.is_some() { scale } else { 1. };
+        let target_size = (screen.0 * clip_scale, screen.1 * clip_scale);
+        let ui_origin = match &this.scaled {
+            Some(_) => Pos2::ZERO,
+            None => area.min,
+        };
+
+        let FrameScratch {
+            meshes,
+            scissors,
+            ranges,
+            hooks,
+            instances,
+            offsets,
+            runs,
+        } = &mut this.scratch;
+        meshes.clear();
+        scissors.clear();
+        hooks.clear();
+        runs.clear();
+
+        // the markers of draw hooks split the primitives into runs, each hook runs after the
+        // meshes of the run before it
+        runs.push((vec![], None));
+        for prim in primitives {
+            let hook = compat::paint_callback(&prim).and_then(draw_hook);
+            if let Some((run, after)) = runs.last_mut() {
+                match hook {
+                    Some(id) => *after = Some(id),
+                    None => run.push(prim),
+                }
+            }
+            if hook.is_some() {
+                runs.push((vec![], None));
+            }
+        }
+
+        // painted meshes go first, below everything of the UI
+        let mut painted = Some(painted);
+        for (run, after) in runs.drain(..) {
+            #[cfg(feature = "rayon")]
+            let converted = crate::parallel::gpu_meshes(run, ppp);
+            #[cfg(not(feature = "rayon"))]
+            let converted = run
+                .into_iter()
+                .filter_map(|prim| GpuMesh::from_primitive(prim, ppp));
+
+            for mesh in painted.take().into_iter().flatten().chain(converted) {
+                let clip = Rect::from_min_max(
+                    (mesh.clip.min.to_vec2() * clip_scale).to_pos2(),
+                    (mesh.clip.max.to_vec2() * clip_scale).to_pos2(),
+                );
+                if let Some(scissor) = scissor_rect(clip, [target_size.0, target_size.1]) {
+                    scissors.push(offset_rect(scissor, ui_origin));
+                    meshes.push(mesh);
+                }
+            }
+
+            if let Some(id) = after {
+                hooks.push((meshes.len(), id));
+            }
+        }
+
+        if meshes.is_empty() && hooks.is_empty() {
+            return Ok(());
+        }
+
+        if this.config.instancing {
+            profile_span!("batch_instances");
+            let breaks = |i| hooks.iter().any(|(at, _)| *at == i);
+            batch_instances(meshes, scissors, breaks, instances, offsets);
+        } else {
+            instances.clear();
+            offsets.clear();
+            offsets.push([0.; 2]);
+        }
+
+        // drawn last, from the same buffers as the UI
+        let ui_meshes = meshes.len();
+        if let Some(mesh) = this
+            .scaled
+            .as_ref()
+            .and_then(|target| target.composite_mesh(screen, scale))
+        {
+            meshes.push(mesh);
+        }
+
+        {
+            profile_span!("upload_buffers");
+            let _stage = StageTimer::enter(FrameStage::Upload);
+            this.buffers.upload(dev, ctx, meshes, ranges)?;
+            this.buffers.upload_instances(dev, ctx, offsets)?;
+        }
+
+        profile_span!("submit");
+        let _stage = StageTimer::enter(FrameStage::Draw);
+
+        let (ui_view, pass) = match &this.scaled {
+            Some(target) => {
+                ctx.ClearRenderTargetView(&target.view, [0f32; 4].as_ptr());
+                (&target.view, Pass::Offscreen)
+            }
+            None => (render_view, Pass::Direct),
+        };
+        this.pipeline.bind(
+            ctx,
+            &this.shaders,
+            &this.constants,
+            &this.buffers,
+            ui_view,
+            Rect::from_min_size(ui_origin, target_size.into()),
+            pass,
+        );
+
+        let slots = this.shaders.slots();
+        let mut bound = BoundState::default();
+        let mut pending_hooks = hooks.iter().copied().peekable();
+
+        for (i, ((mesh, range), scissor)) in meshes
+            .iter()
+            .zip(ranges.iter())
+            .zip(scissors.iter())
+            .enumerate()
+            .take(ui_meshes)
+        {
+            if pending_hooks.peek().is_some_and(|(at, _)| *at <= i) {
+                while let Some((_, id)) = pending_hooks.next_if(|(at, _)| *at <= i) {
+                    run_draw_hook(&mut this.draw_hooks, id, dev, ctx, target, &this.depth)?;
+                }
+
+                this.pipeline.bind(
+                    ctx,
+                    &this.shaders,
+                    &this.constants,
+                    &this.buffers,
+                    ui_view,
+                    Rect::from_min_size(ui_origin, target_size.into()),
+                    pass,
+                );
+                bound = BoundState::default();
+            }
+
+            // drawn as an instance of an earlier mesh
+            let mesh_instances = instances.get(i).copied().unwrap_or(MeshInstances::SINGLE);
+            if mesh_instances.count == 0 {
+                continue;
+            }
+
+            let texture = this.tex_alloc.get_by_id(mesh.texture_id);
+
+            if this.config.wireframe {
+                this.constants.update(
+                    ctx,
+                    ShaderConstants {
+                        tint: mesh_tint(i),
+                        ..ui_constants
+                    },
+                );
+            }
+
+            if bound.scissor.as_ref() != Some(scissor) {
+                ctx.RSSetScissorRects(Some(std::slice::from_ref(scissor)));
+                bound.scissor = Some(*scissor);
+            }
+
+            #[cfg(feature = "frame-debug")]
+            this.last_frame.push_draw(
+                mesh.texture_id,
+                texture.is_some(),
+                scissor,
+                mesh.indices.len(),
+                mesh.vertices.len(),
+            );
+
+            if let Some((texture, sampler)) = texture {
+                // consecutive meshes mostly share the font atlas
+                if bound.texture != Some(mesh.texture_id) {
+                    ctx.PSSetShaderResources(slots.texture, Some(&[texture]));
+                    bound.texture = Some(mesh.texture_id);
+                }
+
+                if sampler != bound.sampler {
+                    let state = this.pipeline.sampler(dev, sampler)?;
+                    ctx.PSSetSamplers(slots.sampler, Some(&[state]));
+                    bound.sampler = sampler;
+                }
+            }
+
+            ctx.IASetIndexBuffer(this.buffers.index_buffer(mesh), mesh.indices.format(), 0);
+            ctx.DrawIndexedInstanced(
+                mesh.indices.len() as _,
+                mesh_instances.count,
+                range.first_index,
+                range.base_vertex,
+                mesh_instances.first,
+            );
+        }
+
+        for (_, id) in pending_hooks {
+            run_draw_hook(&mut this.draw_hooks, id, dev, ctx, target, &this.depth)?;
+        }
+
+        if let (Some(target), Some(mesh), Some(range)) =
+            (&this.scaled, meshes.get(ui_meshes), ranges.get(ui_meshes))
+        {
+            profile_span!("composite");
+
+            this.constants.update(
+                ctx,
+                ShaderConstants {
+                    tint: [0.; 4],
+                    ..constants
+                },
+            );
+            this.pipeline.bind(
+                ctx,
+                &this.shaders,
+                &this.constants,
+                &this.buffers,
+                render_view,
+                area,
+                Pass::Composite,
+            );
+
+            let sampler = this.pipeline.sampler(
+                dev,
+                SamplerOverride {
+                    options: Some(TextureOptions::LINEAR),
+                    address: Some(AddressMode::Clamp),
+                    sampling: None,
+                },
+            )?;
+            ctx.PSSetSamplers(slots.sampler, Some(&[sampler]));
+            ctx.PSSetShaderResources(slots.texture, Some(std::slice::from_ref(&target.resource)));
+            if let Some(scissor) = scissor_rect(mesh.clip, [screen.0, screen.1]) {
+                ctx.RSSetScissorRects(Some(&[offset_rect(scissor, area.min)]));
+            }
+
+            ctx.IASetIndexBuffer(this.buffers.index_buffer(mesh), mesh.indices.format(), 0);
+            ctx.DrawIndexed(
+                mesh.indices.len() as _,
+                range.first_index,
+                range.base_vertex,
+            );
+
+            // the target is drawn into again next frame, the wrapper can't pass a null view
+            let unbound = [std::ptr::null_mut::<c_void>()];
+            (Vtable::vtable(ctx).PSSetShaderResources)(
+                Vtable::as_raw(ctx),
+                slots.texture,
+                1,
+                unbound.as_ptr(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Draws `meshes` below the UI of the next frame it's drawn in, through the same buffers
+    /// and pipeline and with the game's state saved around them, e.g. for crosshairs or boxes
+    /// around players. Call it every frame, meshes are drawn once. Positions and clip rects are
+    /// in physical pixels of the area the UI is laid out on, see [`GpuMesh::new`].
+    pub fn paint_meshes(&self, meshes: &[GpuMesh]) {
+        self.meshes.lock().extend_from_slice(meshes);
+    }
+
+    /// Covers the screen with a selection mode in which the user drags a rectangle, Escape
+    /// cancels it. The region of the game's next frame, without the UI, is read back once the
+    /// drag ends, take it with [`Self::take_region_capture`]. The UI has to be visible.
+    pub fn start_region_capture(&self) {
+        self.lock_data().region_capture.start();
+    }
+
+    /// Leaves the selection mode of [`Self::start_region_capture`] without capturing anything.
+    pub fn cancel_region_capture(&self) {
+        self.lock_data().region_capture.cancel();
+    }
+
+    /// Checks if the user is still selecting the region of [`Self::start_region_capture`].
+    pub fn is_selecting_region(&self) -> bool {
+        self.lock_data().region_capture.is_selecting()
+    }
+
+    /// The last region captured with [`Self::start_region_capture`], once. Save it with
+    /// [`CapturedFrame::save_png`] or [`CapturedFrame::copy_to_clipboard`].
+    pub fn take_region_capture(&self) -> Option<CapturedFrame> {
+        self.lock_data().region_capture.take_captured()
+    }
+
+    /// Reads the current backbuffer, including the UI drawn by [`Self::present`], back into system memory.
+    /// Should be called after `present` and before the original present call.
+    pub fn capture_frame(&self, swap_chain: &IDXGISwapChain) -> Result<CapturedFrame, Error> {
+        let mut this = self.lock_data();
+
+        // the backend knows the device, with D3D11On12 the buffer is a wrapped D3D12 one
+        unsafe {
+            let (dev, ctx, backbuffer) = &this.backend.device_and_buffer(swap_chain)?;
+            this.backend.acquire(backbuffer);
+            let frame = read_texture(dev, ctx, backbuffer);
+            this.backend.release(backbuffer);
+            frame
+        }
+    }
+
+    /// Call when resizing buffers.
+    /// Do not call the original function before it, instead call it inside of the `original` closure.
+    /// # Behavior
+    /// In `origin` closure make sure to call the original `ResizeBuffers`.
+    /// Pass the game's flags to it unchanged, chains created with
+    /// `DXGI_SWAP_CHAIN_FLAG_ALLOW_MODE_SWITCH` or a frame latency waitable object fail to resize
+    /// with any others. The new size and color space are picked up before the next frame.
+    pub fn resize_buffers(
+        &self,
+        swap_chain: &IDXGISwapChain,
+        original: impl FnOnce() -> HRESULT,
+    ) -> HRESULT {
+        let mut this = self.lock_data();
+        // Every reference to the buffers has to be gone, or `ResizeBuffers` fails.
+        this.render_views.clear();
+        // follows the new size with the next frame
+        this.depth = None;
+        this.backend.release_buffers();
+        if this.config.backup == BackupMode::None {
+            if let Ok((_, ctx)) = unsafe { get_device_and_context(swap_chain) } {
+                unsafe { ctx.OMSetRenderTargets(None, None) };
+            }
+        }
+
+        let result = original();
+        log_msg!(info, "Resized buffers: {:?}", result);
+        this.buffer_size = None;
+        // the window is usually resized along with the buffers
+        this.input.refresh_client_size();
+
+        let view = unsafe {
+            let data = &mut *this;
+            data.backend
+                .device_and_buffer(swap_chain)
+                .and_then(|(dev, ctx, buffer)| {
+                    data.render_views
+                        .get(&dev, &ctx, &buffer, data.config.wanted_srgb_view())
+                        .map(|view| view.srgb)
+                })
+        };
+
+        match view {
+            Ok(srgb) => {
+                let mut swap_desc = DXGI_SWAP_CHAIN_DESC::default();
+                if unsafe { swap_chain.GetDesc(&mut swap_desc) }.is_ok() {
+                    let format = swap_desc.BufferDesc.Format;
+                    this.detected_color_space = this
+                        .swap_chain_color_space
+                        .unwrap_or_else(|| ColorSpace::detect(swap_chain, format));
+                    this.capabilities.follow_swap_chain(&swap_desc);
+                    this.capabilities.color_space = this.detected_color_space;
+                }
+                this.capabilities.srgb = srgb;
+            }
+            // `present` acquires the view again
+            Err(e) if this.config.buffer_failure != BufferFailure::Report => {
+                log_msg!(warn, "Failed to acquire the resized buffer: {}", e);
+            }
+            Err(e) => {
+                drop(this);
+                self.report(e);
+            }
+        }
+
+        result
+    }
+
+    /// Answers `WM_GETOBJECT` with the UI's accessibility tree.
+    /// Return the result from the window procedure if it's `Some`, pass the message on otherwise.
+    #[cfg(feature = "accesskit")]
+    pub fn handle_wm_getobject(&self, wparam: WPARAM, lparam: LPARAM) -> Option<LRESULT> {
+        self.accesskit
+            .lock()
+            .as_ref()
+            .and_then(|accesskit| accesskit.handle_wm_getobject(wparam, lparam))
+    }
+
+    /// Call on each `WndProc` occurence.
+    /// Tells which kind of input the message was and whether egui wants it, return
+    /// [`InputResult::lresult`] when it's `Some` instead of calling the original procedure.
+    /// Never waits for `present`, a pressed toggle key takes effect on the next frame.
+    /// Messages are taken as the ones of [`Config::input_window`] if it's set.
+    pub fn wnd_proc(&self, umsg: u32, wparam: WPARAM, lparam: LPARAM) -> InputResult {
+        let (message, passes) = {
+            let mut input = self.lock_input();
+            let passes = input.passes_through(umsg, wparam.0);
+            (input.process_input(umsg, wparam.0, lparam.0), passes)
+        };
+        let result = self.input_result(message, passes);
+        self.update_capture(None, umsg, wparam, result.wanted);
+        result
+    }
+
+    /// [`Self::wnd_proc`] for games that take input through more than one window.
+    /// `hwnd` is the window the message was sent to, either the one the UI is drawn on or one
+    /// added with [`Self::add_input_window`], messages of other windows are ignored.
+    pub fn wnd_proc_from(
+        &self,
+        hwnd: HWND,
+        umsg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> InputResult {
+        let (message, passes) = {
+            let mut input = self.lock_input();
+            let passes = input.passes_through(umsg, wparam.0);
+            (input.process_from(hwnd, umsg, wparam.0, lparam.0), passes)
+        };
+        let result = self.input_result(message, passes);
+        self.update_capture(Some(hwnd), umsg, wparam, result.wanted);
+        result
+    }
+
+    /// Everything a hooked window procedure has to do: the message is swallowed with
+    /// [`InputResult::lresult`] when egui wants it and passed on to `original` otherwise.
+    /// `hwnd` is handled like in [`Self::wnd_proc_from`].
+    pub fn wnd_proc_blocking(
+        &self,
+        hwnd: HWND,
+        umsg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        original: WNDPROC,
+    ) -> LRESULT {
+        #[cfg(feature = "accesskit")]
+        if umsg == WM_GETOBJECT {
+            if let Some(lresult) = self.handle_wm_getobject(wparam, lparam) {
+                return lresult;
+            }
+        }
+
+        match self.wnd_proc_from(hwnd, umsg, wparam, lparam).lresult() {
+            Some(lresult) => lresult,
+            None => unsafe { CallWindowProcW(original, hwnd, umsg, wparam, lparam) },
+        }
+    }
+
+    /// Routes the input of `hwnd` into the UI through [`Self::wnd_proc_from`], e.g. for a child
+    /// window that covers the one the game renders to.
+    /// Positions are moved into the render window's client area by where both windows are on
+    /// screen, or by `transform` if it's `Some`. Adding a window again replaces its transform.
+    pub fn add_input_window(&self, hwnd: HWND, transform: Option<ScreenTransform>) {
+        self.lock_input().add_source(hwnd, transform);
+    }
+
+    /// Stops routing the input of `hwnd`, returns `false` if it wasn't added.
+    pub fn remove_input_window(&self, hwnd: HWND) -> bool {
+        self.lock_input().remove_source(hwnd)
+    }
+
+    /// See [`Config::mouse_capture`], only works on the window's thread.
+    fn update_capture(&self, hwnd: Option<HWND>, umsg: u32, wparam: WPARAM, wanted: bool) {
+        let request = self
+            .lock_input()
+            .update_capture(hwnd, umsg, wparam.0, wanted);
+        // the capture calls send messages of their own
+        if let Some(request) = request {
+            request.apply();
+        }
+    }
+
+    /// `passes` if the message reaches the game regardless, see [`Config::key_passthrough`].
+    fn input_result(&self, message: InputMessage, passes: bool) -> InputResult {
+        let wanted = match (message.category(), self.context.get()) {
+            _ if !self.is_visible() || passes => false,
+            (InputCategory::Unknown, _) | (_, None) => false,
+            (InputCategory::Key | InputCategory::Text, Some(ctx)) => ctx.wants_keyboard_input(),
+            (InputCategory::Pointer, Some(ctx)) => {
+                ctx.wants_pointer_input() || ctx.is_pointer_over_area()
+            }
+        };
+
+        InputResult { message, wanted }
+    }
+
+    /// Reads mouse and keyboard input through low-level hooks, for games that use DirectInput
+    /// or RawInput and never let messages reach the window procedure.
+    /// Input is only taken while the window is in the foreground, with `block` the game doesn't
+    /// see clicks and key presses egui wants. The hooks are removed when the result is dropped.
+    #[cfg(feature = "input-hooks")]
+    pub fn install_input_hooks(&'static self, block: bool) -> Result<crate::InputHooks, Error> {
+        let hwnd = *expect!(self.hwnd.get(), "You need to call init first");
+
+        crate::hooks::install_hooks(
+            self as *const Self as *const (),
+            Self::hook_dispatch,
+            hwnd,
+            block,
+        )
+    }
+
+    /// Reads input through RawInput on a hidden message-only window, for games whose window
+    /// procedure can't be safely intercepted. Devices the game registered for RawInput itself
+    /// are left alone, a process only gets one registration per device type.
+    /// Input stops when the result is dropped.
+    #[cfg(feature = "input-hooks")]
+    pub fn install_raw_input(&'static self) -> Result<crate::InputHooks, Error> {
+        let hwnd = *expect!(self.hwnd.get(), "You need to call init first");
+
+        crate::hooks::install_raw_input(self as *const Self as *const (), Self::hook_dispatch, hwnd)
+    }
+
+    /// Freezes the cursor position the game reads through `GetCursorPos` while egui wants the
+    /// pointer, for games that turn the camera even though their messages are blocked.
+    /// Patches the import table of the game's executable, see [`crate::cursor`] for games that
+    /// resolve the functions at runtime. The game sees the real cursor again once the result
+    /// is dropped. Only one app of the process installs them, the others are covered as well.
+    #[cfg(feature = "cursor-hooks")]
+    pub fn virtualize_cursor(&self) -> Result<crate::cursor::CursorHooks, Error> {
+        unsafe { crate::cursor::CursorHooks::install(None) }
+    }
+
+    #[cfg(feature = "input-hooks")]
+    fn hook_dispatch(app: *const (), umsg: u32, wparam: usize, lparam: isize) -> bool {
+        // SAFETY: the pointer comes from the `&'static self` in `install_input_hooks`.
+        let app = unsafe { &*(app as *const Self) };
+        // the hooks run on a thread of their own, which can't capture the mouse
+        let mut input = app.lock_input();
+        let passes = input.passes_through(umsg, wparam);
+        let message = input.process(umsg, wparam, lparam);
+        drop(input);
+        app.input_result(message, passes).wanted
+    }
+}
+
+/// Color one layer of the overdraw view adds.
+const OVERDRAW_TINT: [f32; 4] = [0.12, 0.05, 0.02, 1.];
+
+/// Distinct color for the `index`th item of a debug view, neighbours never get similar hues.
+fn debug_color(index: usize) -> Color32 {
+    // golden ratio steps spread the hues evenly
+    let hue = (index as f32 * 0.618_034).fract();
+    Color32::from(ecolor::Hsva::new(hue, 0.8, 1., 1.))
+}
+
+fn mesh_tint(index: usize) -> [f32; 4] {
+    // the shader works with gamma encoded colors
+    let [r, g, b, _] = debug_color(index).to_array().map(|c| c as f32 / 255.);
+    [r, g, b, 1.]
+}
+
+/// Overrides `config` with the settings saved in its settings file, defaults for new games.
+#[cfg(feature = "settings")]
+fn load_settings(config: Config) -> (Config, OverlaySettings) {
+    let saved = config.settings.as_deref().and_then(OverlaySettings::load);
+
+    match saved {
+        Some(settings) => {
+            log_msg!(info, "Loaded overlay settings from {:?}", config.settings);
+            (settings.apply(config), settings)
+        }
+        None => {
+            let settings = OverlaySettings::from_config(&config);
+            (config, settings)
+        }
+    }
+}
+
+/// Runs a draw hook on the render target, with the callbacks' depth buffer if there is one.
+unsafe fn run_draw_hook(
+    hooks: &mut DrawHooks,
+    id: DrawHookId,
+    dev: &ID3D11Device,
+    ctx: &ID3D11DeviceContext,
+    target: &FrameTarget,
+    depth: &Option<DepthTarget>,
+) -> Result<(), Error> {
+    match depth {
+        Some(depth) => depth.bind(ctx, target),
+        None => ctx.OMSetRenderTargets(Some(std::slice::from_ref(&target.view)), None),
+    }
+    hooks.run(id, dev, ctx)
+}
+
+/// Outline of every distinct clip rectangle in `shapes`, drawn on top without clipping.
+fn clip_rect_outlines(shapes: &[ClippedShape]) -> Vec<ClippedShape> {
+    let mut rects: Vec<Rect> = vec![];
+    for shape in shapes {
+        if !rects.contains(&shape.0) {
+            rects.push(shape.0);
+        }
+    }
+
+    rects
+        .into_iter()
+        .enumerate()
+        .map(|(i, rect)| {
+            ClippedShape(
+                Rect::EVERYTHING,
+                Shape::rect_stroke(rect, 0., Stroke::new(1., debug_color(i))),
+            )
+        })
+        .collect()
+}
+
+unsafe fn get_device_and_context(
+    swap: &IDXGISwapChain,
+) -> Result<(ID3D11Device, ID3D11DeviceContext), Error> {
+    let device: ID3D11Device = swap
+        .GetDevice()
+        .context("Failed to get swapchain's device")?;
+    let ctx = device.GetImmediateContext();
+
+    Ok((
+        device,
+        ctx.context("Failed to get device's immediate context")?,
+    ))
+}
+
+/// State of the draw loop that is only set again when a mesh needs another one. Draw hooks may
+/// change anything, it's forgotten after them.
+#[derive(Default)]
+struct BoundState {
+    texture: Option<TextureId>,
+    sampler: SamplerOverride,
+    scissor: Option<RECT>,
+}
+
+/// What the UI produced for a frame, before anything is drawn.
+struct FrameOutput {
+    screen: (f32, f32),
+    ppp: f32,
+    textures_delta: TexturesDelta,
+    primitives: Vec<ClippedPrimitive>,
+    /// Drawn below the UI, see [`DirectX11App::paint_meshes`].
+    meshes: Vec<GpuMesh>,
+}
+
+impl FrameOutput {
+    fn is_empty(&self) -> bool {
+        self.primitives.is_empty() && self.textures_delta.is_empty() && self.meshes.is_empty()
+    }
+}
+
+/// Primitives of the last UI run, drawn again while [`Config::max_ui_fps`] limits it.
+struct UiCache {
+    ran_at: Instant,
+    screen: (f32, f32),
+    ppp: f32,
+    primitives: Vec<ClippedPrimitive>,
+    repaint_after: Duration,
+}
+
+impl UiCache {
+    /// The last run's output if the UI doesn't have to run for this frame.
+    fn reuse(
+        &self,
+        input: &RawInput,
+        screen: (f32, f32),
+        ppp: f32,
+        max_fps: Option<u32>,
+        repaint: bool,
+    ) -> Option<FrameOutput> {
+        let interval = Duration::from_secs(1) / max_fps?;
+        let fresh = self.ran_at.elapsed() < interval
+            && input.events.is_empty()
+            && !repaint
+            && !self.repaint_after.is_zero()
+            && self.screen == screen
+            && self.ppp == ppp;
+
+        fresh.then(|| FrameOutput {
+            screen,
+            ppp,
+            textures_delta: TexturesDelta::default(),
+            primitives: self.primitives.clone(),
+            meshes: vec![],
+        })
+    }
+}
+
+/// Lets the frame limit of [`Config::max_ui_fps`] know about repaints requested between runs.
+/// `None` stops it, egui can't remove the callback, so it's replaced by one that does nothing.
+pub(crate) fn watch_repaints(ctx: &Context, requested: Option<&Arc<AtomicBool>>) {
+    match requested.cloned() {
+        Some(requested) => crate::compat::set_repaint_callback(ctx, move || {
+            requested.store(true, Ordering::Relaxed)
+        }),
+        None => crate::compat::set_repaint_callback(ctx, || {}),
+    }
+}
+
+/// Moves a scissor rect from the UI's area onto the render target.
+fn offset_rect(rect: RECT, origin: Pos2) -> RECT {
+    let (x, y) = (origin.x as i32, origin.y as i32);
+    RECT {
+        left: rect.left + x,
+        top: rect.top + y,
+        right: rect.right + x,
+        bottom: rect.bottom + y,
+    }
+}
+
+/// Sets the display affinity of `hwnd`, see [`Config::exclude_from_capture`].
+unsafe fn exclude_from_capture(hwnd: HWND, exclude: bool) -> Result<(), Error> {
+    // only top-level windows have an affinity, engines may render to a child window
+    let hwnd = GetAncestor(hwnd, GA_ROOT);
+    let affinity = if exclude {
+        WDA_EXCLUDEFROMCAPTURE
+    } else {
+        WDA_NONE
+    };
+
+    SetWindowDisplayAffinity(hwnd, affinity)
+        .ok()
+        .context("Failed to set the window's display affinity")
+}
+
+/// How much a backbuffer of `buffer` pixels is stretched onto the display in exclusive
+/// fullscreen, `None` while windowed or if the display mode has the buffer's size.
+unsafe fn fullscreen_scale(swap_chain: &IDXGISwapChain, buffer: (f32, f32)) -> Option<Vec2> {
+    let mut fullscreen = BOOL::default();
+    let mut output = None;
+    swap_chain
+        .GetFullscreenState(Some(&mut fullscreen), Some(&mut output))
+        .ok()?;
+    let output = output.filter(|_| fullscreen.as_bool())?;
+
+    // the desktop follows the display mode while the chain is fullscreen
+    let mut desc = DXGI_OUTPUT_DESC::default();
+    output.GetDesc(&mut desc).ok()?;
+    let rect = desc.DesktopCoordinates;
+    let mode = Vec2::new(
+        (rect.right - rect.left) as f32,
+        (rect.bottom - rect.top) as f32,
+    );
+
+    let buffer = Vec2::from(buffer);
+    (mode.x > 0. && mode.y > 0. && mode != buffer).then(|| buffer / mode)
+}
+
+unsafe fn backbuffer_size(backbuffer: &ID3D11Texture2D) -> (f32, f32) {
+    let mut desc = D3D11_TEXTURE2D_DESC::default();
+    backbuffer.GetDesc(&mut desc);
+
+    (desc.Width as f32, desc.Height as f32)
+}
+
+/// What init learns about the surface the UI is drawn on.
+struct Surface {
+    hwnd: HWND,
+    device: ID3D11Device,
+    render_views: RenderTargets,
+    srgb: bool,
+    color_space: ColorSpace,
+    samples: u32,
+    swap_desc: Option<DXGI_SWAP_CHAIN_DESC>,
+}
+
+/// Where the device and the buffer the UI is drawn into come from.
+enum Backend {
+    D3D11,
+    #[cfg(feature = "d3d12")]
+    D3D12(D3D12Bridge),
+}
+
+impl Backend {
+    unsafe fn device_and_buffer(
+        &mut self,
+        swap: &IDXGISwapChain,
+    ) -> Result<(ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D), Error> {
+        match self {
+            Self::D3D11 => {
+                let (dev, ctx) = get_device_and_context(swap)?;
+                let backbuffer = swap
+                    .GetBuffer(0)
+                    .context("Failed to get swapchain's backbuffer")?;
+
+                Ok((dev, ctx, backbuffer))
+            }
+            #[cfg(feature = "d3d12")]
+            Self::D3D12(bridge) => Ok((
+                bridge.device.clone(),
+                bridge.context.clone(),
+                bridge.current_buffer(swap)?,
+            )),
+        }
+    }
+
+    fn acquire(&self, _backbuffer: &ID3D11Texture2D) {
+        #[cfg(feature = "d3d12")]
+        if let Self::D3D12(bridge) = self {
+            bridge.acquire(_backbuffer);
+        }
+    }
+
+    fn release(&self, _backbuffer: &ID3D11Texture2D) {
+        #[cfg(feature = "d3d12")]
+        if let Self::D3D12(bridge) = self {
+            bridge.release(_backbuffer);
+        }
+    }
+
+    fn release_buffers(&mut self) {
+        #[cfg(feature = "d3d12")]
+        if let Self::D3D12(bridge) = self {
+            bridge.release_buffers();
+        }
+    }
 }
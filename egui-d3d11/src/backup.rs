@@ -1,17 +1,23 @@
-use std::{cell::RefCell, mem::zeroed};
-use windows::Win32::{
-    Foundation::RECT,
-    Graphics::{
-        Direct3D::D3D_PRIMITIVE_TOPOLOGY,
-        Direct3D11::{
-            ID3D11BlendState, ID3D11Buffer, ID3D11ClassInstance, ID3D11DepthStencilState,
-            ID3D11DeviceContext, ID3D11GeometryShader, ID3D11InputLayout, ID3D11PixelShader,
-            ID3D11RasterizerState, ID3D11SamplerState, ID3D11ShaderResourceView,
-            ID3D11VertexShader, D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT,
-            D3D11_COMMONSHADER_INPUT_RESOURCE_SLOT_COUNT, D3D11_COMMONSHADER_SAMPLER_SLOT_COUNT,
-            D3D11_VIEWPORT, D3D11_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE,
+use std::{
+    cell::RefCell,
+    mem::{take, zeroed},
+};
+use windows::{
+    core::Vtable,
+    Win32::{
+        Foundation::RECT,
+        Graphics::{
+            Direct3D::D3D_PRIMITIVE_TOPOLOGY,
+            Direct3D11::{
+                ID3D11BlendState, ID3D11Buffer, ID3D11ClassInstance, ID3D11DepthStencilState,
+                ID3D11DeviceContext, ID3D11GeometryShader, ID3D11InputLayout, ID3D11PixelShader,
+                ID3D11RasterizerState, ID3D11SamplerState, ID3D11ShaderResourceView,
+                ID3D11VertexShader, D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT,
+                D3D11_COMMONSHADER_INPUT_RESOURCE_SLOT_COUNT, D3D11_COMMONSHADER_SAMPLER_SLOT_COUNT,
+                D3D11_VIEWPORT, D3D11_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE,
+            },
+            Dxgi::Common::DXGI_FORMAT,
         },
-        Dxgi::Common::DXGI_FORMAT,
     },
 };
 
@@ -36,12 +42,17 @@ impl BackupState {
     }
 }
 
+/// Viewports and scissor rects a pipeline can have bound at once, all of them are kept.
+const MAX_VIEWPORTS: usize = D3D11_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE as _;
+/// Vertex buffer slots the UI binds, the vertices and the offsets of the instances.
+const VERTEX_SLOTS: usize = 2;
+
 #[derive(Default)]
 struct InnerState {
-    scissor_rects: [RECT; D3D11_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE as _],
+    scissor_rects: [RECT; MAX_VIEWPORTS],
     scissor_count: u32,
 
-    viewports: [D3D11_VIEWPORT; D3D11_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE as _],
+    viewports: [D3D11_VIEWPORT; MAX_VIEWPORTS],
     viewport_count: u32,
 
     raster_state: Option<ID3D11RasterizerState>,
@@ -53,11 +64,10 @@ struct InnerState {
     depth_stencil_state: Option<ID3D11DepthStencilState>,
     stencil_ref: u32,
 
-    pixel_shader_resources: Array<
-        { (D3D11_COMMONSHADER_INPUT_RESOURCE_SLOT_COUNT - 1) as usize },
-        ID3D11ShaderResourceView,
-    >,
-    samplers: Array<{ (D3D11_COMMONSHADER_SAMPLER_SLOT_COUNT - 1) as usize }, ID3D11SamplerState>,
+    // every slot is kept, the UI binds its resources to the ones of `ShaderSlots`
+    pixel_shader_resources:
+        Array<{ D3D11_COMMONSHADER_INPUT_RESOURCE_SLOT_COUNT as usize }, ID3D11ShaderResourceView>,
+    samplers: Array<{ D3D11_COMMONSHADER_SAMPLER_SLOT_COUNT as usize }, ID3D11SamplerState>,
 
     vertex_shader: Option<ID3D11VertexShader>,
     vertex_shader_instances: Array<256, ID3D11ClassInstance>,
@@ -72,16 +82,18 @@ struct InnerState {
     pixel_shader_instances_count: u32,
 
     constant_buffers:
-        Array<{ (D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT - 1) as usize }, ID3D11Buffer>,
+        Array<{ D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT as usize }, ID3D11Buffer>,
+    pixel_constant_buffers:
+        Array<{ D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT as usize }, ID3D11Buffer>,
     primitive_topology: D3D_PRIMITIVE_TOPOLOGY,
 
     index_buffer: Option<ID3D11Buffer>,
     index_buffer_format: DXGI_FORMAT,
     index_buffer_offest: u32,
 
-    vertex_buffer: Option<ID3D11Buffer>,
-    vertex_buffer_strides: u32,
-    vertex_buffer_offsets: u32,
+    vertex_buffers: [Option<ID3D11Buffer>; VERTEX_SLOTS],
+    vertex_buffer_strides: [u32; VERTEX_SLOTS],
+    vertex_buffer_offsets: [u32; VERTEX_SLOTS],
 
     input_layout: Option<ID3D11InputLayout>,
 }
@@ -89,10 +101,14 @@ struct InnerState {
 impl InnerState {
     #[inline]
     pub unsafe fn save(&mut self, ctx: &ID3D11DeviceContext) {
+        // The counts go in as the capacity of the arrays and come back as the number bound,
+        // games with split screen or several cameras bind more than the first one.
+        self.scissor_count = MAX_VIEWPORTS as _;
         ctx.RSGetScissorRects(
             &mut self.scissor_count,
             Some(self.scissor_rects.as_mut_ptr()),
         );
+        self.viewport_count = MAX_VIEWPORTS as _;
         ctx.RSGetViewports(&mut self.viewport_count, Some(self.viewports.as_mut_ptr()));
         self.raster_state = ctx.RSGetState().ok();
         ctx.OMGetBlendState(
@@ -127,6 +143,7 @@ impl InnerState {
         );
 
         ctx.VSGetConstantBuffers(0, Some(self.constant_buffers.as_mut_slice()));
+        ctx.PSGetConstantBuffers(0, Some(self.pixel_constant_buffers.as_mut_slice()));
         self.primitive_topology = ctx.IAGetPrimitiveTopology();
         ctx.IAGetIndexBuffer(
             Some(&mut self.index_buffer),
@@ -135,10 +152,10 @@ impl InnerState {
         );
         ctx.IAGetVertexBuffers(
             0,
-            1,
-            Some(&mut self.vertex_buffer),
-            Some(&mut self.vertex_buffer_strides),
-            Some(&mut self.vertex_buffer_offsets),
+            VERTEX_SLOTS as _,
+            Some(self.vertex_buffers.as_mut_ptr()),
+            Some(self.vertex_buffer_strides.as_mut_ptr()),
+            Some(self.vertex_buffer_offsets.as_mut_ptr()),
         );
         self.input_layout = ctx.IAGetInputLayout().ok();
     }
@@ -159,36 +176,15 @@ impl InnerState {
             Some(self.blend_factor.as_ptr()),
             self.blend_mask,
         );
-        if let Some(depth_stencil_state) = self.depth_stencil_state.take() {
-            ctx.OMSetDepthStencilState(&depth_stencil_state, self.stencil_ref);
-        }
-        // this is really dumb, but I couldn't find a way to make it cleaner
-        // as PSGetShaderResources gives us a &[Option<ID3D11ShaderResourceView>] while
-        // PSSetShaderResources wants a &[ID3D11ShaderResourceView]
-        // there's multiple of these filter maps sadly
-        ctx.PSSetShaderResources(
-            0,
-            Some(
-                &self
-                    .pixel_shader_resources
-                    .0
-                    .iter()
-                    .filter_map(|x| x.clone())
-                    .collect::<Vec<_>>(),
-            ),
-        );
-
-        ctx.PSSetSamplers(
-            0,
-            Some(
-                &self
-                    .samplers
-                    .0
-                    .iter()
-                    .filter_map(|x| x.clone())
-                    .collect::<Vec<_>>(),
-            ),
-        );
+        // `None` is the default state, it has to be restored as well since the UI binds its own
+        ctx.OMSetDepthStencilState(self.depth_stencil_state.take().as_ref(), self.stencil_ref);
+        // The wrappers take slices without gaps, the raw calls put every binding back into the
+        // slot it came from and empty the slots the UI used. `None` is a null pointer.
+        let (vtable, raw) = (Vtable::vtable(ctx), Vtable::as_raw(ctx));
+        let resources = take(&mut self.pixel_shader_resources);
+        (vtable.PSSetShaderResources)(raw, 0, resources.0.len() as _, resources.0.as_ptr() as _);
+        let samplers = take(&mut self.samplers);
+        (vtable.PSSetSamplers)(raw, 0, samplers.0.len() as _, samplers.0.as_ptr() as _);
         ctx.PSSetShader(
             self.pixel_shader.take().as_ref(),
             Some(
@@ -229,25 +225,23 @@ impl InnerState {
         }
         self.geometry_shader_instances.release();
 
-        let constant_buffers = self
-            .constant_buffers
-            .0
-            .iter()
-            .filter_map(|x| x.clone())
-            .collect::<Vec<_>>();
-        ctx.VSSetConstantBuffers(0, Some(&constant_buffers));
+        let buffers = take(&mut self.constant_buffers);
+        (vtable.VSSetConstantBuffers)(raw, 0, buffers.0.len() as _, buffers.0.as_ptr() as _);
+        let buffers = take(&mut self.pixel_constant_buffers);
+        (vtable.PSSetConstantBuffers)(raw, 0, buffers.0.len() as _, buffers.0.as_ptr() as _);
         ctx.IASetPrimitiveTopology(self.primitive_topology);
         ctx.IASetIndexBuffer(
             self.index_buffer.take().as_ref(),
             self.index_buffer_format,
             self.index_buffer_offest,
         );
+        let vertex_buffers = std::mem::take(&mut self.vertex_buffers);
         ctx.IASetVertexBuffers(
             0,
-            1,
-            Some(&self.vertex_buffer.take()),
-            Some(&self.vertex_buffer_strides),
-            Some(&self.vertex_buffer_offsets),
+            VERTEX_SLOTS as _,
+            Some(vertex_buffers.as_ptr()),
+            Some(self.vertex_buffer_strides.as_ptr()),
+            Some(self.vertex_buffer_offsets.as_ptr()),
         );
 
         if let Some(input_layout) = &self.input_layout.take() {
@@ -263,11 +257,6 @@ impl<const N: usize, T> Array<N, T> {
         &mut self.0[0]
     }
 
-    #[inline]
-    pub fn as_slice(&self) -> &[Option<T>] {
-        self.0.as_slice()
-    }
-
     #[inline]
     pub fn as_mut_slice(&mut self) -> &mut [Option<T>] {
         self.0.as_mut_slice()
@@ -0,0 +1,67 @@
+use crate::{hdr::ColorSpace, texture::max_texture_side};
+use windows::Win32::Graphics::{
+    Direct3D::D3D_FEATURE_LEVEL,
+    Direct3D11::ID3D11Device,
+    Dxgi::{
+        Common::DXGI_FORMAT, DXGI_SWAP_CHAIN_DESC, DXGI_SWAP_EFFECT, DXGI_SWAP_EFFECT_FLIP_DISCARD,
+        DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL,
+    },
+};
+
+/// What the renderer detected about the device and the swapchain, see
+/// [`crate::DirectX11App::capabilities`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Format of the swapchain's buffers, `None` for apps initialized without a swapchain.
+    pub backbuffer_format: Option<DXGI_FORMAT>,
+    /// Whether the UI is drawn through an `_SRGB` view, see [`crate::Config::srgb_view`].
+    pub srgb: bool,
+    pub feature_level: D3D_FEATURE_LEVEL,
+    /// Biggest side of a texture the device can create, egui's font atlas never outgrows it.
+    pub max_texture_side: usize,
+    /// Whether the swapchain uses one of the flip presentation models, which hand out another
+    /// buffer every frame.
+    pub flip_model: bool,
+    /// Color space of the backbuffer, before [`crate::Config::color_space`] overrides it.
+    pub color_space: ColorSpace,
+    /// Samples per pixel of the backbuffer, 1 without MSAA.
+    pub samples: u32,
+    /// Whether the UI is drawn on a D3D12 swapchain, see [`crate::DirectX11App::init_d3d12`].
+    pub d3d12: bool,
+}
+
+impl Capabilities {
+    /// What `dev` supports, the surface is filled in by the caller.
+    pub(crate) fn new(dev: &ID3D11Device) -> Self {
+        Self {
+            backbuffer_format: None,
+            srgb: false,
+            feature_level: unsafe { dev.GetFeatureLevel() },
+            max_texture_side: max_texture_side(dev),
+            flip_model: false,
+            color_space: ColorSpace::Srgb,
+            samples: 1,
+            d3d12: false,
+        }
+    }
+
+    /// Takes the buffers of a swapchain that was just created or resized.
+    pub(crate) fn follow_swap_chain(&mut self, desc: &DXGI_SWAP_CHAIN_DESC) {
+        self.backbuffer_format = Some(desc.BufferDesc.Format);
+        self.flip_model = is_flip_model(desc.SwapEffect);
+        self.samples = desc.SampleDesc.Count;
+    }
+
+    /// Takes the limits of the device the game recreated.
+    pub(crate) fn follow_device(&mut self, dev: &ID3D11Device) {
+        self.feature_level = unsafe { dev.GetFeatureLevel() };
+        self.max_texture_side = max_texture_side(dev);
+    }
+}
+
+fn is_flip_model(effect: DXGI_SWAP_EFFECT) -> bool {
+    matches!(
+        effect,
+        DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL | DXGI_SWAP_EFFECT_FLIP_DISCARD
+    )
+}
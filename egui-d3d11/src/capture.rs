@@ -0,0 +1,241 @@
+use crate::{
+    error::{Error, ErrorContext},
+    format::typed_format,
+};
+use egui::ecolor::gamma_u8_from_linear_f32;
+use std::slice::from_raw_parts;
+use windows::Win32::Graphics::{
+    Direct3D11::{
+        ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_BOX, D3D11_CPU_ACCESS_READ,
+        D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+        D3D11_USAGE_STAGING,
+    },
+    Dxgi::Common::{
+        DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+        DXGI_FORMAT_B8G8R8X8_UNORM, DXGI_FORMAT_B8G8R8X8_UNORM_SRGB, DXGI_FORMAT_R10G10B10A2_UNORM,
+        DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        DXGI_SAMPLE_DESC,
+    },
+};
+
+/// Contents of the backbuffer read back into system memory.
+pub struct CapturedFrame {
+    pub width: usize,
+    pub height: usize,
+    /// Tightly packed, non premultiplied RGBA8 pixels in sRGB space.
+    pub pixels: Vec<u8>,
+}
+
+impl CapturedFrame {
+    /// Writes the frame into a png file.
+    #[cfg(feature = "png")]
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width as _, self.height as _);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(std::io::Error::other)
+    }
+
+    /// Puts the frame on the Windows clipboard as a bitmap, e.g. for pasting into chat or an
+    /// image editor.
+    pub fn copy_to_clipboard(&self) -> Result<(), Error> {
+        crate::clipboard::set_image(self.width, self.height, &self.pixels)
+            .context("Failed to copy the frame to the clipboard")
+    }
+}
+
+/// Copies any texture of a readable format through a staging texture and converts it to RGBA8.
+pub unsafe fn read_texture(
+    dev: &ID3D11Device,
+    ctx: &ID3D11DeviceContext,
+    texture: &ID3D11Texture2D,
+) -> Result<CapturedFrame, Error> {
+    read_texture_region(dev, ctx, texture, [0, 0, u32::MAX, u32::MAX])
+}
+
+/// Like [`read_texture`], but only reads the `[left, top, right, bottom]` pixels of `texture`,
+/// clamped to its size.
+pub unsafe fn read_texture_region(
+    dev: &ID3D11Device,
+    ctx: &ID3D11DeviceContext,
+    texture: &ID3D11Texture2D,
+    [left, top, right, bottom]: [u32; 4],
+) -> Result<CapturedFrame, Error> {
+    let mut desc = D3D11_TEXTURE2D_DESC::default();
+    texture.GetDesc(&mut desc);
+
+    let format = typed_format(desc.Format);
+    let Some(bpp) = bytes_per_pixel(format) else {
+        return Err(Error::UnsupportedFormat(format));
+    };
+
+    let region = D3D11_BOX {
+        left: left.min(desc.Width),
+        top: top.min(desc.Height),
+        front: 0,
+        right: right.min(desc.Width),
+        bottom: bottom.min(desc.Height),
+        back: 1,
+    };
+    if region.right <= region.left || region.bottom <= region.top {
+        return Err(Error::EmptyRegion([left, top, right, bottom]));
+    }
+    let (width, height) = (region.right - region.left, region.bottom - region.top);
+    let row_len = width as usize * bpp;
+
+    let mut copy_desc = D3D11_TEXTURE2D_DESC {
+        Width: width,
+        Height: height,
+        MipLevels: 1,
+        ArraySize: 1,
+        Format: desc.Format,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Usage: D3D11_USAGE_STAGING,
+        CPUAccessFlags: D3D11_CPU_ACCESS_READ,
+        ..Default::default()
+    };
+
+    let mut staging: Option<ID3D11Texture2D> = None;
+    dev.CreateTexture2D(&copy_desc, None, Some(&mut staging))
+        .context("Failed to create staging texture")?;
+    let staging = staging.context("Failed to create staging texture")?;
+
+    if desc.SampleDesc.Count > 1 {
+        // only whole subresources can be resolved
+        copy_desc.Width = desc.Width;
+        copy_desc.Height = desc.Height;
+        copy_desc.Usage = D3D11_USAGE_DEFAULT;
+        copy_desc.CPUAccessFlags = Default::default();
+
+        let mut resolved: Option<ID3D11Texture2D> = None;
+        dev.CreateTexture2D(&copy_desc, None, Some(&mut resolved))
+            .context("Failed to create resolve texture")?;
+        let resolved = resolved.context("Failed to create resolve texture")?;
+
+        ctx.ResolveSubresource(&resolved, 0, texture, 0, format);
+        ctx.CopySubresourceRegion(&staging, 0, 0, 0, 0, &resolved, 0, Some(&region));
+    } else {
+        ctx.CopySubresourceRegion(&staging, 0, 0, 0, 0, texture, 0, Some(&region));
+    }
+
+    let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+    ctx.Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))
+        .context("Failed to map staging texture")?;
+
+    let (width, height) = (width as usize, height as usize);
+    let mut pixels = Vec::with_capacity(width * height * 4);
+
+    for y in 0..height {
+        let row = from_raw_parts(
+            (mapped.pData as *const u8).add(y * mapped.RowPitch as usize),
+            row_len,
+        );
+        convert_row(format, row, &mut pixels);
+    }
+
+    ctx.Unmap(&staging, 0);
+
+    Ok(CapturedFrame {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn bytes_per_pixel(format: DXGI_FORMAT) -> Option<usize> {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM
+        | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+        | DXGI_FORMAT_B8G8R8A8_UNORM
+        | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+        | DXGI_FORMAT_B8G8R8X8_UNORM
+        | DXGI_FORMAT_B8G8R8X8_UNORM_SRGB
+        | DXGI_FORMAT_R10G10B10A2_UNORM => Some(4),
+        DXGI_FORMAT_R16G16B16A16_FLOAT => Some(8),
+        _ => None,
+    }
+}
+
+fn convert_row(format: DXGI_FORMAT, row: &[u8], out: &mut Vec<u8>) {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => out.extend_from_slice(row),
+        DXGI_FORMAT_B8G8R8A8_UNORM | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => {
+            for px in row.chunks_exact(4) {
+                out.extend_from_slice(&[px[2], px[1], px[0], px[3]]);
+            }
+        }
+        DXGI_FORMAT_B8G8R8X8_UNORM | DXGI_FORMAT_B8G8R8X8_UNORM_SRGB => {
+            for px in row.chunks_exact(4) {
+                out.extend_from_slice(&[px[2], px[1], px[0], 255]);
+            }
+        }
+        DXGI_FORMAT_R10G10B10A2_UNORM => {
+            for px in row.chunks_exact(4) {
+                let v = u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+                let channel = |shift: u32| ((v >> shift & 0x3FF) * 255 / 0x3FF) as u8;
+
+                out.extend_from_slice(&[
+                    channel(0),
+                    channel(10),
+                    channel(20),
+                    ((v >> 30) * 255 / 3) as u8,
+                ]);
+            }
+        }
+        DXGI_FORMAT_R16G16B16A16_FLOAT => {
+            for px in row.chunks_exact(8) {
+                let channel = |i: usize| f16_to_f32(u16::from_le_bytes([px[i * 2], px[i * 2 + 1]]));
+
+                out.extend_from_slice(&[
+                    gamma_u8_from_linear_f32(channel(0)),
+                    gamma_u8_from_linear_f32(channel(1)),
+                    gamma_u8_from_linear_f32(channel(2)),
+                    (channel(3).clamp(0., 1.) * 255. + 0.5) as u8,
+                ]);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Converts IEEE 754 half precision float into `f32`.
+fn f16_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1. } else { 1. };
+    let exp = (h >> 10 & 0x1F) as i32;
+    let mantissa = (h & 0x3FF) as f32;
+
+    match exp {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1F if mantissa == 0. => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => sign * (1. + mantissa / 1024.) * 2f32.powi(exp - 15),
+    }
+}
+
+#[test]
+fn test_convert_row() {
+    assert_eq!(f16_to_f32(0x3C00), 1.);
+    assert_eq!(f16_to_f32(0xC000), -2.);
+    assert_eq!(f16_to_f32(0x0000), 0.);
+
+    let mut out = vec![];
+    convert_row(DXGI_FORMAT_B8G8R8A8_UNORM, &[1, 2, 3, 4], &mut out);
+    assert_eq!(out, [3, 2, 1, 4]);
+
+    out.clear();
+    convert_row(
+        DXGI_FORMAT_R10G10B10A2_UNORM,
+        &(0x3FFu32 | 3 << 30).to_le_bytes(),
+        &mut out,
+    );
+    assert_eq!(out, [255, 0, 0, 255]);
+}
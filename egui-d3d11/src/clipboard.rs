@@ -0,0 +1,206 @@
+use crate::DefaultRawMutex;
+use lock_api::Mutex;
+use std::{
+    fmt, iter, mem::size_of, ptr::copy_nonoverlapping, slice::from_raw_parts, sync::Arc, thread,
+    time::Duration,
+};
+use windows::{
+    core::Error,
+    Win32::{
+        Foundation::{HANDLE, HWND},
+        Graphics::Gdi::{BITMAPINFOHEADER, BI_RGB},
+        System::{
+            DataExchange::{
+                CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, SetClipboardData,
+            },
+            Memory::{
+                GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+            },
+        },
+    },
+};
+
+// Their constants live in `Win32_System_Ole`, which would be pulled in just for this.
+const CF_DIB: u32 = 8;
+const CF_UNICODETEXT: u32 = 13;
+
+/// Other processes keep the clipboard open for short moments, opening it is retried this often.
+const OPEN_ATTEMPTS: u32 = 10;
+
+/// Source and destination of egui's copy and paste, see [`crate::Config::clipboard`].
+pub trait ClipboardBackend: Send + Sync + 'static {
+    /// Text pasted on `Ctrl+V`, `None` if there is nothing to paste.
+    fn get(&self) -> Option<String>;
+
+    /// Called with the text egui copied or cut.
+    fn set(&self, text: String);
+}
+
+/// The Windows clipboard, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WindowsClipboard;
+
+impl ClipboardBackend for WindowsClipboard {
+    fn get(&self) -> Option<String> {
+        let _open = OpenGuard::open()?;
+
+        unsafe {
+            let handle = GetClipboardData(CF_UNICODETEXT).ok()?;
+            let data = GlobalLock(handle.0) as *const u16;
+            if data.is_null() {
+                return None;
+            }
+
+            // the size may be rounded up, the text ends at the first zero
+            let wide = from_raw_parts(data, GlobalSize(handle.0) / 2);
+            let len = wide.iter().position(|c| *c == 0).unwrap_or(wide.len());
+            let text = String::from_utf16_lossy(&wide[..len]);
+
+            GlobalUnlock(handle.0);
+            Some(text)
+        }
+    }
+
+    fn set(&self, text: String) {
+        if let Err(_e) = set_text(&text) {
+            log_msg!(warn, "Failed to set clipboard contents: {}", _e);
+        }
+    }
+}
+
+fn set_text(text: &str) -> Result<(), Error> {
+    let wide: Vec<u8> = text
+        .encode_utf16()
+        .chain(iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect();
+
+    set_data(CF_UNICODETEXT, &wide)
+}
+
+/// Replaces the clipboard's contents with a bitmap of tightly packed RGBA8 `pixels`.
+pub(crate) fn set_image(width: usize, height: usize, pixels: &[u8]) -> Result<(), Error> {
+    let header = BITMAPINFOHEADER {
+        biSize: size_of::<BITMAPINFOHEADER>() as u32,
+        biWidth: width as i32,
+        // bottom-up, top-down bitmaps aren't understood by every program
+        biHeight: height as i32,
+        biPlanes: 1,
+        biBitCount: 32,
+        biCompression: BI_RGB,
+        biSizeImage: (width * height * 4) as u32,
+        ..Default::default()
+    };
+
+    let mut dib = Vec::with_capacity(size_of::<BITMAPINFOHEADER>() + width * height * 4);
+    dib.extend_from_slice(unsafe {
+        from_raw_parts(
+            &header as *const BITMAPINFOHEADER as *const u8,
+            size_of::<BITMAPINFOHEADER>(),
+        )
+    });
+    for row in pixels.chunks_exact(width * 4).rev() {
+        for px in row.chunks_exact(4) {
+            dib.extend_from_slice(&[px[2], px[1], px[0], px[3]]);
+        }
+    }
+
+    set_data(CF_DIB, &dib)
+}
+
+fn set_data(format: u32, bytes: &[u8]) -> Result<(), Error> {
+    let _open = OpenGuard::open().ok_or_else(Error::from_win32)?;
+
+    unsafe {
+        EmptyClipboard().ok()?;
+
+        let mem = GlobalAlloc(GMEM_MOVEABLE, bytes.len());
+        if mem == 0 {
+            return Err(Error::from_win32());
+        }
+
+        let data = GlobalLock(mem) as *mut u8;
+        if data.is_null() {
+            GlobalFree(mem);
+            return Err(Error::from_win32());
+        }
+        copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+        GlobalUnlock(mem);
+
+        // on success the memory belongs to the system
+        if let Err(e) = SetClipboardData(format, HANDLE(mem)) {
+            GlobalFree(mem);
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// The clipboard opened by this thread, closed again on drop.
+struct OpenGuard;
+
+impl OpenGuard {
+    fn open() -> Option<Self> {
+        for _ in 0..OPEN_ATTEMPTS {
+            if unsafe { OpenClipboard(HWND::default()) }.as_bool() {
+                return Some(Self);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        None
+    }
+}
+
+impl Drop for OpenGuard {
+    fn drop(&mut self) {
+        unsafe {
+            CloseClipboard();
+        }
+    }
+}
+
+/// Ignores copies and never pastes anything.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoClipboard;
+
+impl ClipboardBackend for NoClipboard {
+    fn get(&self) -> Option<String> {
+        None
+    }
+
+    fn set(&self, _text: String) {}
+}
+
+/// Clipboard that lives only inside the process, the Windows clipboard is never touched.
+#[derive(Default)]
+pub struct LocalClipboard {
+    text: Mutex<DefaultRawMutex, Option<String>>,
+}
+
+impl ClipboardBackend for LocalClipboard {
+    fn get(&self) -> Option<String> {
+        self.text.lock().clone()
+    }
+
+    fn set(&self, text: String) {
+        *self.text.lock() = Some(text);
+    }
+}
+
+/// Handle to the backend, shared between the message thread and the render thread.
+#[derive(Clone)]
+pub(crate) struct Clipboard(pub(crate) Arc<dyn ClipboardBackend>);
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self(Arc::new(WindowsClipboard))
+    }
+}
+
+impl fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Clipboard")
+    }
+}
@@ -0,0 +1,121 @@
+//! Shims over the parts of egui that differ between the versions of the `egui-*` features,
+//! the rest of the crate goes through these instead of the fields themselves.
+
+use egui::{
+    epaint::{ClippedPrimitive, ClippedShape, PaintCallback, Primitive},
+    Context, Event, FullOutput, Mesh, Modifiers, PlatformOutput, RawInput, Rect, TexturesDelta,
+    Vec2,
+};
+use std::time::Duration;
+
+/// What the backend takes from a [`FullOutput`].
+pub struct UiOutput {
+    pub platform_output: PlatformOutput,
+    pub shapes: Vec<ClippedShape>,
+    pub textures_delta: TexturesDelta,
+    /// Zero if egui wants to run again right away.
+    pub repaint_after: Duration,
+}
+
+impl From<FullOutput> for UiOutput {
+    fn from(output: FullOutput) -> Self {
+        let FullOutput {
+            platform_output,
+            textures_delta,
+            shapes,
+            repaint_after,
+        } = output;
+
+        Self {
+            platform_output,
+            shapes,
+            textures_delta,
+            repaint_after,
+        }
+    }
+}
+
+/// Sets whether the window has the keyboard focus, `has_focus` before 0.22 and `focused` since.
+pub fn set_focused(input: &mut RawInput, focused: bool) {
+    #[cfg(feature = "egui-0_21")]
+    {
+        input.has_focus = focused;
+    }
+    #[cfg(feature = "egui-0_22")]
+    {
+        input.focused = focused;
+    }
+}
+
+/// Calls `callback` whenever a repaint is requested, from any thread. Replaces the context's
+/// previous callback.
+pub fn set_repaint_callback(ctx: &Context, callback: impl Fn() + Send + Sync + 'static) {
+    #[cfg(feature = "egui-0_21")]
+    ctx.set_request_repaint_callback(callback);
+    #[cfg(feature = "egui-0_22")]
+    ctx.set_request_repaint_callback(move |_| callback());
+}
+
+/// Raw wheel event that egui 0.22 sends next to `Scroll`, `delta` is in lines if `lines` is set
+/// and in points otherwise. `None` before 0.22, which only knows `Scroll`.
+#[allow(unused_variables)]
+pub fn mouse_wheel(delta: Vec2, lines: bool, modifiers: Modifiers) -> Option<Event> {
+    #[cfg(feature = "egui-0_21")]
+    return None;
+
+    #[cfg(feature = "egui-0_22")]
+    return Some(Event::MouseWheel {
+        unit: if lines {
+            egui::MouseWheelUnit::Line
+        } else {
+            egui::MouseWheelUnit::Point
+        },
+        delta,
+        modifiers,
+    });
+}
+
+/// Mesh of a primitive with its clip rect, `None` for paint callbacks.
+#[cfg(feature = "replay")]
+pub fn mesh(prim: &ClippedPrimitive) -> Option<(Rect, &Mesh)> {
+    match &prim.primitive {
+        Primitive::Mesh(mesh) => Some((prim.clip_rect, mesh)),
+        Primitive::Callback(_) => None,
+    }
+}
+
+/// Paint callback of a primitive, `None` for meshes.
+pub fn paint_callback(prim: &ClippedPrimitive) -> Option<&PaintCallback> {
+    match &prim.primitive {
+        Primitive::Mesh(_) => None,
+        Primitive::Callback(callback) => Some(callback),
+    }
+}
+
+/// Same as [`mesh`], taking the primitive.
+pub fn into_mesh(prim: ClippedPrimitive) -> Option<(Rect, Mesh)> {
+    match prim.primitive {
+        Primitive::Mesh(mesh) => Some((prim.clip_rect, mesh)),
+        Primitive::Callback(_) => None,
+    }
+}
+
+/// Primitive drawing `mesh` clipped to `clip_rect`.
+#[cfg(feature = "replay")]
+pub fn mesh_primitive(clip_rect: Rect, mesh: Mesh) -> ClippedPrimitive {
+    ClippedPrimitive {
+        clip_rect,
+        primitive: Primitive::Mesh(mesh),
+    }
+}
+
+#[test]
+fn test_set_focused() {
+    let mut input = RawInput::default();
+    set_focused(&mut input, false);
+
+    #[cfg(feature = "egui-0_21")]
+    assert!(!input.has_focus);
+    #[cfg(feature = "egui-0_22")]
+    assert!(!input.focused);
+}
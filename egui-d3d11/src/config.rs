@@ -0,0 +1,973 @@
+use crate::{
+    clipboard::{Clipboard, ClipboardBackend, LocalClipboard, WindowsClipboard},
+    hdr::{ColorSpace, DEFAULT_SDR_WHITE_LEVEL},
+};
+use egui::{
+    epaint::TessellationOptions, Context, FontDefinitions, Key, Pos2, Rgba, TextureFilter, Vec2,
+};
+#[cfg(any(feature = "persistence", feature = "settings"))]
+use std::path::PathBuf;
+use std::{sync::Arc, time::Duration};
+use windows::Win32::Graphics::{
+    Direct3D11::{
+        D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT,
+        D3D11_COMMONSHADER_INPUT_RESOURCE_SLOT_COUNT, D3D11_COMMONSHADER_SAMPLER_SLOT_COUNT,
+    },
+    Dxgi::Common::DXGI_FORMAT,
+};
+use windows::Win32::{Foundation::HWND, UI::Input::KeyboardAndMouse::VIRTUAL_KEY};
+
+/// How the game's pipeline state is preserved around the UI pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BackupMode {
+    /// Saves the whole pipeline state before drawing and restores it afterwards.
+    #[default]
+    Full,
+    /// Leaves the pipeline as the UI pass set it up.
+    /// Only useful when the app owns the device, e.g. when used as a standalone renderer.
+    None,
+}
+
+/// Addressing mode of the texture sampler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum AddressMode {
+    /// Coordinates outside of the texture sample a white border.
+    #[default]
+    Border,
+    Clamp,
+    Wrap,
+    Mirror,
+}
+
+/// Mip range and anisotropic filtering of a single texture,
+/// see [`crate::DirectX11App::set_texture_sampling`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureSampling {
+    /// Samples of anisotropic filtering, clamped to `1..=16`.
+    /// `1` keeps the texture's nearest or linear filtering.
+    pub anisotropy: u8,
+    /// Most detailed mip level that gets sampled.
+    pub min_lod: f32,
+    /// Least detailed mip level that gets sampled, [`f32::MAX`] allows every level.
+    pub max_lod: f32,
+    /// Added to the mip level the GPU picks, negative values sharpen.
+    pub lod_bias: f32,
+}
+
+impl Default for TextureSampling {
+    fn default() -> Self {
+        Self {
+            anisotropy: 1,
+            min_lod: 0.,
+            max_lod: f32::MAX,
+            lod_bias: 0.,
+        }
+    }
+}
+
+impl TextureSampling {
+    /// Every mip level with `samples` of anisotropic filtering, for textures shown at oblique
+    /// or strongly uneven scales.
+    pub fn anisotropic(samples: u8) -> Self {
+        Self {
+            anisotropy: samples,
+            ..Default::default()
+        }
+    }
+}
+
+/// How the UI is blended into the render target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AlphaMode {
+    /// Blends with the source alpha, for drawing straight onto the game's backbuffer.
+    #[default]
+    Straight,
+    /// Blends egui's premultiplied colors as they are and leaves premultiplied colors with
+    /// the coverage in alpha, for intermediate textures that are composited later.
+    Premultiplied,
+}
+
+/// What happens when the UI's GPU memory exceeds [`Config::memory_budget`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BudgetAction {
+    /// Only logs a warning.
+    #[default]
+    Warn,
+    /// Frees the least recently drawn textures loaded with [`crate::DirectX11App::load_dds`]
+    /// until the UI fits again, see [`crate::DirectX11App::take_evicted_textures`].
+    EvictUserTextures,
+}
+
+/// What happens when an internal lock is held longer than [`Config::lock_watchdog`] allows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum WatchdogAction {
+    /// Reports [`crate::Error::LockTimeout`] and keeps waiting.
+    #[default]
+    Report,
+    /// Reports the error and skips the frame when `present` is the one waiting,
+    /// every other call keeps waiting.
+    SkipFrame,
+}
+
+/// What happens when the backbuffer or its render target view can't be acquired, see
+/// [`Config::buffer_failure`]. Usually a transient failure while the game switches resolutions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BufferFailure {
+    /// Reports the error through the error handler, or panics without one.
+    #[default]
+    Report,
+    /// Logs the error and skips the frame, the buffer is acquired again on the next `present`.
+    SkipFrame,
+    /// Skips frames like [`Self::SkipFrame`] until this many presents in a row failed, then
+    /// reports the error and starts counting again.
+    Retry(u32),
+}
+
+/// Keys whose messages reach the game while egui wants the keyboard, see
+/// [`Config::key_passthrough`]. egui sees every key either way.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum KeyPassthrough {
+    /// Every key egui wants is blocked.
+    #[default]
+    None,
+    /// Only these keys reach the game, e.g. push-to-talk or the screenshot key.
+    Allow(Vec<VIRTUAL_KEY>),
+    /// Every key but these reaches the game.
+    Deny(Vec<VIRTUAL_KEY>),
+}
+
+impl KeyPassthrough {
+    pub(crate) fn passes(&self, key: VIRTUAL_KEY) -> bool {
+        match self {
+            Self::None => false,
+            Self::Allow(keys) => keys.contains(&key),
+            Self::Deny(keys) => !keys.contains(&key),
+        }
+    }
+}
+
+/// Where in the game's frame the UI is drawn, see [`Config::submit_point`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SubmitPoint {
+    /// In [`crate::DirectX11App::present`], right before the game's present.
+    #[default]
+    Present,
+    /// Only in [`crate::DirectX11App::submit`], `present` does nothing.
+    Manual,
+}
+
+/// Space vertex colors and textures are multiplied and blended in, see [`Config::color_blending`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorBlending {
+    /// Gamma encoded colors are multiplied and blended as they are, through a plain view.
+    /// Matches egui's reference renderer, translucent panels look the same as in eframe.
+    Gamma,
+    /// Colors are decoded before they are multiplied and blended through an `_SRGB` view,
+    /// physically correct but translucent panels look lighter than egui intends.
+    Linear,
+}
+
+/// Trade-off between smooth and cheap thin lines, e.g. of plots, see [`Config::line_quality`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LineQuality {
+    /// No feathering, edges are aliased but meshes are about half the size.
+    Fast,
+    /// egui's own feathering.
+    #[default]
+    Default,
+    /// Wider feathering that hides the stair steps of thin, steep lines at the cost of slightly
+    /// softer edges. Line primitives, e.g. of the wireframe view, are rasterized anti-aliased.
+    Smooth,
+}
+
+impl LineQuality {
+    /// Feathering of `Smooth`, egui's default is one pixel.
+    const SMOOTH_FEATHERING: f32 = 1.5;
+
+    pub(crate) fn apply(self, options: &mut TessellationOptions) {
+        match self {
+            Self::Fast => options.feathering = false,
+            Self::Default => {}
+            Self::Smooth => {
+                options.feathering = true;
+                options.feathering_size_in_pixels = options
+                    .feathering_size_in_pixels
+                    .max(Self::SMOOTH_FEATHERING);
+            }
+        }
+    }
+}
+
+/// Target the shaders are compiled for, see [`Config::shader_compile`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderModel {
+    /// `vs_4_0` and `ps_4_0`, for feature level 10 devices.
+    Sm4,
+    /// `vs_5_0` and `ps_5_0`, needs feature level 11.
+    Sm5,
+}
+
+/// Registers the UI's pixel shader reads its texture and sampler from and both shaders their
+/// constants, see [`Config::shader_slots`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct ShaderSlots {
+    /// `t` register of the texture, below 128.
+    pub texture: u32,
+    /// `s` register of the sampler, below 16.
+    pub sampler: u32,
+    /// `b` register of the constant buffer, below 14.
+    pub constants: u32,
+}
+
+/// Optimization level of the shader compiler, see [`Config::shader_compile`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ShaderOptimization {
+    /// No optimizations at all, the easiest to step through in a graphics debugger.
+    Skip,
+    Level0,
+    #[default]
+    Level1,
+    Level2,
+    Level3,
+}
+
+/// Where the size of the area the UI is laid out on comes from while no [`ScreenTransform`] is set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ScreenSizeSource {
+    /// The swapchain's buffers. Input is stretched onto them when the window's client area
+    /// has another size, the window is only used when it can't be queried.
+    #[default]
+    Swapchain,
+    /// The window's client area, the buffers only when it can't be queried.
+    Window,
+}
+
+impl ScreenSizeSource {
+    /// Size of the screen in pixels and the transform input needs, from the size of the client
+    /// area, `None` if it couldn't be queried, and the one of the buffers.
+    pub(crate) fn resolve(
+        self,
+        client: Option<Vec2>,
+        buffer: Vec2,
+    ) -> (Vec2, Option<ScreenTransform>) {
+        match client {
+            Some(client) if self == Self::Window || client == buffer => (client, None),
+            Some(client) => (buffer, Some(ScreenTransform::stretch(client, buffer))),
+            None => (buffer, None),
+        }
+    }
+}
+
+/// Maps the window's client area onto the backbuffer, for games that render at a different
+/// resolution than their window or don't fill all of it.
+/// A point `p` of the client area ends up at `(p - offset) * scale` on the backbuffer.
+///
+/// While set, the UI is laid out in backbuffer pixels instead of window pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenTransform {
+    pub offset: Vec2,
+    pub scale: Vec2,
+}
+
+impl ScreenTransform {
+    /// Leaves positions as they are.
+    pub const IDENTITY: Self = Self {
+        offset: Vec2::ZERO,
+        scale: Vec2::splat(1.),
+    };
+
+    /// Backbuffer of `backbuffer` pixels stretched over a client area of `client` pixels.
+    pub fn stretch(client: Vec2, backbuffer: Vec2) -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            scale: backbuffer / client,
+        }
+    }
+
+    /// Moves the result of the transform by `-origin` backbuffer pixels, for a UI confined to an
+    /// area starting at `origin`.
+    pub fn shifted(self, origin: Vec2) -> Self {
+        Self {
+            offset: self.offset + origin / self.scale,
+            scale: self.scale,
+        }
+    }
+
+    #[inline]
+    pub fn apply(&self, pos: Pos2) -> Pos2 {
+        ((pos - self.offset).to_vec2() * self.scale).to_pos2()
+    }
+
+    /// Maps a backbuffer position back onto the client area.
+    #[inline]
+    pub fn inverse(&self, pos: Pos2) -> Pos2 {
+        (pos.to_vec2() / self.scale + self.offset).to_pos2()
+    }
+}
+
+/// Runtime options of [`crate::DirectX11App`], passed to [`crate::DirectX11App::init_with_config`].
+///
+/// ```ignore
+/// let config = Config::default()
+///     .clear_color(Some(Rgba::BLACK))
+///     .toggle_key(Some(Key::Insert));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub(crate) clear_color: Option<Rgba>,
+    pub(crate) color_space: Option<ColorSpace>,
+    pub(crate) sdr_white_level: f32,
+    pub(crate) backup: BackupMode,
+    pub(crate) sampler_filter: TextureFilter,
+    pub(crate) sampler_address: AddressMode,
+    pub(crate) toggle_key: Option<Key>,
+    pub(crate) mouse_capture: bool,
+    pub(crate) input_window: Option<HWND>,
+    pub(crate) key_passthrough: KeyPassthrough,
+    pub(crate) visible: bool,
+    pub(crate) ui_scale: f32,
+    pub(crate) fonts: Option<FontDefinitions>,
+    pub(crate) context: Option<Context>,
+    pub(crate) transform: Option<ScreenTransform>,
+    pub(crate) screen_size: ScreenSizeSource,
+    pub(crate) clipboard: Clipboard,
+    pub(crate) srgb_view: Option<bool>,
+    pub(crate) color_blending: Option<ColorBlending>,
+    pub(crate) upload_budget: Option<usize>,
+    pub(crate) staged_atlas: Option<usize>,
+    pub(crate) deferred_uploads: bool,
+    pub(crate) retain_textures: bool,
+    pub(crate) memory_budget: Option<(usize, BudgetAction)>,
+    pub(crate) dither: bool,
+    pub(crate) gamma: f32,
+    pub(crate) render_scale: f32,
+    pub(crate) exclude_from_capture: bool,
+    pub(crate) brightness: f32,
+    pub(crate) alpha_mode: AlphaMode,
+    pub(crate) software_cursor: bool,
+    pub(crate) wireframe: bool,
+    pub(crate) clip_rects: bool,
+    pub(crate) overdraw: bool,
+    pub(crate) tessellation: Option<TessellationOptions>,
+    pub(crate) line_quality: LineQuality,
+    pub(crate) shader_model: Option<ShaderModel>,
+    pub(crate) shader_debug: bool,
+    pub(crate) shader_optimization: ShaderOptimization,
+    pub(crate) shader_slots: ShaderSlots,
+    pub(crate) watchdog: Option<(Duration, WatchdogAction)>,
+    pub(crate) max_ui_fps: Option<u32>,
+    pub(crate) submit_point: SubmitPoint,
+    pub(crate) resize_fallback: bool,
+    pub(crate) buffer_failure: BufferFailure,
+    pub(crate) frame_timings: usize,
+    pub(crate) slow_frame: Option<Duration>,
+    pub(crate) profiler: bool,
+    pub(crate) instancing: bool,
+    pub(crate) callback_depth: Option<DXGI_FORMAT>,
+    #[cfg(feature = "persistence")]
+    pub(crate) persistence: Option<(PathBuf, Duration)>,
+    #[cfg(feature = "settings")]
+    pub(crate) settings: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clear_color: None,
+            color_space: None,
+            sdr_white_level: DEFAULT_SDR_WHITE_LEVEL,
+            backup: BackupMode::Full,
+            sampler_filter: TextureFilter::Linear,
+            sampler_address: AddressMode::Border,
+            toggle_key: None,
+            mouse_capture: true,
+            input_window: None,
+            key_passthrough: KeyPassthrough::None,
+            visible: true,
+            ui_scale: 1.,
+            fonts: None,
+            context: None,
+            transform: None,
+            screen_size: ScreenSizeSource::Swapchain,
+            clipboard: Clipboard::default(),
+            srgb_view: None,
+            color_blending: None,
+            upload_budget: None,
+            staged_atlas: None,
+            deferred_uploads: false,
+            retain_textures: true,
+            memory_budget: None,
+            dither: false,
+            gamma: 1.,
+            render_scale: 1.,
+            exclude_from_capture: false,
+            brightness: 1.,
+            alpha_mode: AlphaMode::Straight,
+            software_cursor: false,
+            wireframe: false,
+            clip_rects: false,
+            overdraw: false,
+            tessellation: None,
+            line_quality: LineQuality::Default,
+            shader_model: None,
+            shader_debug: cfg!(debug_assertions),
+            shader_optimization: ShaderOptimization::Level1,
+            shader_slots: ShaderSlots::default(),
+            watchdog: None,
+            max_ui_fps: None,
+            submit_point: SubmitPoint::Present,
+            resize_fallback: false,
+            buffer_failure: BufferFailure::Report,
+            frame_timings: 120,
+            slow_frame: None,
+            profiler: false,
+            instancing: false,
+            callback_depth: None,
+            #[cfg(feature = "persistence")]
+            persistence: None,
+            #[cfg(feature = "settings")]
+            settings: None,
+        }
+    }
+}
+
+impl Config {
+    /// View the render target is drawn through, [`Self::srgb_view`] or the one the blending needs.
+    pub(crate) fn wanted_srgb_view(&self) -> Option<bool> {
+        self.srgb_view.or_else(|| match self.color_blending? {
+            ColorBlending::Gamma => Some(false),
+            ColorBlending::Linear => Some(true),
+        })
+    }
+
+    /// Color the backbuffer is cleared with before drawing the UI, `None` disables clearing.
+    #[must_use]
+    pub fn clear_color(mut self, color: Option<Rgba>) -> Self {
+        self.clear_color = color;
+        self
+    }
+
+    /// Color space the UI is rendered in, `None` detects it from the swapchain.
+    #[must_use]
+    pub fn color_space(mut self, color_space: Option<ColorSpace>) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Draws through an `_SRGB` render target view, so blending and anti-aliasing happen in linear space.
+    /// `None` does what the game's own view does. Ignored for formats without an sRGB variant.
+    #[must_use]
+    pub fn srgb_view(mut self, srgb: Option<bool>) -> Self {
+        self.srgb_view = srgb;
+        self
+    }
+
+    /// Picks how colors are blended, `None` blends in whatever space the render target view
+    /// uses. Picks the view as well unless [`Self::srgb_view`] forces one, formats without an
+    /// sRGB variant always blend the way they store colors.
+    #[must_use]
+    pub fn color_blending(mut self, blending: Option<ColorBlending>) -> Self {
+        self.color_blending = blending;
+        self
+    }
+
+    /// Brightness of egui's white in nits when rendering into HDR backbuffers.
+    #[must_use]
+    pub fn sdr_white_level(mut self, nits: f32) -> Self {
+        self.sdr_white_level = nits;
+        self
+    }
+
+    /// How the game's pipeline state is preserved around the UI pass.
+    #[must_use]
+    pub fn backup(mut self, backup: BackupMode) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// Filtering and addressing of the texture sampler. Textures egui created with
+    /// non-default [`egui::TextureOptions`] keep their own filtering,
+    /// see [`crate::DirectX11App::set_texture_address`] for addressing per texture and
+    /// [`crate::DirectX11App::set_texture_sampling`] for mipmaps and anisotropic filtering.
+    #[must_use]
+    pub fn sampler(mut self, filter: TextureFilter, address: AddressMode) -> Self {
+        self.sampler_filter = filter;
+        self.sampler_address = address;
+        self
+    }
+
+    /// Key that toggles the visibility of the UI.
+    #[must_use]
+    pub fn toggle_key(mut self, key: Option<Key>) -> Self {
+        self.toggle_key = key;
+        self
+    }
+
+    /// Captures the mouse while a button is held down on egui, so dragging a slider or window
+    /// keeps going when the cursor leaves the game's window. The capture is never taken from the
+    /// game, disable it for games that react badly to `WM_CAPTURECHANGED`. On by default.
+    #[must_use]
+    pub fn mouse_capture(mut self, enabled: bool) -> Self {
+        self.mouse_capture = enabled;
+        self
+    }
+
+    /// Window the game's input arrives at when it isn't the one the swapchain renders to, e.g.
+    /// the parent of an engine's child render window. Its messages are routed like the ones of
+    /// [`crate::DirectX11App::add_input_window`] and [`crate::DirectX11App::wnd_proc`] takes
+    /// them from it, the screen size still comes from the render window.
+    /// [`crate::hook_entry!`] subclasses it instead of the swapchain's window.
+    #[must_use]
+    pub fn input_window(mut self, hwnd: Option<HWND>) -> Self {
+        self.input_window = hwnd;
+        self
+    }
+
+    /// Keys the game still sees while egui wants the keyboard, the characters they type follow
+    /// them. Modifiers arrive as `VK_SHIFT`, `VK_CONTROL` and `VK_MENU`, not as their left
+    /// and right variants. Every key is blocked by default.
+    #[must_use]
+    pub fn key_passthrough(mut self, keys: KeyPassthrough) -> Self {
+        self.key_passthrough = keys;
+        self
+    }
+
+    /// Scale of the UI on top of the one of the monitor, `1.0` by default.
+    #[must_use]
+    pub fn ui_scale(mut self, scale: f32) -> Self {
+        self.ui_scale = scale;
+        self
+    }
+
+    /// Whether the UI is visible right after init.
+    #[must_use]
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Fonts installed before the first frame, e.g. to add CJK or icon fonts.
+    #[must_use]
+    pub fn fonts(mut self, fonts: FontDefinitions) -> Self {
+        self.fonts = Some(fonts);
+        self
+    }
+
+    /// egui context owned by the host, e.g. shared with another egui frontend drawing to a
+    /// different window so memory and style stay in sync. Only used by `init_*`,
+    /// a new context is created if this isn't set.
+    #[must_use]
+    pub fn context(mut self, ctx: Context) -> Self {
+        self.context = Some(ctx);
+        self
+    }
+
+    /// Maps window coordinates onto the backbuffer, `None` leaves it to [`Self::screen_size_source`].
+    #[must_use]
+    pub fn transform(mut self, transform: Option<ScreenTransform>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Whether the UI is laid out on the swapchain's buffers or the window's client area
+    /// when they have different sizes.
+    #[must_use]
+    pub fn screen_size_source(mut self, source: ScreenSizeSource) -> Self {
+        self.screen_size = source;
+        self
+    }
+
+    /// Where copied text goes and pasted text comes from, the Windows clipboard by default.
+    #[must_use]
+    pub fn clipboard(mut self, clipboard: impl ClipboardBackend) -> Self {
+        self.clipboard = Clipboard(Arc::new(clipboard));
+        self
+    }
+
+    /// `false` keeps copied text inside the process and never reads or writes the Windows clipboard.
+    /// Replaces the backend set with [`Self::clipboard`].
+    #[must_use]
+    pub fn system_clipboard(self, enabled: bool) -> Self {
+        if enabled {
+            self.clipboard(WindowsClipboard)
+        } else {
+            self.clipboard(LocalClipboard::default())
+        }
+    }
+
+    /// Bytes of changed texture regions copied to the GPU per frame, the rest is spread over
+    /// the next frames. Only applies to big textures like the font atlas, `None` uploads
+    /// everything in the frame it changed.
+    #[must_use]
+    pub fn upload_budget(mut self, bytes: Option<usize>) -> Self {
+        self.upload_budget = bytes;
+        self
+    }
+
+    /// Copies a rebuilt font atlas, e.g. after the scale changed or fonts were installed, into
+    /// a new texture over several frames, at most `bytes` per frame, and swaps it in once it's
+    /// complete. The UI shows the frame from before the rebuild meanwhile, which costs a copy
+    /// of every frame's primitives while enabled. `None` uploads the whole atlas in the frame
+    /// egui rebuilt it, the default.
+    #[must_use]
+    pub fn staged_atlas(mut self, bytes: Option<usize>) -> Self {
+        self.staged_atlas = bytes;
+        self
+    }
+
+    /// Records texture updates on a deferred context and executes them at the start of the next
+    /// frame, so they don't add to the frame time of the game's frame that changed them.
+    /// Changed glyphs show up one frame late.
+    #[must_use]
+    pub fn deferred_uploads(mut self, enabled: bool) -> Self {
+        self.deferred_uploads = enabled;
+        self
+    }
+
+    /// Keeps a copy of the pixels of big egui textures like the font atlas, which are otherwise
+    /// only kept on the GPU, so they can be uploaded again when the game recreates its device.
+    /// Enabled by default, only affects textures allocated afterwards.
+    /// User textures are restored by [`crate::DirectX11App::set_texture_restorer`].
+    #[must_use]
+    pub fn retain_textures(mut self, enabled: bool) -> Self {
+        self.retain_textures = enabled;
+        self
+    }
+
+    /// Adds a little noise to the output to hide banding of dark translucent panels on 8 bit backbuffers.
+    #[must_use]
+    pub fn dither(mut self, enabled: bool) -> Self {
+        self.dither = enabled;
+        self
+    }
+
+    /// Draws the UI at a fraction of the backbuffer's resolution and stretches it over the
+    /// backbuffer, trading sharpness for speed on weak GPUs driving big resolutions.
+    /// Clamped to `0.25..=1.0`, `1.0` draws straight into the backbuffer.
+    #[must_use]
+    pub fn render_scale(mut self, scale: f32) -> Self {
+        self.render_scale = scale.clamp(0.25, 1.);
+        self
+    }
+
+    /// Hides the window the UI is drawn on from screen capture, so it doesn't show up in streams
+    /// and recordings. Meant for overlays with a window of their own, see
+    /// [`crate::DirectX11App::init_with_device`], the game disappears as well if it's its window.
+    /// Needs Windows 10 2004 or later.
+    #[must_use]
+    pub fn exclude_from_capture(mut self, exclude: bool) -> Self {
+        self.exclude_from_capture = exclude;
+        self
+    }
+
+    /// Quick fix for games whose tonemapping makes the UI look washed out or too dark.
+    /// Colors are raised to `1 / gamma` and multiplied by `brightness`, `1.0` leaves them alone.
+    #[must_use]
+    pub fn gamma(mut self, gamma: f32, brightness: f32) -> Self {
+        self.gamma = gamma;
+        self.brightness = brightness;
+        self
+    }
+
+    /// Blending of the UI, [`AlphaMode::Premultiplied`] when the target is composited later,
+    /// e.g. by DirectComposition or the game's own UI pass.
+    #[must_use]
+    pub fn alpha_mode(mut self, mode: AlphaMode) -> Self {
+        self.alpha_mode = mode;
+        self
+    }
+
+    /// Draws egui's own cursor and hides the OS one while egui wants the pointer,
+    /// for games that hide or lock the cursor. `WM_SETCURSOR` has to be routed through
+    /// [`crate::DirectX11App::wnd_proc`] for the OS cursor to stay hidden.
+    #[must_use]
+    pub fn software_cursor(mut self, enabled: bool) -> Self {
+        self.software_cursor = enabled;
+        self
+    }
+
+    /// Draws the outlines of the UI's triangles instead of filling them, every mesh in its own color.
+    /// For debugging clipping, tessellation and how the UI is split into draw calls.
+    #[must_use]
+    pub fn wireframe(mut self, enabled: bool) -> Self {
+        self.wireframe = enabled;
+        self
+    }
+
+    /// Outlines the clip rectangle of every shape, each one in its own color.
+    #[must_use]
+    pub fn clip_rects(mut self, enabled: bool) -> Self {
+        self.clip_rects = enabled;
+        self
+    }
+
+    /// Draws the UI as an additive heat map, the more triangles cover a pixel the brighter it gets.
+    /// For finding stacked translucent panels that cost a lot of fill rate.
+    #[must_use]
+    pub fn overdraw(mut self, enabled: bool) -> Self {
+        self.overdraw = enabled;
+        self
+    }
+
+    /// Options the UI is tessellated with, e.g. a smaller feathering size or no feathering at all
+    /// for cheaper meshes in heavy scenes. `None` leaves egui's own options alone.
+    #[must_use]
+    pub fn tessellation(mut self, options: Option<TessellationOptions>) -> Self {
+        self.tessellation = options;
+        self
+    }
+
+    /// How smooth thin lines are drawn, applied on top of [`Self::tessellation`].
+    #[must_use]
+    pub fn line_quality(mut self, quality: LineQuality) -> Self {
+        self.line_quality = quality;
+        self
+    }
+
+    /// How the shaders are compiled on init. `None` picks the newest model the device supports,
+    /// `debug` embeds debug info for tools like RenderDoc and defaults to debug builds only.
+    #[must_use]
+    pub fn shader_compile(
+        mut self,
+        model: Option<ShaderModel>,
+        debug: bool,
+        optimization: ShaderOptimization,
+    ) -> Self {
+        self.shader_model = model;
+        self.shader_debug = debug;
+        self.shader_optimization = optimization;
+        self
+    }
+
+    /// Slots the UI binds its texture, sampler and constant buffer to, all 0 by default. Higher
+    /// ones keep them apart from bindings games leave in slot 0 and from the ones of draw hooks.
+    /// Slots past the last one D3D11 has are clamped to it. Takes effect when the shaders are
+    /// compiled, on init and when the game recreates its device.
+    #[must_use]
+    pub fn shader_slots(mut self, slots: ShaderSlots) -> Self {
+        self.shader_slots = ShaderSlots {
+            texture: slots
+                .texture
+                .min(D3D11_COMMONSHADER_INPUT_RESOURCE_SLOT_COUNT - 1),
+            sampler: slots.sampler.min(D3D11_COMMONSHADER_SAMPLER_SLOT_COUNT - 1),
+            constants: slots
+                .constants
+                .min(D3D11_COMMONSHADER_CONSTANT_BUFFER_API_SLOT_COUNT - 1),
+        };
+        self
+    }
+
+    /// Bytes of textures and buffers the UI may use before `action` is taken, `None` disables the check.
+    /// See [`crate::DirectX11App::gpu_memory_usage`].
+    #[must_use]
+    pub fn memory_budget(mut self, bytes: Option<usize>, action: BudgetAction) -> Self {
+        self.memory_budget = bytes.map(|bytes| (bytes, action));
+        self
+    }
+
+    /// Reports locks held longer than `threshold` through the error handler, with where they
+    /// were taken and where the call is waiting, instead of freezing silently. `None` disables it.
+    /// See [`crate::DirectX11App::set_error_handler`].
+    #[must_use]
+    pub fn lock_watchdog(mut self, threshold: Option<Duration>, action: WatchdogAction) -> Self {
+        self.watchdog = threshold.map(|threshold| (threshold, action));
+        self
+    }
+
+    /// Runs the UI at most `fps` times a second, frames in between draw the primitives of the last
+    /// run again. Input, animations and repaint requests always run it. `None`, the default, runs
+    /// it every frame. See [`crate::DirectX11App::set_max_ui_fps`].
+    #[must_use]
+    pub fn max_ui_fps(mut self, fps: Option<u32>) -> Self {
+        self.max_ui_fps = fps.filter(|&fps| fps > 0);
+        self
+    }
+
+    /// Where the UI is drawn, in `present` by default.
+    ///
+    /// Games with a frame latency waitable object, created with
+    /// `DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT`, wait on the handle of
+    /// `IDXGISwapChain2::GetFrameLatencyWaitableObject` before they start a frame. It's only
+    /// signaled once the GPU finished an earlier frame, so UI work added after the game already
+    /// waited for the GPU elsewhere in the frame, e.g. on a query or a mapped readback, delays
+    /// the next signal and can stall the game. [`SubmitPoint::Manual`] lets you submit it with
+    /// [`crate::DirectX11App::submit`] from your own hook before such a wait instead.
+    #[must_use]
+    pub fn submit_point(mut self, point: SubmitPoint) -> Self {
+        self.submit_point = point;
+        self
+    }
+
+    /// For setups that can't hook `ResizeBuffers`: the views of the backbuffer are released
+    /// after every frame, so the game's own resize doesn't fail on references the app holds, and
+    /// size changes are picked up in `present`. Costs creating a view every frame. Off by
+    /// default, [`crate::DirectX11App::resize_buffers`] is cheaper when it can be hooked.
+    #[must_use]
+    pub fn resize_fallback(mut self, enabled: bool) -> Self {
+        self.resize_fallback = enabled;
+        self
+    }
+
+    /// What happens when `GetBuffer` or creating the render target view fails in `present` or
+    /// [`crate::DirectX11App::resize_buffers`], the error is reported by default. A failure in
+    /// `ResizeBuffers` is only logged with the other policies, `present` tries again anyway.
+    #[must_use]
+    pub fn buffer_failure(mut self, policy: BufferFailure) -> Self {
+        self.buffer_failure = policy;
+        self
+    }
+
+    /// Keeps the stage timings of the last `frames` frames, 120 by default. Frames that take
+    /// `slow` or longer are logged with their stages and counted, `None` doesn't report any.
+    /// See [`crate::DirectX11App::frame_timings`].
+    #[must_use]
+    pub fn frame_timings(mut self, frames: usize, slow: Option<Duration>) -> Self {
+        self.frame_timings = frames;
+        self.slow_frame = slow;
+        self
+    }
+
+    /// Shows a window with the timings of the last frames on top of the UI.
+    /// See [`crate::DirectX11App::set_profiler_visible`].
+    #[must_use]
+    pub fn profiler(mut self, visible: bool) -> Self {
+        self.profiler = visible;
+        self
+    }
+
+    /// Draws consecutive meshes that only differ in their position, e.g. rows of identical icons
+    /// in separate clip rects, with one instanced call. Costs a comparison of the small meshes
+    /// of every frame, so it only pays off for UIs with many of them. Off by default.
+    #[must_use]
+    pub fn instancing(mut self, enabled: bool) -> Self {
+        self.instancing = enabled;
+        self
+    }
+
+    /// Gives the draw callbacks a depth-stencil buffer of `format`, e.g.
+    /// `DXGI_FORMAT_D24_UNORM_S8_UINT`, for drawing 3D meshes. It's owned by the app, cleared
+    /// every frame and bound with the render target only while the callbacks run, the UI itself
+    /// never uses it. `None`, the default, runs them with the game's targets.
+    /// See [`crate::DirectX11App::set_pre_draw`] and [`crate::DirectX11App::add_draw_hook`].
+    #[must_use]
+    pub fn callback_depth(mut self, format: Option<DXGI_FORMAT>) -> Self {
+        self.callback_depth = format;
+        self
+    }
+
+    /// File egui's memory is loaded from on init and saved to every `interval`.
+    /// Call [`crate::DirectX11App::save_memory`] on shutdown to not lose the last changes.
+    #[cfg(feature = "persistence")]
+    #[must_use]
+    pub fn persistence(mut self, path: impl Into<PathBuf>, interval: Duration) -> Self {
+        self.persistence = Some((path.into(), interval));
+        self
+    }
+
+    /// File the [`crate::settings::OverlaySettings`] are loaded from on init, they override the
+    /// options of this config. Call [`crate::DirectX11App::save_settings`] on shutdown to write
+    /// them back. Usually [`crate::settings::default_path`], which keeps one file per game.
+    #[cfg(feature = "settings")]
+    #[must_use]
+    pub fn settings(mut self, path: Option<PathBuf>) -> Self {
+        self.settings = path;
+        self
+    }
+}
+
+#[test]
+fn test_screen_size_source() {
+    let buffer = Vec2::new(1920., 1080.);
+    let client = Vec2::new(1280., 720.);
+
+    let (screen, transform) = ScreenSizeSource::Swapchain.resolve(Some(client), buffer);
+    assert_eq!(screen, buffer);
+    assert_eq!(
+        transform.unwrap().apply(Pos2::new(640., 360.)),
+        Pos2::new(960., 540.)
+    );
+
+    assert_eq!(
+        ScreenSizeSource::Swapchain.resolve(Some(buffer), buffer),
+        (buffer, None)
+    );
+    assert_eq!(
+        ScreenSizeSource::Window.resolve(Some(client), buffer),
+        (client, None)
+    );
+    assert_eq!(
+        ScreenSizeSource::Window.resolve(None, buffer),
+        (buffer, None)
+    );
+
+    let shifted = transform.unwrap().shifted(Vec2::new(100., 50.));
+    assert_eq!(shifted.apply(Pos2::new(640., 360.)), Pos2::new(860., 490.));
+    assert_eq!(
+        shifted.inverse(Pos2::new(860., 490.)),
+        Pos2::new(640., 360.)
+    );
+}
+
+#[test]
+fn test_line_quality() {
+    let mut options = TessellationOptions::default();
+    LineQuality::Default.apply(&mut options);
+    assert_eq!(options, TessellationOptions::default());
+
+    LineQuality::Smooth.apply(&mut options);
+    assert!(options.feathering);
+    assert_eq!(options.feathering_size_in_pixels, 1.5);
+
+    LineQuality::Fast.apply(&mut options);
+    assert!(!options.feathering);
+}
+
+#[test]
+fn test_color_blending_view() {
+    assert_eq!(Config::default().wanted_srgb_view(), None);
+
+    let config = Config::default().color_blending(Some(ColorBlending::Gamma));
+    assert_eq!(config.wanted_srgb_view(), Some(false));
+    let config = Config::default().color_blending(Some(ColorBlending::Linear));
+    assert_eq!(config.wanted_srgb_view(), Some(true));
+
+    // a forced view wins over the blending
+    let config = config.srgb_view(Some(false));
+    assert_eq!(config.wanted_srgb_view(), Some(false));
+}
+
+#[test]
+fn test_shader_slots_clamped() {
+    let config = Config::default().shader_slots(ShaderSlots {
+        texture: 200,
+        sampler: 3,
+        constants: 20,
+    });
+    assert_eq!(
+        config.shader_slots,
+        ShaderSlots {
+            texture: 127,
+            sampler: 3,
+            constants: 13,
+        }
+    );
+}
+
+#[test]
+fn test_max_ui_fps_disabled() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let ctx = Context::default();
+    let requested = Arc::new(AtomicBool::new(false));
+    crate::app::watch_repaints(&ctx, Some(&requested));
+    ctx.request_repaint();
+    assert!(requested.load(Ordering::Relaxed));
+
+    // clearing the limit has to unhook the callback of the old one
+    let ctx = Context::default();
+    let requested = Arc::new(AtomicBool::new(false));
+    crate::app::watch_repaints(&ctx, Some(&requested));
+    crate::app::watch_repaints(&ctx, None);
+    ctx.request_repaint();
+    assert!(!requested.load(Ordering::Relaxed));
+}
@@ -0,0 +1,66 @@
+use std::mem::size_of;
+use windows::Win32::Graphics::Direct3D11::{
+    ID3D11Buffer, ID3D11Device, ID3D11DeviceContext, D3D11_BIND_CONSTANT_BUFFER, D3D11_BUFFER_DESC,
+    D3D11_SUBRESOURCE_DATA, D3D11_USAGE_DEFAULT,
+};
+
+/// Mirrors `constants` cbuffer from `shader.hlsl`, must stay 16 bytes aligned.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct ShaderConstants {
+    pub screen_size: [f32; 2],
+    pub sdr_white_level: f32,
+    /// Amplitude of the dither noise, one quantization step of the target. `0` disables dithering.
+    pub dither: f32,
+    /// Replaces the output color when its alpha isn't `0`, see [`crate::Config::wireframe`].
+    pub tint: [f32; 4],
+    /// `1 / gamma` of [`crate::Config::gamma`], the exponent colors are raised to.
+    pub gamma_exponent: f32,
+    pub brightness: f32,
+    pub _padding: [f32; 2],
+}
+
+/// Constant buffer shared by vertex and pixel shaders, only updated when the values change.
+pub struct ConstantBuffer {
+    pub buffer: ID3D11Buffer,
+    current: ShaderConstants,
+}
+
+impl ConstantBuffer {
+    pub fn new(device: &ID3D11Device, initial: ShaderConstants) -> Self {
+        let desc = D3D11_BUFFER_DESC {
+            ByteWidth: size_of::<ShaderConstants>() as _,
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_CONSTANT_BUFFER,
+            ..Default::default()
+        };
+
+        let init = D3D11_SUBRESOURCE_DATA {
+            pSysMem: &initial as *const _ as _,
+            ..Default::default()
+        };
+
+        let mut buffer: Option<ID3D11Buffer> = None;
+
+        unsafe {
+            expect!(
+                device.CreateBuffer(&desc, Some(&init), Some(&mut buffer)),
+                "Failed to create constant buffer"
+            );
+        }
+
+        Self {
+            buffer: expect!(buffer, "Failed to create constant buffer"),
+            current: initial,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &ID3D11DeviceContext, constants: ShaderConstants) {
+        if self.current != constants {
+            unsafe {
+                ctx.UpdateSubresource(&self.buffer, 0, None, &constants as *const _ as _, 0, 0);
+            }
+            self.current = constants;
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Virtualized cursor position for games that poll `GetCursorPos` instead of reading messages,
+//! so their camera doesn't turn while egui has the pointer.
+//! See [`crate::DirectX11App::virtualize_cursor`].
+//!
+//! While frozen, `GetCursorPos` keeps returning the position the cursor had when egui took it
+//! and `SetCursorPos` only moves that frozen position, the real cursor stays where the user
+//! put it. Hooks of the game's import table cover most games, for those that resolve the
+//! functions with `GetProcAddress` detour them with a library of your choice, call
+//! [`get_cursor_pos`] and [`set_cursor_pos`] from the detours and hand the trampolines to
+//! [`CursorHooks::detoured`].
+
+use crate::{
+    error::{Error, ErrorContext},
+    lock::DefaultRawMutex,
+};
+use lock_api::Mutex;
+use std::{
+    mem::size_of,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+use windows::{
+    core::PCWSTR,
+    s, w,
+    Win32::{
+        Foundation::{BOOL, HINSTANCE, POINT},
+        System::{
+            Diagnostics::Debug::IMAGE_DIRECTORY_ENTRY_IMPORT,
+            LibraryLoader::{GetModuleHandleW, GetProcAddress},
+            Memory::{VirtualProtect, PAGE_PROTECTION_FLAGS, PAGE_READWRITE},
+            SystemServices::{IMAGE_DOS_HEADER, IMAGE_IMPORT_DESCRIPTOR},
+        },
+    },
+};
+
+#[cfg(target_pointer_width = "64")]
+use windows::Win32::System::Diagnostics::Debug::IMAGE_NT_HEADERS64 as IMAGE_NT_HEADERS;
+#[cfg(target_pointer_width = "32")]
+use windows::Win32::System::Diagnostics::Debug::IMAGE_NT_HEADERS32 as IMAGE_NT_HEADERS;
+
+/// Signature of `user32!GetCursorPos`.
+pub type FnGetCursorPos = unsafe extern "system" fn(*mut POINT) -> BOOL;
+/// Signature of `user32!SetCursorPos`.
+pub type FnSetCursorPos = unsafe extern "system" fn(i32, i32) -> BOOL;
+
+// Only one set of hooks can be active, the detours don't get any user data.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static FROZEN: AtomicBool = AtomicBool::new(false);
+// x in the high half, y in the low one
+static FROZEN_POS: AtomicU64 = AtomicU64::new(0);
+static ORIGINAL_GET: AtomicUsize = AtomicUsize::new(0);
+static ORIGINAL_SET: AtomicUsize = AtomicUsize::new(0);
+// apps that want the pointer, several of them share the hooks and the cursor stays frozen
+// while any of them does
+static WANTED_BY: Mutex<DefaultRawMutex, Vec<usize>> =
+    Mutex::const_new(<DefaultRawMutex as lock_api::RawMutex>::INIT, Vec::new());
+
+fn pack(pos: POINT) -> u64 {
+    (pos.x as u32 as u64) << 32 | pos.y as u32 as u64
+}
+
+fn unpack(packed: u64) -> POINT {
+    POINT {
+        x: (packed >> 32) as i32,
+        y: packed as i32,
+    }
+}
+
+unsafe fn original_get() -> FnGetCursorPos {
+    std::mem::transmute::<usize, FnGetCursorPos>(ORIGINAL_GET.load(Ordering::Relaxed))
+}
+
+unsafe fn original_set() -> FnSetCursorPos {
+    std::mem::transmute::<usize, FnSetCursorPos>(ORIGINAL_SET.load(Ordering::Relaxed))
+}
+
+/// Freezes the reported position while egui of any app wants the pointer, called by every
+/// app once per frame with its own address as `app`. Does nothing if no hooks are installed.
+pub(crate) fn set_frozen(app: usize, wanted: bool) {
+    if !ACTIVE.load(Ordering::Acquire) {
+        return;
+    }
+
+    let frozen = {
+        let mut wanted_by = WANTED_BY.lock();
+        wanted_by.retain(|other| *other != app);
+        if wanted {
+            wanted_by.push(app);
+        }
+        !wanted_by.is_empty()
+    };
+    if FROZEN.load(Ordering::Relaxed) == frozen {
+        return;
+    }
+
+    if frozen {
+        let mut pos = POINT::default();
+        if !unsafe { original_get()(&mut pos) }.as_bool() {
+            return;
+        }
+        FROZEN_POS.store(pack(pos), Ordering::Relaxed);
+    }
+    FROZEN.store(frozen, Ordering::Release);
+}
+
+/// Checks if the reported cursor position is currently frozen.
+pub fn is_frozen() -> bool {
+    FROZEN.load(Ordering::Acquire)
+}
+
+/// Body of a `GetCursorPos` detour, returns the frozen position while egui has the pointer.
+///
+/// # Safety
+/// Same as `GetCursorPos`, `point` has to be writable.
+pub unsafe extern "system" fn get_cursor_pos(point: *mut POINT) -> BOOL {
+    if !FROZEN.load(Ordering::Acquire) {
+        return original_get()(point);
+    }
+    if point.is_null() {
+        return BOOL(0);
+    }
+
+    *point = unpack(FROZEN_POS.load(Ordering::Relaxed));
+    BOOL(1)
+}
+
+/// Body of a `SetCursorPos` detour, only moves the frozen position while egui has the pointer.
+///
+/// # Safety
+/// Same as `SetCursorPos`.
+pub unsafe extern "system" fn set_cursor_pos(x: i32, y: i32) -> BOOL {
+    if !FROZEN.load(Ordering::Acquire) {
+        return original_set()(x, y);
+    }
+
+    FROZEN_POS.store(pack(POINT { x, y }), Ordering::Relaxed);
+    BOOL(1)
+}
+
+/// Active cursor virtualization, dropping it restores the import table and unfreezes the cursor.
+pub struct CursorHooks {
+    // import table slots with the original they held
+    patched: Vec<(*mut usize, usize)>,
+}
+
+// The slots live as long as the module, which outlives the overlay.
+unsafe impl Send for CursorHooks {}
+
+impl CursorHooks {
+    /// Patches every import of `GetCursorPos` and `SetCursorPos` in the import table of `module`,
+    /// the game's executable if `None`.
+    ///
+    /// # Safety
+    /// `module` has to be a loaded module, no other thread may patch its import table meanwhile.
+    pub unsafe fn install(module: Option<HINSTANCE>) -> Result<Self, Error> {
+        let user32 =
+            GetModuleHandleW(w!("user32.dll")).context("Failed to get handle of user32.dll")?;
+        let get = GetProcAddress(user32, s!("GetCursorPos")).context("Missing GetCursorPos")?;
+        let set = GetProcAddress(user32, s!("SetCursorPos")).context("Missing SetCursorPos")?;
+
+        let module = match module {
+            Some(module) => module,
+            None => GetModuleHandleW(PCWSTR::null()).context("Failed to get module handle")?,
+        };
+
+        // SAFETY: both are the exports of user32 with exactly these signatures.
+        let mut hooks = Self::activate(
+            std::mem::transmute::<_, FnGetCursorPos>(get),
+            std::mem::transmute::<_, FnSetCursorPos>(set),
+        )?;
+        for (original, hook) in [
+            (get as usize, get_cursor_pos as FnGetCursorPos as usize),
+            (set as usize, set_cursor_pos as FnSetCursorPos as usize),
+        ] {
+            for slot in import_slots(module, original) {
+                write_slot(slot, hook)?;
+                hooks.patched.push((slot, original));
+            }
+        }
+
+        log_msg!(info, "Virtualized {} cursor imports", hooks.patched.len());
+        Ok(hooks)
+    }
+
+    /// Enables the virtualization for detours installed by other means, [`get_cursor_pos`] and
+    /// [`set_cursor_pos`] call `get` and `set` while the cursor isn't frozen.
+    pub fn detoured(get: FnGetCursorPos, set: FnSetCursorPos) -> Result<Self, Error> {
+        Self::activate(get, set)
+    }
+
+    fn activate(get: FnGetCursorPos, set: FnSetCursorPos) -> Result<Self, Error> {
+        if ACTIVE.swap(true, Ordering::AcqRel) {
+            return Err(Error::CursorHooksInstalled);
+        }
+        ORIGINAL_GET.store(get as usize, Ordering::Relaxed);
+        ORIGINAL_SET.store(set as usize, Ordering::Relaxed);
+
+        Ok(Self { patched: vec![] })
+    }
+}
+
+impl Drop for CursorHooks {
+    fn drop(&mut self) {
+        for &(slot, original) in &self.patched {
+            if let Err(e) = unsafe { write_slot(slot, original) } {
+                log_msg!(error, "Failed to restore cursor import: {}", e);
+            }
+        }
+
+        WANTED_BY.lock().clear();
+        FROZEN.store(false, Ordering::Release);
+        ACTIVE.store(false, Ordering::Release);
+    }
+}
+
+/// Import address table entries of `module` that currently point to `target`.
+unsafe fn import_slots(module: HINSTANCE, target: usize) -> Vec<*mut usize> {
+    let base = module.0 as *const u8;
+    let dos = &*(base as *const IMAGE_DOS_HEADER);
+    let nt = &*(base.offset(dos.e_lfanew as isize) as *const IMAGE_NT_HEADERS);
+    let imports = nt.OptionalHeader.DataDirectory[IMAGE_DIRECTORY_ENTRY_IMPORT.0 as usize];
+
+    let mut slots = vec![];
+    if imports.VirtualAddress == 0 {
+        return slots;
+    }
+
+    let mut desc = base.add(imports.VirtualAddress as usize) as *const IMAGE_IMPORT_DESCRIPTOR;
+    while (*desc).Name != 0 {
+        let mut slot = base.add((*desc).FirstThunk as usize) as *mut usize;
+        while *slot != 0 {
+            if *slot == target {
+                slots.push(slot);
+            }
+            slot = slot.add(1);
+        }
+        desc = desc.add(1);
+    }
+
+    slots
+}
+
+unsafe fn write_slot(slot: *mut usize, value: usize) -> Result<(), Error> {
+    let mut old = PAGE_PROTECTION_FLAGS::default();
+    VirtualProtect(slot as _, size_of::<usize>(), PAGE_READWRITE, &mut old)
+        .ok()
+        .context("Failed to unprotect import table")?;
+    slot.write_volatile(value);
+    VirtualProtect(slot as _, size_of::<usize>(), old, &mut old)
+        .ok()
+        .context("Failed to protect import table")
+}
+
+#[test]
+fn test_pack_roundtrip() {
+    for pos in [
+        POINT { x: 0, y: 0 },
+        POINT { x: 1920, y: 1080 },
+        POINT { x: -1280, y: -5 },
+    ] {
+        assert_eq!(unpack(pack(pos)), pos);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::error::{Error, ErrorContext};
+use windows::{
+    core::{IUnknown, Interface},
+    Win32::Graphics::{
+        Direct3D11::{
+            ID3D11Device, ID3D11DeviceContext, ID3D11Resource, ID3D11Texture2D,
+            D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+        },
+        Direct3D11on12::{D3D11On12CreateDevice, ID3D11On12Device, D3D11_RESOURCE_FLAGS},
+        Direct3D12::{
+            ID3D12CommandQueue, ID3D12Device, ID3D12Resource, D3D12_RESOURCE_STATE_PRESENT,
+        },
+        Dxgi::{IDXGISwapChain, IDXGISwapChain3},
+    },
+};
+
+/// D3D11 device layered on top of a D3D12 game's command queue, the UI is drawn
+/// into wrapped swapchain buffers with the regular D3D11 path.
+pub struct D3D12Bridge {
+    pub device: ID3D11Device,
+    pub context: ID3D11DeviceContext,
+    on12: ID3D11On12Device,
+    // Wrapped lazily, indexed by the swapchain's buffer index.
+    buffers: Vec<Option<ID3D11Texture2D>>,
+}
+
+impl D3D12Bridge {
+    /// `queue` has to be the direct queue the game presents with.
+    pub fn new(queue: &ID3D12CommandQueue) -> Result<Self, Error> {
+        unsafe {
+            let mut device12: Option<ID3D12Device> = None;
+            queue
+                .GetDevice(&mut device12)
+                .context("Failed to get command queue's device")?;
+            let device12 = device12.context("Failed to get command queue's device")?;
+
+            let queue: IUnknown = queue.cast().context("Failed to cast command queue")?;
+
+            let mut device = None;
+            let mut context = None;
+            D3D11On12CreateDevice(
+                &device12,
+                D3D11_CREATE_DEVICE_BGRA_SUPPORT.0 as _,
+                None,
+                Some(&[queue]),
+                0,
+                Some(&mut device),
+                Some(&mut context),
+                None,
+            )
+            .context("Failed to create D3D11On12 device")?;
+
+            let device: ID3D11Device = device.context("Failed to create D3D11On12 device")?;
+            let on12 = device
+                .cast::<ID3D11On12Device>()
+                .context("Failed to get D3D11On12 interface")?;
+
+            log_msg!(info, "Created D3D11On12 device");
+
+            Ok(Self {
+                device,
+                context: context.context("Failed to create D3D11On12 device")?,
+                on12,
+                buffers: vec![],
+            })
+        }
+    }
+
+    /// Wrapped buffer the game is going to present next.
+    pub fn current_buffer(&mut self, swap: &IDXGISwapChain) -> Result<ID3D11Texture2D, Error> {
+        unsafe {
+            let swap: IDXGISwapChain3 = swap
+                .cast()
+                .context("D3D12 swapchains have to implement IDXGISwapChain3")?;
+            let index = swap.GetCurrentBackBufferIndex() as usize;
+
+            if self.buffers.len() <= index {
+                self.buffers.resize(index + 1, None);
+            }
+
+            if let Some(buffer) = &self.buffers[index] {
+                return Ok(buffer.clone());
+            }
+
+            let resource: ID3D12Resource = swap
+                .GetBuffer(index as _)
+                .context("Failed to get swapchain's backbuffer")?;
+
+            let flags = D3D11_RESOURCE_FLAGS {
+                BindFlags: (D3D11_BIND_RENDER_TARGET.0 | D3D11_BIND_SHADER_RESOURCE.0) as _,
+                ..Default::default()
+            };
+
+            let mut buffer: Option<ID3D11Texture2D> = None;
+            self.on12
+                .CreateWrappedResource(
+                    &resource,
+                    &flags,
+                    D3D12_RESOURCE_STATE_PRESENT,
+                    D3D12_RESOURCE_STATE_PRESENT,
+                    &mut buffer,
+                )
+                .context("Failed to wrap swapchain's backbuffer")?;
+
+            let buffer = buffer.context("Failed to wrap swapchain's backbuffer")?;
+            self.buffers[index] = Some(buffer.clone());
+
+            Ok(buffer)
+        }
+    }
+
+    /// Hands `buffer` over to D3D11, has to be called before drawing into it.
+    pub fn acquire(&self, buffer: &ID3D11Texture2D) {
+        unsafe {
+            self.on12
+                .AcquireWrappedResources(&[ID3D11Resource::from(buffer)]);
+        }
+    }
+
+    /// Hands `buffer` back in the present state and submits the work to the game's queue.
+    pub fn release(&self, buffer: &ID3D11Texture2D) {
+        unsafe {
+            self.on12
+                .ReleaseWrappedResources(&[ID3D11Resource::from(buffer)]);
+            self.context.Flush();
+        }
+    }
+
+    /// Drops the wrapped buffers, has to happen before the swapchain is resized.
+    pub fn release_buffers(&mut self) {
+        self.buffers.clear();
+        unsafe {
+            self.context.ClearState();
+            self.context.Flush();
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    error::{Error, ErrorContext},
+    target::RenderTargets,
+};
+use windows::{
+    core::Interface,
+    Win32::{
+        Foundation::HWND,
+        Graphics::{
+            Direct3D11::{ID3D11Device, ID3D11Texture2D},
+            DirectComposition::{
+                DCompositionCreateDevice, IDCompositionDevice, IDCompositionTarget,
+                IDCompositionVisual,
+            },
+            Dxgi::{
+                Common::{
+                    DXGI_ALPHA_MODE_PREMULTIPLIED, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_UNKNOWN,
+                    DXGI_SAMPLE_DESC,
+                },
+                IDXGIDevice, IDXGIFactory2, IDXGISwapChain1, DXGI_SCALING_STRETCH,
+                DXGI_SWAP_CHAIN_DESC1, DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL,
+                DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            },
+        },
+    },
+};
+
+/// Transparent swapchain shown on a DirectComposition visual above the game's window.
+/// The UI is drawn into it instead of the game's backbuffer, so the game's post-processing
+/// never sees it. See [`crate::DirectX11App::enable_composition`].
+pub struct CompositionLayer {
+    pub swap_chain: IDXGISwapChain1,
+    pub render_views: RenderTargets,
+    size: (u32, u32),
+    // Released last, the visual disappears together with the target.
+    _visual: IDCompositionVisual,
+    _target: IDCompositionTarget,
+    _device: IDCompositionDevice,
+}
+
+impl CompositionLayer {
+    pub unsafe fn new(
+        dev: &ID3D11Device,
+        hwnd: HWND,
+        (width, height): (u32, u32),
+    ) -> Result<Self, Error> {
+        log_msg!(debug, "Creating composition layer of {}x{}", width, height);
+
+        let dxgi: IDXGIDevice = dev.cast().context("Failed to get DXGI device")?;
+        let factory: IDXGIFactory2 = dxgi
+            .GetAdapter()
+            .and_then(|adapter| adapter.GetParent())
+            .context("Failed to get DXGI factory")?;
+
+        let desc = DXGI_SWAP_CHAIN_DESC1 {
+            Width: width.max(1),
+            Height: height.max(1),
+            Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            BufferCount: 2,
+            Scaling: DXGI_SCALING_STRETCH,
+            SwapEffect: DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL,
+            AlphaMode: DXGI_ALPHA_MODE_PREMULTIPLIED,
+            ..Default::default()
+        };
+        let swap_chain = factory
+            .CreateSwapChainForComposition(dev, &desc, None)
+            .context("Failed to create composition swapchain")?;
+
+        let device: IDCompositionDevice =
+            DCompositionCreateDevice(&dxgi).context("Failed to create composition device")?;
+        let target = device
+            .CreateTargetForHwnd(hwnd, true)
+            .context("Failed to create composition target")?;
+        let visual = device
+            .CreateVisual()
+            .context("Failed to create composition visual")?;
+        visual
+            .SetContent(&swap_chain)
+            .context("Failed to set visual's content")?;
+        target
+            .SetRoot(&visual)
+            .context("Failed to set composition root")?;
+        device.Commit().context("Failed to commit composition")?;
+
+        Ok(Self {
+            swap_chain,
+            render_views: RenderTargets::default(),
+            size: (desc.Width, desc.Height),
+            _visual: visual,
+            _target: target,
+            _device: device,
+        })
+    }
+
+    /// Follows the window's client area, the visual isn't scaled.
+    pub unsafe fn resize(&mut self, (width, height): (u32, u32)) -> Result<(), Error> {
+        let size = (width.max(1), height.max(1));
+        if size == self.size {
+            return Ok(());
+        }
+
+        self.render_views.clear();
+        self.swap_chain
+            .ResizeBuffers(0, size.0, size.1, DXGI_FORMAT_UNKNOWN, 0)
+            .context("Failed to resize composition swapchain")?;
+        self.size = size;
+
+        Ok(())
+    }
+
+    pub unsafe fn buffer(&self) -> Result<ID3D11Texture2D, Error> {
+        self.swap_chain
+            .GetBuffer(0)
+            .context("Failed to get composition swapchain's buffer")
+    }
+
+    pub unsafe fn present(&self) -> Result<(), Error> {
+        self.swap_chain
+            .Present(0, 0)
+            .ok()
+            .context("Failed to present composition swapchain")
+    }
+}
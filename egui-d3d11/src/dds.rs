@@ -0,0 +1,157 @@
+use crate::error::Error;
+use windows::Win32::Graphics::Dxgi::Common::{
+    DXGI_FORMAT, DXGI_FORMAT_BC1_UNORM, DXGI_FORMAT_BC1_UNORM_SRGB, DXGI_FORMAT_BC2_UNORM,
+    DXGI_FORMAT_BC2_UNORM_SRGB, DXGI_FORMAT_BC3_UNORM, DXGI_FORMAT_BC3_UNORM_SRGB,
+    DXGI_FORMAT_BC4_SNORM, DXGI_FORMAT_BC4_UNORM, DXGI_FORMAT_BC5_SNORM, DXGI_FORMAT_BC5_UNORM,
+    DXGI_FORMAT_BC6H_SF16, DXGI_FORMAT_BC6H_UF16, DXGI_FORMAT_BC7_UNORM,
+    DXGI_FORMAT_BC7_UNORM_SRGB,
+};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: usize = 124;
+const DX10_HEADER_SIZE: usize = 20;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+
+/// Block-compressed image parsed out of a DDS file, the mips borrow the file's bytes.
+pub struct DdsImage<'a> {
+    pub format: DXGI_FORMAT,
+    pub width: usize,
+    pub height: usize,
+    /// Data and row pitch of every mip, biggest first.
+    pub mips: Vec<(&'a [u8], usize)>,
+}
+
+impl<'a> DdsImage<'a> {
+    /// Only 2D textures with a BC1-BC7 format are supported, everything else is rejected
+    /// since it would have to be decompressed first.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < 4 + HEADER_SIZE || &data[..4] != MAGIC {
+            return Err(Error::InvalidDds("missing DDS header"));
+        }
+
+        let read = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+        if read(4) as usize != HEADER_SIZE {
+            return Err(Error::InvalidDds("wrong header size"));
+        }
+
+        let flags = read(8);
+        let height = read(12) as usize;
+        let width = read(16) as usize;
+        let mip_count = match read(28) {
+            count if flags & DDSD_MIPMAPCOUNT != 0 && count > 0 => count as usize,
+            _ => 1,
+        };
+
+        if read(80) & DDPF_FOURCC == 0 {
+            return Err(Error::InvalidDds("uncompressed pixel format"));
+        }
+
+        let mut offset = 4 + HEADER_SIZE;
+        let format = match &data[84..88] {
+            b"DXT1" => DXGI_FORMAT_BC1_UNORM,
+            b"DXT2" | b"DXT3" => DXGI_FORMAT_BC2_UNORM,
+            b"DXT4" | b"DXT5" => DXGI_FORMAT_BC3_UNORM,
+            b"ATI1" | b"BC4U" => DXGI_FORMAT_BC4_UNORM,
+            b"BC4S" => DXGI_FORMAT_BC4_SNORM,
+            b"ATI2" | b"BC5U" => DXGI_FORMAT_BC5_UNORM,
+            b"BC5S" => DXGI_FORMAT_BC5_SNORM,
+            b"DX10" => {
+                if data.len() < offset + DX10_HEADER_SIZE {
+                    return Err(Error::InvalidDds("missing DX10 header"));
+                }
+                offset += DX10_HEADER_SIZE;
+
+                let format = DXGI_FORMAT(read(4 + HEADER_SIZE));
+                if block_size(format).is_none() {
+                    return Err(Error::InvalidDds("unsupported DXGI format"));
+                }
+                format
+            }
+            _ => return Err(Error::InvalidDds("unsupported four character code")),
+        };
+
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDds("empty image"));
+        }
+
+        let block = block_size(format).unwrap();
+        let mut mips = Vec::with_capacity(mip_count);
+
+        for level in 0..mip_count {
+            let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+            let pitch = w.div_ceil(4).max(1) * block;
+            let size = pitch * h.div_ceil(4).max(1);
+
+            let mip = data
+                .get(offset..offset + size)
+                .ok_or(Error::InvalidDds("truncated image data"))?;
+            mips.push((mip, pitch));
+            offset += size;
+        }
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            mips,
+        })
+    }
+}
+
+/// Bytes per 4x4 block, `None` for formats that aren't block-compressed.
+fn block_size(format: DXGI_FORMAT) -> Option<usize> {
+    match format {
+        DXGI_FORMAT_BC1_UNORM
+        | DXGI_FORMAT_BC1_UNORM_SRGB
+        | DXGI_FORMAT_BC4_UNORM
+        | DXGI_FORMAT_BC4_SNORM => Some(8),
+        DXGI_FORMAT_BC2_UNORM
+        | DXGI_FORMAT_BC2_UNORM_SRGB
+        | DXGI_FORMAT_BC3_UNORM
+        | DXGI_FORMAT_BC3_UNORM_SRGB
+        | DXGI_FORMAT_BC5_UNORM
+        | DXGI_FORMAT_BC5_SNORM
+        | DXGI_FORMAT_BC6H_UF16
+        | DXGI_FORMAT_BC6H_SF16
+        | DXGI_FORMAT_BC7_UNORM
+        | DXGI_FORMAT_BC7_UNORM_SRGB => Some(16),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_parse_dds() {
+    let mut header = vec![0u8; 4 + HEADER_SIZE];
+    header[..4].copy_from_slice(MAGIC);
+    header[4..8].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+    header[8..12].copy_from_slice(&DDSD_MIPMAPCOUNT.to_le_bytes());
+    header[12..16].copy_from_slice(&6u32.to_le_bytes());
+    header[16..20].copy_from_slice(&10u32.to_le_bytes());
+    header[28..32].copy_from_slice(&3u32.to_le_bytes());
+    header[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+    header[84..88].copy_from_slice(b"DXT1");
+
+    // 10x6 -> 3x2 blocks, 5x3 -> 2x1, 2x1 -> 1x1
+    let mut file = header;
+    file.resize(file.len() + (6 + 2 + 1) * 8, 0);
+
+    let image = DdsImage::parse(&file).unwrap();
+    assert_eq!(image.format, DXGI_FORMAT_BC1_UNORM);
+    assert_eq!((image.width, image.height), (10, 6));
+    let mips: Vec<_> = image.mips.iter().map(|(d, p)| (d.len(), *p)).collect();
+    assert_eq!(mips, [(48, 24), (16, 16), (8, 8)]);
+
+    assert!(DdsImage::parse(&file[..file.len() - 1]).is_err());
+
+    file[84..88].copy_from_slice(b"DX10");
+    let mut dx10 = file[..4 + HEADER_SIZE].to_vec();
+    dx10.extend(DXGI_FORMAT_BC7_UNORM.0.to_le_bytes());
+    dx10.extend([0; DX10_HEADER_SIZE - 4]);
+    dx10.resize(dx10.len() + (6 + 2 + 1) * 16, 0);
+
+    let image = DdsImage::parse(&dx10).unwrap();
+    assert_eq!(image.format, DXGI_FORMAT_BC7_UNORM);
+    assert_eq!(image.mips[0], (&[0u8; 96][..], 48));
+}
@@ -0,0 +1,362 @@
+//! Test pages for checking this backend inside a game: gradients for gamma and blending,
+//! textures for filtering, a scroll area for clipping, a glyph table and the common widgets.
+//! Also has an inspector for the textures the UI allocated.
+
+use crate::TextureInfo;
+use egui::{
+    epaint::Mesh, pos2, vec2, Color32, ColorImage, Context, FontId, Grid, Id, Rect, ScrollArea,
+    Sense, Shape, TextureFilter, TextureHandle, TextureId, TextureOptions, Ui, Window,
+};
+use std::sync::Arc;
+
+const GRADIENT_SIZE: egui::Vec2 = vec2(256., 16.);
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Page {
+    #[default]
+    Colors,
+    Glyphs,
+    Widgets,
+    Textures,
+}
+
+/// Window with the test pages, keep it between frames.
+/// ```no_run
+/// # let mut debug = egui_d3d11::debug_ui::DebugUi::default();
+/// # let ctx = egui::Context::default();
+/// debug.show(&ctx);
+/// ```
+#[derive(Default)]
+pub struct DebugUi {
+    page: Page,
+    textures: Option<TestTextures>,
+    gallery: Gallery,
+    inspector: TextureInspector,
+}
+
+struct TestTextures {
+    gradient: TextureHandle,
+    checker_nearest: TextureHandle,
+    checker_linear: TextureHandle,
+}
+
+#[derive(Default)]
+struct Gallery {
+    text: String,
+    checked: bool,
+    value: f32,
+    choice: usize,
+    color: Color32,
+}
+
+impl DebugUi {
+    pub fn show(&mut self, ctx: &Context) {
+        Window::new("egui-d3d11 self-test").show(ctx, |ui| self.ui(ui));
+    }
+
+    /// Same as [`Self::show`] but inside of your own window or panel.
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.page, Page::Colors, "Colors");
+            ui.selectable_value(&mut self.page, Page::Glyphs, "Glyphs");
+            ui.selectable_value(&mut self.page, Page::Widgets, "Widgets");
+            ui.selectable_value(&mut self.page, Page::Textures, "Textures");
+        });
+        ui.separator();
+
+        match self.page {
+            Page::Colors => {
+                let textures = self
+                    .textures
+                    .get_or_insert_with(|| TestTextures::new(ui.ctx()));
+                color_test(ui, textures);
+            }
+            Page::Glyphs => glyph_table(ui),
+            Page::Widgets => self.gallery.ui(ui),
+            Page::Textures => self.inspector.ui(ui),
+        }
+    }
+}
+
+impl TestTextures {
+    fn new(ctx: &Context) -> Self {
+        let gradient = ColorImage {
+            size: [256, 1],
+            pixels: (0..=255).map(Color32::from_gray).collect(),
+        };
+
+        let checker = ColorImage {
+            size: [8, 8],
+            pixels: (0..64)
+                .map(|i| {
+                    if (i % 8 + i / 8) % 2 == 0 {
+                        Color32::WHITE
+                    } else {
+                        Color32::BLACK
+                    }
+                })
+                .collect(),
+        };
+
+        Self {
+            gradient: ctx.load_texture("self-test gradient", gradient, TextureOptions::LINEAR),
+            checker_nearest: ctx.load_texture(
+                "self-test checker nearest",
+                checker.clone(),
+                TextureOptions::NEAREST,
+            ),
+            checker_linear: ctx.load_texture(
+                "self-test checker linear",
+                checker,
+                // not the default, so the texture's own options are tested
+                TextureOptions {
+                    magnification: TextureFilter::Linear,
+                    minification: TextureFilter::Nearest,
+                },
+            ),
+        }
+    }
+}
+
+fn color_test(ui: &mut Ui, textures: &TestTextures) {
+    ui.label("Each pair should look the same, with even steps and no banding:");
+    Grid::new("gradients").show(ui, |ui| {
+        ui.label("Vertex colors");
+        gradient(ui, Color32::BLACK, Color32::WHITE);
+        ui.end_row();
+
+        ui.label("Texture");
+        textured(ui, textures.gradient.id(), GRADIENT_SIZE);
+        ui.end_row();
+
+        ui.label("Red to green");
+        gradient(ui, Color32::RED, Color32::GREEN);
+        ui.end_row();
+
+        ui.label("Faded to white");
+        gradient(ui, Color32::TRANSPARENT, Color32::WHITE);
+        ui.end_row();
+
+        ui.label("Faded to black");
+        gradient(ui, Color32::TRANSPARENT, Color32::BLACK);
+        ui.end_row();
+    });
+
+    ui.separator();
+    ui.label("Left should be sharp squares, right blurred:");
+    ui.horizontal(|ui| {
+        textured(ui, textures.checker_nearest.id(), vec2(96., 96.));
+        textured(ui, textures.checker_linear.id(), vec2(96., 96.));
+    });
+
+    ui.separator();
+    ui.label("Nothing may be drawn outside of this box:");
+    ScrollArea::both().max_height(80.).show(ui, |ui| {
+        for i in 0..20 {
+            ui.colored_label(
+                Color32::from_rgb(255 - i * 12, i * 12, 128),
+                "Clipped line of text that is longer than the scroll area is wide",
+            );
+        }
+    });
+}
+
+fn gradient(ui: &mut Ui, left: Color32, right: Color32) {
+    let (rect, _) = ui.allocate_exact_size(GRADIENT_SIZE, Sense::hover());
+
+    let mut mesh = Mesh::default();
+    mesh.colored_vertex(rect.left_top(), left);
+    mesh.colored_vertex(rect.right_top(), right);
+    mesh.colored_vertex(rect.left_bottom(), left);
+    mesh.colored_vertex(rect.right_bottom(), right);
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(1, 2, 3);
+
+    ui.painter().add(Shape::mesh(mesh));
+}
+
+fn textured(ui: &mut Ui, texture: TextureId, size: egui::Vec2) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+
+    let mut mesh = Mesh::with_texture(texture);
+    mesh.add_rect_with_uv(
+        rect,
+        Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+        Color32::WHITE,
+    );
+
+    ui.painter().add(Shape::mesh(mesh));
+    response
+}
+
+/// Every character the default fonts have from Latin up to the box drawing block.
+fn glyph_table(ui: &mut Ui) {
+    let font = FontId::proportional(18.);
+    let chars: Vec<char> = ui.fonts(|fonts| {
+        ('\u{20}'..'\u{2600}')
+            .filter(|c| !c.is_control() && fonts.has_glyph(&font, *c))
+            .collect()
+    });
+
+    ui.label(format!("{} glyphs, hover for the code point", chars.len()));
+    ScrollArea::vertical().max_height(400.).show(ui, |ui| {
+        Grid::new("glyphs").show(ui, |ui| {
+            for (i, c) in chars.into_iter().enumerate() {
+                ui.label(egui::RichText::new(c).font(font.clone()))
+                    .on_hover_text(format!("U+{:04X}", c as u32));
+
+                if i % 16 == 15 {
+                    ui.end_row();
+                }
+            }
+        });
+    });
+}
+
+impl Gallery {
+    fn ui(&mut self, ui: &mut Ui) {
+        Grid::new("gallery").num_columns(2).show(ui, |ui| {
+            ui.label("Label");
+            ui.label("Text with some weight");
+            ui.end_row();
+
+            ui.label("Button");
+            if ui.button("Click me").clicked() {
+                self.checked = !self.checked;
+            }
+            ui.end_row();
+
+            ui.label("Checkbox");
+            ui.checkbox(&mut self.checked, "Checked");
+            ui.end_row();
+
+            ui.label("Radio");
+            ui.horizontal(|ui| {
+                for (i, name) in ["First", "Second", "Third"].into_iter().enumerate() {
+                    ui.radio_value(&mut self.choice, i, name);
+                }
+            });
+            ui.end_row();
+
+            ui.label("Slider");
+            ui.add(egui::Slider::new(&mut self.value, 0.0..=1.0));
+            ui.end_row();
+
+            ui.label("Drag value");
+            ui.add(egui::DragValue::new(&mut self.value).speed(0.01));
+            ui.end_row();
+
+            ui.label("Progress");
+            ui.add(egui::ProgressBar::new(self.value).show_percentage());
+            ui.end_row();
+
+            ui.label("Text edit");
+            ui.text_edit_singleline(&mut self.text);
+            ui.end_row();
+
+            ui.label("Color");
+            ui.color_edit_button_srgba(&mut self.color);
+            ui.end_row();
+
+            ui.label("Spinner");
+            ui.spinner();
+            ui.end_row();
+        });
+
+        ui.collapsing("Collapsing header", |ui| {
+            ui.label("Contents of the collapsing header");
+        });
+    }
+}
+
+/// Lists every texture of the allocator with a thumbnail, its size, format and memory,
+/// e.g. to spot font atlas churn or user textures that are never freed.
+#[derive(Default)]
+pub struct TextureInspector {
+    hide_managed: bool,
+}
+
+impl TextureInspector {
+    pub fn show(&mut self, ctx: &Context) {
+        Window::new("egui-d3d11 textures").show(ctx, |ui| self.ui(ui));
+    }
+
+    /// Same as [`Self::show`] but inside of your own window or panel.
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let mut textures = ui
+            .ctx()
+            .data_mut(|data| data.get_temp::<Arc<Vec<TextureInfo>>>(textures_id()))
+            .map(|textures| textures.to_vec())
+            .unwrap_or_default();
+        textures.sort_by_key(|tex| sort_key(tex.id));
+
+        let bytes: usize = textures.iter().map(|tex| tex.bytes).sum();
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{} textures, {}",
+                textures.len(),
+                format_bytes(bytes)
+            ));
+            ui.checkbox(&mut self.hide_managed, "Only user textures");
+        });
+        ui.separator();
+
+        ScrollArea::vertical().max_height(400.).show(ui, |ui| {
+            Grid::new("textures").striped(true).show(ui, |ui| {
+                ui.label("");
+                ui.label("Id");
+                ui.label("Size");
+                ui.label("Format");
+                ui.label("Memory");
+                ui.label("Idle frames");
+                ui.end_row();
+
+                for tex in textures {
+                    if self.hide_managed && matches!(tex.id, TextureId::Managed(_)) {
+                        continue;
+                    }
+
+                    let [width, height] = tex.size.map(|side| side.max(1) as f32);
+                    let scale = (THUMBNAIL_SIZE / width).min(THUMBNAIL_SIZE / height);
+                    textured(ui, tex.id, vec2(width, height) * scale.min(1.)).on_hover_ui(|ui| {
+                        let scale = (512. / width).min(512. / height).min(1.);
+                        textured(ui, tex.id, vec2(width, height) * scale);
+                    });
+
+                    ui.label(format!("{:?}", tex.id));
+                    ui.label(format!("{}x{}", tex.size[0], tex.size[1]));
+                    ui.label(format!("{} ({} mips)", tex.format.0, tex.mip_levels));
+                    ui.label(format_bytes(tex.bytes));
+                    ui.label(tex.idle_frames.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+const THUMBNAIL_SIZE: f32 = 48.;
+
+fn textures_id() -> Id {
+    Id::new("egui-d3d11 textures")
+}
+
+/// Makes the allocator's textures available to [`TextureInspector`] for the next UI run.
+pub(crate) fn publish_textures(ctx: &Context, textures: Vec<TextureInfo>) {
+    ctx.data_mut(|data| data.insert_temp(textures_id(), Arc::new(textures)));
+}
+
+fn sort_key(tid: TextureId) -> (bool, u64) {
+    match tid {
+        TextureId::Managed(id) => (false, id),
+        TextureId::User(id) => (true, id),
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f32 / 1024.),
+        _ => format!("{:.1} MiB", bytes as f32 / 1_048_576.),
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{
+    error::{Error, ErrorContext},
+    target::FrameTarget,
+};
+use windows::Win32::Graphics::{
+    Direct3D11::{
+        ID3D11DepthStencilView, ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D,
+        D3D11_BIND_DEPTH_STENCIL, D3D11_CLEAR_DEPTH, D3D11_CLEAR_STENCIL, D3D11_TEXTURE2D_DESC,
+        D3D11_USAGE_DEFAULT,
+    },
+    Dxgi::Common::{DXGI_FORMAT, DXGI_SAMPLE_DESC},
+};
+
+/// Depth-stencil buffer the draw callbacks render with, see [`crate::Config::callback_depth`].
+/// Matches the size and sample count of the render target, the game's own is never touched.
+pub struct DepthTarget {
+    view: ID3D11DepthStencilView,
+    format: DXGI_FORMAT,
+    size: (u32, u32),
+    samples: DXGI_SAMPLE_DESC,
+}
+
+impl DepthTarget {
+    unsafe fn new(
+        dev: &ID3D11Device,
+        format: DXGI_FORMAT,
+        size: (u32, u32),
+        samples: DXGI_SAMPLE_DESC,
+    ) -> Result<Self, Error> {
+        log_msg!(debug, "Creating depth target of {}x{}", size.0, size.1);
+
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: size.0,
+            Height: size.1,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: format,
+            SampleDesc: samples,
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_DEPTH_STENCIL,
+            ..Default::default()
+        };
+
+        let mut texture: Option<ID3D11Texture2D> = None;
+        dev.CreateTexture2D(&desc, None, Some(&mut texture))
+            .context("Failed to create depth target")?;
+        let texture = texture.context("Failed to create depth target")?;
+
+        let mut view = None;
+        dev.CreateDepthStencilView(&texture, None, Some(&mut view))
+            .context("Failed to create depth stencil view")?;
+
+        Ok(Self {
+            view: view.context("Failed to create depth stencil view")?,
+            format,
+            size,
+            samples,
+        })
+    }
+
+    /// Keeps `depth` matching `target`, recreated if the target's texture changed its size or
+    /// sample count, then clears it for the frame.
+    pub unsafe fn prepare(
+        depth: &mut Option<Self>,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        target: &FrameTarget,
+        format: DXGI_FORMAT,
+    ) -> Result<(), Error> {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        target.texture.GetDesc(&mut desc);
+        let size = (desc.Width, desc.Height);
+
+        let current = depth.as_ref().map(|depth| {
+            (
+                depth.format,
+                depth.size,
+                depth.samples.Count,
+                depth.samples.Quality,
+            )
+        });
+        let wanted = (format, size, desc.SampleDesc.Count, desc.SampleDesc.Quality);
+        if current != Some(wanted) {
+            // the old buffer is released before the new one is allocated
+            *depth = None;
+            *depth = Some(Self::new(dev, format, size, desc.SampleDesc)?);
+        }
+
+        if let Some(depth) = depth {
+            ctx.ClearDepthStencilView(
+                &depth.view,
+                D3D11_CLEAR_DEPTH.0 | D3D11_CLEAR_STENCIL.0,
+                1.,
+                0,
+            );
+        }
+        Ok(())
+    }
+
+    /// Binds the render target with this buffer, for the duration of a callback.
+    pub unsafe fn bind(&self, ctx: &ID3D11DeviceContext, target: &FrameTarget) {
+        ctx.OMSetRenderTargets(Some(std::slice::from_ref(&target.view)), &self.view);
+    }
+}
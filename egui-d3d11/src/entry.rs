@@ -0,0 +1,371 @@
+//! Pieces of the trampolines [`crate::hook_entry!`] generates, for hooks that need more control.
+
+use crate::{DirectX11App, Error, RawMutex};
+use std::{
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering},
+        Once,
+    },
+    time::Duration,
+};
+use windows::{
+    core::Vtable,
+    Win32::{
+        Graphics::Dxgi::DXGI_SWAP_CHAIN_DESC,
+        UI::WindowsAndMessaging::{CallWindowProcW, GWLP_WNDPROC, WNDPROC},
+    },
+};
+
+#[cfg(target_pointer_width = "64")]
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetWindowLongPtrW as GetWindowLong, SetWindowLongPtrW as SetWindowLong,
+};
+#[cfg(target_pointer_width = "32")]
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetWindowLongW as GetWindowLong, SetWindowLongW as SetWindowLong,
+};
+
+pub use windows::{
+    core::HRESULT,
+    Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+        Graphics::Dxgi::{Common::DXGI_FORMAT, IDXGISwapChain},
+    },
+};
+
+/// `IDXGISwapChain::Present`, the swapchain is passed as a raw pointer, nothing is released.
+pub type FnPresent = unsafe extern "system" fn(*mut c_void, u32, u32) -> HRESULT;
+
+/// `IDXGISwapChain::ResizeBuffers`.
+pub type FnResizeBuffers =
+    unsafe extern "system" fn(*mut c_void, u32, u32, u32, DXGI_FORMAT, u32) -> HRESULT;
+
+/// Window procedure the swapchain's window is subclassed with.
+pub type FnWndProc = unsafe extern "system" fn(HWND, u32, WPARAM, LPARAM) -> LRESULT;
+
+/// Initializes the app on the first present and keeps the window's original procedure.
+pub struct HookEntry {
+    init: Once,
+    wnd_proc: AtomicIsize,
+    // the subclassed window and the procedure it got, 0 until then
+    hwnd: AtomicIsize,
+    hook: AtomicIsize,
+    // set by `eject`, the hooks only forward to the originals from then on
+    ejected: AtomicBool,
+    // hook calls that are still running, `eject` waits for them to return
+    active: AtomicUsize,
+}
+
+/// One running hook call, see [`HookEntry::enter`].
+struct ActiveCall<'a> {
+    active: &'a AtomicUsize,
+    // the call only forwards to the original
+    ejected: bool,
+}
+
+impl Drop for ActiveCall<'_> {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl HookEntry {
+    pub const fn new() -> Self {
+        Self {
+            init: Once::new(),
+            wnd_proc: AtomicIsize::new(0),
+            hwnd: AtomicIsize::new(0),
+            hook: AtomicIsize::new(0),
+            ejected: AtomicBool::new(false),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Window that was subclassed on the first present, `None` before that.
+    pub fn window(&self) -> Option<HWND> {
+        match self.hwnd.load(Ordering::Acquire) {
+            0 => None,
+            hwnd => Some(HWND(hwnd)),
+        }
+    }
+
+    /// Procedure the window had before it was subclassed, `None` before that.
+    pub fn original_wnd_proc(&self) -> WNDPROC {
+        // `Option` of a function pointer has the layout of a nullable pointer
+        unsafe { std::mem::transmute::<isize, WNDPROC>(self.wnd_proc.load(Ordering::Acquire)) }
+    }
+
+    /// Checks if [`Self::eject`] ran, the hooks only call the originals then.
+    pub fn is_ejected(&self) -> bool {
+        self.ejected.load(Ordering::SeqCst)
+    }
+
+    /// Counts a hook call `eject` has to wait for, forwarding ones included. The count goes up
+    /// before the flag is read, so either the call sees the flag or `eject` sees the call.
+    fn enter(&self) -> ActiveCall<'_> {
+        self.active.fetch_add(1, Ordering::SeqCst);
+        ActiveCall {
+            active: &self.active,
+            ejected: self.ejected.load(Ordering::SeqCst),
+        }
+    }
+
+    fn wait_for_calls(&self) {
+        while self.active.load(Ordering::SeqCst) != 0 {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Undoes everything the hooks did so the DLL can be unloaded. Hooks called from now on
+    /// only forward to the originals, the window gets its original procedure back, `unhook`
+    /// removes the `Present` and `ResizeBuffers` hooks and the app is shut down once the hook
+    /// calls still running returned, see [`DirectX11App::shutdown`]. Returns the app's state.
+    ///
+    /// Fails without changing anything if the window procedure was replaced after it was
+    /// subclassed, restoring the original one would cut off whoever replaced it. The DLL must
+    /// stay loaded then, its procedure is still called.
+    ///
+    /// # Safety
+    /// Must not be called from inside of a hook or from the window's thread, it would wait for
+    /// itself. The DLL may only be unloaded once this returned `Ok`.
+    pub unsafe fn eject<T, R: RawMutex>(
+        &self,
+        app: &DirectX11App<T, R>,
+        unhook: impl FnOnce(),
+    ) -> Result<Option<T>, Error> {
+        self.ejected.store(true, Ordering::SeqCst);
+        // a first present may still be subclassing the window
+        self.wait_for_calls();
+
+        if let Some(hwnd) = self.window() {
+            let current = get_wnd_proc(hwnd);
+            if current != self.hook.load(Ordering::Acquire) {
+                self.ejected.store(false, Ordering::SeqCst);
+                return Err(Error::WindowSubclassed(hwnd));
+            }
+            set_wnd_proc(hwnd, self.wnd_proc.load(Ordering::Acquire));
+        }
+
+        unhook();
+        // calls that entered the hooks before they were removed are forwarding to the originals
+        self.wait_for_calls();
+
+        log_msg!(info, "Ejected the hooks");
+        Ok(app.shutdown())
+    }
+
+    /// Body of the `Present` hook. The first call runs `init` and subclasses the swapchain's
+    /// window with `wnd_proc`, which has to call [`Self::wnd_proc`].
+    ///
+    /// # Safety
+    /// `swap_chain` has to be the swapchain `Present` was called on.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn present<T, R: RawMutex>(
+        &self,
+        app: &DirectX11App<T, R>,
+        original: Option<FnPresent>,
+        swap_chain: *mut c_void,
+        sync_interval: u32,
+        flags: u32,
+        init: impl FnOnce(&DirectX11App<T, R>, &IDXGISwapChain),
+        wnd_proc: FnWndProc,
+    ) -> HRESULT {
+        let original = expect!(original, "The original Present wasn't set");
+        let call = self.enter();
+        if call.ejected {
+            return original(swap_chain, sync_interval, flags);
+        }
+        let chain = IDXGISwapChain::from_raw_borrowed(&swap_chain);
+
+        self.init.call_once(|| {
+            init(app, chain);
+
+            let mut desc = DXGI_SWAP_CHAIN_DESC::default();
+            expect!(
+                chain.GetDesc(&mut desc),
+                "Failed to get swapchain's description"
+            );
+            // a swapchain drawing into a child window still gets its input from the top level one
+            let input = app.input_window().unwrap_or(desc.OutputWindow);
+            self.subclass(input, wnd_proc);
+        });
+
+        app.present(chain);
+
+        original(swap_chain, sync_interval, flags)
+    }
+
+    /// Body of the `ResizeBuffers` hook.
+    ///
+    /// # Safety
+    /// `swap_chain` has to be the swapchain `ResizeBuffers` was called on.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn resize_buffers<T, R: RawMutex>(
+        &self,
+        app: &DirectX11App<T, R>,
+        original: Option<FnResizeBuffers>,
+        swap_chain: *mut c_void,
+        buffer_count: u32,
+        width: u32,
+        height: u32,
+        format: DXGI_FORMAT,
+        flags: u32,
+    ) -> HRESULT {
+        let original = expect!(original, "The original ResizeBuffers wasn't set");
+        let call = || original(swap_chain, buffer_count, width, height, format, flags);
+
+        // the app only knows the swapchain once it's initialized on the first present
+        let active = self.enter();
+        if active.ejected || !self.init.is_completed() {
+            return call();
+        }
+
+        app.resize_buffers(IDXGISwapChain::from_raw_borrowed(&swap_chain), call)
+    }
+
+    /// Body of the window procedure, see [`DirectX11App::wnd_proc_blocking`].
+    pub fn wnd_proc<T, R: RawMutex>(
+        &self,
+        app: &DirectX11App<T, R>,
+        hwnd: HWND,
+        umsg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        let original = self.original_wnd_proc();
+        let call = self.enter();
+        if call.ejected {
+            return unsafe { CallWindowProcW(original, hwnd, umsg, wparam, lparam) };
+        }
+        app.wnd_proc_blocking(hwnd, umsg, wparam, lparam, original)
+    }
+
+    /// The original is stored before the new procedure can receive any message.
+    unsafe fn subclass(&self, hwnd: HWND, wnd_proc: FnWndProc) {
+        let hook = wnd_proc as usize as isize;
+        self.wnd_proc.store(get_wnd_proc(hwnd), Ordering::Release);
+        self.hook.store(hook, Ordering::Release);
+        self.hwnd.store(hwnd.0, Ordering::Release);
+        set_wnd_proc(hwnd, hook);
+    }
+}
+
+#[allow(clippy::unnecessary_cast)]
+unsafe fn get_wnd_proc(hwnd: HWND) -> isize {
+    GetWindowLong(hwnd, GWLP_WNDPROC) as isize
+}
+
+unsafe fn set_wnd_proc(hwnd: HWND, wnd_proc: isize) {
+    SetWindowLong(hwnd, GWLP_WNDPROC, wnd_proc as _);
+}
+
+impl Default for HookEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates everything a DLL needs between its hooks and the app: the `static $app`,
+/// the `O_PRESENT`/`O_RESIZE_BUFFERS` slots for the original functions and the trampolines
+/// `hk_present`, `hk_resize_buffers` and `hk_wnd_proc`. The app is initialized with
+/// `Default` state on the first present, with `$config` if given, and subclasses the window.
+/// Only the hooks themselves have to be installed, with whatever hooking library is used.
+/// `eject(unhook)` undoes everything before the DLL is unloaded, see [`HookEntry::eject`].
+///
+/// Starting with `mod name,` puts everything into a module of that name, so several apps can
+/// live next to each other, e.g. `hook_entry!(mod tool, TOOL: i32, ui)` and `tool::hk_present`.
+/// The module sees the items of the one it's generated in.
+///
+/// ```no_run
+/// # fn ui(_: &egui::Context, _: &mut i32) {}
+/// egui_d3d11::hook_entry!(APP: i32, ui);
+///
+/// # unsafe fn install(present: usize, resize_buffers: usize) {
+/// // e.g. with a detour library that writes the original into the slot
+/// O_PRESENT = Some(std::mem::transmute(present));
+/// O_RESIZE_BUFFERS = Some(std::mem::transmute(resize_buffers));
+/// # let _ = (hk_present as egui_d3d11::entry::FnPresent, hk_resize_buffers as egui_d3d11::entry::FnResizeBuffers);
+/// # }
+///
+/// # unsafe fn unload() {
+/// // on a thread of its own, before `FreeLibraryAndExitThread`
+/// let state = eject(|| { /* remove the hooks */ }).expect("The window was subclassed again");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! hook_entry {
+    (mod $name:ident, $app:ident: $state:ty, $ui:expr $(, $config:expr)?) => {
+        pub mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            $crate::hook_entry!($app: $state, $ui $(, $config)?);
+        }
+    };
+    ($app:ident: $state:ty, $ui:expr) => {
+        $crate::hook_entry!($app: $state, $ui, $crate::Config::default());
+    };
+    ($app:ident: $state:ty, $ui:expr, $config:expr) => {
+        pub(crate) static $app: $crate::DirectX11App<$state> = $crate::DirectX11App::new();
+        static HOOK_ENTRY: $crate::entry::HookEntry = $crate::entry::HookEntry::new();
+        pub(crate) static mut O_PRESENT: Option<$crate::entry::FnPresent> = None;
+        pub(crate) static mut O_RESIZE_BUFFERS: Option<$crate::entry::FnResizeBuffers> = None;
+
+        pub(crate) unsafe extern "system" fn hk_present(
+            swap_chain: *mut ::std::ffi::c_void,
+            sync_interval: u32,
+            flags: u32,
+        ) -> $crate::entry::HRESULT {
+            HOOK_ENTRY.present(
+                &$app,
+                *::std::ptr::addr_of!(O_PRESENT),
+                swap_chain,
+                sync_interval,
+                flags,
+                |app, swap_chain| {
+                    app.init_with_config(swap_chain, $ui, Default::default(), $config)
+                },
+                hk_wnd_proc,
+            )
+        }
+
+        pub(crate) unsafe extern "system" fn hk_resize_buffers(
+            swap_chain: *mut ::std::ffi::c_void,
+            buffer_count: u32,
+            width: u32,
+            height: u32,
+            format: $crate::entry::DXGI_FORMAT,
+            flags: u32,
+        ) -> $crate::entry::HRESULT {
+            HOOK_ENTRY.resize_buffers(
+                &$app,
+                *::std::ptr::addr_of!(O_RESIZE_BUFFERS),
+                swap_chain,
+                buffer_count,
+                width,
+                height,
+                format,
+                flags,
+            )
+        }
+
+        pub(crate) unsafe extern "system" fn hk_wnd_proc(
+            hwnd: $crate::entry::HWND,
+            umsg: u32,
+            wparam: $crate::entry::WPARAM,
+            lparam: $crate::entry::LPARAM,
+        ) -> $crate::entry::LRESULT {
+            HOOK_ENTRY.wnd_proc(&$app, hwnd, umsg, wparam, lparam)
+        }
+
+        /// Restores the window procedure, removes the hooks through `unhook` and shuts the app
+        /// down, see `HookEntry::eject`.
+        #[allow(dead_code)]
+        pub(crate) unsafe fn eject(
+            unhook: impl FnOnce(),
+        ) -> ::std::result::Result<::std::option::Option<$state>, $crate::Error> {
+            HOOK_ENTRY.eject(&$app, unhook)
+        }
+    };
+}
@@ -0,0 +1,126 @@
+use std::{any::Any, panic::Location};
+use windows::Win32::{Foundation::HWND, Graphics::Dxgi::Common::DXGI_FORMAT};
+
+/// Failure that happened while drawing or resizing.
+/// See [`crate::DirectX11App::set_error_handler`].
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A Direct3D or DXGI call returned a failing `HRESULT`.
+    #[error("{0}: {1}")]
+    Windows(&'static str, #[source] windows::core::Error),
+    /// A call succeeded but didn't return the object it was supposed to create.
+    #[error("{0}")]
+    Missing(&'static str),
+    /// Compiler output of a shader that failed to compile.
+    #[error("Failed to compile shader: {0}")]
+    Shader(String),
+    /// A texture of this size is bigger than the device allows.
+    #[error("Texture of {0:?} exceeds the maximum side of {1}")]
+    TextureTooLarge([usize; 2], usize),
+    /// DDS data that isn't a block-compressed 2D texture.
+    #[error("Invalid DDS file: {0}")]
+    InvalidDds(&'static str),
+    /// A backbuffer format that can't be read back, or a texture format the device can't sample.
+    #[error("Unsupported format {0:?}")]
+    UnsupportedFormat(DXGI_FORMAT),
+    /// An internal lock was held longer than [`crate::Config::lock_watchdog`] allows.
+    #[error("The {0} lock was held for too long, waiting at {1}, taken at {2}")]
+    LockTimeout(&'static str, &'static Location<'static>, String),
+    /// A region to read back that lies outside of the texture.
+    #[error("The region {0:?} is empty")]
+    EmptyRegion([u32; 4]),
+    /// The window procedure was replaced after the app subclassed the window, so the original
+    /// one can't be restored, see [`crate::entry::HookEntry::eject`].
+    #[error("The procedure of window {0:?} was replaced by someone else")]
+    WindowSubclassed(HWND),
+    /// The UI, its tessellation or a draw callback panicked, the frame was skipped.
+    #[error("The UI panicked: {0}")]
+    Panic(String),
+    /// Only one input source can be installed per window at a time.
+    #[cfg(feature = "input-hooks")]
+    #[error("Input hooks are already installed for this window")]
+    HooksInstalled,
+    /// Only one set of cursor hooks can be active at a time.
+    #[cfg(feature = "cursor-hooks")]
+    #[error("Cursor hooks are already installed")]
+    CursorHooksInstalled,
+    /// An image of [`crate::images`] that couldn't be read or decoded.
+    #[cfg(feature = "image-loaders")]
+    #[error("Failed to load image {0}: {1}")]
+    Image(String, String),
+    /// DirectComposition was enabled on a D3D12 swapchain.
+    #[cfg(feature = "dcomp")]
+    #[error("DirectComposition only works with D3D11 swapchains")]
+    CompositionUnsupported,
+}
+
+/// Why [`crate::DirectX11App::try_init_with_state`] didn't initialize the app. Nothing was set
+/// up, so init can be tried again, e.g. on the next `Present` of a game that creates its
+/// swapchain lazily.
+#[derive(Debug, thiserror::Error)]
+pub enum InitError {
+    /// One of the `init_*` functions already succeeded.
+    #[error("You must call init only once")]
+    AlreadyInitialized,
+    /// The swapchain's output window is missing or was already destroyed.
+    #[error("Invalid output window {0:?}")]
+    InvalidWindow(HWND),
+    /// The backbuffer has a format the UI can't be drawn into.
+    #[error("Unsupported backbuffer format {0:?}")]
+    UnsupportedFormat(DXGI_FORMAT),
+    /// The swapchain's device was removed or reset.
+    #[error("The device was removed: {0}")]
+    DeviceRemoved(#[source] windows::core::Error),
+    /// Creating the render target view, shaders or pipeline failed.
+    #[error(transparent)]
+    Failed(#[from] Error),
+}
+
+impl Error {
+    /// Keeps the message of a payload caught by `catch_unwind`.
+    pub(crate) fn from_panic(payload: Box<dyn Any + Send>) -> Self {
+        let msg = match payload.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(msg) => (*msg).to_owned(),
+                Err(_) => "unknown payload".to_owned(),
+            },
+        };
+
+        Self::Panic(msg)
+    }
+}
+
+/// Attaches a message to failed calls, same as `expect!` but without panicking.
+pub(crate) trait ErrorContext<T> {
+    fn context(self, msg: &'static str) -> Result<T, Error>;
+}
+
+impl<T> ErrorContext<T> for windows::core::Result<T> {
+    #[inline]
+    fn context(self, msg: &'static str) -> Result<T, Error> {
+        self.map_err(|e| Error::Windows(msg, e))
+    }
+}
+
+impl<T> ErrorContext<T> for Option<T> {
+    #[inline]
+    fn context(self, msg: &'static str) -> Result<T, Error> {
+        self.ok_or(Error::Missing(msg))
+    }
+}
+
+#[test]
+fn test_panic_message() {
+    let payload = std::panic::catch_unwind(|| panic!("at {}", 1)).unwrap_err();
+    assert_eq!(
+        Error::from_panic(payload).to_string(),
+        "The UI panicked: at 1"
+    );
+
+    let payload = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+    assert_eq!(
+        Error::from_panic(payload).to_string(),
+        "The UI panicked: static"
+    );
+}
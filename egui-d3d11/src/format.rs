@@ -0,0 +1,98 @@
+use windows::Win32::Graphics::Dxgi::Common::{
+    DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_TYPELESS, DXGI_FORMAT_B8G8R8A8_UNORM,
+    DXGI_FORMAT_B8G8R8A8_UNORM_SRGB, DXGI_FORMAT_B8G8R8X8_TYPELESS, DXGI_FORMAT_B8G8R8X8_UNORM,
+    DXGI_FORMAT_B8G8R8X8_UNORM_SRGB, DXGI_FORMAT_R10G10B10A2_TYPELESS,
+    DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT,
+    DXGI_FORMAT_R16G16B16A16_TYPELESS, DXGI_FORMAT_R16G16B16A16_UNORM,
+    DXGI_FORMAT_R8G8B8A8_TYPELESS, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+};
+
+/// Maps typeless backbuffer formats to a format that can be used to create views.
+/// Already typed formats are returned unchanged.
+pub fn typed_format(format: DXGI_FORMAT) -> DXGI_FORMAT {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_TYPELESS => DXGI_FORMAT_R8G8B8A8_UNORM,
+        DXGI_FORMAT_B8G8R8A8_TYPELESS => DXGI_FORMAT_B8G8R8A8_UNORM,
+        DXGI_FORMAT_B8G8R8X8_TYPELESS => DXGI_FORMAT_B8G8R8X8_UNORM,
+        DXGI_FORMAT_R10G10B10A2_TYPELESS => DXGI_FORMAT_R10G10B10A2_UNORM,
+        DXGI_FORMAT_R16G16B16A16_TYPELESS => DXGI_FORMAT_R16G16B16A16_FLOAT,
+        other => other,
+    }
+}
+
+/// `_SRGB` variant of a backbuffer format, `None` if the format has none.
+pub fn srgb_format(format: DXGI_FORMAT) -> Option<DXGI_FORMAT> {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_TYPELESS
+        | DXGI_FORMAT_R8G8B8A8_UNORM
+        | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => Some(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB),
+        DXGI_FORMAT_B8G8R8A8_TYPELESS
+        | DXGI_FORMAT_B8G8R8A8_UNORM
+        | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => Some(DXGI_FORMAT_B8G8R8A8_UNORM_SRGB),
+        DXGI_FORMAT_B8G8R8X8_TYPELESS
+        | DXGI_FORMAT_B8G8R8X8_UNORM
+        | DXGI_FORMAT_B8G8R8X8_UNORM_SRGB => Some(DXGI_FORMAT_B8G8R8X8_UNORM_SRGB),
+        _ => None,
+    }
+}
+
+/// Plain `_UNORM` variant of a backbuffer format, the opposite of [`srgb_format`].
+pub fn unorm_format(format: DXGI_FORMAT) -> DXGI_FORMAT {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => DXGI_FORMAT_R8G8B8A8_UNORM,
+        DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => DXGI_FORMAT_B8G8R8A8_UNORM,
+        DXGI_FORMAT_B8G8R8X8_UNORM_SRGB => DXGI_FORMAT_B8G8R8X8_UNORM,
+        other => typed_format(other),
+    }
+}
+
+pub fn is_srgb(format: DXGI_FORMAT) -> bool {
+    matches!(
+        format,
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+            | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+            | DXGI_FORMAT_B8G8R8X8_UNORM_SRGB
+    )
+}
+
+/// Checks if the UI can be drawn into a backbuffer of this format.
+pub fn is_supported_backbuffer(format: DXGI_FORMAT) -> bool {
+    matches!(
+        format,
+        DXGI_FORMAT_R8G8B8A8_TYPELESS
+            | DXGI_FORMAT_R8G8B8A8_UNORM
+            | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+            | DXGI_FORMAT_B8G8R8A8_TYPELESS
+            | DXGI_FORMAT_B8G8R8A8_UNORM
+            | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+            | DXGI_FORMAT_B8G8R8X8_TYPELESS
+            | DXGI_FORMAT_B8G8R8X8_UNORM
+            | DXGI_FORMAT_B8G8R8X8_UNORM_SRGB
+            | DXGI_FORMAT_R10G10B10A2_TYPELESS
+            | DXGI_FORMAT_R10G10B10A2_UNORM
+            | DXGI_FORMAT_R16G16B16A16_TYPELESS
+            | DXGI_FORMAT_R16G16B16A16_FLOAT
+            | DXGI_FORMAT_R16G16B16A16_UNORM
+    )
+}
+
+/// Size of a pixel of a backbuffer format, every supported format but the 16 bit ones uses 4 bytes.
+pub fn bytes_per_pixel(format: DXGI_FORMAT) -> usize {
+    match format {
+        DXGI_FORMAT_R16G16B16A16_TYPELESS
+        | DXGI_FORMAT_R16G16B16A16_FLOAT
+        | DXGI_FORMAT_R16G16B16A16_UNORM => 8,
+        _ => 4,
+    }
+}
+
+/// Bits per color and per alpha channel of a backbuffer format.
+pub fn channel_bits(format: DXGI_FORMAT) -> (u32, u32) {
+    match format {
+        DXGI_FORMAT_R10G10B10A2_TYPELESS | DXGI_FORMAT_R10G10B10A2_UNORM => (10, 2),
+        DXGI_FORMAT_R16G16B16A16_TYPELESS
+        | DXGI_FORMAT_R16G16B16A16_FLOAT
+        | DXGI_FORMAT_R16G16B16A16_UNORM => (16, 16),
+        _ => (8, 8),
+    }
+}
@@ -0,0 +1,57 @@
+use egui::{epaint::ClippedPrimitive, pos2, Rect, TextureId};
+use windows::Win32::Foundation::RECT;
+
+/// What the most recent frame submitted to the GPU, see [`crate::DirectX11App::last_frame_debug`].
+#[derive(Clone, Debug, Default)]
+pub struct FrameDebug {
+    /// Size in pixels the UI was laid out on.
+    pub screen_size: (f32, f32),
+    pub pixels_per_point: f32,
+    /// Output of egui's tessellation, in points.
+    pub primitives: Vec<ClippedPrimitive>,
+    /// Draw calls of the UI in submission order, primitives that were empty or clipped away
+    /// entirely have none.
+    pub draws: Vec<DrawCall>,
+}
+
+/// One indexed draw of a mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawCall {
+    pub texture_id: TextureId,
+    /// A texture was registered under `texture_id`, the previous binding stays otherwise.
+    pub texture_bound: bool,
+    /// Scissor rectangle in pixels of the render target.
+    pub scissor: Rect,
+    pub indices: usize,
+    pub vertices: usize,
+}
+
+impl FrameDebug {
+    pub(crate) fn new(screen_size: (f32, f32), pixels_per_point: f32) -> Self {
+        Self {
+            screen_size,
+            pixels_per_point,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn push_draw(
+        &mut self,
+        texture_id: TextureId,
+        texture_bound: bool,
+        scissor: &RECT,
+        indices: usize,
+        vertices: usize,
+    ) {
+        self.draws.push(DrawCall {
+            texture_id,
+            texture_bound,
+            scissor: Rect::from_min_max(
+                pos2(scissor.left as f32, scissor.top as f32),
+                pos2(scissor.right as f32, scissor.bottom as f32),
+            ),
+            indices,
+            vertices,
+        });
+    }
+}
@@ -0,0 +1,127 @@
+use egui::{ecolor::gamma_from_linear, Rgba};
+use windows::{
+    core::Interface,
+    Win32::Graphics::Dxgi::{
+        Common::{
+            DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709, DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+            DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709, DXGI_COLOR_SPACE_TYPE, DXGI_FORMAT,
+            DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT,
+        },
+        IDXGIOutput6, IDXGISwapChain, DXGI_OUTPUT_DESC1,
+    },
+};
+
+/// Default brightness of the UI in nits when rendering into HDR backbuffers.
+pub const DEFAULT_SDR_WHITE_LEVEL: f32 = 200.;
+
+/// Color space the UI is rendered in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Regular SDR output, egui's colors are written as is.
+    #[default]
+    Srgb,
+    /// Linear scRGB, used with `R16G16B16A16_FLOAT` backbuffers.
+    ScRgb,
+    /// Rec. 2020 primaries with the PQ (ST.2084) transfer function, used with `R10G10B10A2_UNORM` backbuffers.
+    Hdr10,
+}
+
+impl ColorSpace {
+    /// Guesses the color space from the backbuffer format and the HDR state of the output the window is on.
+    pub fn detect(swap: &IDXGISwapChain, format: DXGI_FORMAT) -> Self {
+        match format {
+            DXGI_FORMAT_R16G16B16A16_FLOAT => Self::ScRgb,
+            DXGI_FORMAT_R10G10B10A2_UNORM if is_output_hdr(swap) => Self::Hdr10,
+            _ => Self::Srgb,
+        }
+    }
+
+    /// Color space of a swapchain as set with `IDXGISwapChain3::SetColorSpace1`, `None` for the
+    /// ones the UI can't be rendered in, e.g. YCbCr or studio range ones.
+    pub fn from_dxgi(color_space: DXGI_COLOR_SPACE_TYPE) -> Option<Self> {
+        match color_space {
+            DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709 => Some(Self::Srgb),
+            DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709 => Some(Self::ScRgb),
+            DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020 => Some(Self::Hdr10),
+            _ => None,
+        }
+    }
+
+    /// The swapchain color space matching this one, inverse of [`Self::from_dxgi`].
+    pub fn to_dxgi(&self) -> DXGI_COLOR_SPACE_TYPE {
+        match self {
+            Self::Srgb => DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709,
+            Self::ScRgb => DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+            Self::Hdr10 => DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+        }
+    }
+
+    /// Converts linear color into values that can be written into a backbuffer of this color space.
+    pub(crate) fn encode(&self, color: Rgba, sdr_white_level: f32) -> [f32; 4] {
+        let [r, g, b, a] = color.to_array();
+
+        match self {
+            Self::Srgb => [
+                gamma_from_linear(r),
+                gamma_from_linear(g),
+                gamma_from_linear(b),
+                a,
+            ],
+            Self::ScRgb => {
+                let scale = sdr_white_level / 80.;
+                [r * scale, g * scale, b * scale, a]
+            }
+            Self::Hdr10 => {
+                let pq = |c: f32| {
+                    let y = (c * sdr_white_level / 10000.)
+                        .clamp(0., 1.)
+                        .powf(0.159_301_76);
+                    ((0.835_937_5 + 18.851_563 * y) / (1. + 18.687_5 * y)).powf(78.843_75)
+                };
+
+                [
+                    pq(0.627_404 * r + 0.329_282 * g + 0.043_313_6 * b),
+                    pq(0.069_097 * r + 0.919_54 * g + 0.011_361_2 * b),
+                    pq(0.016_391_6 * r + 0.088_013_2 * g + 0.895_595 * b),
+                    a,
+                ]
+            }
+        }
+    }
+
+    pub(crate) fn shader_defines(&self) -> &'static [&'static str] {
+        match self {
+            Self::Srgb => &[],
+            Self::ScRgb => &["OUTPUT_SCRGB"],
+            Self::Hdr10 => &["OUTPUT_HDR10"],
+        }
+    }
+}
+
+fn is_output_hdr(swap: &IDXGISwapChain) -> bool {
+    unsafe {
+        let Ok(output) = swap
+            .GetContainingOutput()
+            .and_then(|o| o.cast::<IDXGIOutput6>())
+        else {
+            return false;
+        };
+
+        let mut desc = DXGI_OUTPUT_DESC1::default();
+        output.GetDesc1(&mut desc).is_ok()
+            && desc.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020
+    }
+}
+
+#[test]
+fn test_dxgi_roundtrip() {
+    use windows::Win32::Graphics::Dxgi::Common::DXGI_COLOR_SPACE_YCBCR_FULL_G22_LEFT_P709;
+
+    for space in [ColorSpace::Srgb, ColorSpace::ScRgb, ColorSpace::Hdr10] {
+        assert_eq!(ColorSpace::from_dxgi(space.to_dxgi()), Some(space));
+    }
+    assert_eq!(
+        ColorSpace::from_dxgi(DXGI_COLOR_SPACE_YCBCR_FULL_G22_LEFT_P709),
+        None
+    );
+}
@@ -0,0 +1,619 @@
+use crate::{
+    error::{Error, ErrorContext},
+    lock::DefaultRawMutex,
+};
+use lock_api::Mutex;
+use std::{
+    mem::size_of,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread::{self, JoinHandle},
+};
+use windows::{
+    core::PCWSTR,
+    w,
+    Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM},
+        Graphics::Gdi::ScreenToClient,
+        System::{
+            LibraryLoader::GetModuleHandleW,
+            SystemServices::{MK_CONTROL, MK_SHIFT},
+            Threading::GetCurrentThreadId,
+        },
+        UI::{
+            Input::{
+                GetRawInputData, GetRegisteredRawInputDevices,
+                KeyboardAndMouse::{
+                    GetAsyncKeyState, GetKeyState, ToUnicode, VK_CAPITAL, VK_CONTROL, VK_MENU,
+                    VK_SHIFT,
+                },
+                RegisterRawInputDevices, HRAWINPUT, RAWINPUT, RAWINPUTDEVICE, RAWINPUTHEADER,
+                RAWKEYBOARD, RAWMOUSE, RIDEV_INPUTSINK, RIDEV_REMOVE, RID_DEVICE_INFO_TYPE,
+                RID_INPUT, RIM_TYPEKEYBOARD, RIM_TYPEMOUSE,
+            },
+            WindowsAndMessaging::{
+                CallNextHookEx, CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW,
+                GetAncestor, GetCursorPos, GetForegroundWindow, GetMessageW, PostThreadMessageW,
+                RegisterClassW, SetWindowsHookExW, UnhookWindowsHookEx, UnregisterClassW, GA_ROOT,
+                HC_ACTION, HHOOK, HMENU, HWND_MESSAGE, KBDLLHOOKSTRUCT, LLKHF_ALTDOWN,
+                LLKHF_EXTENDED, LLKHF_UP, MSG, MSLLHOOKSTRUCT, RI_KEY_BREAK, RI_KEY_E0,
+                RI_MOUSE_BUTTON_4_DOWN, RI_MOUSE_BUTTON_4_UP, RI_MOUSE_BUTTON_5_DOWN,
+                RI_MOUSE_BUTTON_5_UP, RI_MOUSE_HWHEEL, RI_MOUSE_LEFT_BUTTON_DOWN,
+                RI_MOUSE_LEFT_BUTTON_UP, RI_MOUSE_MIDDLE_BUTTON_DOWN, RI_MOUSE_MIDDLE_BUTTON_UP,
+                RI_MOUSE_RIGHT_BUTTON_DOWN, RI_MOUSE_RIGHT_BUTTON_UP, RI_MOUSE_WHEEL,
+                WH_KEYBOARD_LL, WH_MOUSE_LL, WINDOW_EX_STYLE, WINDOW_STYLE, WM_CHAR, WM_INPUT,
+                WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP,
+                WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_QUIT, WM_RBUTTONDOWN, WM_RBUTTONUP,
+                WM_SYSKEYDOWN, WM_SYSKEYUP, WM_XBUTTONDOWN, WM_XBUTTONUP, WNDCLASSW, XBUTTON1,
+                XBUTTON2,
+            },
+        },
+    },
+};
+
+// Don't change the dead key state, so the game still gets accents right.
+const TOUNICODE_KEEP_STATE: u32 = 0x4;
+
+const RAW_INPUT_CLASS: PCWSTR = w!("egui-d3d11-raw-input");
+
+// Generic desktop page, usages of mice and keyboards.
+const USAGE_PAGE_GENERIC: u16 = 0x01;
+const USAGE_MOUSE: u16 = 0x02;
+const USAGE_KEYBOARD: u16 = 0x06;
+
+/// Receives the translated messages, returns whether egui wants the input.
+pub type Dispatch = fn(*const (), u32, usize, isize) -> bool;
+
+// Undoes the setup, runs on the input thread after its message loop ended.
+type Teardown = Box<dyn FnOnce()>;
+
+struct Sink {
+    app: *const (),
+    dispatch: Dispatch,
+    hwnd: HWND,
+    // top-level window of `hwnd`, the only one that can be in the foreground
+    root: HWND,
+    block: bool,
+    raw: bool,
+    // input thread the sink belongs to, each one runs its own hooks
+    thread_id: u32,
+}
+
+// The app is `'static` and only touched through its input lock.
+unsafe impl Send for Sink {}
+
+// Hook procedures don't get any user data, the sinks are keyed by their window instead.
+// There is one input source per window, so several apps can take input of their own one.
+static SINKS: Mutex<DefaultRawMutex, Vec<Sink>> =
+    Mutex::const_new(<DefaultRawMutex as lock_api::RawMutex>::INIT, Vec::new());
+
+/// Input source running on a thread of its own, see [`crate::DirectX11App::install_input_hooks`]
+/// and [`crate::DirectX11App::install_raw_input`]. Dropping it stops the thread.
+pub struct InputHooks {
+    thread: Option<JoinHandle<()>>,
+    thread_id: u32,
+}
+
+/// Installs low-level mouse and keyboard hooks, they are called on the installing
+/// thread and need it to pump messages.
+pub fn install_hooks(
+    app: *const (),
+    dispatch: Dispatch,
+    hwnd: HWND,
+    block: bool,
+) -> Result<InputHooks, Error> {
+    let sink = Sink {
+        app,
+        dispatch,
+        hwnd,
+        root: unsafe { GetAncestor(hwnd, GA_ROOT) },
+        block,
+        raw: false,
+        thread_id: 0,
+    };
+    spawn(sink, set_hooks)
+}
+
+/// Registers RawInput against a message-only window, the game's window is never touched.
+/// RawInput can't be blocked. The process has one registration per device type, the first
+/// app's window passes the input on to the other apps that installed RawInput.
+pub fn install_raw_input(
+    app: *const (),
+    dispatch: Dispatch,
+    hwnd: HWND,
+) -> Result<InputHooks, Error> {
+    let sink = Sink {
+        app,
+        dispatch,
+        hwnd,
+        root: unsafe { GetAncestor(hwnd, GA_ROOT) },
+        block: false,
+        raw: true,
+        thread_id: 0,
+    };
+    spawn(sink, create_raw_input_window)
+}
+
+fn spawn(
+    mut sink: Sink,
+    setup: unsafe fn() -> Result<Teardown, Error>,
+) -> Result<InputHooks, Error> {
+    let (sender, receiver) = mpsc::channel();
+    let thread = thread::spawn(move || unsafe {
+        {
+            let mut sinks = SINKS.lock();
+            if sinks.iter().any(|other| other.hwnd == sink.hwnd) {
+                let _ = sender.send(Err(Error::HooksInstalled));
+                return;
+            }
+            sink.thread_id = GetCurrentThreadId();
+            sinks.push(sink);
+        }
+
+        match setup() {
+            Ok(teardown) => {
+                let _ = sender.send(Ok(GetCurrentThreadId()));
+
+                let mut msg = MSG::default();
+                while GetMessageW(&mut msg, HWND(0), 0, 0).0 > 0 {
+                    DispatchMessageW(&msg);
+                }
+
+                teardown();
+            }
+            Err(e) => {
+                let _ = sender.send(Err(e));
+            }
+        }
+
+        let thread_id = GetCurrentThreadId();
+        SINKS.lock().retain(|sink| sink.thread_id != thread_id);
+    });
+
+    match receiver
+        .recv()
+        .unwrap_or(Err(Error::Missing("Input thread exited")))
+    {
+        Ok(thread_id) => {
+            log_msg!(info, "Started input thread {}", thread_id);
+            Ok(InputHooks {
+                thread: Some(thread),
+                thread_id,
+            })
+        }
+        Err(e) => {
+            let _ = thread.join();
+            Err(e)
+        }
+    }
+}
+
+impl Drop for InputHooks {
+    fn drop(&mut self) {
+        unsafe {
+            PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0));
+        }
+        // the thread removes its sink on the way out
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+unsafe fn set_hooks() -> Result<Teardown, Error> {
+    let module = GetModuleHandleW(PCWSTR::null()).context("Failed to get module handle")?;
+
+    let mouse = SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), module, 0)
+        .context("Failed to install mouse hook")?;
+
+    match SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_proc), module, 0) {
+        Ok(keyboard) => Ok(Box::new(move || {
+            UnhookWindowsHookEx(mouse);
+            UnhookWindowsHookEx(keyboard);
+        })),
+        Err(e) => {
+            UnhookWindowsHookEx(mouse);
+            Err(Error::Windows("Failed to install keyboard hook", e))
+        }
+    }
+}
+
+unsafe fn create_raw_input_window() -> Result<Teardown, Error> {
+    let module = GetModuleHandleW(PCWSTR::null()).context("Failed to get module handle")?;
+
+    RegisterClassW(&WNDCLASSW {
+        lpfnWndProc: Some(raw_input_proc),
+        hInstance: module,
+        lpszClassName: RAW_INPUT_CLASS,
+        ..Default::default()
+    });
+
+    let window = CreateWindowExW(
+        WINDOW_EX_STYLE::default(),
+        RAW_INPUT_CLASS,
+        PCWSTR::null(),
+        WINDOW_STYLE::default(),
+        0,
+        0,
+        0,
+        0,
+        HWND_MESSAGE,
+        HMENU::default(),
+        module,
+        None,
+    );
+    if window == HWND::default() {
+        UnregisterClassW(RAW_INPUT_CLASS, module);
+        return Err(Error::Missing("Failed to create message-only window"));
+    }
+
+    // There is one registration per device type and process, taking over the game's
+    // would cut it off from its own input.
+    let registered = registered_devices();
+    let devices: Vec<_> = [USAGE_MOUSE, USAGE_KEYBOARD]
+        .into_iter()
+        .filter(|usage| {
+            let taken = registered.contains(usage);
+            if taken {
+                log_msg!(warn, "RawInput usage {:#x} is taken by the game", usage);
+            }
+            !taken
+        })
+        .map(|usage| RAWINPUTDEVICE {
+            usUsagePage: USAGE_PAGE_GENERIC,
+            usUsage: usage,
+            dwFlags: RIDEV_INPUTSINK,
+            hwndTarget: window,
+        })
+        .collect();
+
+    if !devices.is_empty()
+        && !RegisterRawInputDevices(&devices, size_of::<RAWINPUTDEVICE>() as _).as_bool()
+    {
+        DestroyWindow(window);
+        UnregisterClassW(RAW_INPUT_CLASS, module);
+        return Err(Error::Windows(
+            "Failed to register raw input devices",
+            windows::core::Error::from_win32(),
+        ));
+    }
+
+    Ok(Box::new(move || {
+        let removed: Vec<_> = devices
+            .iter()
+            .map(|device| RAWINPUTDEVICE {
+                dwFlags: RIDEV_REMOVE,
+                hwndTarget: HWND::default(),
+                ..*device
+            })
+            .collect();
+        if !removed.is_empty() {
+            RegisterRawInputDevices(&removed, size_of::<RAWINPUTDEVICE>() as _);
+        }
+
+        DestroyWindow(window);
+        UnregisterClassW(RAW_INPUT_CLASS, module);
+    }))
+}
+
+/// Generic desktop usages the process already registered for RawInput.
+unsafe fn registered_devices() -> Vec<u16> {
+    let mut count = 0;
+    GetRegisteredRawInputDevices(None, &mut count, size_of::<RAWINPUTDEVICE>() as _);
+
+    let mut devices = vec![RAWINPUTDEVICE::default(); count as usize];
+    let read = GetRegisteredRawInputDevices(
+        Some(devices.as_mut_ptr()),
+        &mut count,
+        size_of::<RAWINPUTDEVICE>() as _,
+    );
+    devices.truncate(read.min(count) as usize);
+
+    devices
+        .into_iter()
+        .filter(|device| device.usUsagePage == USAGE_PAGE_GENERIC)
+        .map(|device| device.usUsage)
+        .collect()
+}
+
+/// Passes messages to the app whose window is in the foreground, among the ones fed by this
+/// thread's hooks or by RawInput. `None` if the hook has to call the next one.
+fn dispatch(
+    translate: impl FnOnce(HWND) -> Vec<(u32, usize, isize)>,
+    raw: bool,
+    blockable: bool,
+) -> Option<LRESULT> {
+    let foreground = unsafe { GetForegroundWindow() };
+    let thread_id = unsafe { GetCurrentThreadId() };
+
+    let sinks = SINKS.lock();
+    let sink = sinks.iter().find(|sink| {
+        sink.root == foreground && if raw { sink.raw } else { sink.thread_id == thread_id }
+    })?;
+
+    let mut wanted = false;
+    for (msg, wparam, lparam) in translate(sink.hwnd) {
+        wanted |= (sink.dispatch)(sink.app, msg, wparam, lparam);
+    }
+
+    (sink.block && blockable && wanted).then_some(LRESULT(1))
+}
+
+unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+        let msg = wparam.0 as u32;
+
+        // Moving can't be blocked without freezing the cursor,
+        // releases always reach the game so it doesn't see stuck buttons.
+        let blockable = !matches!(msg, WM_MOUSEMOVE) && !is_release(msg);
+
+        let result = dispatch(
+            |hwnd| {
+                let mut pos = info.pt;
+                ScreenToClient(hwnd, &mut pos);
+
+                let mut wparam = mouse_modifiers();
+                if matches!(
+                    msg,
+                    WM_MOUSEWHEEL | WM_MOUSEHWHEEL | WM_XBUTTONDOWN | WM_XBUTTONUP
+                ) {
+                    wparam |= (info.mouseData & 0xFFFF0000) as usize;
+                }
+
+                vec![(msg, wparam, pack_point(pos))]
+            },
+            false,
+            blockable,
+        );
+
+        if let Some(result) = result {
+            return result;
+        }
+    }
+
+    CallNextHookEx(HHOOK(0), code, wparam, lparam)
+}
+
+unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+        let msg = wparam.0 as u32;
+
+        let result = dispatch(
+            |_| {
+                let up = info.flags & LLKHF_UP == LLKHF_UP;
+                let lparam = key_lparam(
+                    info.scanCode,
+                    info.flags & LLKHF_EXTENDED == LLKHF_EXTENDED,
+                    info.flags & LLKHF_ALTDOWN == LLKHF_ALTDOWN,
+                    up,
+                    set_key_down(info.vkCode, !up),
+                );
+                key_messages(msg, info.vkCode, info.scanCode, lparam)
+            },
+            false,
+            matches!(msg, WM_KEYDOWN | WM_SYSKEYDOWN),
+        );
+
+        if let Some(result) = result {
+            return result;
+        }
+    }
+
+    CallNextHookEx(HHOOK(0), code, wparam, lparam)
+}
+
+unsafe extern "system" fn raw_input_proc(
+    window: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_INPUT {
+        let mut input = RAWINPUT::default();
+        let mut size = size_of::<RAWINPUT>() as u32;
+
+        let read = GetRawInputData(
+            HRAWINPUT(lparam.0),
+            RID_INPUT,
+            Some(&mut input as *mut _ as _),
+            &mut size,
+            size_of::<RAWINPUTHEADER>() as _,
+        );
+
+        // HID devices don't fit, they aren't registered anyway.
+        if read != u32::MAX && read > 0 {
+            dispatch(
+                |hwnd| match RID_DEVICE_INFO_TYPE(input.header.dwType) {
+                    RIM_TYPEMOUSE => raw_mouse_messages(&input.data.mouse, hwnd),
+                    RIM_TYPEKEYBOARD => raw_keyboard_messages(&input.data.keyboard),
+                    _ => vec![],
+                },
+                true,
+                false,
+            );
+        }
+    }
+
+    DefWindowProcW(window, msg, wparam, lparam)
+}
+
+/// RawInput only has relative movement, the position comes from the cursor.
+unsafe fn raw_mouse_messages(mouse: &RAWMOUSE, hwnd: HWND) -> Vec<(u32, usize, isize)> {
+    const BUTTONS: [(u32, u32, u16); 10] = [
+        (RI_MOUSE_LEFT_BUTTON_DOWN, WM_LBUTTONDOWN, 0),
+        (RI_MOUSE_LEFT_BUTTON_UP, WM_LBUTTONUP, 0),
+        (RI_MOUSE_RIGHT_BUTTON_DOWN, WM_RBUTTONDOWN, 0),
+        (RI_MOUSE_RIGHT_BUTTON_UP, WM_RBUTTONUP, 0),
+        (RI_MOUSE_MIDDLE_BUTTON_DOWN, WM_MBUTTONDOWN, 0),
+        (RI_MOUSE_MIDDLE_BUTTON_UP, WM_MBUTTONUP, 0),
+        (RI_MOUSE_BUTTON_4_DOWN, WM_XBUTTONDOWN, XBUTTON1),
+        (RI_MOUSE_BUTTON_4_UP, WM_XBUTTONUP, XBUTTON1),
+        (RI_MOUSE_BUTTON_5_DOWN, WM_XBUTTONDOWN, XBUTTON2),
+        (RI_MOUSE_BUTTON_5_UP, WM_XBUTTONUP, XBUTTON2),
+    ];
+
+    let mut pos = POINT::default();
+    GetCursorPos(&mut pos);
+    ScreenToClient(hwnd, &mut pos);
+
+    let lparam = pack_point(pos);
+    let modifiers = mouse_modifiers();
+    let flags = mouse.Anonymous.Anonymous.usButtonFlags as u32;
+    let data = (mouse.Anonymous.Anonymous.usButtonData as usize) << 16;
+
+    let mut messages = vec![];
+    if mouse.lLastX != 0 || mouse.lLastY != 0 || mouse.usFlags != 0 {
+        messages.push((WM_MOUSEMOVE, modifiers, lparam));
+    }
+
+    for (flag, msg, button) in BUTTONS {
+        if flags & flag != 0 {
+            messages.push((msg, modifiers | (button as usize) << 16, lparam));
+        }
+    }
+
+    if flags & RI_MOUSE_WHEEL != 0 {
+        messages.push((WM_MOUSEWHEEL, modifiers | data, lparam));
+    }
+    if flags & RI_MOUSE_HWHEEL != 0 {
+        messages.push((WM_MOUSEHWHEEL, modifiers | data, lparam));
+    }
+
+    messages
+}
+
+fn raw_keyboard_messages(keyboard: &RAWKEYBOARD) -> Vec<(u32, usize, isize)> {
+    // Fake key that is part of an escape sequence.
+    if keyboard.VKey == 0xFF {
+        return vec![];
+    }
+
+    let flags = keyboard.Flags as u32;
+    let up = flags & RI_KEY_BREAK != 0;
+    let lparam = key_lparam(
+        keyboard.MakeCode as _,
+        flags & RI_KEY_E0 != 0,
+        matches!(keyboard.Message, WM_SYSKEYDOWN | WM_SYSKEYUP),
+        up,
+        set_key_down(keyboard.VKey as _, !up),
+    );
+
+    key_messages(
+        keyboard.Message,
+        keyboard.VKey as _,
+        keyboard.MakeCode as _,
+        lparam,
+    )
+}
+
+/// The key message itself followed by the characters a key press produces.
+fn key_messages(msg: u32, vk: u32, scan: u32, lparam: isize) -> Vec<(u32, usize, isize)> {
+    let mut messages = vec![(msg, vk as usize, lparam)];
+
+    if matches!(msg, WM_KEYDOWN | WM_SYSKEYDOWN) {
+        messages.extend(
+            translate_chars(vk, scan)
+                .into_iter()
+                .map(|unit| (WM_CHAR, unit as usize, lparam)),
+        );
+    }
+
+    messages
+}
+
+fn is_release(msg: u32) -> bool {
+    matches!(
+        msg,
+        WM_LBUTTONUP | WM_RBUTTONUP | WM_MBUTTONUP | WM_XBUTTONUP | WM_KEYUP | WM_SYSKEYUP
+    )
+}
+
+fn pack_point(pos: POINT) -> isize {
+    ((pos.x as u16 as u32) | (pos.y as u16 as u32) << 16) as isize
+}
+
+/// `lparam` of a key message, `was_down` sets the previous key state bit that marks auto repeat.
+fn key_lparam(scan: u32, extended: bool, alt: bool, up: bool, was_down: bool) -> isize {
+    let mut lparam = 1 | (scan & 0xFF) << 16;
+    if extended {
+        lparam |= 1 << 24;
+    }
+    if alt {
+        lparam |= 1 << 29;
+    }
+    if up {
+        lparam |= 3 << 30;
+    } else if was_down {
+        lparam |= 1 << 30;
+    }
+    lparam as isize
+}
+
+// Hooks and RawInput report auto repeat as more presses, the previous state is tracked here.
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_KEYS: AtomicU64 = AtomicU64::new(0);
+static KEYS_DOWN: [AtomicU64; 4] = [NO_KEYS; 4];
+
+/// Records the state of virtual key `vk`, returns whether it was already down.
+fn set_key_down(vk: u32, down: bool) -> bool {
+    let (word, bit) = ((vk as usize / 64) % 4, 1 << (vk % 64));
+    let previous = if down {
+        KEYS_DOWN[word].fetch_or(bit, Ordering::Relaxed)
+    } else {
+        KEYS_DOWN[word].fetch_and(!bit, Ordering::Relaxed)
+    };
+    previous & bit != 0
+}
+
+fn mouse_modifiers() -> usize {
+    let mut wparam = 0;
+    if is_down(VK_CONTROL.0) {
+        wparam |= MK_CONTROL.0 as usize;
+    }
+    if is_down(VK_SHIFT.0) {
+        wparam |= MK_SHIFT.0 as usize;
+    }
+    wparam
+}
+
+fn is_down(vk: u16) -> bool {
+    unsafe { GetAsyncKeyState(vk as i32) as u16 & 0x8000 != 0 }
+}
+
+/// Characters the key produces, the input thread has no keyboard state of its own
+/// so it's put together from the modifiers.
+fn translate_chars(vk: u32, scan: u32) -> Vec<u16> {
+    let mut state = [0u8; 256];
+    for key in [VK_SHIFT, VK_CONTROL, VK_MENU] {
+        if is_down(key.0) {
+            state[key.0 as usize] = 0x80;
+        }
+    }
+    if unsafe { GetKeyState(VK_CAPITAL.0 as i32) } & 1 != 0 {
+        state[VK_CAPITAL.0 as usize] = 1;
+    }
+
+    let mut buffer = [0u16; 8];
+    let len = unsafe { ToUnicode(vk, scan, Some(&state), &mut buffer, TOUNICODE_KEEP_STATE) };
+
+    buffer[..len.max(0) as usize].to_vec()
+}
+
+#[test]
+fn test_key_lparam() {
+    assert_eq!(key_lparam(0x1E, false, false, false, false), 0x001E_0001);
+    assert_eq!(key_lparam(0x1E, false, false, false, true), 0x401E_0001);
+    assert_eq!(key_lparam(0x1E, true, false, true, true) as u32, 0xC11E_0001);
+    assert_eq!(key_lparam(0x38, false, true, false, false), 0x2038_0001);
+
+    assert!(!set_key_down(0x41, true));
+    assert!(set_key_down(0x41, true));
+    assert!(set_key_down(0x41, false));
+    assert!(!set_key_down(0x41, true));
+
+    assert_eq!(pack_point(POINT { x: -1, y: 2 }), 0x0002_FFFF);
+}
@@ -0,0 +1,109 @@
+use egui::{Key, Modifiers};
+
+/// Handle of a hotkey registered with [`crate::DirectX11App::register_hotkey`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HotkeyId(u64);
+
+/// Key combination the input collector watches for.
+#[derive(Clone, Copy)]
+pub struct Binding {
+    pub id: HotkeyId,
+    modifiers: Modifiers,
+    key: Key,
+}
+
+impl Binding {
+    /// Only Alt, Ctrl and Shift are compared, the other modifiers mirror them on Windows.
+    pub fn matches(&self, modifiers: Modifiers, key: Key) -> bool {
+        self.key == key
+            && self.modifiers.alt == modifiers.alt
+            && self.modifiers.ctrl == modifiers.ctrl
+            && self.modifiers.shift == modifiers.shift
+    }
+}
+
+#[allow(clippy::type_complexity)]
+struct Hotkey<T> {
+    binding: Binding,
+    action: Box<dyn FnMut(&mut T) + 'static>,
+}
+
+/// Actions bound to key combinations, they run on the render thread at the start of the next frame.
+pub struct Hotkeys<T> {
+    hotkeys: Vec<Hotkey<T>>,
+    next_id: u64,
+}
+
+impl<T> Default for Hotkeys<T> {
+    fn default() -> Self {
+        Self {
+            hotkeys: vec![],
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> Hotkeys<T> {
+    pub fn add(
+        &mut self,
+        modifiers: Modifiers,
+        key: Key,
+        action: impl FnMut(&mut T) + 'static,
+    ) -> HotkeyId {
+        let id = HotkeyId(self.next_id);
+        self.next_id += 1;
+
+        log_msg!(
+            debug,
+            "Registering hotkey {:?} {:?} as {:?}",
+            modifiers,
+            key,
+            id
+        );
+
+        self.hotkeys.push(Hotkey {
+            binding: Binding { id, modifiers, key },
+            action: Box::new(action),
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: HotkeyId) -> bool {
+        let len = self.hotkeys.len();
+        self.hotkeys.retain(|hotkey| hotkey.binding.id != id);
+        self.hotkeys.len() != len
+    }
+
+    pub fn bindings(&self) -> Vec<Binding> {
+        self.hotkeys.iter().map(|hotkey| hotkey.binding).collect()
+    }
+
+    /// Does nothing if the hotkey was removed after it was pressed.
+    pub fn run(&mut self, id: HotkeyId, state: &mut T) {
+        if let Some(hotkey) = self.hotkeys.iter_mut().find(|h| h.binding.id == id) {
+            (hotkey.action)(state);
+        }
+    }
+}
+
+#[test]
+fn test_hotkeys() {
+    let mut hotkeys = Hotkeys::<Vec<u8>>::default();
+    let first = hotkeys.add(Modifiers::CTRL, Key::R, |state| state.push(1));
+    let second = hotkeys.add(Modifiers::NONE, Key::F1, |state| state.push(2));
+
+    let bindings = hotkeys.bindings();
+    assert!(bindings[0].matches(Modifiers::CTRL | Modifiers::COMMAND, Key::R));
+    assert!(!bindings[0].matches(Modifiers::CTRL | Modifiers::SHIFT, Key::R));
+    assert!(!bindings[1].matches(Modifiers::NONE, Key::F2));
+
+    let mut state = vec![];
+    hotkeys.run(second, &mut state);
+    hotkeys.run(first, &mut state);
+    assert_eq!(state, [2, 1]);
+
+    assert!(hotkeys.remove(first));
+    assert!(!hotkeys.remove(first));
+    hotkeys.run(first, &mut state);
+    assert_eq!(state, [2, 1]);
+}
@@ -0,0 +1,95 @@
+//! Images by uri, egui 0.21 has no loaders of its own and `ui.image` only takes textures.
+//! The loaders are installed on the context at init, loaded images become managed textures
+//! and go through the same allocator as the fonts.
+//!
+//! ```no_run
+//! # let ctx = egui::Context::default();
+//! # egui::CentralPanel::default().show(&ctx, |ui| {
+//! egui_d3d11::images::image(ui, "file://C:/overlay/logo.png", None);
+//! # });
+//! ```
+
+use crate::error::Error;
+use egui::{Color32, ColorImage, Context, Id, Response, TextureHandle, TextureOptions, Ui, Vec2};
+use std::{
+    collections::HashMap,
+    fs::File,
+    sync::{Arc, Mutex},
+};
+
+/// Failures are kept as well, a missing file is only read once.
+type Cache = Arc<Mutex<HashMap<String, Result<TextureHandle, String>>>>;
+
+fn cache_id() -> Id {
+    Id::new("egui-d3d11 images")
+}
+
+/// Called by init, [`load_image`] panics on contexts it wasn't called for.
+pub(crate) fn install(ctx: &Context) {
+    ctx.data_mut(|data| data.insert_temp(cache_id(), Cache::default()));
+}
+
+/// Texture of the PNG at `uri`, a path with or without `file://`.
+/// Loaded on first use, later calls return the same texture until [`forget_image`].
+pub fn load_image(ctx: &Context, uri: &str) -> Result<TextureHandle, Error> {
+    let cache = ctx
+        .data_mut(|data| data.get_temp::<Cache>(cache_id()))
+        .expect("Image loaders aren't installed on this context");
+    let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+
+    let loaded = cache.entry(uri.to_owned()).or_insert_with(|| {
+        let image = decode_png(uri.strip_prefix("file://").unwrap_or(uri))?;
+        Ok(ctx.load_texture(uri, image, TextureOptions::default()))
+    });
+
+    loaded.clone().map_err(|e| Error::Image(uri.to_owned(), e))
+}
+
+/// Drops the texture of `uri`, e.g. after the file changed. Returns `false` if it wasn't loaded.
+pub fn forget_image(ctx: &Context, uri: &str) -> bool {
+    ctx.data_mut(|data| data.get_temp::<Cache>(cache_id()))
+        .is_some_and(|cache| {
+            let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+            cache.remove(uri).is_some()
+        })
+}
+
+/// Shows the image at `uri`, at its own size if `size` is `None`.
+/// Shows why it couldn't be loaded instead if it failed.
+pub fn image(ui: &mut Ui, uri: &str, size: Option<Vec2>) -> Response {
+    match load_image(ui.ctx(), uri) {
+        Ok(texture) => ui.image(texture.id(), size.unwrap_or_else(|| texture.size_vec2())),
+        Err(e) => ui.colored_label(Color32::RED, e.to_string()),
+    }
+}
+
+fn decode_png(path: &str) -> Result<ColorImage, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+
+    let mut decoder = png::Decoder::new(file);
+    // everything ends up as 8 bit RGB(A) or grayscale
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    let pixels = &buffer[..info.buffer_size()];
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => pixels.to_vec(),
+        png::ColorType::Rgb => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => pixels
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&p| [p, p, p, 255]).collect(),
+        png::ColorType::Indexed => return Err("indexed colors weren't expanded".into()),
+    };
+
+    Ok(ColorImage::from_rgba_unmultiplied(
+        [info.width as _, info.height as _],
+        &rgba,
+    ))
+}
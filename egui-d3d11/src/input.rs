@@ -1,38 +1,140 @@
 #![allow(dead_code)]
 
-use clipboard::{windows_clipboard::WindowsClipboardContext, ClipboardProvider};
+use crate::{
+    clipboard::Clipboard,
+    compat,
+    config::{KeyPassthrough, ScreenTransform},
+    hotkey::{Binding, HotkeyId},
+    platform::PlatformRequests,
+    Config,
+};
+use crossbeam_queue::ArrayQueue;
 use egui::{Event, Key, Modifiers, PointerButton, Pos2, RawInput, Rect, Vec2};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+    Arc,
+};
 use windows::Win32::{
-    Foundation::{HWND, RECT},
+    Foundation::{HWND, LRESULT, POINT, RECT},
+    Graphics::Gdi::MapWindowPoints,
     System::{
-        SystemServices::{MK_CONTROL, MK_SHIFT},
+        SystemServices::{
+            MK_CONTROL, MK_LBUTTON, MK_MBUTTON, MK_RBUTTON, MK_SHIFT, MK_XBUTTON1, MK_XBUTTON2,
+        },
         WindowsProgramming::NtQuerySystemTime,
     },
     UI::{
+        HiDpi::GetDpiForWindow,
         Input::KeyboardAndMouse::{
-            GetAsyncKeyState, VIRTUAL_KEY, VK_BACK, VK_CONTROL, VK_DELETE, VK_DOWN, VK_END,
-            VK_ESCAPE, VK_HOME, VK_INSERT, VK_LEFT, VK_LSHIFT, VK_NEXT, VK_PRIOR, VK_RETURN,
-            VK_RIGHT, VK_SPACE, VK_TAB, VK_UP,
+            GetCapture, GetDoubleClickTime, GetKeyState, ReleaseCapture, SetCapture, VIRTUAL_KEY,
+            VK_BACK, VK_CONTROL, VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE, VK_HOME, VK_INSERT,
+            VK_LEFT, VK_MENU, VK_NEXT, VK_PRIOR, VK_RETURN, VK_RIGHT, VK_RMENU, VK_SHIFT, VK_SPACE,
+            VK_TAB, VK_UP,
         },
         WindowsAndMessaging::{
-            GetClientRect, WHEEL_DELTA, WM_CHAR, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDBLCLK,
-            WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDBLCLK, WM_MBUTTONDOWN, WM_MBUTTONUP,
-            WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDBLCLK, WM_RBUTTONDOWN,
-            WM_RBUTTONUP, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_XBUTTONDBLCLK, WM_XBUTTONDOWN,
-            WM_XBUTTONUP, XBUTTON1, XBUTTON2,
+            GetClientRect, GetMessageTime, GetSystemMetrics, HTCLIENT, SM_CXDOUBLECLK,
+            SM_CYDOUBLECLK, SPI_GETWHEELSCROLLCHARS, SPI_GETWHEELSCROLLLINES,
+            SPI_SETWHEELSCROLLCHARS, SPI_SETWHEELSCROLLLINES, SYSTEM_PARAMETERS_INFO_ACTION,
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, SystemParametersInfoW, USER_DEFAULT_SCREEN_DPI,
+            WHEEL_DELTA, WM_ACTIVATEAPP, WM_CAPTURECHANGED, WM_CHAR, WM_DEADCHAR, WM_DPICHANGED,
+            WM_KEYDOWN, WM_KEYUP, WM_KILLFOCUS, WM_LBUTTONDBLCLK, WM_LBUTTONDOWN, WM_LBUTTONUP,
+            WM_MBUTTONDBLCLK, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE,
+            WM_MOUSEWHEEL, WM_RBUTTONDBLCLK, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SETCURSOR,
+            WM_SETFOCUS, WM_SETTINGCHANGE, WM_SIZE, WM_SYSCHAR, WM_SYSDEADCHAR, WM_SYSKEYDOWN,
+            WM_SYSKEYUP, WM_XBUTTONDBLCLK, WM_XBUTTONDOWN, WM_XBUTTONUP, XBUTTON1,
         },
     },
 };
 
+/// Translates window messages into egui events, runs on the window's thread.
 pub struct InputCollector {
-    hwnd: HWND,
-    events: Vec<Event>,
+    queue: Arc<InputQueue>,
     modifiers: Option<Modifiers>,
+    toggle_key: Option<Key>,
+    hotkeys: Vec<Binding>,
+    sources: Vec<InputSource>,
+    clipboard: Clipboard,
+    clicks: ClickTracker,
+    wheel: WheelSettings,
+    // `WM_CHAR` delivers UTF-16, characters outside the BMP arrive in two messages.
+    high_surrogate: Option<u16>,
+    capture_mouse: bool,
+    key_passthrough: KeyPassthrough,
+    // source that `process_input` takes messages from, see `Config::input_window`
+    input_window: Option<HWND>,
+    // the last key message reached the game, the characters translated from it follow
+    passing_key: bool,
+    // window the mouse was captured for because egui is dragged
+    captured: Option<HWND>,
+}
+
+/// Change of the mouse capture [`InputCollector::update_capture`] asks for. Applied after
+/// the input lock is released, the capture calls send `WM_CAPTURECHANGED` synchronously.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureRequest {
+    Take(HWND),
+    Release,
+}
+
+impl CaptureRequest {
+    pub fn apply(self) {
+        unsafe {
+            match self {
+                Self::Take(hwnd) => {
+                    SetCapture(hwnd);
+                }
+                Self::Release => {
+                    ReleaseCapture();
+                }
+            }
+        }
+    }
+}
+
+/// Hands the translated events over to the render thread without either side ever waiting for the other.
+pub struct InputQueue {
+    hwnd: HWND,
+    events: ArrayQueue<Event>,
+    dropped: AtomicUsize,
+    modifiers: AtomicU8,
+    toggled: AtomicBool,
+    // hotkeys pressed since the last frame, in order
+    hotkeys: ArrayQueue<HotkeyId>,
+    clicks: AtomicU32,
+    // DPI of the monitor the window is on, updated by `WM_DPICHANGED`
+    dpi: AtomicU32,
+    // the window has the keyboard focus, assumed until told otherwise
+    focused: AtomicBool,
+    // client area as `width | height << 32`, updated by `WM_SIZE`, 0 if it couldn't be queried
+    client_size: AtomicU64,
+    // bits of the `x | y << 32` factors from display to backbuffer pixels, 0 if they match
+    display_scale: AtomicU64,
+    // bits of the latest pointer position as `x | y << 32`, `NO_CURSOR` until it moves again
+    cursor: AtomicU64,
+    // bits of the offset and scale input was moved with in the last frame, see `cursor_pos`
+    cursor_transform: [AtomicU64; 2],
+    pub platform: PlatformRequests,
+}
+
+/// Another window whose messages feed the UI, see [`crate::DirectX11App::add_input_window`].
+#[derive(Clone, Copy, Debug)]
+struct InputSource {
+    hwnd: HWND,
+    transform: Option<ScreenTransform>,
+}
+
+/// Counts consecutive presses the way Windows does, following the user's double-click
+/// speed and rectangle. egui 0.21 hard-codes its own timings that can't be changed.
+#[derive(Default)]
+struct ClickTracker {
+    last: Option<(PointerButton, Pos2, u32)>,
+    count: u32,
 }
 
 /// High-level overview of recognized `WndProc` messages.
 #[repr(u8)]
-pub enum InputResult {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputMessage {
     Unknown,
     MouseMove,
     MouseLeft,
@@ -42,6 +144,38 @@ pub enum InputResult {
     Scroll,
     Zoom,
     Key,
+    /// The cursor was set for egui, `WM_SETCURSOR` should return `TRUE`.
+    Cursor,
+}
+
+impl InputMessage {
+    /// Which kind of egui input the message feeds.
+    pub fn category(self) -> InputCategory {
+        match self {
+            Self::Unknown => InputCategory::Unknown,
+            Self::Key => InputCategory::Key,
+            Self::Character => InputCategory::Text,
+            _ => InputCategory::Pointer,
+        }
+    }
+}
+
+/// Kind of input a message carries, each is claimed by egui independently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputCategory {
+    Unknown,
+    Pointer,
+    Key,
+    Text,
+}
+
+/// What [`crate::DirectX11App::wnd_proc`] made of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputResult {
+    pub message: InputMessage,
+    /// The UI is visible and egui wants this category of input right now,
+    /// the game shouldn't see the message.
+    pub wanted: bool,
 }
 
 impl InputResult {
@@ -52,214 +186,367 @@ impl InputResult {
 
     #[inline]
     pub fn is_unknown(&self) -> bool {
-        matches!(*self, InputResult::Unknown)
+        self.message == InputMessage::Unknown
+    }
+
+    #[inline]
+    pub fn category(&self) -> InputCategory {
+        self.message.category()
+    }
+
+    /// What the window procedure should return instead of calling the original one,
+    /// `None` if the message should be passed on.
+    pub fn lresult(&self) -> Option<LRESULT> {
+        match self.message {
+            // `WM_SETCURSOR` returns `TRUE` to stop the default cursor from being set
+            InputMessage::Cursor => Some(LRESULT(1)),
+            _ if self.wanted => Some(LRESULT(0)),
+            _ => None,
+        }
     }
 }
 
 impl InputCollector {
-    pub fn new(hwnd: HWND) -> Self {
+    pub fn new(queue: Arc<InputQueue>) -> Self {
         Self {
-            hwnd,
-            events: vec![],
+            queue,
             modifiers: None,
+            toggle_key: None,
+            hotkeys: vec![],
+            sources: vec![],
+            clipboard: Clipboard::default(),
+            clicks: ClickTracker::default(),
+            wheel: WheelSettings::query(),
+            high_surrogate: None,
+            capture_mouse: true,
+            key_passthrough: KeyPassthrough::None,
+            input_window: None,
+            passing_key: false,
+            captured: None,
         }
     }
 
-    pub fn process(&mut self, umsg: u32, wparam: usize, lparam: isize) -> InputResult {
-        match umsg {
-            WM_MOUSEMOVE => {
-                self.alter_modifiers(get_mouse_modifiers(wparam));
+    pub fn configure(&mut self, config: &Config) {
+        self.toggle_key = config.toggle_key;
+        self.capture_mouse = config.mouse_capture;
+        self.key_passthrough = config.key_passthrough.clone();
 
-                self.events.push(Event::PointerMoved(get_pos(lparam)));
-                InputResult::MouseMove
-            }
-            WM_LBUTTONDOWN | WM_LBUTTONDBLCLK => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Primary,
-                    pressed: true,
-                    modifiers,
-                });
-                InputResult::MouseLeft
+        if self.input_window != config.input_window {
+            if let Some(old) = self.input_window {
+                self.remove_source(old);
             }
-            WM_LBUTTONUP => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Primary,
-                    pressed: false,
-                    modifiers,
-                });
-                InputResult::MouseLeft
+            if let Some(hwnd) = config.input_window {
+                self.add_source(hwnd, None);
             }
-            WM_RBUTTONDOWN | WM_RBUTTONDBLCLK => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Secondary,
-                    pressed: true,
-                    modifiers,
-                });
-                InputResult::MouseRight
+            self.input_window = config.input_window;
+        }
+        self.clipboard = config.clipboard.clone();
+    }
+
+    pub fn set_hotkeys(&mut self, hotkeys: Vec<Binding>) {
+        self.hotkeys = hotkeys;
+    }
+
+    /// Replaces the transform if `hwnd` already is a source.
+    pub fn add_source(&mut self, hwnd: HWND, transform: Option<ScreenTransform>) {
+        self.remove_source(hwnd);
+        self.sources.push(InputSource { hwnd, transform });
+    }
+
+    pub fn remove_source(&mut self, hwnd: HWND) -> bool {
+        let len = self.sources.len();
+        self.sources.retain(|source| source.hwnd != hwnd);
+        self.sources.len() != len
+    }
+
+    /// Window the messages of [`Self::process_input`] come from.
+    pub fn input_window(&self) -> HWND {
+        self.input_window.unwrap_or(self.queue.hwnd)
+    }
+
+    /// [`Self::process`] for the render window or [`Self::process_from`] for the input window,
+    /// see [`Config::input_window`].
+    pub fn process_input(&mut self, umsg: u32, wparam: usize, lparam: isize) -> InputMessage {
+        match self.input_window {
+            Some(hwnd) => self.process_from(hwnd, umsg, wparam, lparam),
+            None => self.process(umsg, wparam, lparam),
+        }
+    }
+
+    /// Like [`Self::process`] for a message sent to `hwnd`, which has to be the render window
+    /// or one added with [`Self::add_source`]. Positions are moved into the render window's
+    /// client area, messages about the other window itself are ignored.
+    pub fn process_from(
+        &mut self,
+        hwnd: HWND,
+        umsg: u32,
+        wparam: usize,
+        lparam: isize,
+    ) -> InputMessage {
+        if hwnd == self.queue.hwnd {
+            return self.process(umsg, wparam, lparam);
+        }
+
+        let Some(source) = self
+            .sources
+            .iter()
+            .find(|source| source.hwnd == hwnd)
+            .copied()
+        else {
+            return InputMessage::Unknown;
+        };
+
+        match umsg {
+            WM_SIZE | WM_DPICHANGED => InputMessage::Unknown,
+            _ if get_pressed_button(umsg, wparam).is_some()
+                || matches!(
+                    umsg,
+                    WM_MOUSEMOVE | WM_LBUTTONUP | WM_RBUTTONUP | WM_MBUTTONUP | WM_XBUTTONUP
+                ) =>
+            {
+                let pos = source.map(get_pos(lparam), self.queue.hwnd);
+                self.process(umsg, wparam, pack_pos(pos))
             }
-            WM_RBUTTONUP => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Secondary,
-                    pressed: false,
-                    modifiers,
-                });
-                InputResult::MouseRight
+            _ => self.process(umsg, wparam, lparam),
+        }
+    }
+
+    /// Captures the mouse for `hwnd`, the input window if `None`, when a button is pressed on
+    /// egui so drags keep going outside of the window, and releases it with the last button.
+    /// Never takes the capture from the game.
+    pub fn update_capture(
+        &mut self,
+        hwnd: Option<HWND>,
+        umsg: u32,
+        wparam: usize,
+        wanted: bool,
+    ) -> Option<CaptureRequest> {
+        let hwnd = hwnd.unwrap_or(self.input_window());
+
+        match umsg {
+            WM_CAPTURECHANGED if self.captured == Some(hwnd) => {
+                self.captured = None;
+                None
             }
-            WM_MBUTTONDOWN | WM_MBUTTONDBLCLK => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Middle,
-                    pressed: true,
-                    modifiers,
-                });
-                InputResult::MouseMiddle
+            _ if get_pressed_button(umsg, wparam).is_some() => {
+                let free = self.captured.is_none() && unsafe { GetCapture() }.0 == 0;
+                (wanted && self.capture_mouse && free).then(|| {
+                    self.captured = Some(hwnd);
+                    CaptureRequest::Take(hwnd)
+                })
             }
-            WM_MBUTTONUP => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: PointerButton::Middle,
-                    pressed: false,
-                    modifiers,
-                });
-                InputResult::MouseMiddle
+            WM_LBUTTONUP | WM_RBUTTONUP | WM_MBUTTONUP | WM_XBUTTONUP
+                if self.captured.is_some() && wparam & HELD_BUTTONS == 0 =>
+            {
+                self.captured = None;
+                Some(CaptureRequest::Release)
             }
-            WM_XBUTTONDOWN | WM_XBUTTONDBLCLK => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: if (wparam as u32) >> 16 & (XBUTTON1 as u32) != 0 {
-                        PointerButton::Extra1
-                    } else if (wparam as u32) >> 16 & (XBUTTON2 as u32) != 0 {
-                        PointerButton::Extra2
-                    } else {
-                        unreachable!()
-                    },
-                    pressed: true,
-                    modifiers,
-                });
-                InputResult::MouseMiddle
+            _ => None,
+        }
+    }
+
+    /// Checks if the game should see the message even when egui wants it,
+    /// see [`Config::key_passthrough`].
+    pub fn passes_through(&mut self, umsg: u32, wparam: usize) -> bool {
+        match umsg {
+            WM_KEYDOWN | WM_KEYUP | WM_SYSKEYDOWN | WM_SYSKEYUP => {
+                self.passing_key = self.key_passthrough.passes(VIRTUAL_KEY(wparam as u16));
+                self.passing_key
             }
-            WM_XBUTTONUP => {
-                let modifiers = get_mouse_modifiers(wparam);
-                self.alter_modifiers(modifiers);
-
-                self.events.push(Event::PointerButton {
-                    pos: get_pos(lparam),
-                    button: if (wparam as u32) >> 16 & (XBUTTON1 as u32) != 0 {
-                        PointerButton::Extra1
-                    } else if (wparam as u32) >> 16 & (XBUTTON2 as u32) != 0 {
-                        PointerButton::Extra2
-                    } else {
-                        unreachable!()
-                    },
-                    pressed: false,
-                    modifiers,
-                });
-                InputResult::MouseMiddle
+            WM_CHAR | WM_SYSCHAR | WM_DEADCHAR | WM_SYSDEADCHAR => self.passing_key,
+            _ => false,
+        }
+    }
+
+    /// Gives up the mouse capture egui took, for shutting down in the middle of a drag.
+    pub fn release_capture(&mut self) -> Option<CaptureRequest> {
+        self.captured.take().map(|_| CaptureRequest::Release)
+    }
+
+    pub fn process(&mut self, umsg: u32, wparam: usize, lparam: isize) -> InputMessage {
+        if self.toggle_key.is_some()
+            && umsg == WM_KEYDOWN
+            && get_key(wparam) == self.toggle_key
+            && !is_repeat(lparam)
+        {
+            self.queue.toggled.fetch_xor(true, Ordering::Relaxed);
+        }
+
+        if let Some(button) = get_pressed_button(umsg, wparam) {
+            let (time, max_delay, max_dist) = unsafe {
+                (
+                    GetMessageTime() as u32,
+                    GetDoubleClickTime(),
+                    Vec2::new(
+                        GetSystemMetrics(SM_CXDOUBLECLK) as f32,
+                        GetSystemMetrics(SM_CYDOUBLECLK) as f32,
+                    ),
+                )
+            };
+
+            let count = self
+                .clicks
+                .press(button, self.pos(lparam), time, max_delay, max_dist);
+            self.queue.clicks.store(count, Ordering::Relaxed);
+        }
+
+        self.queue.platform.apply_ime(self.queue.hwnd);
+
+        let result = self.translate(umsg, wparam, lparam);
+        self.queue.set_modifiers(self.modifiers);
+        result
+    }
+
+    fn translate(&mut self, umsg: u32, wparam: usize, lparam: isize) -> InputMessage {
+        let message = match umsg {
+            WM_MOUSEMOVE => InputMessage::MouseMove,
+            WM_LBUTTONDOWN | WM_LBUTTONDBLCLK | WM_LBUTTONUP => InputMessage::MouseLeft,
+            WM_RBUTTONDOWN | WM_RBUTTONDBLCLK | WM_RBUTTONUP => InputMessage::MouseRight,
+            WM_MBUTTONDOWN | WM_MBUTTONDBLCLK | WM_MBUTTONUP | WM_XBUTTONDOWN
+            | WM_XBUTTONDBLCLK | WM_XBUTTONUP => InputMessage::MouseMiddle,
+            WM_MOUSEWHEEL | WM_MOUSEHWHEEL if wparam & MK_CONTROL.0 as usize != 0 => {
+                InputMessage::Zoom
             }
+            WM_MOUSEWHEEL | WM_MOUSEHWHEEL => InputMessage::Scroll,
             WM_CHAR => {
-                if let Some(ch) = char::from_u32(wparam as _) {
-                    if !ch.is_control() {
-                        self.events.push(Event::Text(ch.into()));
-                    }
+                if let Some(ch) = self.decode_char(wparam as u16) {
+                    self.push_events(decode_message(WM_CHAR, ch as _, lparam, Modifiers::NONE));
                 }
-                InputResult::Character
+                return InputMessage::Character;
+            }
+            // The accent is combined with the next key by `TranslateMessage`,
+            // the result arrives as a regular `WM_CHAR`.
+            WM_DEADCHAR | WM_SYSDEADCHAR => {
+                self.high_surrogate = None;
+                return InputMessage::Character;
             }
-            WM_MOUSEWHEEL => {
-                self.alter_modifiers(get_mouse_modifiers(wparam));
+            WM_KEYDOWN | WM_SYSKEYDOWN | WM_KEYUP | WM_SYSKEYUP => {
+                let modifiers = get_key_modifiers();
+                self.modifiers = Some(modifiers);
 
-                let delta = (wparam >> 16) as i16 as f32 * 10. / WHEEL_DELTA as f32;
+                let pressed = matches!(umsg, WM_KEYDOWN | WM_SYSKEYDOWN);
+                if let Some(key) = get_key(wparam).filter(|_| pressed) {
+                    // Fired even while egui has the keyboard focus, ignoring auto repeat.
+                    if !is_repeat(lparam) {
+                        for binding in self.hotkeys.iter().filter(|b| b.matches(modifiers, key)) {
+                            self.queue.push_hotkey(binding.id);
+                        }
+                    }
 
-                if wparam & MK_CONTROL.0 as usize != 0 {
-                    self.events
-                        .push(Event::Zoom(if delta > 0. { 1.5 } else { 0.5 }));
-                    InputResult::Zoom
-                } else {
-                    self.events.push(Event::Scroll(Vec2::new(0., delta)));
-                    InputResult::Scroll
+                    if let Some(event) = get_clipboard_event(key, modifiers, &self.clipboard) {
+                        self.queue.push(event);
+                    }
                 }
-            }
-            WM_MOUSEHWHEEL => {
-                self.alter_modifiers(get_mouse_modifiers(wparam));
 
-                let delta = (wparam >> 16) as i16 as f32 * 10. / WHEEL_DELTA as f32;
-
-                if wparam & MK_CONTROL.0 as usize != 0 {
-                    self.events
-                        .push(Event::Zoom(if delta > 0. { 1.5 } else { 0.5 }));
-                    InputResult::Zoom
+                self.push_events(decode_message(umsg, wparam, lparam, modifiers));
+                return InputMessage::Key;
+            }
+            WM_SETCURSOR if lparam & 0xFFFF == HTCLIENT as isize => {
+                return if self.queue.platform.set_cursor() {
+                    InputMessage::Cursor
                 } else {
-                    self.events.push(Event::Scroll(Vec2::new(delta, 0.)));
-                    InputResult::Scroll
+                    InputMessage::Unknown
+                };
+            }
+            WM_ACTIVATEAPP => {
+                self.set_focused(wparam != 0);
+                return InputMessage::Unknown;
+            }
+            WM_SETFOCUS | WM_KILLFOCUS => {
+                self.set_focused(umsg == WM_SETFOCUS);
+                return InputMessage::Unknown;
+            }
+            // Still handled by the game, it usually resizes its window to the suggested rect.
+            WM_DPICHANGED => {
+                // a DPI of 0 would scale every position to infinity
+                if let dpi @ 1.. = wparam as u32 & 0xFFFF {
+                    self.queue.dpi.store(dpi, Ordering::Relaxed);
                 }
+                self.queue.refresh_client_size();
+                return InputMessage::Unknown;
             }
-            msg @ (WM_KEYDOWN | WM_SYSKEYDOWN) => {
-                let modifiers = get_key_modifiers(msg);
-                self.modifiers = Some(modifiers);
+            WM_SETTINGCHANGE
+                if matches!(
+                    SYSTEM_PARAMETERS_INFO_ACTION(wparam as u32),
+                    SPI_SETWHEELSCROLLLINES | SPI_SETWHEELSCROLLCHARS
+                ) =>
+            {
+                self.wheel = WheelSettings::query();
+                return InputMessage::Unknown;
+            }
+            WM_SIZE => {
+                let (width, height) = (lparam & 0xFFFF, (lparam >> 16) & 0xFFFF);
+                self.queue
+                    .client_size
+                    .store(width as u64 | (height as u64) << 32, Ordering::Relaxed);
+                return InputMessage::Unknown;
+            }
+            _ => return InputMessage::Unknown,
+        };
 
-                if let Some(key) = get_key(wparam) {
-                    if key == Key::V && modifiers.ctrl {
-                        if let Some(clipboard) = get_clipboard_text() {
-                            self.events.push(Event::Text(clipboard));
-                        }
-                    }
+        // mouse messages carry their own modifiers
+        let modifiers = get_mouse_modifiers(wparam);
+        self.alter_modifiers(modifiers);
 
-                    if key == Key::C && modifiers.ctrl {
-                        self.events.push(Event::Copy);
-                    }
+        let mut events = decode_message(umsg, wparam, lparam, Modifiers::NONE);
+        if message == InputMessage::Scroll {
+            let page = self.queue.client_size().map_or(0., |size| size.y) / LINE_HEIGHT;
+            self.wheel.apply(&mut events, wparam, page, modifiers);
+        }
+        self.push_events(events);
+        message
+    }
 
-                    if key == Key::X && modifiers.ctrl {
-                        self.events.push(Event::Cut);
-                    }
+    /// Queues decoded events, moving positions the way [`Self::pos`] does.
+    fn push_events(&self, events: Vec<Event>) {
+        let scale = self.queue.display_scale();
 
-                    self.events.push(Event::Key {
-                        pressed: true,
-                        modifiers,
-                        key,
-                        repeat: lparam & 0b1111_1111_1111_1111_0000_0000_0000_0000 > 0,
-                    });
-                }
-                InputResult::Key
+        for mut event in events {
+            if let (Some(scale), Event::PointerMoved(pos) | Event::PointerButton { pos, .. }) =
+                (scale, &mut event)
+            {
+                *pos = (pos.to_vec2() * scale).to_pos2();
             }
-            msg @ (WM_KEYUP | WM_SYSKEYUP) => {
-                let modifiers = get_key_modifiers(msg);
-                self.modifiers = Some(modifiers);
+            self.queue.push(event);
+        }
+    }
 
-                if let Some(key) = get_key(wparam) {
-                    self.events.push(Event::Key {
-                        pressed: false,
-                        modifiers,
-                        key,
-                        repeat: false,
-                    });
-                }
-                InputResult::Key
+    /// Position of a mouse message. In exclusive fullscreen the client area covers the display
+    /// mode, which may differ from the backbuffer when the driver stretches it.
+    fn pos(&self, lparam: isize) -> Pos2 {
+        let pos = get_pos(lparam);
+
+        match self.queue.display_scale() {
+            Some(scale) => (pos.to_vec2() * scale).to_pos2(),
+            None => pos,
+        }
+    }
+
+    /// Without focus egui stops the cursor blinking, the pointer is gone until it moves again.
+    fn set_focused(&mut self, focused: bool) {
+        if !self.queue.focused.swap(focused, Ordering::Relaxed) || focused {
+            return;
+        }
+
+        self.queue.push(Event::PointerGone);
+        // key ups go to the newly focused window, no modifier may stay stuck
+        self.alter_modifiers(Modifiers::NONE);
+    }
+
+    fn decode_char(&mut self, unit: u16) -> Option<char> {
+        match unit {
+            0xD800..=0xDBFF => {
+                self.high_surrogate = Some(unit);
+                None
+            }
+            0xDC00..=0xDFFF => {
+                let high = self.high_surrogate.take()?;
+                char::decode_utf16([high, unit]).next()?.ok()
+            }
+            _ => {
+                self.high_surrogate = None;
+                char::from_u32(unit as _)
             }
-            _ => InputResult::Unknown,
         }
     }
 
@@ -268,20 +555,135 @@ impl InputCollector {
             *old = new;
         }
     }
+}
+
+impl InputQueue {
+    /// Events past this are dropped, oldest first, e.g. while nothing is presented.
+    const CAPACITY: usize = 1024;
+    const HOTKEY_CAPACITY: usize = 64;
+
+    pub fn new(hwnd: HWND) -> Self {
+        Self {
+            hwnd,
+            events: ArrayQueue::new(Self::CAPACITY),
+            dropped: AtomicUsize::new(0),
+            modifiers: AtomicU8::new(0),
+            toggled: AtomicBool::new(false),
+            hotkeys: ArrayQueue::new(Self::HOTKEY_CAPACITY),
+            clicks: AtomicU32::new(0),
+            dpi: AtomicU32::new(match unsafe { GetDpiForWindow(hwnd) } {
+                0 => USER_DEFAULT_SCREEN_DPI,
+                dpi => dpi,
+            }),
+            focused: AtomicBool::new(true),
+            client_size: AtomicU64::new(query_client_size(hwnd)),
+            display_scale: AtomicU64::new(0),
+            cursor: AtomicU64::new(NO_CURSOR),
+            cursor_transform: [
+                AtomicU64::new(vec2_bits(ScreenTransform::IDENTITY.offset)),
+                AtomicU64::new(vec2_bits(ScreenTransform::IDENTITY.scale)),
+            ],
+            platform: PlatformRequests::default(),
+        }
+    }
 
-    pub fn collect_input(&mut self) -> RawInput {
-        RawInput {
-            modifiers: self.modifiers.unwrap_or_default(),
-            events: std::mem::take(&mut self.events),
-            screen_rect: Some(self.get_screen_rect()),
+    /// Scale of the monitor the window is on, `1.0` unless the game is per-monitor DPI aware.
+    pub fn pixels_per_point(&self) -> f32 {
+        self.dpi.load(Ordering::Relaxed) as f32 / USER_DEFAULT_SCREEN_DPI as f32
+    }
+
+    pub fn push(&self, event: Event) {
+        match &event {
+            Event::PointerMoved(pos) | Event::PointerButton { pos, .. } => {
+                self.cursor.store(vec2_bits(pos.to_vec2()), Ordering::Relaxed);
+            }
+            Event::PointerGone => self.cursor.store(NO_CURSOR, Ordering::Relaxed),
+            _ => {}
+        }
+
+        if self.events.force_push(event).is_some() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of events that were dropped because the queue was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of consecutive clicks of the last pressed button, 2 for a double click.
+    pub fn clicks(&self) -> u32 {
+        self.clicks.load(Ordering::Relaxed)
+    }
+
+    /// Checks if the toggle key was pressed an odd number of times since the last call.
+    pub fn take_toggled(&self) -> bool {
+        self.toggled.swap(false, Ordering::Relaxed)
+    }
+
+    fn push_hotkey(&self, id: HotkeyId) {
+        self.hotkeys.force_push(id);
+    }
+
+    /// Next hotkey pressed since the last frame, oldest first.
+    pub fn take_hotkey(&self) -> Option<HotkeyId> {
+        self.hotkeys.pop()
+    }
+
+    fn set_modifiers(&self, modifiers: Option<Modifiers>) {
+        let bits = modifiers.map_or(0, |m| {
+            0b1_0000
+                | m.alt as u8
+                | (m.ctrl as u8) << 1
+                | (m.shift as u8) << 2
+                | (m.command as u8) << 3
+        });
+        self.modifiers.store(bits, Ordering::Relaxed);
+    }
+
+    fn modifiers(&self) -> Modifiers {
+        let bits = self.modifiers.load(Ordering::Relaxed);
+        Modifiers {
+            alt: bits & 1 != 0,
+            ctrl: bits & 1 << 1 != 0,
+            shift: bits & 1 << 2 != 0,
+            mac_cmd: false,
+            command: bits & 1 << 3 != 0,
+        }
+    }
+
+    /// Takes all queued events. High polling rate mice send hundreds of moves per frame,
+    /// only the latest of consecutive ones is kept, anything in between, like a click, ends the run.
+    pub fn drain(&self) -> Vec<Event> {
+        let mut events = Vec::with_capacity(self.events.len());
+
+        while let Some(event) = self.events.pop() {
+            match (events.last_mut(), event) {
+                (Some(Event::PointerMoved(last)), Event::PointerMoved(pos)) => *last = pos,
+                (_, event) => events.push(event),
+            }
+        }
+
+        events
+    }
+
+    pub fn collect_input(&self) -> RawInput {
+        let mut input = RawInput {
+            modifiers: self.modifiers(),
+            events: self.drain(),
+            screen_rect: self
+                .client_size()
+                .map(|size| Rect::from_min_size(Pos2::ZERO, size)),
             time: Some(Self::get_system_time()),
             pixels_per_point: Some(1.),
             max_texture_side: None,
             predicted_dt: 1. / 60.,
             hovered_files: vec![],
             dropped_files: vec![],
-            has_focus: true,
-        }
+            ..Default::default()
+        };
+        compat::set_focused(&mut input, self.focused.load(Ordering::Relaxed));
+        input
     }
 
     /// Returns time in seconds.
@@ -298,25 +700,315 @@ impl InputCollector {
         (time as f64) / 10_000_000.
     }
 
-    #[inline]
-    pub fn get_screen_size(&self) -> Pos2 {
-        let mut rect = RECT::default();
-        unsafe {
-            GetClientRect(self.hwnd, &mut rect);
+    /// Size of the client area in pixels, `None` if it's empty or the window can't be queried,
+    /// e.g. for some borderless and child windows.
+    pub fn client_size(&self) -> Option<Vec2> {
+        let size = self.client_size.load(Ordering::Relaxed);
+        let (width, height) = (size as u32, (size >> 32) as u32);
+
+        (width > 0 && height > 0).then(|| Vec2::new(width as f32, height as f32))
+    }
+
+    /// Set every frame by the render thread, see [`InputCollector`]'s handling of positions.
+    pub fn set_display_scale(&self, scale: Option<Vec2>) {
+        let bits = scale.map_or(0, |scale| {
+            scale.x.to_bits() as u64 | (scale.y.to_bits() as u64) << 32
+        });
+        self.display_scale.store(bits, Ordering::Relaxed);
+    }
+
+    /// How much the backbuffer is stretched onto the display, `Some` only in exclusive fullscreen
+    /// with a display mode of another size.
+    pub fn display_scale(&self) -> Option<Vec2> {
+        let bits = self.display_scale.load(Ordering::Relaxed);
+
+        (bits != 0).then(|| {
+            Vec2::new(
+                f32::from_bits(bits as u32),
+                f32::from_bits((bits >> 32) as u32),
+            )
+        })
+    }
+
+    /// Set every frame by the render thread, the transform [`transform_input`] moved input with.
+    pub fn set_cursor_transform(&self, transform: Option<ScreenTransform>) {
+        let transform = transform.unwrap_or(ScreenTransform::IDENTITY);
+        self.cursor_transform[0].store(vec2_bits(transform.offset), Ordering::Relaxed);
+        self.cursor_transform[1].store(vec2_bits(transform.scale), Ordering::Relaxed);
+    }
+
+    /// Position of the latest mouse message in pixels of the area the UI is laid out on, moved
+    /// the way the last frame moved its input. `None` until the pointer moved or after it left.
+    pub fn cursor_pos(&self) -> Option<Pos2> {
+        let bits = self.cursor.load(Ordering::Relaxed);
+        if bits == NO_CURSOR {
+            return None;
         }
 
-        Pos2::new(
-            (rect.right - rect.left) as f32,
-            (rect.bottom - rect.top) as f32,
-        )
+        let transform = ScreenTransform {
+            offset: bits_vec2(self.cursor_transform[0].load(Ordering::Relaxed)),
+            scale: bits_vec2(self.cursor_transform[1].load(Ordering::Relaxed)),
+        };
+        Some(transform.apply(bits_vec2(bits).to_pos2()))
     }
 
-    #[inline]
-    pub fn get_screen_rect(&self) -> Rect {
-        Rect {
-            min: Pos2::ZERO,
-            max: self.get_screen_size(),
+    /// Size of the area pointer positions are in, the backbuffer's while they are corrected
+    /// for an exclusive fullscreen display mode, the client area's otherwise.
+    pub fn input_size(&self, buffer: Vec2) -> Option<Vec2> {
+        match self.display_scale() {
+            Some(_) => Some(buffer),
+            None => self.client_size(),
+        }
+    }
+
+    /// Queries the client area again, it's otherwise only updated by `WM_SIZE`.
+    pub fn refresh_client_size(&self) {
+        self.client_size
+            .store(query_client_size(self.hwnd), Ordering::Relaxed);
+    }
+}
+
+// NaN bits that no position has
+const NO_CURSOR: u64 = u64::MAX;
+
+fn vec2_bits(v: Vec2) -> u64 {
+    v.x.to_bits() as u64 | (v.y.to_bits() as u64) << 32
+}
+
+fn bits_vec2(bits: u64) -> Vec2 {
+    Vec2::new(f32::from_bits(bits as u32), f32::from_bits((bits >> 32) as u32))
+}
+
+fn query_client_size(hwnd: HWND) -> u64 {
+    let mut rect = RECT::default();
+    if !unsafe { GetClientRect(hwnd, &mut rect) }.as_bool() {
+        return 0;
+    }
+
+    let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+    width.max(0) as u64 | (height.max(0) as u64) << 32
+}
+
+/// Converts pixel positions into egui's points, applied after [`transform_input`].
+pub fn scale_input(input: &mut RawInput, pixels_per_point: f32) {
+    input.pixels_per_point = Some(pixels_per_point);
+
+    if pixels_per_point == 1. {
+        return;
+    }
+
+    if let Some(rect) = &mut input.screen_rect {
+        *rect = Rect::from_min_max(
+            (rect.min.to_vec2() / pixels_per_point).to_pos2(),
+            (rect.max.to_vec2() / pixels_per_point).to_pos2(),
+        );
+    }
+
+    for event in &mut input.events {
+        match event {
+            Event::PointerMoved(pos) | Event::PointerButton { pos, .. } => {
+                *pos = (pos.to_vec2() / pixels_per_point).to_pos2();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Moves pointer events from window coordinates onto the backbuffer.
+pub fn transform_input(input: &mut RawInput, transform: &ScreenTransform) {
+    for event in &mut input.events {
+        match event {
+            Event::PointerMoved(pos) | Event::PointerButton { pos, .. } => {
+                *pos = transform.apply(*pos);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl InputSource {
+    /// Moves a position from the source's client area into the one of `target`.
+    fn map(&self, pos: Pos2, target: HWND) -> Pos2 {
+        if let Some(transform) = &self.transform {
+            return transform.apply(pos);
+        }
+
+        let mut point = [POINT {
+            x: pos.x as i32,
+            y: pos.y as i32,
+        }];
+        unsafe { MapWindowPoints(self.hwnd, target, &mut point) };
+        Pos2::new(point[0].x as f32, point[0].y as f32)
+    }
+}
+
+impl ClickTracker {
+    fn press(
+        &mut self,
+        button: PointerButton,
+        pos: Pos2,
+        time: u32,
+        max_delay: u32,
+        max_dist: Vec2,
+    ) -> u32 {
+        let chained = self.last.is_some_and(|(last_button, last_pos, last_time)| {
+            let delta = (pos - last_pos).abs();
+
+            last_button == button
+                && time.wrapping_sub(last_time) <= max_delay
+                && delta.x <= max_dist.x / 2.
+                && delta.y <= max_dist.y / 2.
+        });
+
+        self.count = if chained { self.count + 1 } else { 1 };
+        self.last = Some((button, pos, time));
+        self.count
+    }
+}
+
+/// `wparam` bits of mouse messages for the buttons still held down.
+const HELD_BUTTONS: usize =
+    (MK_LBUTTON.0 | MK_RBUTTON.0 | MK_MBUTTON.0 | MK_XBUTTON1.0 | MK_XBUTTON2.0) as usize;
+
+fn get_pressed_button(umsg: u32, wparam: usize) -> Option<PointerButton> {
+    match umsg {
+        WM_LBUTTONDOWN | WM_LBUTTONDBLCLK => Some(PointerButton::Primary),
+        WM_RBUTTONDOWN | WM_RBUTTONDBLCLK => Some(PointerButton::Secondary),
+        WM_MBUTTONDOWN | WM_MBUTTONDBLCLK => Some(PointerButton::Middle),
+        WM_XBUTTONDOWN | WM_XBUTTONDBLCLK => Some(get_x_button(wparam)),
+        _ => None,
+    }
+}
+
+/// Points a wheel notch scrolls per line or character of the system settings.
+const LINE_HEIGHT: f32 = 10.;
+
+// `SPI_GETWHEELSCROLLLINES` result that scrolls a whole page per notch.
+const WHEEL_PAGESCROLL: u32 = u32::MAX;
+
+/// Lines and characters a wheel notch scrolls, as set in the mouse settings of Windows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct WheelSettings {
+    // `None` scrolls a page
+    lines: Option<f32>,
+    chars: f32,
+}
+
+impl WheelSettings {
+    fn query() -> Self {
+        let get = |action, default| {
+            let mut value = default;
+            let ok = unsafe {
+                SystemParametersInfoW(
+                    action,
+                    0,
+                    Some(&mut value as *mut u32 as _),
+                    SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+                )
+            };
+            if ok.as_bool() {
+                value
+            } else {
+                default
+            }
+        };
+
+        let lines = get(SPI_GETWHEELSCROLLLINES, 3);
+        Self {
+            lines: (lines != WHEEL_PAGESCROLL).then_some(lines as f32),
+            chars: get(SPI_GETWHEELSCROLLCHARS, 3) as f32,
+        }
+    }
+
+    /// Scales the scroll events [`decode_message`] made of a wheel message, where a notch is a
+    /// single line. `page` is the number of lines of the client area.
+    fn apply(&self, events: &mut Vec<Event>, wparam: usize, page: f32, modifiers: Modifiers) {
+        let lines = self.lines.unwrap_or(page);
+        // precision touchpads send fractions of a notch, those stay smooth
+        let precise = (wparam >> 16) as i16 % WHEEL_DELTA as i16 != 0;
+
+        let mut wheel = None;
+        for event in events.iter_mut() {
+            if let Event::Scroll(delta) = event {
+                delta.x *= self.chars;
+                delta.y *= lines;
+                // egui 0.22 also wants the raw amount, in lines for notched wheels
+                let raw = if precise { *delta } else { *delta / LINE_HEIGHT };
+                wheel = compat::mouse_wheel(raw, !precise, modifiers);
+            }
         }
+        events.extend(wheel);
+    }
+}
+
+/// Events of a single message, without any state of the collector or the system.
+/// `modifiers` are the ones of key messages, mouse messages carry their own. `WM_CHAR` takes a
+/// whole code point, surrogate pairs have to be joined before. Clipboard shortcuts, hotkeys and
+/// messages that only change state aren't decoded here.
+pub fn decode_message(umsg: u32, wparam: usize, lparam: isize, modifiers: Modifiers) -> Vec<Event> {
+    let button = |button, pressed| Event::PointerButton {
+        pos: get_pos(lparam),
+        button,
+        pressed,
+        modifiers: get_mouse_modifiers(wparam),
+    };
+    let wheel = || {
+        let delta = (wparam >> 16) as i16 as f32 * LINE_HEIGHT / WHEEL_DELTA as f32;
+
+        if wparam & MK_CONTROL.0 as usize != 0 {
+            Event::Zoom(if delta > 0. { 1.5 } else { 0.5 })
+        } else if umsg == WM_MOUSEHWHEEL {
+            Event::Scroll(Vec2::new(delta, 0.))
+        } else {
+            Event::Scroll(Vec2::new(0., delta))
+        }
+    };
+
+    if let Some(pressed) = get_pressed_button(umsg, wparam) {
+        return vec![button(pressed, true)];
+    }
+
+    let event = match umsg {
+        WM_MOUSEMOVE => Event::PointerMoved(get_pos(lparam)),
+        WM_LBUTTONUP => button(PointerButton::Primary, false),
+        WM_RBUTTONUP => button(PointerButton::Secondary, false),
+        WM_MBUTTONUP => button(PointerButton::Middle, false),
+        WM_XBUTTONUP => button(get_x_button(wparam), false),
+        WM_MOUSEWHEEL | WM_MOUSEHWHEEL => wheel(),
+        WM_CHAR => match char::from_u32(wparam as _) {
+            Some(ch) if !ch.is_control() => Event::Text(ch.into()),
+            _ => return vec![],
+        },
+        // Text comes from `WM_CHAR`, key events only identify the key for shortcuts and navigation.
+        WM_KEYDOWN | WM_SYSKEYDOWN | WM_KEYUP | WM_SYSKEYUP => {
+            let Some(key) = get_key(wparam) else {
+                return vec![];
+            };
+            let pressed = matches!(umsg, WM_KEYDOWN | WM_SYSKEYDOWN);
+
+            Event::Key {
+                pressed,
+                modifiers,
+                key,
+                repeat: pressed && is_repeat(lparam),
+            }
+        }
+        _ => return vec![],
+    };
+
+    vec![event]
+}
+
+/// Checks the previous key state bit of a key message, set on auto repeat of held keys.
+fn is_repeat(lparam: isize) -> bool {
+    lparam & (1 << 30) != 0
+}
+
+fn get_x_button(wparam: usize) -> PointerButton {
+    if (wparam >> 16) as u16 & XBUTTON1 != 0 {
+        PointerButton::Extra1
+    } else {
+        PointerButton::Extra2
     }
 }
 
@@ -327,6 +1019,10 @@ fn get_pos(lparam: isize) -> Pos2 {
     Pos2::new(x, y)
 }
 
+fn pack_pos(pos: Pos2) -> isize {
+    (pos.x as i16 as u16 as isize) | (pos.y as i16 as u16 as isize) << 16
+}
+
 fn get_mouse_modifiers(wparam: usize) -> Modifiers {
     Modifiers {
         alt: false,
@@ -337,12 +1033,31 @@ fn get_mouse_modifiers(wparam: usize) -> Modifiers {
     }
 }
 
-fn get_key_modifiers(msg: u32) -> Modifiers {
-    let ctrl = unsafe { GetAsyncKeyState(VK_CONTROL.0 as _) != 0 };
-    let shift = unsafe { GetAsyncKeyState(VK_LSHIFT.0 as _) != 0 };
+/// Maps the usual Windows shortcuts, including the `Insert`/`Delete` ones, to egui's clipboard events.
+fn get_clipboard_event(key: Key, modifiers: Modifiers, clipboard: &Clipboard) -> Option<Event> {
+    match (key, modifiers.ctrl, modifiers.shift) {
+        (Key::C | Key::Insert, true, false) => Some(Event::Copy),
+        (Key::X, true, false) | (Key::Delete, false, true) => Some(Event::Cut),
+        (Key::V, true, false) | (Key::Insert, false, true) => clipboard.0.get().map(Event::Text),
+        _ => None,
+    }
+}
+
+fn get_key_modifiers() -> Modifiers {
+    // High bit is the state at the time of the message, the low one is garbage left by other callers.
+    let pressed = |key: VIRTUAL_KEY| unsafe { GetKeyState(key.0 as _) < 0 };
+    let shift = pressed(VK_SHIFT);
+    let mut ctrl = pressed(VK_CONTROL);
+    let mut alt = pressed(VK_MENU);
+
+    // AltGr arrives as left Ctrl + right Alt, it picks characters on the layout, it isn't a shortcut.
+    if ctrl && pressed(VK_RMENU) {
+        ctrl = false;
+        alt = false;
+    }
 
     Modifiers {
-        alt: msg == WM_SYSKEYDOWN,
+        alt,
         mac_cmd: false,
         command: ctrl,
         shift,
@@ -350,7 +1065,7 @@ fn get_key_modifiers(msg: u32) -> Modifiers {
     }
 }
 
-fn get_key(wparam: usize) -> Option<Key> {
+pub fn get_key(wparam: usize) -> Option<Key> {
     match wparam {
         0x30..=0x39 => unsafe { Some(std::mem::transmute::<_, Key>(wparam as u8 - 0x1F)) },
         0x41..=0x5A => unsafe { Some(std::mem::transmute::<_, Key>(wparam as u8 - 0x26)) },
@@ -388,6 +1103,452 @@ fn test_key_map() {
     assert_eq!(get_key(0x83), Some(Key::F20));
 }
 
-fn get_clipboard_text() -> Option<String> {
-    WindowsClipboardContext.get_contents().ok()
+#[test]
+fn test_mouse_move_coalescing() {
+    let queue = Arc::new(InputQueue::new(HWND(0)));
+    let mut input = InputCollector::new(queue.clone());
+    let pos = |x: isize, y: isize| x | y << 16;
+
+    input.process(WM_MOUSEMOVE, 0, pos(1, 1));
+    input.process(WM_MOUSEMOVE, 0, pos(2, 2));
+    input.process(WM_LBUTTONDOWN, 0, pos(2, 2));
+    input.process(WM_MOUSEMOVE, 0, pos(3, 3));
+    input.process(WM_MOUSEMOVE, 0, pos(4, 4));
+
+    let moves = queue
+        .drain()
+        .into_iter()
+        .map(|event| match event {
+            Event::PointerMoved(pos) => Some(pos),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        moves,
+        [Some(Pos2::new(2., 2.)), None, Some(Pos2::new(4., 4.))]
+    );
+}
+
+#[test]
+fn test_clipboard_shortcuts() {
+    let ctrl = Modifiers::CTRL;
+    let shift = Modifiers::SHIFT;
+    let none = Clipboard(Arc::new(crate::NoClipboard));
+
+    assert_eq!(get_clipboard_event(Key::C, ctrl, &none), Some(Event::Copy));
+    assert_eq!(
+        get_clipboard_event(Key::Insert, ctrl, &none),
+        Some(Event::Copy)
+    );
+    assert_eq!(get_clipboard_event(Key::X, ctrl, &none), Some(Event::Cut));
+    assert_eq!(
+        get_clipboard_event(Key::Delete, shift, &none),
+        Some(Event::Cut)
+    );
+
+    assert_eq!(get_clipboard_event(Key::C, Modifiers::NONE, &none), None);
+    assert_eq!(get_clipboard_event(Key::C, ctrl | shift, &none), None);
+    assert_eq!(
+        get_clipboard_event(Key::Delete, Modifiers::NONE, &none),
+        None
+    );
+}
+
+#[test]
+fn test_surrogate_pairs() {
+    let queue = Arc::new(InputQueue::new(HWND(0)));
+    let mut input = InputCollector::new(queue.clone());
+
+    for unit in "a😀".encode_utf16() {
+        input.process(WM_CHAR, unit as _, 0);
+    }
+    // lone surrogates and control characters are dropped
+    input.process(WM_CHAR, 0xDC00, 0);
+    input.process(WM_CHAR, 0xD83D, 0);
+    input.process(WM_CHAR, 0x08, 0);
+    input.process(WM_CHAR, 'b' as _, 0);
+
+    assert_eq!(
+        queue.drain(),
+        [
+            Event::Text("a".into()),
+            Event::Text("😀".into()),
+            Event::Text("b".into())
+        ]
+    );
+}
+
+#[test]
+fn test_click_count() {
+    let mut clicks = ClickTracker::default();
+    let dist = Vec2::splat(4.);
+    let press = |clicks: &mut ClickTracker, button, x, time| {
+        clicks.press(button, Pos2::new(x, 0.), time, 500, dist)
+    };
+
+    assert_eq!(press(&mut clicks, PointerButton::Primary, 0., 1000), 1);
+    assert_eq!(press(&mut clicks, PointerButton::Primary, 1., 1400), 2);
+    assert_eq!(press(&mut clicks, PointerButton::Primary, 2., 1800), 3);
+    // too slow
+    assert_eq!(press(&mut clicks, PointerButton::Primary, 2., 2400), 1);
+    // too far
+    assert_eq!(press(&mut clicks, PointerButton::Primary, 5., 2500), 1);
+    // another button
+    assert_eq!(press(&mut clicks, PointerButton::Secondary, 5., 2600), 1);
+    // message time wraps around after ~50 days
+    press(&mut clicks, PointerButton::Primary, 0., u32::MAX - 100);
+    assert_eq!(press(&mut clicks, PointerButton::Primary, 0., 100), 2);
+}
+
+#[test]
+fn test_focus() {
+    let queue = Arc::new(InputQueue::new(HWND(0)));
+    let mut input = InputCollector::new(queue.clone());
+
+    input.process(WM_KILLFOCUS, 0, 0);
+    input.process(WM_ACTIVATEAPP, 0, 0);
+    assert!(!queue.focused.load(Ordering::Relaxed));
+    assert_eq!(queue.drain(), [Event::PointerGone]);
+
+    input.process(WM_ACTIVATEAPP, 1, 0);
+    assert!(queue.focused.load(Ordering::Relaxed));
+    assert!(queue.drain().is_empty());
+}
+
+#[test]
+fn test_client_size() {
+    let queue = Arc::new(InputQueue::new(HWND(0)));
+    let mut input = InputCollector::new(queue.clone());
+
+    input.process(WM_SIZE, 0, 1280 | 720 << 16);
+    assert_eq!(queue.client_size(), Some(Vec2::new(1280., 720.)));
+
+    // minimized
+    input.process(WM_SIZE, 1, 0);
+    assert_eq!(queue.client_size(), None);
+}
+
+#[test]
+fn test_input_sources() {
+    let queue = Arc::new(InputQueue::new(HWND(1)));
+    let mut input = InputCollector::new(queue.clone());
+    let child = HWND(2);
+    let transform = ScreenTransform {
+        offset: Vec2::new(-100., -50.),
+        scale: Vec2::splat(1.),
+    };
+
+    input.process_from(child, WM_MOUSEMOVE, 0, pack_pos(Pos2::new(1., 1.)));
+    assert!(queue.drain().is_empty());
+
+    input.add_source(child, Some(transform));
+    input.process_from(child, WM_MOUSEMOVE, 0, pack_pos(Pos2::new(1., 1.)));
+    input.process_from(child, WM_SIZE, 0, 10 | 10 << 16);
+    assert_eq!(
+        queue.drain(),
+        vec![Event::PointerMoved(Pos2::new(101., 51.))]
+    );
+    assert_ne!(queue.client_size(), Some(Vec2::new(10., 10.)));
+
+    assert!(input.remove_source(child));
+    assert_eq!(get_pos(pack_pos(Pos2::new(-3., 7.))), Pos2::new(-3., 7.));
+}
+
+#[test]
+fn test_display_scale() {
+    let queue = Arc::new(InputQueue::new(HWND(0)));
+    let mut input = InputCollector::new(queue.clone());
+
+    queue.set_display_scale(Some(Vec2::new(0.5, 0.75)));
+    input.process(WM_MOUSEMOVE, 0, pack_pos(Pos2::new(200., 100.)));
+    assert_eq!(
+        queue.drain(),
+        vec![Event::PointerMoved(Pos2::new(100., 75.))]
+    );
+    assert_eq!(
+        queue.input_size(Vec2::new(960., 540.)),
+        Some(Vec2::new(960., 540.))
+    );
+
+    queue.set_display_scale(None);
+    assert_eq!(queue.display_scale(), None);
+}
+
+#[test]
+fn test_cursor_pos() {
+    let queue = Arc::new(InputQueue::new(HWND(0)));
+    let mut input = InputCollector::new(queue.clone());
+    assert_eq!(queue.cursor_pos(), None);
+
+    input.process(WM_MOUSEMOVE, 0, pack_pos(Pos2::new(200., 100.)));
+    assert_eq!(queue.cursor_pos(), Some(Pos2::new(200., 100.)));
+
+    // fresher than the frame, but moved like its input
+    queue.set_cursor_transform(Some(ScreenTransform::IDENTITY.shifted(Vec2::new(50., 0.))));
+    input.process(WM_MOUSEMOVE, 0, pack_pos(Pos2::new(210., 100.)));
+    assert_eq!(queue.cursor_pos(), Some(Pos2::new(160., 100.)));
+
+    input.process(WM_KILLFOCUS, 0, 0);
+    assert_eq!(queue.cursor_pos(), None);
+}
+
+#[test]
+fn test_wheel_settings() {
+    let scroll = |settings: WheelSettings, umsg, delta: i16, page| {
+        let wparam = (delta as u16 as usize) << 16;
+        let mut events = decode_message(umsg, wparam, 0, Modifiers::NONE);
+        settings.apply(&mut events, wparam, page, Modifiers::NONE);
+        match events[0] {
+            Event::Scroll(delta) => delta,
+            _ => panic!("Not a scroll event"),
+        }
+    };
+    let settings = WheelSettings {
+        lines: Some(3.),
+        chars: 2.,
+    };
+
+    assert_eq!(
+        scroll(settings, WM_MOUSEWHEEL, -120, 0.),
+        Vec2::new(0., -30.)
+    );
+    assert_eq!(
+        scroll(settings, WM_MOUSEHWHEEL, 240, 0.),
+        Vec2::new(40., 0.)
+    );
+    // a precision touchpad's fraction of a notch
+    assert_eq!(scroll(settings, WM_MOUSEWHEEL, 60, 0.), Vec2::new(0., 15.));
+
+    let pages = WheelSettings {
+        lines: None,
+        ..settings
+    };
+    assert_eq!(scroll(pages, WM_MOUSEWHEEL, 120, 50.), Vec2::new(0., 500.));
+}
+
+#[test]
+fn test_decode_message() {
+    let pos = pack_pos(Pos2::new(10., -5.));
+    let button = |button, pressed, modifiers| Event::PointerButton {
+        pos: Pos2::new(10., -5.),
+        button,
+        pressed,
+        modifiers,
+    };
+    let decode = |umsg, wparam, lparam| decode_message(umsg, wparam, lparam, Modifiers::NONE);
+
+    // mouse
+    assert_eq!(
+        decode(WM_MOUSEMOVE, 0, pos),
+        [Event::PointerMoved(Pos2::new(10., -5.))]
+    );
+    assert_eq!(
+        decode(WM_LBUTTONDOWN, MK_SHIFT.0 as _, pos),
+        [button(PointerButton::Primary, true, Modifiers::SHIFT)]
+    );
+    assert_eq!(
+        decode(WM_RBUTTONDBLCLK, 0, pos),
+        [button(PointerButton::Secondary, true, Modifiers::NONE)]
+    );
+    assert_eq!(
+        decode(WM_MBUTTONUP, MK_CONTROL.0 as _, pos),
+        [button(
+            PointerButton::Middle,
+            false,
+            Modifiers::COMMAND | Modifiers::CTRL
+        )]
+    );
+    assert_eq!(
+        decode(WM_XBUTTONDOWN, (XBUTTON1 as usize) << 16, pos),
+        [button(PointerButton::Extra1, true, Modifiers::NONE)]
+    );
+    assert_eq!(
+        decode(WM_XBUTTONUP, 2 << 16, pos),
+        [button(PointerButton::Extra2, false, Modifiers::NONE)]
+    );
+
+    // wheel, one notch is 10 points
+    let notches = |n: i16| ((n * WHEEL_DELTA as i16) as u16 as usize) << 16;
+    assert_eq!(
+        decode(WM_MOUSEWHEEL, notches(-2), pos),
+        [Event::Scroll(Vec2::new(0., -20.))]
+    );
+    assert_eq!(
+        decode(WM_MOUSEHWHEEL, notches(1), pos),
+        [Event::Scroll(Vec2::new(10., 0.))]
+    );
+    assert_eq!(
+        decode(WM_MOUSEWHEEL, notches(1) | MK_CONTROL.0 as usize, pos),
+        [Event::Zoom(1.5)]
+    );
+
+    // keys
+    assert_eq!(
+        decode_message(WM_KEYDOWN, VK_RETURN.0 as _, 0, Modifiers::CTRL),
+        [Event::Key {
+            key: Key::Enter,
+            pressed: true,
+            repeat: false,
+            modifiers: Modifiers::CTRL
+        }]
+    );
+    assert_eq!(
+        decode(WM_SYSKEYUP, 0x41, 0),
+        [Event::Key {
+            key: Key::A,
+            pressed: false,
+            repeat: false,
+            modifiers: Modifiers::NONE
+        }]
+    );
+    assert!(decode(WM_KEYDOWN, VK_SHIFT.0 as _, 0).is_empty());
+
+    // auto repeat, only the previous key state bit counts and not the scan code
+    let key = |pressed, repeat| Event::Key {
+        key: Key::ArrowLeft,
+        pressed,
+        repeat,
+        modifiers: Modifiers::NONE,
+    };
+    assert_eq!(
+        decode(WM_KEYDOWN, VK_LEFT.0 as _, 0x014B_0001),
+        [key(true, false)]
+    );
+    assert_eq!(
+        decode(WM_KEYDOWN, VK_LEFT.0 as _, 0x414B_0001),
+        [key(true, true)]
+    );
+    assert_eq!(
+        decode(WM_KEYUP, VK_LEFT.0 as _, 0xC14B_0001_u32 as isize),
+        [key(false, false)]
+    );
+
+    // text
+    assert_eq!(decode(WM_CHAR, 'ä' as _, 0), [Event::Text("ä".into())]);
+    assert_eq!(decode(WM_CHAR, '😀' as _, 0), [Event::Text("😀".into())]);
+    assert!(decode(WM_CHAR, 0x08, 0).is_empty());
+    assert!(decode(WM_SIZE, 0, pos).is_empty());
+}
+
+#[test]
+fn test_mouse_capture() {
+    let mut input = InputCollector::new(Arc::new(InputQueue::new(HWND(0))));
+
+    // the UI doesn't want the click
+    assert_eq!(input.update_capture(None, WM_LBUTTONDOWN, 0, false), None);
+
+    input.captured = Some(HWND(0));
+    let held = MK_RBUTTON.0 as usize;
+    assert_eq!(input.update_capture(None, WM_LBUTTONUP, held, true), None);
+    assert_eq!(
+        input.update_capture(None, WM_RBUTTONUP, 0, false),
+        Some(CaptureRequest::Release)
+    );
+    assert_eq!(input.update_capture(None, WM_RBUTTONUP, 0, false), None);
+
+    input.captured = Some(HWND(0));
+    input.update_capture(None, WM_CAPTURECHANGED, 0, false);
+    assert_eq!(input.captured, None);
+}
+
+#[test]
+fn test_key_passthrough() {
+    let mut input = InputCollector::new(Arc::new(InputQueue::new(HWND(0))));
+    let key = |vk: VIRTUAL_KEY| vk.0 as usize;
+
+    assert!(!input.passes_through(WM_KEYDOWN, key(VK_SPACE)));
+
+    input.key_passthrough = KeyPassthrough::Allow(vec![VK_SPACE]);
+    assert!(input.passes_through(WM_KEYDOWN, key(VK_SPACE)));
+    assert!(input.passes_through(WM_CHAR, ' ' as usize));
+    assert!(!input.passes_through(WM_KEYDOWN, key(VK_RETURN)));
+    assert!(!input.passes_through(WM_CHAR, '\r' as usize));
+    assert!(!input.passes_through(WM_LBUTTONDOWN, 0));
+
+    input.key_passthrough = KeyPassthrough::Deny(vec![VK_ESCAPE]);
+    assert!(!input.passes_through(WM_KEYUP, key(VK_ESCAPE)));
+    assert!(input.passes_through(WM_SYSKEYDOWN, key(VK_MENU)));
+}
+
+/// Messages the collector handles and arbitrary ones, with parameters around the ranges it
+/// looks at: virtual keys, UTF-16 surrogates and anything else.
+#[cfg(test)]
+fn arbitrary_message() -> impl proptest::strategy::Strategy<Value = (u32, usize, isize)> {
+    use proptest::prelude::*;
+
+    let umsg = prop_oneof![
+        4 => proptest::sample::select(vec![
+            WM_MOUSEMOVE, WM_LBUTTONDOWN, WM_LBUTTONDBLCLK, WM_LBUTTONUP, WM_RBUTTONDOWN,
+            WM_RBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_XBUTTONDOWN, WM_XBUTTONDBLCLK,
+            WM_XBUTTONUP, WM_MOUSEWHEEL, WM_MOUSEHWHEEL, WM_CHAR, WM_DEADCHAR, WM_SYSDEADCHAR,
+            WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_SETCURSOR, WM_ACTIVATEAPP,
+            WM_SETFOCUS, WM_KILLFOCUS, WM_DPICHANGED, WM_SETTINGCHANGE, WM_SIZE,
+            WM_CAPTURECHANGED,
+        ]),
+        1 => any::<u32>(),
+    ];
+    let wparam = prop_oneof![0..0x100usize, 0xD800..0xE000usize, any::<usize>()];
+
+    (umsg, wparam, any::<isize>())
+}
+
+// Window messages come from the game and anything else running in its process, no sequence of
+// them may panic, grow the queues or produce events egui can't handle.
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_arbitrary_messages(messages in proptest::collection::vec(arbitrary_message(), 0..256)) {
+        let queue = Arc::new(InputQueue::new(HWND(0)));
+        let mut input = InputCollector::new(queue.clone());
+        input.configure(
+            &Config::default()
+                .toggle_key(Some(Key::Insert))
+                .key_passthrough(KeyPassthrough::Allow(vec![VK_SPACE]))
+                .clipboard(crate::NoClipboard),
+        );
+        input.add_source(
+            HWND(1),
+            Some(ScreenTransform {
+                offset: Vec2::new(-100., 50.),
+                scale: Vec2::splat(2.),
+            }),
+        );
+
+        for (i, &(umsg, wparam, lparam)) in messages.iter().enumerate() {
+            // every other message comes through the second window
+            let hwnd = HWND(i as isize & 1);
+            input.passes_through(umsg, wparam);
+            input.process_from(hwnd, umsg, wparam, lparam);
+            input.update_capture(Some(hwnd), umsg, wparam, true);
+        }
+
+        proptest::prop_assert!(queue.events.len() <= InputQueue::CAPACITY);
+        proptest::prop_assert!(queue.hotkeys.len() <= InputQueue::HOTKEY_CAPACITY);
+        let pixels_per_point = queue.pixels_per_point();
+        proptest::prop_assert!(pixels_per_point.is_finite() && pixels_per_point > 0.);
+
+        let mut raw = queue.collect_input();
+        scale_input(&mut raw, pixels_per_point);
+        for pair in raw.events.windows(2) {
+            let moves = matches!(pair, [Event::PointerMoved(_), Event::PointerMoved(_)]);
+            proptest::prop_assert!(!moves, "consecutive moves weren't coalesced");
+        }
+        for event in &raw.events {
+            match event {
+                Event::PointerMoved(pos) | Event::PointerButton { pos, .. } => {
+                    proptest::prop_assert!(pos.x.is_finite() && pos.y.is_finite());
+                }
+                Event::Scroll(delta) => {
+                    proptest::prop_assert!(delta.x.is_finite() && delta.y.is_finite());
+                }
+                Event::Zoom(factor) => proptest::prop_assert!(*factor > 0.),
+                Event::Text(text) => {
+                    proptest::prop_assert!(!text.is_empty());
+                    proptest::prop_assert!(!text.chars().any(char::is_control));
+                }
+                _ => {}
+            }
+        }
+    }
 }
@@ -0,0 +1,198 @@
+use crate::error::Error;
+use egui::{
+    epaint::{PaintCallback, Shape},
+    Context, Id, Order,
+};
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+};
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11DeviceContext};
+
+/// Handle of a layer added with [`crate::DirectX11App::add_layer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LayerId(u64);
+
+#[allow(clippy::type_complexity)]
+struct Layer<T> {
+    id: LayerId,
+    name: String,
+    ui: Box<dyn FnMut(&Context, &mut T) + 'static>,
+}
+
+/// UI closures contributed at runtime, they run after the main one in the order they were added.
+pub struct Layers<T> {
+    layers: Vec<Layer<T>>,
+    next_id: u64,
+}
+
+impl<T> Default for Layers<T> {
+    fn default() -> Self {
+        Self {
+            layers: vec![],
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> Layers<T> {
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+    ) -> LayerId {
+        let id = LayerId(self.next_id);
+        self.next_id += 1;
+
+        let name = name.into();
+        log_msg!(debug, "Adding layer {:?} as {:?}", name, id);
+
+        self.layers.push(Layer {
+            id,
+            name,
+            ui: Box::new(ui),
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: LayerId) -> bool {
+        let len = self.layers.len();
+        self.layers.retain(|layer| layer.id != id);
+        self.layers.len() != len
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = (LayerId, &str)> {
+        self.layers
+            .iter()
+            .map(|layer| (layer.id, layer.name.as_str()))
+    }
+
+    pub fn run(&mut self, ctx: &Context, state: &mut T) {
+        for layer in &mut self.layers {
+            (layer.ui)(ctx, state);
+        }
+    }
+}
+
+/// Handle of a draw hook added with [`crate::DirectX11App::add_draw_hook`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DrawHookId(u64);
+
+#[allow(clippy::type_complexity)]
+struct DrawHook {
+    id: DrawHookId,
+    order: Order,
+    draw: Box<dyn FnMut(&ID3D11Device, &ID3D11DeviceContext) + 'static>,
+}
+
+/// D3D11 drawing placed between egui's layers. Every hook paints a marker into a layer of its
+/// order, the renderer runs the hook where the marker ends up among the primitives.
+#[derive(Default)]
+pub struct DrawHooks {
+    hooks: Vec<DrawHook>,
+    next_id: u64,
+}
+
+impl DrawHooks {
+    pub fn add(
+        &mut self,
+        order: Order,
+        draw: impl FnMut(&ID3D11Device, &ID3D11DeviceContext) + 'static,
+    ) -> DrawHookId {
+        let id = DrawHookId(self.next_id);
+        self.next_id += 1;
+
+        log_msg!(debug, "Adding draw hook {:?} at {:?}", id, order);
+
+        self.hooks.push(DrawHook {
+            id,
+            order,
+            draw: Box::new(draw),
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: DrawHookId) -> bool {
+        let len = self.hooks.len();
+        self.hooks.retain(|hook| hook.id != id);
+        self.hooks.len() != len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Paints the markers, called from within the frame after every UI closure ran.
+    pub fn paint_markers(&self, ctx: &Context) {
+        for hook in &self.hooks {
+            let layer = egui::LayerId::new(hook.order, Id::new(("egui_d3d11_draw_hook", hook.id)));
+            ctx.layer_painter(layer).add(Shape::Callback(PaintCallback {
+                rect: ctx.screen_rect(),
+                callback: Arc::new(hook.id),
+            }));
+        }
+    }
+
+    /// Runs the hook a marker belongs to, does nothing if it was removed meanwhile.
+    pub fn run(
+        &mut self,
+        id: DrawHookId,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+    ) -> Result<(), Error> {
+        match self.hooks.iter_mut().find(|hook| hook.id == id) {
+            Some(hook) => {
+                catch_unwind(AssertUnwindSafe(|| (hook.draw)(dev, ctx))).map_err(Error::from_panic)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// The hook a primitive stands for, `None` for meshes and callbacks of other kinds.
+pub fn draw_hook(callback: &PaintCallback) -> Option<DrawHookId> {
+    callback.callback.downcast_ref::<DrawHookId>().copied()
+}
+
+#[test]
+fn test_layers() {
+    let mut layers = Layers::<Vec<u8>>::default();
+    let first = layers.add("first", |_, state| state.push(1));
+    layers.add("second", |_, state| state.push(2));
+
+    let mut state = vec![];
+    layers.run(&Context::default(), &mut state);
+    assert_eq!(state, [1, 2]);
+
+    assert!(layers.remove(first));
+    assert!(!layers.remove(first));
+
+    state.clear();
+    layers.run(&Context::default(), &mut state);
+    assert_eq!(state, [2]);
+    assert_eq!(
+        layers.names().map(|(_, name)| name).collect::<Vec<_>>(),
+        ["second"]
+    );
+}
+
+#[test]
+fn test_draw_hook_markers() {
+    let mut hooks = DrawHooks::default();
+    let first = hooks.add(Order::Background, |_, _| {});
+    let second = hooks.add(Order::Foreground, |_, _| {});
+    assert!(hooks.remove(first));
+    assert!(!hooks.remove(first));
+
+    let ctx = Context::default();
+    let output = ctx.run(Default::default(), |ctx| hooks.paint_markers(ctx));
+    let markers = output
+        .shapes
+        .iter()
+        .filter_map(|shape| match &shape.1 {
+            Shape::Callback(callback) => draw_hook(callback),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(markers, [second]);
+}
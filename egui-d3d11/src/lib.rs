@@ -1,10 +1,28 @@
+/// Forwards to the `log` crate when feature `log` is present, compiles to nothing otherwise.
+macro_rules! log_msg {
+    ($level:ident, $($t:tt)*) => {
+        #[cfg(feature = "log")]
+        ::log::$level!(target: "egui_d3d11", $($t)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($t)*);
+    };
+}
+
+/// Reports the rest of the scope as a span when feature `profiling` is present.
+macro_rules! profile_span {
+    ($name:expr) => {
+        #[cfg(feature = "profiling")]
+        let _span = crate::profiling::Span::enter(concat!("egui_d3d11::", $name));
+    };
+}
+
 /// This macros allows to hide panicing messages in output binary when feature `no-msgs` is present.
 macro_rules! expect {
     ($val:expr, $msg:expr) => {
         if cfg!(feature = "no-msgs") {
             $val.unwrap()
         } else {
-            $val.expect($msg)
+            crate::ExpectLogged::expect_logged($val, $msg)
         }
     };
 }
@@ -14,6 +32,7 @@ macro_rules! panic_msg {
         if cfg!(feature = "no-msgs") {
             unimplemented!()
         } else {
+            log_msg!(error, $($t)*);
             panic!($($t)*)
         }
     };
@@ -26,19 +45,161 @@ macro_rules! pc_str {
     };
 }
 
-#[cfg(all(feature = "parking-lot", feature = "spin-lock"))]
-compile_error!("Only one of features `parking-lot`, `spin-lock` must be enabled.");
+#[cfg(all(feature = "egui-0_21", feature = "egui-0_22"))]
+compile_error!("Only one of features `egui-0_21`, `egui-0_22` must be enabled.");
+
+#[cfg(not(any(feature = "egui-0_21", feature = "egui-0_22")))]
+compile_error!("One of the features `egui-0_21`, `egui-0_22` must be enabled.");
+
+#[cfg(all(feature = "egui-0_22", feature = "accesskit"))]
+compile_error!("Feature `accesskit` is only supported with `egui-0_21`.");
 
-#[cfg(not(any(feature = "parking-lot", feature = "spin-lock")))]
-compile_error!("One of the features `parking-lot`, `spin-lock` must be enabled.");
+/// The egui this crate is built against, picked with the `egui-*` features.
+#[cfg(feature = "egui-0_22")]
+pub extern crate egui_0_22 as egui;
+
+/// The egui this crate is built against, picked with the `egui-*` features.
+#[cfg(feature = "egui-0_21")]
+pub use egui;
 
 mod app;
 pub use app::*;
 
+mod config;
+pub use config::*;
+
+mod capabilities;
+pub use capabilities::Capabilities;
+
+mod capture;
+pub use capture::CapturedFrame;
+
+#[cfg(feature = "debug-ui")]
+pub mod debug_ui;
+
+mod clipboard;
+pub use clipboard::{ClipboardBackend, LocalClipboard, NoClipboard, WindowsClipboard};
+
+#[cfg(feature = "cursor-hooks")]
+pub mod cursor;
+
+mod error;
+pub use error::{Error, InitError};
+
+pub mod entry;
+
+mod hdr;
+pub use hdr::ColorSpace;
+
+mod hotkey;
+pub use hotkey::HotkeyId;
+
+#[cfg(feature = "image-loaders")]
+pub mod images;
+
+mod layer;
+pub use layer::{DrawHookId, LayerId};
+
+mod lock;
+pub use lock::{DefaultRawMutex, RawMutex, StdRawMutex};
+
+#[cfg(feature = "profiling")]
+mod profiling;
+#[cfg(feature = "profiling")]
+pub use profiling::{set_span_callback, SpanCallback};
+
+#[cfg(feature = "replay")]
+pub mod replay;
+
+#[cfg(feature = "settings")]
+pub mod settings;
+
+#[cfg(feature = "accesskit")]
+mod accesskit;
 mod backup;
+mod compat;
+mod constants;
+#[cfg(feature = "d3d12")]
+mod d3d12;
+#[cfg(feature = "dcomp")]
+mod dcomp;
+mod dds;
+mod depth;
+mod format;
+#[cfg(feature = "frame-debug")]
+mod frame_debug;
+#[cfg(feature = "input-hooks")]
+mod hooks;
 mod input;
 mod mesh;
+mod mirror;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "persistence")]
+mod persistence;
+mod pipeline;
+mod platform;
+mod raw;
+mod scaled;
+mod screenshot;
 mod shader;
+mod target;
 mod texture;
+mod timings;
+#[cfg(any(test, feature = "bench"))]
+mod warp;
+mod watchdog;
+
+#[cfg(feature = "frame-debug")]
+pub use frame_debug::{DrawCall, FrameDebug};
+#[cfg(feature = "input-hooks")]
+pub use hooks::InputHooks;
+pub use input::{InputCategory, InputMessage, InputResult};
+pub use mesh::{BufferStats, GpuMesh, GpuVertex};
+pub use texture::{FontAtlasStats, GpuMemoryUsage, TextureAllocator, TextureInfo};
+pub use timings::{FrameStage, FrameTimings};
+
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::{
+        mesh::{BufferPool, GpuMesh},
+        texture::TextureAllocator,
+        warp::WarpTarget,
+    };
 
-pub use input::InputResult;
+    /// The allocator only processes deltas for the app itself.
+    pub fn process_deltas(
+        alloc: &mut TextureAllocator,
+        dev: &windows::Win32::Graphics::Direct3D11::ID3D11Device,
+        ctx: &windows::Win32::Graphics::Direct3D11::ID3D11DeviceContext,
+        delta: egui::TexturesDelta,
+    ) -> Result<(), crate::Error> {
+        alloc.process_deltas(dev, ctx, delta)
+    }
+}
+
+/// Same as `expect` but reports the failure to the logger before panicking.
+trait ExpectLogged<T> {
+    fn expect_logged(self, msg: &str) -> T;
+}
+
+impl<T> ExpectLogged<T> for Option<T> {
+    #[track_caller]
+    fn expect_logged(self, msg: &str) -> T {
+        match self {
+            Some(val) => val,
+            None => panic_msg!("{}", msg),
+        }
+    }
+}
+
+impl<T, E: std::fmt::Debug> ExpectLogged<T> for Result<T, E> {
+    #[track_caller]
+    fn expect_logged(self, msg: &str) -> T {
+        match self {
+            Ok(val) => val,
+            Err(e) => panic_msg!("{}: {:?}", msg, e),
+        }
+    }
+}
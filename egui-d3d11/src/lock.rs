@@ -0,0 +1,91 @@
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+pub use lock_api::RawMutex;
+
+/// Raw mutex of [`crate::DirectX11App`] when no other one is picked, `parking_lot`'s or `spin`'s
+/// with their features, [`StdRawMutex`] otherwise.
+#[cfg(feature = "parking-lot")]
+pub type DefaultRawMutex = parking_lot::RawMutex;
+/// `spin`'s mutex, picked by the `spin-lock` feature.
+#[cfg(all(feature = "spin-lock", not(feature = "parking-lot")))]
+pub type DefaultRawMutex = spin::mutex::Mutex<()>;
+/// Used without a lock feature.
+#[cfg(not(any(feature = "parking-lot", feature = "spin-lock")))]
+pub type DefaultRawMutex = StdRawMutex;
+
+/// [`RawMutex`] built on `std::sync`, without dependencies and without spinning.
+/// Waiting threads sleep on a condition variable until the holder unlocks.
+pub struct StdRawMutex {
+    locked: Mutex<bool>,
+    unlocked: Condvar,
+}
+
+impl StdRawMutex {
+    // Only held for a few instructions, a panic can't happen while it is.
+    fn locked(&self) -> MutexGuard<'_, bool> {
+        self.locked.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+unsafe impl RawMutex for StdRawMutex {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        locked: Mutex::new(false),
+        unlocked: Condvar::new(),
+    };
+
+    type GuardMarker = lock_api::GuardSend;
+
+    fn lock(&self) {
+        let mut locked = self.locked();
+        while *locked {
+            locked = self
+                .unlocked
+                .wait(locked)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *locked = true;
+    }
+
+    fn try_lock(&self) -> bool {
+        let mut locked = self.locked();
+        !std::mem::replace(&mut *locked, true)
+    }
+
+    unsafe fn unlock(&self) {
+        *self.locked() = false;
+        self.unlocked.notify_one();
+    }
+
+    fn is_locked(&self) -> bool {
+        *self.locked()
+    }
+}
+
+#[test]
+fn test_std_raw_mutex() {
+    use std::{sync::Arc, thread};
+
+    let mutex = Arc::new(lock_api::Mutex::<StdRawMutex, u32>::new(0));
+    {
+        let _guard = mutex.lock();
+        assert!(mutex.try_lock().is_none());
+    }
+    assert!(!mutex.is_locked());
+
+    let threads = (0..4)
+        .map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    *mutex.lock() += 1;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(*mutex.lock(), 4000);
+}
@@ -1,108 +1,608 @@
-use egui::{epaint::Vertex, Mesh, Pos2, Rect, Rgba, TextureId};
-use std::mem::size_of;
-use windows::Win32::Graphics::Direct3D11::{
-    ID3D11Buffer, ID3D11Device, D3D11_BIND_INDEX_BUFFER, D3D11_BIND_VERTEX_BUFFER,
-    D3D11_BUFFER_DESC, D3D11_SUBRESOURCE_DATA, D3D11_USAGE_DEFAULT,
+use crate::{
+    compat,
+    error::{Error, ErrorContext},
+    layer::DrawHookId,
 };
+use egui::{
+    epaint::{ClippedPrimitive, Vertex},
+    Mesh, Rect, TextureId, Vec2,
+};
+use std::{
+    mem::{size_of, size_of_val},
+    ptr::copy_nonoverlapping,
+};
+use windows::Win32::{
+    Foundation::RECT,
+    Graphics::{
+        Direct3D11::{
+            ID3D11Buffer, ID3D11Device, ID3D11DeviceContext, D3D11_BIND_FLAG,
+            D3D11_BIND_INDEX_BUFFER, D3D11_BIND_VERTEX_BUFFER, D3D11_BUFFER_DESC,
+            D3D11_CPU_ACCESS_WRITE, D3D11_MAP_WRITE_DISCARD, D3D11_USAGE_DYNAMIC,
+        },
+        Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_R16_UINT, DXGI_FORMAT_R32_UINT},
+    },
+};
+
+/// Index data of a mesh, 16 bit indices are used whenever all vertices can be addressed with them.
+/// egui's indices are kept as they are and only narrowed while they are uploaded.
+#[derive(Clone)]
+pub struct MeshIndices {
+    indices: Vec<u32>,
+    wide: bool,
+}
+
+impl MeshIndices {
+    pub fn new(indices: Vec<u32>, vertex_count: usize) -> Self {
+        Self {
+            indices,
+            wide: vertex_count > u16::MAX as usize + 1,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    #[inline]
+    pub fn is_wide(&self) -> bool {
+        self.wide
+    }
+
+    #[inline]
+    pub fn format(&self) -> DXGI_FORMAT {
+        if self.wide {
+            DXGI_FORMAT_R32_UINT
+        } else {
+            DXGI_FORMAT_R16_UINT
+        }
+    }
+}
 
+/// Triangles in physical pixels, as they are uploaded. Hosts can draw their own with
+/// [`crate::DirectX11App::paint_meshes`].
+#[derive(Clone)]
 pub struct GpuMesh {
-    pub indices: Vec<u32>,
+    pub(crate) indices: MeshIndices,
     pub vertices: Vec<GpuVertex>,
+    /// Scissor rect in pixels, clamped to the screen when drawn.
     pub clip: Rect,
     pub texture_id: TextureId,
 }
 
 impl GpuMesh {
-    pub fn from_mesh((w, h): (f32, f32), mesh: Mesh, scissors: Rect) -> Option<Self> {
+    /// Mesh of `vertices` already in pixels, `None` if the indices don't form whole triangles
+    /// or point past the vertices. Untextured meshes use [`TextureId::default`] with
+    /// [`egui::epaint::WHITE_UV`], e.g. what [`Mesh::add_colored_rect`] builds, which can be
+    /// converted with [`Self::from_mesh`] as well.
+    pub fn new(
+        vertices: Vec<GpuVertex>,
+        indices: Vec<u32>,
+        clip: Rect,
+        texture_id: TextureId,
+    ) -> Option<Self> {
+        let in_bounds = indices.iter().all(|&index| (index as usize) < vertices.len());
+        if indices.is_empty() || indices.len() % 3 != 0 || !in_bounds {
+            return None;
+        }
+
+        Some(Self {
+            indices: MeshIndices::new(indices, vertices.len()),
+            vertices,
+            clip,
+            texture_id,
+        })
+    }
+
+    /// Converts positions and the clip rect from points into physical pixels, the same
+    /// `pixels_per_point` egui tessellated with keeps hairlines on pixel boundaries.
+    pub fn from_mesh(mut mesh: Mesh, scissors: Rect, pixels_per_point: f32) -> Option<Self> {
         if mesh.indices.is_empty() || mesh.indices.len() % 3 != 0 {
-            None
+            return None;
+        }
+
+        let clip = if pixels_per_point == 1. {
+            scissors
         } else {
-            let vertices = mesh
-                .vertices
-                .into_iter()
-                .map(|v| GpuVertex {
-                    pos: Pos2::new(
-                        (v.pos.x - w / 2.) / (w / 2.),
-                        (v.pos.y - h / 2.) / -(h / 2.),
-                    ),
-                    uv: v.uv,
-                    color: v.color.into(),
-                })
-                .collect();
-
-            Some(Self {
-                texture_id: mesh.texture_id,
-                indices: mesh.indices,
-                clip: scissors,
-                vertices,
-            })
-        }
-    }
-}
-
-#[repr(C)]
-pub struct GpuVertex {
-    pos: Pos2,
-    uv: Pos2,
-    color: Rgba,
-}
-
-impl From<Vertex> for GpuVertex {
-    fn from(v: Vertex) -> Self {
-        Self {
-            pos: v.pos,
-            uv: v.uv,
-            color: v.color.into(),
+            for vertex in &mut mesh.vertices {
+                vertex.pos = (vertex.pos.to_vec2() * pixels_per_point).to_pos2();
+            }
+
+            Rect::from_min_max(
+                (scissors.min.to_vec2() * pixels_per_point).to_pos2(),
+                (scissors.max.to_vec2() * pixels_per_point).to_pos2(),
+            )
+        };
+
+        Some(Self {
+            texture_id: mesh.texture_id,
+            indices: MeshIndices::new(mesh.indices, mesh.vertices.len()),
+            vertices: mesh.vertices,
+            clip,
+        })
+    }
+
+    /// Whether all vertices lie well inside the clip rect, so the mesh looks the same with any
+    /// scissor rect containing it.
+    fn is_unclipped(&self) -> bool {
+        let mut bounds = Rect::NOTHING;
+        for vertex in &self.vertices {
+            bounds.extend_with(vertex.pos);
+        }
+        self.clip.shrink(1.).contains_rect(bounds)
+    }
+
+    /// Translation that moves `first` onto this mesh, `None` unless both are unclipped and
+    /// otherwise identical.
+    fn translation_from(&self, first: &Self) -> Option<Vec2> {
+        if self.texture_id != first.texture_id
+            || self.vertices.len() != first.vertices.len()
+            || self.vertices.len() > MAX_INSTANCED_VERTICES
+            || self.indices.indices != first.indices.indices
+            || !self.is_unclipped()
+        {
+            return None;
+        }
+
+        let offset = self.vertices.first()?.pos - first.vertices.first()?.pos;
+        let identical = self.vertices.iter().zip(&first.vertices).all(|(vertex, other)| {
+            vertex.uv == other.uv
+                && vertex.color == other.color
+                && (vertex.pos - offset - other.pos).length_sq() <= INSTANCE_TOLERANCE_SQ
+        });
+        identical.then_some(offset)
+    }
+
+    /// [`Self::from_mesh`] for a primitive of egui's tessellator.
+    pub(crate) fn from_primitive(prim: ClippedPrimitive, pixels_per_point: f32) -> Option<Self> {
+        match compat::into_mesh(prim) {
+            Some((clip_rect, mesh)) => Self::from_mesh(mesh, clip_rect, pixels_per_point),
+            None => panic!("Paint callbacks are not yet supported"),
         }
     }
 }
 
-pub fn create_vertex_buffer(device: &ID3D11Device, mesh: &GpuMesh) -> ID3D11Buffer {
-    let desc = D3D11_BUFFER_DESC {
-        ByteWidth: (mesh.vertices.len() * size_of::<GpuVertex>()) as u32,
-        Usage: D3D11_USAGE_DEFAULT,
-        BindFlags: D3D11_BIND_VERTEX_BUFFER,
-        ..Default::default()
-    };
+/// Meshes with more vertices are never instanced, icons and badges stay far below it.
+const MAX_INSTANCED_VERTICES: usize = 256;
+/// Squared distance in pixels an instance's vertices may be off after removing its translation.
+const INSTANCE_TOLERANCE_SQ: f32 = 1. / 4096.;
+
+/// Translation of an instance in pixels, read by the vertex shader per instance.
+pub type InstanceOffset = [f32; 2];
 
-    let init = D3D11_SUBRESOURCE_DATA {
-        pSysMem: mesh.vertices.as_ptr() as _,
-        ..Default::default()
+/// How a mesh is drawn, see [`crate::Config::instancing`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MeshInstances {
+    /// Instances the mesh is drawn with, 0 for meshes drawn as an instance of an earlier one.
+    pub count: u32,
+    /// Offset of the first instance in the instance buffer.
+    pub first: u32,
+}
+
+impl MeshInstances {
+    /// The mesh drawn once where it is, the first offset is always zero.
+    pub const SINGLE: Self = Self { count: 1, first: 0 };
+}
+
+/// Finds runs of consecutive meshes that are the same but for their position, so every run is
+/// drawn by one instanced call of its first mesh. Fills `instances` with one entry per mesh and
+/// `offsets` with the translations of the instances.
+///
+/// Only meshes lying well inside their clip rect are batched, the scissor rect of a run's first
+/// mesh is widened to cover the others. Runs never cross one of the mesh indices in `breaks`.
+pub fn batch_instances(
+    meshes: &[GpuMesh],
+    scissors: &mut [RECT],
+    breaks: impl Fn(usize) -> bool,
+    instances: &mut Vec<MeshInstances>,
+    offsets: &mut Vec<InstanceOffset>,
+) {
+    instances.clear();
+    offsets.clear();
+    offsets.push([0.; 2]);
+
+    let mut start = 0;
+    while start < meshes.len() {
+        let first = &meshes[start];
+        let first_offset = offsets.len();
+        let mut end = start + 1;
+
+        if first.vertices.len() <= MAX_INSTANCED_VERTICES && first.is_unclipped() {
+            while end < meshes.len() && !breaks(end) {
+                let Some(offset) = meshes[end].translation_from(first) else {
+                    break;
+                };
+                if end == start + 1 {
+                    offsets.push([0.; 2]);
+                }
+                offsets.push([offset.x, offset.y]);
+                end += 1;
+            }
+        }
+
+        if end - start > 1 {
+            instances.push(MeshInstances {
+                count: (end - start) as u32,
+                first: first_offset as u32,
+            });
+            instances.extend((start + 1..end).map(|_| MeshInstances { count: 0, first: 0 }));
+
+            let mut merged = scissors[start];
+            for scissor in &scissors[start + 1..end] {
+                merged.left = merged.left.min(scissor.left);
+                merged.top = merged.top.min(scissor.top);
+                merged.right = merged.right.max(scissor.right);
+                merged.bottom = merged.bottom.max(scissor.bottom);
+            }
+            scissors[start] = merged;
+        } else {
+            instances.push(MeshInstances::SINGLE);
+        }
+
+        start = end;
+    }
+}
+
+/// Clamps the clip rect to the viewport, egui's shadows often reach past the screen's edges.
+/// Returns `None` if nothing of it is visible.
+///
+/// `clip` is in pixels, its edges are rounded to the nearest pixel boundary. Truncating them
+/// moved the right and bottom edges of fractional rects, e.g. of panels on odd sized windows
+/// at a fractional scale, a pixel inwards and left seams between neighbouring panels.
+pub fn scissor_rect(clip: Rect, [width, height]: [f32; 2]) -> Option<RECT> {
+    let edge = |value: f32, max: f32| value.round().clamp(0., max.round()) as i32;
+    let rect = RECT {
+        left: edge(clip.left(), width),
+        top: edge(clip.top(), height),
+        right: edge(clip.right(), width),
+        bottom: edge(clip.bottom(), height),
     };
 
-    let mut buffer: Option<ID3D11Buffer> = None;
+    (rect.right > rect.left && rect.bottom > rect.top).then_some(rect)
+}
+
+/// egui's vertices are uploaded in pixels, positions are converted to NDC in the vertex shader
+/// and colors are read as `R8G8B8A8_UNORM`, keeping the vertex at 20 bytes.
+pub type GpuVertex = Vertex;
+
+/// Sizes of the pooled buffers, see [`crate::DirectX11App::buffer_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BufferStats {
+    /// Bytes of the vertex buffer and the one of the instances.
+    pub vertex_bytes: usize,
+    /// Bytes of both index buffers together.
+    pub index_bytes: usize,
+    /// How often a buffer had to be recreated because a frame didn't fit.
+    pub growths: usize,
+}
+
+/// Vectors of the last frame, cleared and refilled every frame so drawing doesn't allocate.
+#[derive(Default)]
+pub struct FrameScratch {
+    pub meshes: Vec<GpuMesh>,
+    pub scissors: Vec<RECT>,
+    pub ranges: Vec<MeshRange>,
+    pub instances: Vec<MeshInstances>,
+    pub offsets: Vec<InstanceOffset>,
+    /// Draw hooks with the index of the mesh they run before.
+    pub hooks: Vec<(usize, DrawHookId)>,
+    /// Primitives between two draw hooks, with the hook that runs after them.
+    pub runs: Vec<(Vec<ClippedPrimitive>, Option<DrawHookId>)>,
+}
+
+/// Where a mesh ended up in the pooled buffers.
+pub struct MeshRange {
+    pub first_index: u32,
+    pub base_vertex: i32,
+}
+
+/// Dynamic buffer that is only recreated when a frame needs more than it holds.
+#[derive(Default)]
+pub struct PooledBuffer {
+    pub buffer: Option<ID3D11Buffer>,
+    capacity: usize,
+}
+
+impl PooledBuffer {
+    // 64 KiB fits the vertices of most simple UIs
+    const MIN_CAPACITY: usize = 1 << 16;
+
+    /// Returns `true` if the buffer was recreated.
+    fn reserve(
+        &mut self,
+        dev: &ID3D11Device,
+        size: usize,
+        bind: D3D11_BIND_FLAG,
+    ) -> Result<bool, Error> {
+        if size <= self.capacity && self.buffer.is_some() {
+            return Ok(false);
+        }
+
+        let capacity = size.next_power_of_two().max(Self::MIN_CAPACITY);
+        let desc = D3D11_BUFFER_DESC {
+            ByteWidth: capacity as u32,
+            Usage: D3D11_USAGE_DYNAMIC,
+            BindFlags: bind,
+            CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
+            ..Default::default()
+        };
+
+        let mut buffer: Option<ID3D11Buffer> = None;
+
+        unsafe {
+            dev.CreateBuffer(&desc, None, Some(&mut buffer))
+                .context("Failed to create pooled buffer")?;
+        }
+
+        log_msg!(debug, "Growing pooled buffer to {} bytes", capacity);
+        self.buffer = Some(buffer.context("Failed to create pooled buffer")?);
+        self.capacity = capacity;
+
+        Ok(true)
+    }
+
+    /// Writes all `chunks` back to back, discarding what was there before.
+    unsafe fn write<'a, T: Copy + 'a>(
+        &self,
+        ctx: &ID3D11DeviceContext,
+        chunks: impl Iterator<Item = &'a [T]>,
+    ) -> Result<(), Error> {
+        let Some(buffer) = &self.buffer else {
+            return Ok(());
+        };
 
-    unsafe {
-        expect!(
-            device.CreateBuffer(&desc, Some(&init), Some(&mut buffer)),
-            "Failed to create vertex buffer"
-        );
+        let mut subr = std::mem::zeroed();
+        ctx.Map(buffer, 0, D3D11_MAP_WRITE_DISCARD, 0, Some(&mut subr))
+            .context("Failed to map pooled buffer")?;
+
+        let mut dst = subr.pData as *mut T;
+        for chunk in chunks {
+            copy_nonoverlapping(chunk.as_ptr(), dst, chunk.len());
+            dst = dst.add(chunk.len());
+        }
+
+        ctx.Unmap(buffer, 0);
+        Ok(())
     }
 
-    expect!(buffer, "Failed to create vertex buffer")
+    /// Same as [`Self::write`] for indices that fit into 16 bits.
+    unsafe fn write_narrowed<'a>(
+        &self,
+        ctx: &ID3D11DeviceContext,
+        chunks: impl Iterator<Item = &'a [u32]>,
+    ) -> Result<(), Error> {
+        let Some(buffer) = &self.buffer else {
+            return Ok(());
+        };
+
+        let mut subr = std::mem::zeroed();
+        ctx.Map(buffer, 0, D3D11_MAP_WRITE_DISCARD, 0, Some(&mut subr))
+            .context("Failed to map pooled buffer")?;
+
+        let mut dst = subr.pData as *mut u16;
+        for index in chunks.flatten() {
+            dst.write(*index as u16);
+            dst = dst.add(1);
+        }
+
+        ctx.Unmap(buffer, 0);
+        Ok(())
+    }
 }
 
-pub fn create_index_buffer(device: &ID3D11Device, mesh: &GpuMesh) -> ID3D11Buffer {
-    let desc = D3D11_BUFFER_DESC {
-        ByteWidth: (mesh.indices.len() * size_of::<u32>()) as u32,
-        Usage: D3D11_USAGE_DEFAULT,
-        BindFlags: D3D11_BIND_INDEX_BUFFER,
-        ..Default::default()
-    };
+/// One vertex and two index buffers that hold all meshes of a frame. They grow to the
+/// biggest frame seen so far and are reused afterwards, so drawing creates no buffers.
+#[derive(Default)]
+pub struct BufferPool {
+    pub vertices: PooledBuffer,
+    pub indices_u16: PooledBuffer,
+    pub indices_u32: PooledBuffer,
+    /// Offsets of the instances, the first one is zero for meshes drawn on their own.
+    pub instances: PooledBuffer,
+    growths: usize,
+}
 
-    let init = D3D11_SUBRESOURCE_DATA {
-        pSysMem: mesh.indices.as_ptr() as _,
-        ..Default::default()
-    };
+impl BufferPool {
+    /// Uploads `meshes` and fills `ranges` with where each of them was placed.
+    pub fn upload(
+        &mut self,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        meshes: &[GpuMesh],
+        ranges: &mut Vec<MeshRange>,
+    ) -> Result<(), Error> {
+        ranges.clear();
+        let (mut vertices, mut u16s, mut u32s) = (0, 0, 0);
+
+        for mesh in meshes {
+            let count = if mesh.indices.is_wide() {
+                &mut u32s
+            } else {
+                &mut u16s
+            };
+            let first_index = *count;
+            *count += mesh.indices.len();
+
+            ranges.push(MeshRange {
+                first_index: first_index as _,
+                base_vertex: vertices as _,
+            });
+            vertices += mesh.vertices.len();
+        }
+
+        let grown = [
+            self.vertices.reserve(
+                dev,
+                vertices * size_of::<GpuVertex>(),
+                D3D11_BIND_VERTEX_BUFFER,
+            )?,
+            u16s > 0
+                && self.indices_u16.reserve(
+                    dev,
+                    u16s * size_of::<u16>(),
+                    D3D11_BIND_INDEX_BUFFER,
+                )?,
+            u32s > 0
+                && self.indices_u32.reserve(
+                    dev,
+                    u32s * size_of::<u32>(),
+                    D3D11_BIND_INDEX_BUFFER,
+                )?,
+        ];
+        self.growths += grown.into_iter().filter(|grown| *grown).count();
 
-    let mut buffer: Option<ID3D11Buffer> = None;
+        unsafe {
+            self.vertices
+                .write(ctx, meshes.iter().map(|mesh| mesh.vertices.as_slice()))?;
 
-    unsafe {
-        expect!(
-            device.CreateBuffer(&desc, Some(&init), Some(&mut buffer)),
-            "Failed to create index buffer"
-        );
+            if u16s > 0 {
+                self.indices_u16.write_narrowed(
+                    ctx,
+                    meshes
+                        .iter()
+                        .filter(|mesh| !mesh.indices.is_wide())
+                        .map(|mesh| mesh.indices.indices.as_slice()),
+                )?;
+            }
+
+            if u32s > 0 {
+                self.indices_u32.write(
+                    ctx,
+                    meshes
+                        .iter()
+                        .filter(|mesh| mesh.indices.is_wide())
+                        .map(|mesh| mesh.indices.indices.as_slice()),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the offsets of the instances, see [`batch_instances`].
+    pub fn upload_instances(
+        &mut self,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        offsets: &[InstanceOffset],
+    ) -> Result<(), Error> {
+        let bytes = size_of_val(offsets);
+        if self.instances.reserve(dev, bytes, D3D11_BIND_VERTEX_BUFFER)? {
+            self.growths += 1;
+        }
+
+        unsafe { self.instances.write(ctx, std::iter::once(offsets)) }
     }
 
-    expect!(buffer, "Failed to create vertex buffer")
+    /// Index buffer holding the indices of `mesh`.
+    pub fn index_buffer(&self, mesh: &GpuMesh) -> Option<&ID3D11Buffer> {
+        if mesh.indices.is_wide() {
+            self.indices_u32.buffer.as_ref()
+        } else {
+            self.indices_u16.buffer.as_ref()
+        }
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.vertices.capacity
+            + self.indices_u16.capacity
+            + self.indices_u32.capacity
+            + self.instances.capacity
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        BufferStats {
+            vertex_bytes: self.vertices.capacity + self.instances.capacity,
+            index_bytes: self.indices_u16.capacity + self.indices_u32.capacity,
+            growths: self.growths,
+        }
+    }
+}
+
+#[test]
+fn test_scissor_rect() {
+    let screen = [800., 600.];
+
+    let rect = scissor_rect(
+        Rect::from_min_max([-4., -4.].into(), [100., 900.].into()),
+        screen,
+    );
+    assert_eq!(
+        rect.map(|r| (r.left, r.top, r.right, r.bottom)),
+        Some((0, 0, 100, 600))
+    );
+
+    assert!(scissor_rect(
+        Rect::from_min_max([900., 0.].into(), [950., 50.].into()),
+        screen
+    )
+    .is_none());
+    assert!(scissor_rect(Rect::NOTHING, screen).is_none());
+
+    // a panel edge at 682.5 pixels, e.g. half of 1365 at a scale of 1.5, is shared by both sides
+    let screen = [1365., 705.];
+    let left = scissor_rect(
+        Rect::from_min_max([0., 0.].into(), [682.5, 704.6].into()),
+        screen,
+    );
+    let right = scissor_rect(
+        Rect::from_min_max([682.5, 0.].into(), [1365., 705.].into()),
+        screen,
+    );
+    assert_eq!(
+        left.map(|r| (r.left, r.top, r.right, r.bottom)),
+        Some((0, 0, 683, 705))
+    );
+    assert_eq!(
+        right.map(|r| (r.left, r.top, r.right, r.bottom)),
+        Some((683, 0, 1365, 705))
+    );
+}
+
+#[test]
+fn test_batch_instances() {
+    use egui::Color32;
+
+    let clip = Rect::from_min_max([0., 0.].into(), [400., 300.].into());
+    let icon = |x: f32, color: Color32| {
+        let mut mesh = Mesh::default();
+        mesh.add_colored_rect(Rect::from_min_size([x, 10.].into(), [16., 16.].into()), color);
+        GpuMesh::from_mesh(mesh, clip, 1.).unwrap()
+    };
+    let meshes = [
+        icon(10., Color32::RED),
+        icon(30.5, Color32::RED),
+        icon(50., Color32::RED),
+        icon(70., Color32::BLUE),
+        icon(90., Color32::BLUE),
+    ];
+    let mut scissors = [RECT::default(); 5];
+    let (mut instances, mut offsets) = (vec![], vec![]);
+
+    batch_instances(&meshes, &mut scissors, |_| false, &mut instances, &mut offsets);
+    assert_eq!(
+        instances.iter().map(|i| i.count).collect::<Vec<_>>(),
+        [3, 0, 0, 2, 0]
+    );
+    assert_eq!(offsets[..4], [[0., 0.], [0., 0.], [20.5, 0.], [40., 0.]]);
+    assert_eq!(instances[3].first, 4);
+
+    // a draw hook between two meshes keeps them apart
+    batch_instances(&meshes, &mut scissors, |i| i == 4, &mut instances, &mut offsets);
+    assert_eq!(
+        instances.iter().map(|i| i.count).collect::<Vec<_>>(),
+        [3, 0, 0, 1, 1]
+    );
+}
+
+#[test]
+fn test_gpu_mesh_new() {
+    let vertex = GpuVertex::default();
+    let clip = Rect::EVERYTHING;
+    let id = TextureId::default();
+
+    assert!(GpuMesh::new(vec![vertex; 3], vec![0, 1, 2], clip, id).is_some());
+    assert!(GpuMesh::new(vec![vertex; 3], vec![0, 1], clip, id).is_none());
+    assert!(GpuMesh::new(vec![vertex; 3], vec![0, 1, 3], clip, id).is_none());
+    assert!(GpuMesh::new(vec![], vec![], clip, id).is_none());
 }
@@ -0,0 +1,126 @@
+use crate::{
+    error::{Error, ErrorContext},
+    format::{bytes_per_pixel, typed_format},
+    texture::TextureAllocator,
+    BACKBUFFER_TEXTURE_ID,
+};
+use windows::Win32::Graphics::{
+    Direct3D::D3D11_SRV_DIMENSION_TEXTURE2D,
+    Direct3D11::{
+        ID3D11Device, ID3D11DeviceContext, ID3D11ShaderResourceView, ID3D11Texture2D,
+        D3D11_BIND_SHADER_RESOURCE, D3D11_SHADER_RESOURCE_VIEW_DESC,
+        D3D11_SHADER_RESOURCE_VIEW_DESC_0, D3D11_TEX2D_SRV, D3D11_TEXTURE2D_DESC,
+        D3D11_USAGE_DEFAULT,
+    },
+    Dxgi::Common::DXGI_SAMPLE_DESC,
+};
+
+/// Copy of the swapchain's backbuffer that is refreshed every frame
+/// and registered in the [`TextureAllocator`] under [`BACKBUFFER_TEXTURE_ID`].
+#[derive(Default)]
+pub struct BackbufferMirror {
+    copy: Option<(ID3D11Texture2D, D3D11_TEXTURE2D_DESC)>,
+}
+
+impl BackbufferMirror {
+    pub fn update(
+        &mut self,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        backbuffer: &ID3D11Texture2D,
+        tex_alloc: &mut TextureAllocator,
+    ) -> Result<(), Error> {
+        unsafe {
+            let mut desc = D3D11_TEXTURE2D_DESC::default();
+            backbuffer.GetDesc(&mut desc);
+
+            let outdated = match &self.copy {
+                Some((_, old)) => {
+                    old.Width != desc.Width
+                        || old.Height != desc.Height
+                        || old.Format != desc.Format
+                }
+                None => true,
+            };
+
+            if outdated {
+                let texture = Self::create_copy(dev, &desc, tex_alloc)?;
+                self.copy = Some((texture, desc));
+            }
+
+            let (texture, _) = self
+                .copy
+                .as_ref()
+                .context("Failed to create backbuffer copy")?;
+
+            if desc.SampleDesc.Count > 1 {
+                ctx.ResolveSubresource(texture, 0, backbuffer, 0, typed_format(desc.Format));
+            } else {
+                ctx.CopyResource(texture, backbuffer);
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Releases the copy and unregisters it from the allocator.
+    pub fn release(&mut self, tex_alloc: &mut TextureAllocator) {
+        if self.copy.take().is_some() {
+            tex_alloc.free(BACKBUFFER_TEXTURE_ID);
+        }
+    }
+
+    unsafe fn create_copy(
+        dev: &ID3D11Device,
+        backbuffer: &D3D11_TEXTURE2D_DESC,
+        tex_alloc: &mut TextureAllocator,
+    ) -> Result<ID3D11Texture2D, Error> {
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: backbuffer.Width,
+            Height: backbuffer.Height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: backbuffer.Format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            ..Default::default()
+        };
+
+        let mut texture: Option<ID3D11Texture2D> = None;
+
+        dev.CreateTexture2D(&desc, None, Some(&mut texture))
+            .context("Failed to create backbuffer copy")?;
+
+        let texture = texture.context("Failed to create backbuffer copy")?;
+
+        let view_desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
+            Format: typed_format(desc.Format),
+            ViewDimension: D3D11_SRV_DIMENSION_TEXTURE2D,
+            Anonymous: D3D11_SHADER_RESOURCE_VIEW_DESC_0 {
+                Texture2D: D3D11_TEX2D_SRV {
+                    MostDetailedMip: 0,
+                    MipLevels: 1,
+                },
+            },
+        };
+
+        let mut resource: Option<ID3D11ShaderResourceView> = None;
+
+        dev.CreateShaderResourceView(&texture, Some(&view_desc), Some(&mut resource))
+            .context("Failed to create shader resource view")?;
+
+        tex_alloc.register_native(
+            BACKBUFFER_TEXTURE_ID,
+            texture.clone(),
+            resource.context("Failed to create shader resource view")?,
+            desc.Width as _,
+            (desc.Width * desc.Height) as usize * bytes_per_pixel(desc.Format),
+        );
+
+        Ok(texture)
+    }
+}
@@ -0,0 +1,52 @@
+use crate::mesh::GpuMesh;
+use egui::{
+    epaint::{tessellate_shapes, ClippedPrimitive, ClippedShape},
+    Context,
+};
+use rayon::prelude::*;
+
+/// Below this many shapes a single thread is faster than splitting them up.
+const MIN_PARALLEL_SHAPES: usize = 1024;
+
+/// Same as [`Context::tessellate`], but splits the shapes into one run per thread of rayon's pool.
+/// Shapes of different runs aren't merged into one mesh, so big frames get a few more draw calls,
+/// and egui's own paint stats aren't updated.
+pub fn tessellate(ctx: &Context, shapes: Vec<ClippedShape>) -> Vec<ClippedPrimitive> {
+    let threads = rayon::current_num_threads();
+    if shapes.len() < MIN_PARALLEL_SHAPES || threads < 2 {
+        return ctx.tessellate(shapes);
+    }
+
+    let pixels_per_point = ctx.pixels_per_point();
+    let options = ctx.tessellation_options(|options| *options);
+    let (font_tex_size, prepared_discs) = {
+        let atlas = ctx.fonts(|fonts| fonts.texture_atlas());
+        let atlas = atlas.lock();
+        (atlas.size(), atlas.prepared_discs())
+    };
+
+    let chunk = shapes.len().div_ceil(threads);
+    let runs = shapes
+        .into_par_iter()
+        .chunks(chunk)
+        .map(|shapes| {
+            tessellate_shapes(
+                pixels_per_point,
+                options,
+                font_tex_size,
+                prepared_discs.clone(),
+                shapes,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    runs.into_iter().flatten().collect()
+}
+
+/// Converts the primitives on rayon's pool, keeping their order.
+pub fn gpu_meshes(primitives: Vec<ClippedPrimitive>, pixels_per_point: f32) -> Vec<GpuMesh> {
+    primitives
+        .into_par_iter()
+        .filter_map(|prim| GpuMesh::from_primitive(prim, pixels_per_point))
+        .collect()
+}
@@ -0,0 +1,53 @@
+use egui::{Context, Memory};
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// Keeps egui's memory in a file, so the layout of the UI survives restarts.
+pub struct Persistence {
+    path: PathBuf,
+    interval: Duration,
+    last_save: Instant,
+}
+
+impl Persistence {
+    pub fn new(path: PathBuf, interval: Duration) -> Self {
+        Self {
+            path,
+            interval,
+            last_save: Instant::now(),
+        }
+    }
+
+    /// Restores the memory saved by a previous session. Missing or unreadable files are ignored.
+    pub fn load(&self, ctx: &Context) {
+        let Ok(text) = fs::read_to_string(&self.path) else {
+            return;
+        };
+
+        match ron::from_str::<Memory>(&text) {
+            Ok(memory) => ctx.memory_mut(|m| *m = memory),
+            Err(_e) => {
+                log_msg!(warn, "Failed to load {:?}: {}", self.path, _e);
+            }
+        }
+    }
+
+    pub fn save(&mut self, ctx: &Context) -> io::Result<()> {
+        let text = ctx.memory(ron::to_string).map_err(io::Error::other)?;
+
+        self.last_save = Instant::now();
+        fs::write(&self.path, text)
+    }
+
+    /// Saves the memory once the interval has passed since the last save.
+    pub fn save_if_due(&mut self, ctx: &Context) {
+        if self.last_save.elapsed() >= self.interval {
+            if let Err(_e) = self.save(ctx) {
+                log_msg!(warn, "Failed to save {:?}: {}", self.path, _e);
+            }
+        }
+    }
+}
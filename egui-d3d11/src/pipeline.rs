@@ -0,0 +1,483 @@
+use crate::{
+    config::{AddressMode, AlphaMode, Config, LineQuality},
+    constants::ConstantBuffer,
+    error::{Error, ErrorContext},
+    format::channel_bits,
+    mesh::{BufferPool, GpuVertex, InstanceOffset},
+    shader::CompiledShaders,
+    texture::SamplerOverride,
+};
+use egui::{Rect, TextureFilter};
+use std::{collections::HashMap, mem::size_of};
+use windows::Win32::Graphics::{
+    Direct3D::D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+    Direct3D11::{
+        ID3D11BlendState, ID3D11DepthStencilState, ID3D11Device, ID3D11DeviceContext,
+        ID3D11InputLayout, ID3D11RasterizerState, ID3D11RenderTargetView, ID3D11SamplerState,
+        D3D11_APPEND_ALIGNED_ELEMENT, D3D11_BLEND_DESC, D3D11_BLEND_INV_SRC_ALPHA, D3D11_BLEND_ONE,
+        D3D11_BLEND_OP_ADD, D3D11_BLEND_SRC_ALPHA, D3D11_COLOR_WRITE_ENABLE_ALL,
+        D3D11_COLOR_WRITE_ENABLE_ALPHA, D3D11_COMPARISON_ALWAYS, D3D11_CULL_NONE,
+        D3D11_DEPTH_STENCIL_DESC, D3D11_DEPTH_WRITE_MASK_ZERO, D3D11_FILL_MODE, D3D11_FILL_SOLID,
+        D3D11_FILL_WIREFRAME, D3D11_FILTER, D3D11_FILTER_ANISOTROPIC,
+        D3D11_FILTER_MIN_LINEAR_MAG_POINT_MIP_LINEAR, D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+        D3D11_FILTER_MIN_MAG_MIP_POINT, D3D11_FILTER_MIN_POINT_MAG_LINEAR_MIP_POINT,
+        D3D11_INPUT_ELEMENT_DESC, D3D11_INPUT_PER_INSTANCE_DATA, D3D11_INPUT_PER_VERTEX_DATA,
+        D3D11_RASTERIZER_DESC, D3D11_RENDER_TARGET_BLEND_DESC, D3D11_REQ_MAXANISOTROPY,
+        D3D11_SAMPLER_DESC, D3D11_TEXTURE_ADDRESS_BORDER, D3D11_TEXTURE_ADDRESS_CLAMP,
+        D3D11_TEXTURE_ADDRESS_MIRROR, D3D11_TEXTURE_ADDRESS_WRAP, D3D11_VIEWPORT,
+    },
+    Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_R32G32_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM},
+};
+
+const INPUT_ELEMENTS_DESC: [D3D11_INPUT_ELEMENT_DESC; 4] = [
+    D3D11_INPUT_ELEMENT_DESC {
+        SemanticName: pc_str!("POSITION"),
+        SemanticIndex: 0,
+        Format: DXGI_FORMAT_R32G32_FLOAT,
+        InputSlot: 0,
+        AlignedByteOffset: 0,
+        InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+        InstanceDataStepRate: 0,
+    },
+    D3D11_INPUT_ELEMENT_DESC {
+        SemanticName: pc_str!("TEXCOORD"),
+        SemanticIndex: 0,
+        Format: DXGI_FORMAT_R32G32_FLOAT,
+        InputSlot: 0,
+        AlignedByteOffset: D3D11_APPEND_ALIGNED_ELEMENT,
+        InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+        InstanceDataStepRate: 0,
+    },
+    D3D11_INPUT_ELEMENT_DESC {
+        SemanticName: pc_str!("COLOR"),
+        SemanticIndex: 0,
+        Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+        InputSlot: 0,
+        AlignedByteOffset: D3D11_APPEND_ALIGNED_ELEMENT,
+        InputSlotClass: D3D11_INPUT_PER_VERTEX_DATA,
+        InstanceDataStepRate: 0,
+    },
+    // translation of the instance, zero unless the mesh is drawn instanced
+    D3D11_INPUT_ELEMENT_DESC {
+        SemanticName: pc_str!("OFFSET"),
+        SemanticIndex: 0,
+        Format: DXGI_FORMAT_R32G32_FLOAT,
+        InputSlot: 1,
+        AlignedByteOffset: 0,
+        InputSlotClass: D3D11_INPUT_PER_INSTANCE_DATA,
+        InstanceDataStepRate: 1,
+    },
+];
+
+/// State objects of the UI pass, created once and only bound while presenting.
+pub struct Pipeline {
+    input_layout: ID3D11InputLayout,
+    blend: ID3D11BlendState,
+    // premultiplied blending into the scaled target, created when the UI is first drawn scaled
+    offscreen_blend: Option<ID3D11BlendState>,
+    // created when the overdraw view is first enabled
+    additive: Option<ID3D11BlendState>,
+    raster: ID3D11RasterizerState,
+    // created when the wireframe view is first enabled
+    wireframe: Option<ID3D11RasterizerState>,
+    multisample: bool,
+    // the raster states draw anti-aliased lines
+    smooth_lines: bool,
+    depth: ID3D11DepthStencilState,
+    // created on first use, there are only a handful of combinations
+    samplers: HashMap<SamplerKey, ID3D11SamplerState>,
+    default_sampler: SamplerKey,
+    // options the blend state was created with
+    alpha_mode: AlphaMode,
+    write_alpha: bool,
+    wireframe_enabled: bool,
+    overdraw_enabled: bool,
+}
+
+/// Which part of a frame is drawn, see [`crate::Config::render_scale`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    /// The UI straight into the render target.
+    Direct,
+    /// The UI into the scaled target, without color space conversion.
+    Offscreen,
+    /// The scaled target stretched over the render target.
+    Composite,
+}
+
+/// Everything a sampler state is created from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    magnification: TextureFilter,
+    minification: TextureFilter,
+    address: AddressMode,
+    anisotropy: u8,
+    // bits of the min, max and bias of `TextureSampling`, floats can't be hashed
+    lod: [u32; 3],
+}
+
+impl SamplerKey {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            magnification: config.sampler_filter,
+            minification: config.sampler_filter,
+            address: config.sampler_address,
+            anisotropy: 1,
+            lod: [0f32.to_bits(); 3],
+        }
+    }
+
+    fn with(mut self, overrides: SamplerOverride) -> Self {
+        if let Some(options) = overrides.options {
+            self.magnification = options.magnification;
+            self.minification = options.minification;
+        }
+        if let Some(address) = overrides.address {
+            self.address = address;
+        }
+        if let Some(sampling) = overrides.sampling {
+            self.anisotropy = sampling.anisotropy.clamp(1, D3D11_REQ_MAXANISOTROPY as u8);
+            self.lod = [sampling.min_lod, sampling.max_lod, sampling.lod_bias].map(f32::to_bits);
+        }
+        self
+    }
+}
+
+impl Pipeline {
+    /// `multisample` has to match the render target, otherwise MSAA backbuffers get rasterized incorrectly.
+    pub fn new(
+        dev: &ID3D11Device,
+        shaders: &CompiledShaders,
+        multisample: bool,
+        config: &Config,
+    ) -> Result<Self, Error> {
+        unsafe {
+            let mut input_layout: Option<ID3D11InputLayout> = None;
+
+            dev.CreateInputLayout(
+                &INPUT_ELEMENTS_DESC,
+                shaders.bytecode(),
+                Some(&mut input_layout),
+            )
+            .context("Failed to create input layout")?;
+
+            let smooth_lines = config.line_quality == LineQuality::Smooth;
+            let default_sampler = SamplerKey::from_config(config);
+            let samplers =
+                HashMap::from([(default_sampler, create_sampler(dev, default_sampler)?)]);
+
+            Ok(Self {
+                input_layout: input_layout.context("Failed to create input layout")?,
+                blend: create_blend_state(dev, config.alpha_mode, true)?,
+                offscreen_blend: None,
+                additive: None,
+                raster: create_raster_state(dev, multisample, smooth_lines, D3D11_FILL_SOLID)?,
+                wireframe: None,
+                multisample,
+                smooth_lines,
+                depth: create_depth_state(dev)?,
+                samplers,
+                default_sampler,
+                alpha_mode: config.alpha_mode,
+                write_alpha: true,
+                wireframe_enabled: false,
+                overdraw_enabled: false,
+            })
+        }
+    }
+
+    /// Recreates the states whose options in `config` or the render target's `format`
+    /// changed since they were created.
+    pub fn update(
+        &mut self,
+        dev: &ID3D11Device,
+        config: &Config,
+        format: DXGI_FORMAT,
+    ) -> Result<(), Error> {
+        self.default_sampler = SamplerKey::from_config(config);
+        self.sampler(dev, SamplerOverride::default())?;
+
+        // 2 bit alpha of 10 bit targets can't hold the UI's coverage, games never read it
+        // either, so it's left alone unless the target gets composited.
+        let write_alpha =
+            config.alpha_mode == AlphaMode::Premultiplied || channel_bits(format).1 >= 8;
+
+        if self.alpha_mode != config.alpha_mode || self.write_alpha != write_alpha {
+            self.blend = unsafe { create_blend_state(dev, config.alpha_mode, write_alpha)? };
+            self.alpha_mode = config.alpha_mode;
+            self.write_alpha = write_alpha;
+        }
+
+        // the scaled target keeps the coverage, the composite pass then blends it like egui's colors
+        if config.render_scale < 1. && self.offscreen_blend.is_none() {
+            self.offscreen_blend =
+                Some(unsafe { create_blend_state(dev, AlphaMode::Premultiplied, true)? });
+        }
+
+        let smooth_lines = config.line_quality == LineQuality::Smooth;
+        if self.smooth_lines != smooth_lines {
+            self.raster = unsafe {
+                create_raster_state(dev, self.multisample, smooth_lines, D3D11_FILL_SOLID)?
+            };
+            self.wireframe = None;
+            self.smooth_lines = smooth_lines;
+        }
+
+        if config.wireframe && self.wireframe.is_none() {
+            self.wireframe = Some(unsafe {
+                create_raster_state(dev, self.multisample, smooth_lines, D3D11_FILL_WIREFRAME)?
+            });
+        }
+        self.wireframe_enabled = config.wireframe;
+
+        if config.overdraw && self.additive.is_none() {
+            self.additive = Some(unsafe { create_additive_blend_state(dev)? });
+        }
+        self.overdraw_enabled = config.overdraw;
+
+        Ok(())
+    }
+
+    /// Sampler for a texture with `overrides`, the rest of the options come from the config.
+    pub fn sampler(
+        &mut self,
+        dev: &ID3D11Device,
+        overrides: SamplerOverride,
+    ) -> Result<ID3D11SamplerState, Error> {
+        let key = self.default_sampler.with(overrides);
+        if let Some(sampler) = self.samplers.get(&key) {
+            return Ok(sampler.clone());
+        }
+
+        log_msg!(debug, "Creating sampler for {:?}", key);
+        let sampler = unsafe { create_sampler(dev, key)? };
+        self.samplers.insert(key, sampler.clone());
+        Ok(sampler)
+    }
+
+    /// Binds everything but textures and index buffers, those change per mesh.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn bind(
+        &self,
+        ctx: &ID3D11DeviceContext,
+        shaders: &CompiledShaders,
+        constants: &ConstantBuffer,
+        buffers: &BufferPool,
+        render_view: &ID3D11RenderTargetView,
+        viewport: Rect,
+        pass: Pass,
+    ) {
+        let blend = match (&self.additive, &self.offscreen_blend, pass) {
+            (_, _, Pass::Composite) => &self.blend,
+            (Some(additive), _, _) if self.overdraw_enabled => additive,
+            (_, Some(offscreen), Pass::Offscreen) => offscreen,
+            _ => &self.blend,
+        };
+        ctx.OMSetBlendState(blend, Some([0f32; 4].as_ptr()), 0xffffffff);
+        match &self.wireframe {
+            Some(wireframe) if self.wireframe_enabled && pass != Pass::Composite => {
+                ctx.RSSetState(wireframe)
+            }
+            _ => ctx.RSSetState(&self.raster),
+        }
+        // the game may leave depth testing on, which would reject parts of the UI
+        ctx.OMSetDepthStencilState(&self.depth, 0);
+        // always created by `new` and `update`
+        let slots = shaders.slots();
+        if let Some(sampler) = self.samplers.get(&self.default_sampler) {
+            ctx.PSSetSamplers(slots.sampler, Some(std::slice::from_ref(sampler)));
+        }
+
+        ctx.RSSetViewports(Some(&[D3D11_VIEWPORT {
+            TopLeftX: viewport.min.x,
+            TopLeftY: viewport.min.y,
+            Width: viewport.width(),
+            Height: viewport.height(),
+            MinDepth: 0.,
+            MaxDepth: 1.,
+        }]));
+        ctx.OMSetRenderTargets(Some(std::slice::from_ref(render_view)), None);
+
+        ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+        ctx.IASetInputLayout(&self.input_layout);
+        let vertex_buffers = [
+            buffers.vertices.buffer.clone(),
+            buffers.instances.buffer.clone(),
+        ];
+        let strides = [
+            size_of::<GpuVertex>() as u32,
+            size_of::<InstanceOffset>() as u32,
+        ];
+        ctx.IASetVertexBuffers(
+            0,
+            2,
+            Some(vertex_buffers.as_ptr()),
+            Some(strides.as_ptr()),
+            Some([0; 2].as_ptr()),
+        );
+
+        ctx.VSSetShader(&shaders.vertex, None);
+        match (&shaders.gamma_pixel, pass) {
+            (Some(pixel), Pass::Offscreen) => ctx.PSSetShader(pixel, None),
+            _ => ctx.PSSetShader(&shaders.pixel, None),
+        }
+        let constants = std::slice::from_ref(&constants.buffer);
+        ctx.VSSetConstantBuffers(slots.constants, Some(constants));
+        ctx.PSSetConstantBuffers(slots.constants, Some(constants));
+    }
+}
+
+unsafe fn create_blend_state(
+    dev: &ID3D11Device,
+    alpha_mode: AlphaMode,
+    write_alpha: bool,
+) -> Result<ID3D11BlendState, Error> {
+    let mut targets: [D3D11_RENDER_TARGET_BLEND_DESC; 8] = Default::default();
+    targets[0].BlendEnable = true.into();
+    // egui's colors already are premultiplied
+    targets[0].SrcBlend = match alpha_mode {
+        AlphaMode::Straight => D3D11_BLEND_SRC_ALPHA,
+        AlphaMode::Premultiplied => D3D11_BLEND_ONE,
+    };
+    targets[0].DestBlend = D3D11_BLEND_INV_SRC_ALPHA;
+    targets[0].BlendOp = D3D11_BLEND_OP_ADD;
+    targets[0].SrcBlendAlpha = D3D11_BLEND_ONE;
+    targets[0].DestBlendAlpha = D3D11_BLEND_INV_SRC_ALPHA;
+    targets[0].BlendOpAlpha = D3D11_BLEND_OP_ADD;
+    targets[0].RenderTargetWriteMask = if write_alpha {
+        D3D11_COLOR_WRITE_ENABLE_ALL.0 as _
+    } else {
+        (D3D11_COLOR_WRITE_ENABLE_ALL.0 & !D3D11_COLOR_WRITE_ENABLE_ALPHA.0) as _
+    };
+
+    let blend_desc = D3D11_BLEND_DESC {
+        AlphaToCoverageEnable: false.into(),
+        IndependentBlendEnable: false.into(),
+        RenderTarget: targets,
+    };
+
+    let mut blend_state: Option<ID3D11BlendState> = None;
+
+    dev.CreateBlendState(&blend_desc, Some(&mut blend_state))
+        .context("Failed to create blend state")?;
+
+    blend_state.context("Failed to create blend state")
+}
+
+/// Adds up everything drawn, for the overdraw view.
+unsafe fn create_additive_blend_state(dev: &ID3D11Device) -> Result<ID3D11BlendState, Error> {
+    let mut targets: [D3D11_RENDER_TARGET_BLEND_DESC; 8] = Default::default();
+    targets[0].BlendEnable = true.into();
+    targets[0].SrcBlend = D3D11_BLEND_ONE;
+    targets[0].DestBlend = D3D11_BLEND_ONE;
+    targets[0].BlendOp = D3D11_BLEND_OP_ADD;
+    targets[0].SrcBlendAlpha = D3D11_BLEND_ONE;
+    targets[0].DestBlendAlpha = D3D11_BLEND_ONE;
+    targets[0].BlendOpAlpha = D3D11_BLEND_OP_ADD;
+    targets[0].RenderTargetWriteMask = D3D11_COLOR_WRITE_ENABLE_ALL.0 as _;
+
+    let blend_desc = D3D11_BLEND_DESC {
+        AlphaToCoverageEnable: false.into(),
+        IndependentBlendEnable: false.into(),
+        RenderTarget: targets,
+    };
+
+    let mut blend_state: Option<ID3D11BlendState> = None;
+
+    dev.CreateBlendState(&blend_desc, Some(&mut blend_state))
+        .context("Failed to create blend state")?;
+
+    blend_state.context("Failed to create blend state")
+}
+
+unsafe fn create_raster_state(
+    dev: &ID3D11Device,
+    multisample: bool,
+    smooth_lines: bool,
+    fill: D3D11_FILL_MODE,
+) -> Result<ID3D11RasterizerState, Error> {
+    let raster_desc = D3D11_RASTERIZER_DESC {
+        FillMode: fill,
+        CullMode: D3D11_CULL_NONE,
+        FrontCounterClockwise: false.into(),
+        DepthBias: false.into(),
+        DepthBiasClamp: 0.,
+        SlopeScaledDepthBias: 0.,
+        DepthClipEnable: false.into(),
+        ScissorEnable: true.into(),
+        MultisampleEnable: multisample.into(),
+        // only used by the driver when `MultisampleEnable` is off
+        AntialiasedLineEnable: smooth_lines.into(),
+    };
+
+    let mut options: Option<ID3D11RasterizerState> = None;
+
+    dev.CreateRasterizerState(&raster_desc, Some(&mut options))
+        .context("Failed to create rasterizer state")?;
+
+    options.context("Failed to create rasterizer state")
+}
+
+unsafe fn create_depth_state(dev: &ID3D11Device) -> Result<ID3D11DepthStencilState, Error> {
+    let desc = D3D11_DEPTH_STENCIL_DESC {
+        DepthEnable: false.into(),
+        DepthWriteMask: D3D11_DEPTH_WRITE_MASK_ZERO,
+        DepthFunc: D3D11_COMPARISON_ALWAYS,
+        StencilEnable: false.into(),
+        ..Default::default()
+    };
+
+    let mut state: Option<ID3D11DepthStencilState> = None;
+
+    dev.CreateDepthStencilState(&desc, Some(&mut state))
+        .context("Failed to create depth stencil state")?;
+
+    state.context("Failed to create depth stencil state")
+}
+
+unsafe fn create_sampler(dev: &ID3D11Device, key: SamplerKey) -> Result<ID3D11SamplerState, Error> {
+    let address = match key.address {
+        AddressMode::Border => D3D11_TEXTURE_ADDRESS_BORDER,
+        AddressMode::Clamp => D3D11_TEXTURE_ADDRESS_CLAMP,
+        AddressMode::Wrap => D3D11_TEXTURE_ADDRESS_WRAP,
+        AddressMode::Mirror => D3D11_TEXTURE_ADDRESS_MIRROR,
+    };
+
+    let filtering = match key.anisotropy {
+        1 => filter(key.minification, key.magnification),
+        _ => D3D11_FILTER_ANISOTROPIC,
+    };
+    let [min_lod, max_lod, lod_bias] = key.lod.map(f32::from_bits);
+
+    let desc = D3D11_SAMPLER_DESC {
+        Filter: filtering,
+        AddressU: address,
+        AddressV: address,
+        AddressW: address,
+        MipLODBias: lod_bias,
+        MaxAnisotropy: key.anisotropy as u32,
+        ComparisonFunc: D3D11_COMPARISON_ALWAYS,
+        MinLOD: min_lod,
+        MaxLOD: max_lod,
+        BorderColor: [1., 1., 1., 1.],
+    };
+
+    let mut sampler: Option<ID3D11SamplerState> = None;
+
+    dev.CreateSamplerState(&desc, Some(&mut sampler))
+        .context("Failed to create sampler")?;
+
+    sampler.context("Failed to create sampler")
+}
+
+/// Mips are filtered the same way as minification.
+fn filter(minification: TextureFilter, magnification: TextureFilter) -> D3D11_FILTER {
+    use TextureFilter::{Linear, Nearest};
+
+    match (minification, magnification) {
+        (Linear, Linear) => D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+        (Linear, Nearest) => D3D11_FILTER_MIN_LINEAR_MAG_POINT_MIP_LINEAR,
+        (Nearest, Linear) => D3D11_FILTER_MIN_POINT_MAG_LINEAR_MIP_POINT,
+        (Nearest, Nearest) => D3D11_FILTER_MIN_MAG_MIP_POINT,
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{clipboard::Clipboard, config::ScreenTransform};
+use egui::{
+    vec2, Color32, Context, CursorIcon, Id, LayerId, Order, PlatformOutput, Pos2, Shape, Stroke,
+};
+use std::{
+    iter,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+        mpsc::{sync_channel, SyncSender, TrySendError},
+    },
+    thread,
+};
+use windows::{
+    core::PCWSTR,
+    w,
+    Win32::{
+        Foundation::{HWND, POINT},
+        UI::{
+            Input::Ime::{
+                ImmGetContext, ImmReleaseContext, ImmSetCompositionWindow, CFS_FORCE_POSITION,
+                COMPOSITIONFORM,
+            },
+            Shell::ShellExecuteW,
+            WindowsAndMessaging::{
+                LoadCursorW, SetCursor, HCURSOR, IDC_APPSTARTING, IDC_ARROW, IDC_CROSS, IDC_HAND,
+                IDC_HELP, IDC_IBEAM, IDC_NO, IDC_SIZEALL, IDC_SIZENESW, IDC_SIZENS, IDC_SIZENWSE,
+                IDC_SIZEWE, IDC_WAIT, SW_SHOWNORMAL,
+            },
+        },
+    },
+};
+
+/// Parts of egui's platform output that have to be applied on the window's thread.
+/// Written by `present`, read by `wnd_proc`.
+pub struct PlatformRequests {
+    cursor: AtomicU8,
+    // the software cursor is drawn, the OS one has to stay hidden
+    hide_cursor: AtomicBool,
+    ime_pos: AtomicU64,
+    ime_dirty: AtomicBool,
+}
+
+impl Default for PlatformRequests {
+    fn default() -> Self {
+        Self {
+            cursor: AtomicU8::new(CursorIcon::Default as u8),
+            hide_cursor: AtomicBool::new(false),
+            ime_pos: AtomicU64::new(0),
+            ime_dirty: AtomicBool::new(false),
+        }
+    }
+}
+
+impl PlatformRequests {
+    pub fn update(
+        &self,
+        output: &PlatformOutput,
+        transform: Option<&ScreenTransform>,
+        pixels_per_point: f32,
+    ) {
+        self.cursor
+            .store(output.cursor_icon as u8, Ordering::Relaxed);
+
+        if let Some(pos) = output.text_cursor_pos {
+            let pos = (pos.to_vec2() * pixels_per_point).to_pos2();
+            let pos = transform.map_or(pos, |t| t.inverse(pos));
+            let packed = (pos.x as i32 as u32 as u64) << 32 | pos.y as i32 as u32 as u64;
+
+            if self.ime_pos.swap(packed, Ordering::Relaxed) != packed {
+                self.ime_dirty.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Hides the OS cursor on the next `WM_SETCURSOR` while the software cursor is drawn.
+    pub fn hide_cursor(&self, hide: bool) {
+        self.hide_cursor.store(hide, Ordering::Relaxed);
+    }
+
+    /// Handles `WM_SETCURSOR`, returns `false` if egui doesn't care about the cursor
+    /// and the game's one should be kept.
+    pub fn set_cursor(&self) -> bool {
+        if self.hide_cursor.load(Ordering::Relaxed) {
+            unsafe {
+                SetCursor(HCURSOR(0));
+            }
+            return true;
+        }
+
+        let icon = CursorIcon::ALL[self.cursor.load(Ordering::Relaxed) as usize];
+        if icon == CursorIcon::Default {
+            return false;
+        }
+
+        unsafe {
+            let cursor = get_cursor_name(icon)
+                .and_then(|name| LoadCursorW(None, name).ok())
+                .unwrap_or(HCURSOR(0));
+
+            SetCursor(cursor);
+        }
+
+        true
+    }
+
+    /// Moves the IME's candidate window next to egui's text cursor, if it moved since the last call.
+    pub fn apply_ime(&self, hwnd: HWND) {
+        if !self.ime_dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        let packed = self.ime_pos.load(Ordering::Relaxed);
+        let pos = Pos2::new((packed >> 32) as i32 as f32, packed as i32 as f32);
+
+        unsafe {
+            let himc = ImmGetContext(hwnd);
+            if himc.0 == 0 {
+                return;
+            }
+
+            let form = COMPOSITIONFORM {
+                dwStyle: CFS_FORCE_POSITION,
+                ptCurrentPos: POINT {
+                    x: pos.x as _,
+                    y: pos.y as _,
+                },
+                ..Default::default()
+            };
+
+            ImmSetCompositionWindow(himc, &form);
+            ImmReleaseContext(hwnd, himc);
+        }
+    }
+}
+
+/// Side effects waiting for the [`PlatformWorker`].
+enum PlatformTask {
+    Copy(Clipboard, String),
+    OpenUrl(String),
+}
+
+impl PlatformTask {
+    fn run(self) {
+        match self {
+            Self::Copy(clipboard, text) => clipboard.0.set(text),
+            Self::OpenUrl(url) => {
+                let wide: Vec<u16> = url.encode_utf16().chain(iter::once(0)).collect();
+                let result = unsafe {
+                    ShellExecuteW(
+                        HWND::default(),
+                        w!("open"),
+                        PCWSTR(wide.as_ptr()),
+                        PCWSTR::null(),
+                        PCWSTR::null(),
+                        SW_SHOWNORMAL,
+                    )
+                };
+
+                // anything up to 32 is an error code
+                if result.0 <= 32 {
+                    log_msg!(warn, "Failed to open {}: {}", url, result.0);
+                }
+            }
+        }
+    }
+}
+
+/// Tasks that can wait before the ones beyond are dropped, egui emits at most two per frame.
+const PLATFORM_QUEUE: usize = 16;
+
+/// Applies the parts of egui's platform output that can block for a while, writing the
+/// clipboard and opening links, on a thread of its own so `present` doesn't stall the game.
+/// The thread is spawned with the first task and exits when the worker is dropped.
+#[derive(Default)]
+pub struct PlatformWorker {
+    sender: Option<SyncSender<PlatformTask>>,
+}
+
+impl PlatformWorker {
+    pub fn apply(&mut self, output: &mut PlatformOutput, clipboard: &Clipboard) {
+        if !output.copied_text.is_empty() {
+            let text = std::mem::take(&mut output.copied_text);
+            self.send(PlatformTask::Copy(clipboard.clone(), text));
+        }
+
+        if let Some(open) = output.open_url.take() {
+            self.send(PlatformTask::OpenUrl(open.url));
+        }
+    }
+
+    fn send(&mut self, task: PlatformTask) {
+        if self.sender.is_none() {
+            self.sender = Self::spawn();
+        }
+
+        let Some(sender) = &self.sender else {
+            return task.run();
+        };
+
+        match sender.try_send(task) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                log_msg!(warn, "Platform worker is busy, dropped a clipboard or shell task");
+            }
+            Err(TrySendError::Disconnected(task)) => {
+                // the thread died with a panicking clipboard backend, a new one takes over
+                self.sender = None;
+                self.send(task);
+            }
+        }
+    }
+
+    fn spawn() -> Option<SyncSender<PlatformTask>> {
+        let (sender, receiver) = sync_channel::<PlatformTask>(PLATFORM_QUEUE);
+        let spawned = thread::Builder::new()
+            .name("egui-d3d11 platform".to_owned())
+            .spawn(move || {
+                for task in receiver {
+                    task.run();
+                }
+            });
+
+        match spawned {
+            Ok(_) => Some(sender),
+            Err(_e) => {
+                log_msg!(warn, "Failed to spawn platform worker, running tasks inline: {}", _e);
+                None
+            }
+        }
+    }
+}
+
+/// Draws egui's cursor at the pointer on top of everything else, for games that hide
+/// or lock the OS cursor. Only an arrow and an I-beam are drawn, other icons use the arrow.
+pub fn paint_software_cursor(ctx: &Context) {
+    let Some(pos) = ctx.pointer_latest_pos() else {
+        return;
+    };
+
+    let painter = ctx.layer_painter(LayerId::new(Order::Debug, Id::new("egui_d3d11_cursor")));
+    let outline = Stroke::new(1., Color32::WHITE);
+
+    match ctx.output(|output| output.cursor_icon) {
+        CursorIcon::None => {}
+        CursorIcon::Text | CursorIcon::VerticalText => {
+            let (top, bottom) = (pos - vec2(0., 8.), pos + vec2(0., 8.));
+            for (from, to) in [
+                (top, bottom),
+                (top - vec2(3., 0.), top + vec2(3., 0.)),
+                (bottom - vec2(3., 0.), bottom + vec2(3., 0.)),
+            ] {
+                painter.line_segment([from, to], Stroke::new(3., Color32::WHITE));
+                painter.line_segment([from, to], Stroke::new(1., Color32::BLACK));
+            }
+        }
+        _ => {
+            let arrow = [
+                (0., 0.),
+                (0., 17.),
+                (4., 13.),
+                (7., 20.),
+                (10., 19.),
+                (7., 12.),
+                (12., 12.),
+            ]
+            .into_iter()
+            .map(|(x, y)| pos + vec2(x, y))
+            .collect();
+
+            painter.add(Shape::convex_polygon(arrow, Color32::BLACK, outline));
+        }
+    }
+}
+
+/// `None` hides the cursor.
+fn get_cursor_name(icon: CursorIcon) -> Option<PCWSTR> {
+    Some(match icon {
+        CursorIcon::None => return None,
+        CursorIcon::Text | CursorIcon::VerticalText => IDC_IBEAM,
+        CursorIcon::PointingHand => IDC_HAND,
+        CursorIcon::Help => IDC_HELP,
+        CursorIcon::Wait => IDC_WAIT,
+        CursorIcon::Progress => IDC_APPSTARTING,
+        CursorIcon::Crosshair | CursorIcon::Cell => IDC_CROSS,
+        CursorIcon::NoDrop | CursorIcon::NotAllowed => IDC_NO,
+        CursorIcon::Move | CursorIcon::Grab | CursorIcon::Grabbing | CursorIcon::AllScroll => {
+            IDC_SIZEALL
+        }
+        CursorIcon::ResizeHorizontal
+        | CursorIcon::ResizeEast
+        | CursorIcon::ResizeWest
+        | CursorIcon::ResizeColumn => IDC_SIZEWE,
+        CursorIcon::ResizeVertical
+        | CursorIcon::ResizeNorth
+        | CursorIcon::ResizeSouth
+        | CursorIcon::ResizeRow => IDC_SIZENS,
+        CursorIcon::ResizeNeSw | CursorIcon::ResizeNorthEast | CursorIcon::ResizeSouthWest => {
+            IDC_SIZENESW
+        }
+        CursorIcon::ResizeNwSe | CursorIcon::ResizeNorthWest | CursorIcon::ResizeSouthEast => {
+            IDC_SIZENWSE
+        }
+        _ => IDC_ARROW,
+    })
+}
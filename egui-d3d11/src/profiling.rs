@@ -0,0 +1,44 @@
+//! Spans around the CPU side of a frame, for tracy, puffin or any other profiler.
+
+use once_cell::sync::OnceCell;
+
+/// Called with `true` when a span begins and `false` when it ends, always on the same thread
+/// and properly nested, so it maps to tracy's zones or puffin's scopes.
+pub type SpanCallback = fn(name: &'static str, begin: bool);
+
+static CALLBACK: OnceCell<SpanCallback> = OnceCell::new();
+
+/// Sets the callback receiving the spans of every app, returns `false` if one was set already.
+/// ```no_run
+/// egui_d3d11::set_span_callback(|name, begin| {
+///     if begin {
+///         println!("{name} {{");
+///     } else {
+///         println!("}}");
+///     }
+/// });
+/// ```
+pub fn set_span_callback(callback: SpanCallback) -> bool {
+    CALLBACK.set(callback).is_ok()
+}
+
+/// Ends the span when dropped.
+pub(crate) struct Span(&'static str);
+
+impl Span {
+    #[inline]
+    pub fn enter(name: &'static str) -> Option<Self> {
+        let callback = CALLBACK.get()?;
+        callback(name, true);
+        Some(Self(name))
+    }
+}
+
+impl Drop for Span {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(callback) = CALLBACK.get() {
+            callback(self.0, false);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Entry points taking plain pointers and integers, for hooks built on another version of
+//! `windows` or on `windows-sys`, whose types don't match the ones of this crate.
+
+use crate::{Config, DirectX11App, InputResult, RawMutex};
+use egui::Context;
+use std::ffi::c_void;
+use windows::{
+    core::{Vtable, HRESULT},
+    Win32::{
+        Foundation::{LPARAM, WPARAM},
+        Graphics::Dxgi::IDXGISwapChain,
+    },
+};
+
+/// # Safety
+/// `raw` has to point to an `IDXGISwapChain` or any interface derived from it.
+unsafe fn swap_chain(raw: &*mut c_void) -> &IDXGISwapChain {
+    if raw.is_null() {
+        panic_msg!("Swapchain pointer is null");
+    }
+
+    IDXGISwapChain::from_raw_borrowed(raw)
+}
+
+impl<T, R: RawMutex> DirectX11App<T, R> {
+    /// Same as [`Self::init_with_config`], with the swapchain as an `IDXGISwapChain*`.
+    /// # Safety
+    /// `swap_chain` has to point to an `IDXGISwapChain` or any interface derived from it.
+    pub unsafe fn init_with_config_raw(
+        &self,
+        swap_chain: *mut c_void,
+        ui: impl FnMut(&Context, &mut T) + 'static,
+        state: T,
+        config: Config,
+    ) {
+        self.init_with_config(self::swap_chain(&swap_chain), ui, state, config);
+    }
+
+    /// Same as [`Self::present`], with the swapchain as an `IDXGISwapChain*`.
+    /// # Safety
+    /// `swap_chain` has to point to an `IDXGISwapChain` or any interface derived from it.
+    pub unsafe fn present_raw(&self, swap_chain: *mut c_void) {
+        self.present(self::swap_chain(&swap_chain));
+    }
+
+    /// Same as [`Self::submit`], with the swapchain as an `IDXGISwapChain*`.
+    /// # Safety
+    /// `swap_chain` has to point to an `IDXGISwapChain` or any interface derived from it.
+    pub unsafe fn submit_raw(&self, swap_chain: *mut c_void) {
+        self.submit(self::swap_chain(&swap_chain));
+    }
+
+    /// Same as [`Self::resize_buffers`], with the swapchain as an `IDXGISwapChain*`
+    /// and the `HRESULT`s as plain integers.
+    /// # Safety
+    /// `swap_chain` has to point to an `IDXGISwapChain` or any interface derived from it.
+    pub unsafe fn resize_buffers_raw(
+        &self,
+        swap_chain: *mut c_void,
+        original: impl FnOnce() -> i32,
+    ) -> i32 {
+        self.resize_buffers(self::swap_chain(&swap_chain), || HRESULT(original()))
+            .0
+    }
+
+    /// Same as [`Self::wnd_proc`], with the message's parameters as plain integers.
+    #[inline]
+    pub fn wnd_proc_raw(&self, umsg: u32, wparam: usize, lparam: isize) -> InputResult {
+        self.wnd_proc(umsg, WPARAM(wparam), LPARAM(lparam))
+    }
+}
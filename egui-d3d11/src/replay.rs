@@ -0,0 +1,112 @@
+//! Draw data of captured frames, so rendering bugs reported from games can be
+//! reproduced without the game. See [`crate::DirectX11App::capture_frames`].
+
+use crate::compat;
+use egui::{epaint::ClippedPrimitive, Mesh, Rect, TexturesDelta};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+/// Everything the backend got from egui in one frame.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Size of the viewport in pixels.
+    pub screen_size: [f32; 2],
+    pub pixels_per_point: f32,
+    /// The first frame of a recording also recreates every texture that existed before it.
+    pub textures_delta: TexturesDelta,
+    /// Meshes in points with their clip rectangles, paint callbacks are left out.
+    pub meshes: Vec<(Rect, Mesh)>,
+}
+
+impl RecordedFrame {
+    pub(crate) fn new(
+        screen_size: [f32; 2],
+        pixels_per_point: f32,
+        textures_delta: TexturesDelta,
+        primitives: &[ClippedPrimitive],
+    ) -> Self {
+        let meshes = primitives
+            .iter()
+            .filter_map(|prim| {
+                compat::mesh(prim).map(|(clip_rect, mesh)| (clip_rect, mesh.clone()))
+            })
+            .collect();
+
+        Self {
+            screen_size,
+            pixels_per_point,
+            textures_delta,
+            meshes,
+        }
+    }
+
+    pub(crate) fn primitives(meshes: Vec<(Rect, Mesh)>) -> Vec<ClippedPrimitive> {
+        meshes
+            .into_iter()
+            .map(|(clip_rect, mesh)| compat::mesh_primitive(clip_rect, mesh))
+            .collect()
+    }
+}
+
+/// Reads the frames written by [`crate::DirectX11App::capture_frames`].
+pub fn load_recording(path: impl AsRef<Path>) -> io::Result<Vec<RecordedFrame>> {
+    let file = BufReader::new(File::open(path)?);
+    ron::de::from_reader(file).map_err(io::Error::other)
+}
+
+/// Collects the next frames and writes them once there are enough.
+pub struct Recorder {
+    path: PathBuf,
+    remaining: usize,
+    frames: Vec<RecordedFrame>,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf, count: usize) -> Self {
+        Self {
+            path,
+            remaining: count,
+            frames: Vec::with_capacity(count),
+        }
+    }
+
+    /// Nothing was recorded yet, the textures that already exist have to be added.
+    pub fn is_starting(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns `true` once the last frame was recorded and the file written.
+    pub fn record(&mut self, frame: RecordedFrame) -> bool {
+        self.frames.push(frame);
+        self.remaining = self.remaining.saturating_sub(1);
+
+        if self.remaining > 0 {
+            return false;
+        }
+
+        match self.write() {
+            Ok(()) => {
+                log_msg!(
+                    info,
+                    "Wrote {} frames to {:?}",
+                    self.frames.len(),
+                    self.path
+                );
+            }
+            Err(_e) => {
+                log_msg!(warn, "Failed to write {:?}: {}", self.path, _e);
+            }
+        }
+
+        true
+    }
+
+    fn write(&self) -> io::Result<()> {
+        let file = BufWriter::new(File::create(&self.path)?);
+        ron::ser::to_writer(file, &self.frames).map_err(io::Error::other)
+    }
+}
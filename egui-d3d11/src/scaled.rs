@@ -0,0 +1,94 @@
+use crate::{
+    error::{Error, ErrorContext},
+    mesh::GpuMesh,
+};
+use egui::{epaint::Mesh, pos2, Color32, Rect, TextureId};
+use windows::Win32::Graphics::{
+    Direct3D11::{
+        ID3D11Device, ID3D11RenderTargetView, ID3D11ShaderResourceView, ID3D11Texture2D,
+        D3D11_BIND_RENDER_TARGET, D3D11_BIND_SHADER_RESOURCE, D3D11_TEXTURE2D_DESC,
+        D3D11_USAGE_DEFAULT,
+    },
+    Dxgi::Common::{DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_SAMPLE_DESC},
+};
+
+/// Offscreen target the UI is drawn into at a fraction of the screen's resolution and then
+/// stretched over the backbuffer, see [`crate::Config::render_scale`].
+/// Holds premultiplied, gamma encoded colors, the composite pass does the color space conversion.
+pub struct ScaledTarget {
+    pub view: ID3D11RenderTargetView,
+    pub resource: ID3D11ShaderResourceView,
+    pub size: (u32, u32),
+}
+
+impl ScaledTarget {
+    /// Size of the target for a screen of `screen` pixels drawn at `scale`.
+    pub fn size_for(screen: (f32, f32), scale: f32) -> (u32, u32) {
+        (
+            (screen.0 * scale).ceil().max(1.) as u32,
+            (screen.1 * scale).ceil().max(1.) as u32,
+        )
+    }
+
+    pub unsafe fn new(dev: &ID3D11Device, (width, height): (u32, u32)) -> Result<Self, Error> {
+        log_msg!(debug, "Creating scaled target of {}x{}", width, height);
+
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_RENDER_TARGET | D3D11_BIND_SHADER_RESOURCE,
+            ..Default::default()
+        };
+
+        let mut texture: Option<ID3D11Texture2D> = None;
+        dev.CreateTexture2D(&desc, None, Some(&mut texture))
+            .context("Failed to create scaled target")?;
+        let texture = texture.context("Failed to create scaled target")?;
+
+        let mut view = None;
+        dev.CreateRenderTargetView(&texture, None, Some(&mut view))
+            .context("Failed to create render target view")?;
+
+        let mut resource = None;
+        dev.CreateShaderResourceView(&texture, None, Some(&mut resource))
+            .context("Failed to create shader resource view")?;
+
+        Ok(Self {
+            view: view.context("Failed to create render target view")?,
+            resource: resource.context("Failed to create shader resource view")?,
+            size: (width, height),
+        })
+    }
+
+    /// Quad that stretches the part of the target the UI was drawn into over the whole screen.
+    pub fn composite_mesh(&self, screen: (f32, f32), scale: f32) -> Option<GpuMesh> {
+        let screen_rect = Rect::from_min_max(pos2(0., 0.), pos2(screen.0, screen.1));
+        let uv = Rect::from_min_max(
+            pos2(0., 0.),
+            pos2(
+                screen.0 * scale / self.size.0 as f32,
+                screen.1 * scale / self.size.1 as f32,
+            ),
+        );
+
+        // the texture is bound directly, the id is never looked up
+        let mut mesh = Mesh::with_texture(TextureId::default());
+        mesh.add_rect_with_uv(screen_rect, uv, Color32::WHITE);
+        GpuMesh::from_mesh(mesh, screen_rect, 1.)
+    }
+}
+
+#[test]
+fn test_scaled_size() {
+    assert_eq!(ScaledTarget::size_for((1920., 1080.), 0.5), (960, 540));
+    assert_eq!(ScaledTarget::size_for((1001., 3.), 0.5), (501, 2));
+    assert_eq!(ScaledTarget::size_for((1., 1.), 0.25), (1, 1));
+}
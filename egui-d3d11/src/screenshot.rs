@@ -0,0 +1,152 @@
+use crate::capture::CapturedFrame;
+use egui::{
+    pos2, vec2, Align2, Area, Color32, Context, CursorIcon, FontId, Id, Key, Order, Pos2, Rect,
+    Sense, Stroke,
+};
+
+/// Drag-to-select mode of [`crate::DirectX11App::start_region_capture`].
+#[derive(Default)]
+pub(crate) struct RegionCapture {
+    state: State,
+    captured: Option<CapturedFrame>,
+}
+
+#[derive(Default)]
+enum State {
+    #[default]
+    Idle,
+    /// Waiting for or following the drag, in points.
+    Selecting(Option<Pos2>),
+    /// `[left, top, right, bottom]` pixels, read back before the next frame's UI is drawn.
+    Selected([u32; 4]),
+}
+
+impl RegionCapture {
+    pub fn start(&mut self) {
+        self.state = State::Selecting(None);
+    }
+
+    pub fn cancel(&mut self) {
+        self.state = State::Idle;
+    }
+
+    pub fn is_selecting(&self) -> bool {
+        matches!(self.state, State::Selecting(_))
+    }
+
+    /// The region to read back, once the drag ended.
+    pub fn take_selection(&mut self) -> Option<[u32; 4]> {
+        match self.state {
+            State::Selected(region) => {
+                self.state = State::Idle;
+                Some(region)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn finish(&mut self, frame: CapturedFrame) {
+        self.captured = Some(frame);
+    }
+
+    pub fn take_captured(&mut self) -> Option<CapturedFrame> {
+        self.captured.take()
+    }
+
+    /// Dims the screen outside of the dragged rectangle, `origin` is the pixel the UI's screen
+    /// starts at. Escape cancels.
+    pub fn show(&mut self, ctx: &Context, origin: (f32, f32)) {
+        let State::Selecting(start) = &mut self.state else {
+            return;
+        };
+        if ctx.input(|input| input.key_pressed(Key::Escape)) {
+            self.state = State::Idle;
+            return;
+        }
+
+        let screen = ctx.screen_rect();
+        let response = Area::new(Id::new("egui_d3d11_region_capture"))
+            .order(Order::Foreground)
+            .fixed_pos(screen.min)
+            .show(ctx, |ui| ui.allocate_rect(screen, Sense::drag()))
+            .inner;
+        ctx.set_cursor_icon(CursorIcon::Crosshair);
+
+        let (press, pos) = ctx.input(|input| {
+            (input.pointer.press_origin(), input.pointer.interact_pos())
+        });
+        if response.drag_started() {
+            *start = press;
+        }
+        let selection = match (*start, pos) {
+            (Some(start), Some(pos)) => Some(Rect::from_two_pos(start, pos)),
+            _ => None,
+        };
+
+        let painter = ctx.layer_painter(response.layer_id);
+        let shade = Color32::from_black_alpha(128);
+        match selection {
+            Some(rect) => {
+                for outside in [
+                    Rect::from_min_max(screen.min, pos2(screen.right(), rect.top())),
+                    Rect::from_min_max(pos2(screen.left(), rect.bottom()), screen.max),
+                    Rect::from_min_max(pos2(screen.left(), rect.top()), rect.left_bottom()),
+                    Rect::from_min_max(rect.right_top(), pos2(screen.right(), rect.bottom())),
+                ] {
+                    painter.rect_filled(outside, 0., shade);
+                }
+                painter.rect_stroke(rect, 0., Stroke::new(1., Color32::WHITE));
+
+                let pixels = rect.size() * ctx.pixels_per_point();
+                painter.text(
+                    rect.left_bottom() + vec2(0., 4.),
+                    Align2::LEFT_TOP,
+                    format!("{} x {}", pixels.x.round(), pixels.y.round()),
+                    FontId::monospace(12.),
+                    Color32::WHITE,
+                );
+            }
+            None => {
+                painter.rect_filled(screen, 0., shade);
+                painter.text(
+                    screen.center(),
+                    Align2::CENTER_CENTER,
+                    "Drag to capture a region, Escape to cancel",
+                    FontId::proportional(16.),
+                    Color32::WHITE,
+                );
+            }
+        }
+
+        if response.drag_released() {
+            self.state = match selection {
+                Some(rect) if rect.width() >= 1. && rect.height() >= 1. => {
+                    State::Selected(to_pixels(rect, ctx.pixels_per_point(), origin))
+                }
+                // a click without dragging, wait for a proper drag
+                _ => State::Selecting(None),
+            };
+        }
+    }
+}
+
+fn to_pixels(rect: Rect, ppp: f32, (x, y): (f32, f32)) -> [u32; 4] {
+    let pixel = |pos: Pos2| pos2(pos.x * ppp + x, pos.y * ppp + y).round();
+    let (min, max) = (pixel(rect.min), pixel(rect.max));
+    [min.x as u32, min.y as u32, max.x as u32, max.y as u32]
+}
+
+#[test]
+fn test_region_to_pixels() {
+    let rect = Rect::from_min_max(pos2(10., 20.), pos2(30.4, 40.6));
+    assert_eq!(to_pixels(rect, 1., (0., 0.)), [10, 20, 30, 41]);
+    assert_eq!(to_pixels(rect, 2., (100., 0.)), [120, 40, 161, 81]);
+
+    let mut capture = RegionCapture::default();
+    capture.start();
+    assert!(capture.is_selecting());
+    assert_eq!(capture.take_selection(), None);
+    capture.state = State::Selected([1, 2, 3, 4]);
+    assert_eq!(capture.take_selection(), Some([1, 2, 3, 4]));
+    assert!(!capture.is_selecting());
+}
@@ -0,0 +1,127 @@
+use crate::config::Config;
+use egui::{Key, Modifiers, Rgba};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Key combination stored in [`OverlaySettings::hotkeys`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HotkeyBinding {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+/// Overlay options that survive restarts, stored per game, see [`Config::settings`].
+/// Fields missing from the file keep their defaults, so it can be written by hand.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    /// Whether the UI is visible, see [`Config::visible`].
+    pub visible: bool,
+    /// See [`Config::ui_scale`].
+    pub scale: f32,
+    /// See [`Config::clear_color`].
+    pub clear_color: Option<Rgba>,
+    /// See [`Config::toggle_key`].
+    pub toggle_key: Option<Key>,
+    /// Bindings of the tool's own actions by name, the app doesn't use them itself. Register
+    /// them with [`crate::DirectX11App::register_hotkey`].
+    pub hotkeys: BTreeMap<String, HotkeyBinding>,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
+
+impl OverlaySettings {
+    /// The options `config` currently has, without any hotkeys.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            visible: config.visible,
+            scale: config.ui_scale,
+            clear_color: config.clear_color,
+            toggle_key: config.toggle_key,
+            hotkeys: BTreeMap::new(),
+        }
+    }
+
+    /// Overrides the options of `config` with these.
+    pub fn apply(&self, config: Config) -> Config {
+        config
+            .visible(self.visible)
+            .ui_scale(self.scale)
+            .clear_color(self.clear_color)
+            .toggle_key(self.toggle_key)
+    }
+
+    /// Reads the settings saved by a previous session. Missing or unreadable files give `None`.
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = fs::read_to_string(path).ok()?;
+
+        match ron::from_str(&text) {
+            Ok(settings) => Some(settings),
+            Err(_e) => {
+                log_msg!(warn, "Failed to load {:?}: {}", path, _e);
+                None
+            }
+        }
+    }
+
+    /// Writes the settings into `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text =
+            ron::ser::to_string_pretty(self, Default::default()).map_err(io::Error::other)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+}
+
+/// `%APPDATA%\<tool>\<game>.ron`, keyed by the file name of the game's executable.
+/// `None` if either of them can't be found.
+pub fn default_path(tool: &str) -> Option<PathBuf> {
+    let appdata = env::var_os("APPDATA")?;
+    let exe = env::current_exe().ok()?;
+    let game = exe.file_stem()?;
+
+    let mut path = PathBuf::from(appdata);
+    path.push(tool);
+    path.push(game);
+    path.set_extension("ron");
+    Some(path)
+}
+
+#[test]
+fn test_settings_roundtrip() {
+    let mut settings = OverlaySettings {
+        scale: 1.25,
+        clear_color: Some(Rgba::BLACK),
+        toggle_key: Some(Key::Insert),
+        ..Default::default()
+    };
+    settings.hotkeys.insert(
+        "esp".to_owned(),
+        HotkeyBinding {
+            modifiers: Modifiers::CTRL,
+            key: Key::E,
+        },
+    );
+
+    let text = ron::to_string(&settings).unwrap();
+    assert_eq!(ron::from_str::<OverlaySettings>(&text).unwrap(), settings);
+
+    // older files lack newer fields
+    let partial = ron::from_str::<OverlaySettings>("(visible: false)").unwrap();
+    assert!(!partial.visible);
+    assert_eq!(partial.scale, 1.);
+
+    let config = settings.apply(Config::default());
+    assert_eq!(OverlaySettings::from_config(&config).toggle_key, Some(Key::Insert));
+}
@@ -1,168 +1,251 @@
+use crate::{
+    config::{ColorBlending, ShaderModel, ShaderOptimization, ShaderSlots},
+    error::{Error, ErrorContext},
+    hdr::ColorSpace,
+    Config,
+};
 use std::{io::Write, slice::from_raw_parts};
-
 use windows::{
     core::PCSTR,
     Win32::Graphics::{
         Direct3D::{
-            Fxc::{D3DCompile, D3DCOMPILE_DEBUG, D3DCOMPILE_ENABLE_STRICTNESS},
-            ID3DBlob,
+            Fxc::{
+                D3DCompile, D3DCOMPILE_DEBUG, D3DCOMPILE_ENABLE_STRICTNESS,
+                D3DCOMPILE_OPTIMIZATION_LEVEL0, D3DCOMPILE_OPTIMIZATION_LEVEL1,
+                D3DCOMPILE_OPTIMIZATION_LEVEL3, D3DCOMPILE_SKIP_OPTIMIZATION,
+            },
+            ID3DBlob, D3D_FEATURE_LEVEL_11_0, D3D_SHADER_MACRO,
         },
         Direct3D11::{ID3D11Device, ID3D11PixelShader, ID3D11VertexShader},
     },
 };
 
-trait Shader {
+trait Shader: Sized {
     const ENTRY: PCSTR;
-    const TARGET: PCSTR;
+    /// Stage prefix of the compile target, the shader model is appended.
+    const STAGE: &'static str;
 
-    unsafe fn create_shader(device: &ID3D11Device, blob: &ShaderData) -> Self;
+    unsafe fn create_shader(device: &ID3D11Device, bytecode: &[u8]) -> Result<Self, Error>;
 }
 
 impl Shader for ID3D11VertexShader {
     const ENTRY: PCSTR = pc_str!("vs_main");
-    const TARGET: PCSTR = pc_str!("vs_5_0");
+    const STAGE: &'static str = "vs";
 
-    unsafe fn create_shader(device: &ID3D11Device, blob: &ShaderData) -> Self {
+    unsafe fn create_shader(device: &ID3D11Device, bytecode: &[u8]) -> Result<Self, Error> {
         let mut shader: Option<ID3D11VertexShader> = None;
 
-        match blob {
-            ShaderData::EmbeddedData(arr) => {
-                expect!(
-                    device.CreateVertexShader(arr, None, Some(&mut shader)),
-                    "Failed to create vertex shader"
-                );
-            }
-            ShaderData::CompiledBlob(blob) => {
-                expect!(
-                    device.CreateVertexShader(
-                        from_raw_parts(blob.GetBufferPointer() as _, blob.GetBufferSize()),
-                        None,
-                        Some(&mut shader)
-                    ),
-                    "Failed to create vertex shader"
-                );
-            }
-        }
+        device
+            .CreateVertexShader(bytecode, None, Some(&mut shader))
+            .context("Failed to create vertex shader")?;
 
-        expect!(shader, "Failed to create vertex shader")
+        shader.context("Failed to create vertex shader")
     }
 }
 
 impl Shader for ID3D11PixelShader {
     const ENTRY: PCSTR = pc_str!("ps_main");
-    const TARGET: PCSTR = pc_str!("ps_5_0");
+    const STAGE: &'static str = "ps";
 
-    unsafe fn create_shader(device: &ID3D11Device, blob: &ShaderData) -> Self {
+    unsafe fn create_shader(device: &ID3D11Device, bytecode: &[u8]) -> Result<Self, Error> {
         let mut shader: Option<ID3D11PixelShader> = None;
 
-        match blob {
-            ShaderData::EmbeddedData(arr) => {
-                expect!(
-                    device.CreatePixelShader(arr, None, Some(&mut shader)),
-                    "Failed to create pixel shader"
-                )
-            }
-            ShaderData::CompiledBlob(blob) => {
-                expect!(
-                    device.CreatePixelShader(
-                        from_raw_parts(blob.GetBufferPointer() as _, blob.GetBufferSize()),
-                        None,
-                        Some(&mut shader)
-                    ),
-                    "Failed to create pixel shader"
-                )
-            }
-        }
+        device
+            .CreatePixelShader(bytecode, None, Some(&mut shader))
+            .context("Failed to create pixel shader")?;
 
-        expect!(shader, "Failed to create pixel shader")
+        shader.context("Failed to create pixel shader")
     }
 }
 
-pub enum ShaderData {
-    EmbeddedData(&'static [u8]),
-    CompiledBlob(ID3DBlob),
-}
-
 pub struct CompiledShaders {
     pub vertex: ID3D11VertexShader,
     pub pixel: ID3D11PixelShader,
-    cache: ShaderData,
+    /// Pixel shader without color space conversion, for the scaled target.
+    pub gamma_pixel: Option<ID3D11PixelShader>,
+    pub color_space: ColorSpace,
+    /// Colors are written linear, for `_SRGB` render target views.
+    pub linear: bool,
+    cache: ID3DBlob,
+    options: CompileOptions,
 }
 
-impl CompiledShaders {
-    pub fn new(device: &ID3D11Device) -> Self {
-        if cfg!(feature = "force-compile") {
-            let (vcache, vertex) = Self::compile_shader::<ID3D11VertexShader>(device);
-            let (_pcache, pixel) = Self::compile_shader::<ID3D11PixelShader>(device);
-
-            if cfg!(feature = "save-blob") {
-                unsafe {
-                    std::fs::OpenOptions::new()
-                        .write(true)
-                        .read(true)
-                        .create(true)
-                        .open("vertex.bin")
-                        .unwrap()
-                        .write_all(std::slice::from_raw_parts(
-                            vcache.GetBufferPointer() as *mut u8,
-                            vcache.GetBufferSize(),
-                        ))
-                        .unwrap();
-
-                    std::fs::OpenOptions::new()
-                        .write(true)
-                        .read(true)
-                        .create(true)
-                        .open("pixel.bin")
-                        .unwrap()
-                        .write_all(std::slice::from_raw_parts(
-                            _pcache.GetBufferPointer() as *mut u8,
-                            _pcache.GetBufferSize(),
-                        ))
-                        .unwrap();
-                }
-            }
+/// Target and compiler flags from the config, kept for recompiling the pixel shader.
+#[derive(Clone, Copy)]
+struct CompileOptions {
+    model: ShaderModel,
+    flags: u32,
+    linear_blending: bool,
+    slots: ShaderSlots,
+}
 
-            Self {
-                vertex,
-                pixel,
-                cache: ShaderData::CompiledBlob(vcache),
-            }
-        } else {
-            unsafe {
-                let cache = ShaderData::EmbeddedData(include_bytes!("vertex.bin"));
-                let vertex = ID3D11VertexShader::create_shader(device, &cache);
-                let pixel = ID3D11PixelShader::create_shader(
-                    device,
-                    &ShaderData::EmbeddedData(include_bytes!("pixel.bin")),
-                );
-
-                Self {
-                    cache,
-                    vertex,
-                    pixel,
+impl CompileOptions {
+    fn new(device: &ID3D11Device, config: &Config) -> Self {
+        let mut flags = D3DCOMPILE_ENABLE_STRICTNESS
+            | match config.shader_optimization {
+                ShaderOptimization::Skip => D3DCOMPILE_SKIP_OPTIMIZATION,
+                ShaderOptimization::Level0 => D3DCOMPILE_OPTIMIZATION_LEVEL0,
+                ShaderOptimization::Level1 => D3DCOMPILE_OPTIMIZATION_LEVEL1,
+                // the headers define it as both bits, the bindings leave it out
+                ShaderOptimization::Level2 => {
+                    D3DCOMPILE_OPTIMIZATION_LEVEL0 | D3DCOMPILE_OPTIMIZATION_LEVEL3
                 }
-            }
+                ShaderOptimization::Level3 => D3DCOMPILE_OPTIMIZATION_LEVEL3,
+            };
+        if config.shader_debug {
+            flags |= D3DCOMPILE_DEBUG;
+        }
+
+        Self {
+            model: config.shader_model.unwrap_or_else(|| shader_model(device)),
+            flags,
+            linear_blending: config.color_blending == Some(ColorBlending::Linear),
+            slots: config.shader_slots,
         }
     }
 
-    pub fn bytecode(&self) -> &[u8] {
-        match &self.cache {
-            ShaderData::EmbeddedData(arr) => arr,
-            ShaderData::CompiledBlob(blob) => unsafe {
-                from_raw_parts(blob.GetBufferPointer() as _, blob.GetBufferSize())
-            },
+    fn target(&self, stage: &str) -> String {
+        let model = match self.model {
+            ShaderModel::Sm4 => "4_0",
+            ShaderModel::Sm5 => "5_0",
+        };
+        format!("{stage}_{model}\0")
+    }
+}
+
+impl CompiledShaders {
+    pub fn new(
+        device: &ID3D11Device,
+        config: &Config,
+        color_space: ColorSpace,
+        linear: bool,
+    ) -> Result<Self, Error> {
+        let options = CompileOptions::new(device, config);
+        let (vcache, vertex) = Self::compile_shader::<ID3D11VertexShader>(device, options, &[])?;
+        let (pcache, pixel) = Self::compile_shader::<ID3D11PixelShader>(
+            device,
+            options,
+            &pixel_defines(color_space, linear, options.linear_blending),
+        )?;
+
+        if cfg!(feature = "save-blob") {
+            save_blob("vertex.bin", &vcache);
+            save_blob("pixel.bin", &pcache);
         }
+
+        Ok(Self {
+            cache: vcache,
+            color_space,
+            linear,
+            vertex,
+            pixel,
+            gamma_pixel: None,
+            options,
+        })
     }
 
-    fn compile_shader<S: Shader>(device: &ID3D11Device) -> (ID3DBlob, S) {
-        const SHADER_TEXT: &str = include_str!("shader.hlsl");
+    /// Recompiles only the pixel shader, vertex shader and its input layout stay valid.
+    pub fn set_color_space(
+        &mut self,
+        device: &ID3D11Device,
+        color_space: ColorSpace,
+        linear: bool,
+    ) -> Result<(), Error> {
+        let (_, pixel) = Self::compile_shader::<ID3D11PixelShader>(
+            device,
+            self.options,
+            &pixel_defines(color_space, linear, self.options.linear_blending),
+        )?;
 
-        let mut flags = D3DCOMPILE_ENABLE_STRICTNESS;
-        if cfg!(debug_assertions) {
-            flags |= D3DCOMPILE_DEBUG;
+        self.pixel = pixel;
+        self.color_space = color_space;
+        self.linear = linear;
+
+        Ok(())
+    }
+
+    /// Compiles [`Self::gamma_pixel`] if it doesn't exist yet.
+    pub fn prepare_gamma_pixel(&mut self, device: &ID3D11Device) -> Result<(), Error> {
+        if self.gamma_pixel.is_none() {
+            let (_, pixel) = Self::compile_shader::<ID3D11PixelShader>(
+                device,
+                self.options,
+                &pixel_defines(ColorSpace::Srgb, false, self.options.linear_blending),
+            )?;
+            self.gamma_pixel = Some(pixel);
         }
 
+        Ok(())
+    }
+
+    /// Vertex colors and texels are decoded before they are multiplied, see [`ColorBlending`].
+    pub fn linear_blending(&self) -> bool {
+        self.options.linear_blending
+    }
+
+    /// Recompiles the pixel shaders for the other [`ColorBlending`].
+    pub fn set_linear_blending(
+        &mut self,
+        device: &ID3D11Device,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        self.options.linear_blending = enabled;
+        self.gamma_pixel = None;
+        self.set_color_space(device, self.color_space, self.linear)
+    }
+
+    /// Slots the shaders were compiled for, the resources have to be bound to these.
+    pub fn slots(&self) -> ShaderSlots {
+        self.options.slots
+    }
+
+    pub fn bytecode(&self) -> &[u8] {
+        blob_bytes(&self.cache)
+    }
+
+    fn compile_shader<S: Shader>(
+        device: &ID3D11Device,
+        options: CompileOptions,
+        defines: &[&str],
+    ) -> Result<(ID3DBlob, S), Error> {
+        const SHADER_TEXT: &str = include_str!("shader.hlsl");
+
+        let target = options.target(S::STAGE);
+
+        log_msg!(
+            debug,
+            "Compiling {} for {} with {:?}",
+            std::any::type_name::<S>(),
+            target.trim_end_matches('\0'),
+            defines
+        );
+
+        let names = defines
+            .iter()
+            .map(|name| format!("{name}\0"))
+            .collect::<Vec<_>>();
+
+        let slots = options.slots;
+        let registers = [
+            (pc_str!("TEXTURE_REGISTER"), format!("t{}\0", slots.texture)),
+            (pc_str!("SAMPLER_REGISTER"), format!("s{}\0", slots.sampler)),
+            (pc_str!("CONSTANT_REGISTER"), format!("b{}\0", slots.constants)),
+        ];
+
+        // Macro list has to be terminated by a null entry.
+        let macros = names
+            .iter()
+            .map(|name| D3D_SHADER_MACRO {
+                Name: PCSTR(name.as_ptr()),
+                Definition: pc_str!("1"),
+            })
+            .chain(registers.iter().map(|(name, register)| D3D_SHADER_MACRO {
+                Name: *name,
+                Definition: PCSTR(register.as_ptr()),
+            }))
+            .chain(std::iter::once(D3D_SHADER_MACRO::default()))
+            .collect::<Vec<_>>();
+
         let mut code = None;
         let mut error = None;
 
@@ -171,34 +254,68 @@ impl CompiledShaders {
                 SHADER_TEXT.as_ptr() as _,
                 SHADER_TEXT.len(),
                 None,
-                None,
+                Some(macros.as_ptr()),
                 None,
                 S::ENTRY,
-                S::TARGET,
-                flags,
+                PCSTR(target.as_ptr()),
+                options.flags,
                 0,
                 &mut code,
                 Some(&mut error),
             )
             .is_err()
             {
-                if !cfg!(feature = "no-msgs") {
-                    panic!(
-                        "{}",
-                        std::str::from_utf8_unchecked(std::slice::from_raw_parts(
-                            error.as_ref().unwrap().GetBufferPointer() as *const u8,
-                            error.as_ref().unwrap().GetBufferSize(),
-                        ))
-                    );
-                } else {
-                    panic!();
-                }
+                Err(Error::Shader(
+                    error
+                        .map(|e| String::from_utf8_lossy(blob_bytes(&e)).into_owned())
+                        .unwrap_or_default(),
+                ))
             } else {
-                (
-                    code.clone().unwrap(),
-                    S::create_shader(device, &ShaderData::CompiledBlob(code.unwrap())),
-                )
+                let code = code.context("Failed to compile shader")?;
+                let shader = S::create_shader(device, blob_bytes(&code))?;
+
+                Ok((code, shader))
             }
         }
     }
 }
+
+/// Shader model 5 needs feature level 11, older iGPUs and some virtual machines only have 10.x.
+fn shader_model(device: &ID3D11Device) -> ShaderModel {
+    if unsafe { device.GetFeatureLevel() }.0 >= D3D_FEATURE_LEVEL_11_0.0 {
+        ShaderModel::Sm5
+    } else {
+        ShaderModel::Sm4
+    }
+}
+
+fn pixel_defines(
+    color_space: ColorSpace,
+    linear: bool,
+    linear_blending: bool,
+) -> Vec<&'static str> {
+    let mut defines = color_space.shader_defines().to_vec();
+    if linear_blending {
+        defines.push("LINEAR_BLENDING");
+    }
+    // HDR formats have no sRGB views, they are linear already
+    if linear && color_space == ColorSpace::Srgb {
+        defines.push("OUTPUT_LINEAR");
+    }
+    defines
+}
+
+fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+    unsafe { from_raw_parts(blob.GetBufferPointer() as _, blob.GetBufferSize()) }
+}
+
+fn save_blob(path: &str, blob: &ID3DBlob) {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .unwrap()
+        .write_all(blob_bytes(blob))
+        .unwrap();
+}
@@ -1,7 +1,23 @@
+cbuffer constants : register(CONSTANT_REGISTER) {
+  // client size in pixels, used to convert positions into NDC
+  float2 screen_size;
+  // brightness of egui's white in nits, only used by HDR outputs
+  float sdr_white_level;
+  // amplitude of the dither noise, one quantization step of the target, 0 disables it
+  float dither;
+  // replaces the output color when alpha isn't 0, used by the wireframe view
+  float4 tint;
+  // user adjustment of the gamma encoded colors, both 1 leave them alone
+  float gamma_exponent;
+  float brightness;
+};
+
 struct vs_in {
   float2 position : POSITION;
   float2 uv : TEXCOORD;
   float4 color : COLOR;
+  // per instance, zero for meshes that aren't drawn instanced
+  float2 offset : OFFSET;
 };
 
 struct vs_out {
@@ -12,19 +28,81 @@ struct vs_out {
 
 vs_out vs_main(vs_in input) {
   vs_out output;
-  output.clip = float4(input.position, 0.0, 1.0);
+  float2 position = input.position + input.offset;
+  output.clip = float4(
+    2.0 * position.x / screen_size.x - 1.0,
+    1.0 - 2.0 * position.y / screen_size.y,
+    0.0,
+    1.0
+  );
   output.uv = input.uv;
   output.color = input.color;
 
   return output;
 }
 
-sampler sampler0;
-Texture2D texture0;
+// the registers are defined by the compiler, see `ShaderSlots`
+sampler sampler0 : register(SAMPLER_REGISTER);
+Texture2D texture0 : register(TEXTURE_REGISTER);
+
+float3 srgb_to_linear(float3 color) {
+  return color <= 0.04045 ? color / 12.92 : pow((color + 0.055) / 1.055, 2.4);
+}
+
+float3 linear_to_srgb(float3 color) {
+  return color <= 0.0031308 ? color * 12.92 : 1.055 * pow(color, 1.0 / 2.4) - 0.055;
+}
+
+float3 linear_to_pq(float3 nits) {
+  float3 y = pow(saturate(nits / 10000.0), 0.1593017578125);
+  return pow((0.8359375 + 18.8515625 * y) / (1.0 + 18.6875 * y), 78.84375);
+}
+
+static const float3x3 rec709_to_rec2020 = {
+  0.6274040, 0.3292820, 0.0433136,
+  0.0690970, 0.9195400, 0.0113612,
+  0.0163916, 0.0880132, 0.8955950,
+};
+
+// interleaved gradient noise, cheap and without visible patterns at this amplitude
+float dither_noise(float2 pixel) {
+  return frac(52.9829189 * frac(dot(pixel, float2(0.06711056, 0.00583715)))) - 0.5;
+}
 
 float4 ps_main(vs_out input) : SV_TARGET {
-  float4 output = pow(input.color, 1.0 / 2.2);
-  // keep the alpha channel intact, we shouldn't gamma correct it
-  output[3] = input.color[3];
-  return output * texture0.Sample(sampler0, input.uv);
-}
\ No newline at end of file
+  // vertex colors arrive as UNORM Color32, they are already gamma encoded
+  float4 texel = texture0.Sample(sampler0, input.uv);
+#if defined(LINEAR_BLENDING)
+  // multiplied in linear space, encoded again for the adjustments below
+  float4 output = float4(srgb_to_linear(input.color.rgb), input.color.a)
+    * float4(srgb_to_linear(texel.rgb), texel.a);
+  output.rgb = linear_to_srgb(output.rgb);
+#else
+  float4 output = input.color * texel;
+#endif
+
+  // colors are premultiplied, the curve only applies to the color itself
+  if ((gamma_exponent != 1.0 || brightness != 1.0) && output.a > 0.0) {
+    output.rgb = pow(saturate(output.rgb / output.a), gamma_exponent) * brightness * output.a;
+  }
+
+  if (tint.a > 0.0) {
+    output = float4(tint.rgb * tint.a, tint.a);
+  }
+
+  // breaks up banding of dark translucent gradients on 8 bit targets,
+  // scaled by alpha so premultiplied output stays untouched where nothing is drawn
+  output.rgb += dither_noise(input.clip.xy) * dither * output.a;
+
+#if defined(OUTPUT_SCRGB)
+  // scRGB is linear with 1.0 being 80 nits
+  output.rgb = srgb_to_linear(output.rgb) * (sdr_white_level / 80.0);
+#elif defined(OUTPUT_HDR10)
+  output.rgb = linear_to_pq(mul(rec709_to_rec2020, srgb_to_linear(output.rgb)) * sdr_white_level);
+#elif defined(OUTPUT_LINEAR)
+  // the _SRGB view encodes on write, blending happens in linear space
+  output.rgb = srgb_to_linear(output.rgb);
+#endif
+
+  return output;
+}
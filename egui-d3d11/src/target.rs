@@ -0,0 +1,239 @@
+use crate::{
+    error::{Error, ErrorContext},
+    format::{is_srgb, srgb_format, typed_format, unorm_format},
+};
+use windows::{
+    core::{Interface, Vtable},
+    Win32::Graphics::{
+        Direct3D11::{
+            ID3D11Device, ID3D11DeviceContext, ID3D11RenderTargetView, ID3D11Texture2D,
+            D3D11_RENDER_TARGET_VIEW_DESC, D3D11_RENDER_TARGET_VIEW_DESC_0,
+            D3D11_RTV_DIMENSION_TEXTURE2D, D3D11_RTV_DIMENSION_TEXTURE2DMS, D3D11_TEX2D_RTV,
+            D3D11_TEXTURE2D_DESC,
+        },
+        Dxgi::Common::DXGI_FORMAT,
+    },
+};
+
+/// Render target view of the backbuffer, remembers what it was created for so it can be
+/// recreated when the buffer or the wanted format changes.
+pub struct RenderTarget {
+    pub view: ID3D11RenderTargetView,
+    /// The view encodes linear colors into sRGB on write.
+    pub srgb: bool,
+    /// Format of the view, the buffer's own one if it's typeless.
+    pub format: DXGI_FORMAT,
+    buffer: usize,
+    requested: Option<bool>,
+    // Whether the game's own view was seen, only matters when `requested` is `None`.
+    settled: bool,
+}
+
+/// Everything a frame is drawn into, the swapchain's buffer or a view handed in by the user.
+pub struct FrameTarget {
+    pub view: ID3D11RenderTargetView,
+    pub srgb: bool,
+    pub format: DXGI_FORMAT,
+    pub texture: ID3D11Texture2D,
+    pub size: (f32, f32),
+    /// Top left corner of the area the UI is confined to, `size` is then the area's size
+    /// instead of the one the UI is laid out on. See [`crate::DirectX11App::present_with_size`].
+    pub origin: Option<(f32, f32)>,
+}
+
+impl FrameTarget {
+    /// Target of [`crate::DirectX11App::paint_to`], `view` has to be of a 2D texture.
+    pub unsafe fn from_view(
+        view: &ID3D11RenderTargetView,
+        (width, height): (u32, u32),
+    ) -> Result<Self, Error> {
+        let mut desc = D3D11_RENDER_TARGET_VIEW_DESC::default();
+        view.GetDesc(&mut desc);
+
+        let texture = view
+            .GetResource()
+            .and_then(|resource| resource.cast::<ID3D11Texture2D>())
+            .context("Render target view isn't of a 2D texture")?;
+
+        Ok(Self {
+            view: view.clone(),
+            srgb: is_srgb(desc.Format),
+            format: desc.Format,
+            texture,
+            size: (width as f32, height as f32),
+            origin: None,
+        })
+    }
+}
+
+/// Views of the buffers a swapchain rotates through. D3D11 chains always hand out
+/// the same buffer, D3D12 ones have one view per buffer.
+#[derive(Default)]
+pub struct RenderTargets(Vec<RenderTarget>);
+
+impl RenderTargets {
+    /// View of `backbuffer`, created if there is none yet or if the old one is outdated.
+    pub unsafe fn get(
+        &mut self,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        backbuffer: &ID3D11Texture2D,
+        srgb_view: Option<bool>,
+    ) -> Result<&RenderTarget, Error> {
+        let buffer = backbuffer.as_raw() as usize;
+        let index = self.0.iter().position(|rt| rt.buffer == buffer);
+
+        let index = match index {
+            Some(index) if !self.0[index].is_outdated(ctx, srgb_view) => index,
+            index => {
+                log_msg!(debug, "Creating render target view for {:#x}", buffer);
+                let target = RenderTarget::new(dev, backbuffer, srgb_view)?;
+
+                match index {
+                    Some(index) => {
+                        self.0[index] = target;
+                        index
+                    }
+                    None => {
+                        self.0.push(target);
+                        self.0.len() - 1
+                    }
+                }
+            }
+        };
+
+        Ok(&self.0[index])
+    }
+
+    /// Drops all views, has to happen before the swapchain is resized.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl RenderTarget {
+    /// `srgb_view` forces an `_SRGB` view or a plain one, `None` follows the game's own view.
+    unsafe fn new(
+        dev: &ID3D11Device,
+        backbuffer: &ID3D11Texture2D,
+        srgb_view: Option<bool>,
+    ) -> Result<Self, Error> {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        backbuffer.GetDesc(&mut desc);
+
+        let detected = match srgb_view {
+            Some(srgb) => Some(srgb),
+            None => dev
+                .GetImmediateContext()
+                .ok()
+                .and_then(|ctx| game_view_srgb(&ctx, backbuffer.as_raw() as usize)),
+        };
+        let srgb = detected.unwrap_or_else(|| is_srgb(desc.Format));
+
+        // Views of typed buffers may only differ in sRGB-ness on flip model chains,
+        // fall back to the buffer's own format if the driver disagrees.
+        let format = if srgb {
+            srgb_format(desc.Format)
+        } else {
+            Some(unorm_format(desc.Format))
+        };
+
+        let view = format
+            .and_then(|format| {
+                Some((
+                    create_view(dev, backbuffer, &desc, Some(format)).ok()?,
+                    format,
+                ))
+            })
+            .map(|(view, format)| (view, srgb, format));
+
+        let (view, srgb, format) = match view {
+            Some(view) => view,
+            None => {
+                log_msg!(
+                    warn,
+                    "Can't create a view with sRGB = {}, using {:?}",
+                    srgb,
+                    desc.Format
+                );
+                (
+                    create_view(dev, backbuffer, &desc, None)?,
+                    is_srgb(desc.Format),
+                    typed_format(desc.Format),
+                )
+            }
+        };
+
+        Ok(Self {
+            view,
+            srgb,
+            format,
+            buffer: backbuffer.as_raw() as usize,
+            requested: srgb_view,
+            settled: detected.is_some(),
+        })
+    }
+
+    /// Checks if the view has to be recreated because of changed settings.
+    unsafe fn is_outdated(&mut self, ctx: &ID3D11DeviceContext, srgb_view: Option<bool>) -> bool {
+        if self.requested != srgb_view {
+            return true;
+        }
+
+        if !self.settled {
+            if let Some(srgb) = game_view_srgb(ctx, self.buffer) {
+                self.settled = true;
+                return srgb != self.srgb;
+            }
+        }
+
+        false
+    }
+}
+
+unsafe fn create_view(
+    dev: &ID3D11Device,
+    backbuffer: &ID3D11Texture2D,
+    desc: &D3D11_TEXTURE2D_DESC,
+    format: Option<DXGI_FORMAT>,
+) -> Result<ID3D11RenderTargetView, Error> {
+    let view_desc = format.map(|format| D3D11_RENDER_TARGET_VIEW_DESC {
+        Format: format,
+        ViewDimension: if desc.SampleDesc.Count > 1 {
+            D3D11_RTV_DIMENSION_TEXTURE2DMS
+        } else {
+            D3D11_RTV_DIMENSION_TEXTURE2D
+        },
+        Anonymous: D3D11_RENDER_TARGET_VIEW_DESC_0 {
+            Texture2D: D3D11_TEX2D_RTV { MipSlice: 0 },
+        },
+    });
+
+    let mut render_view: Option<ID3D11RenderTargetView> = None;
+
+    dev.CreateRenderTargetView(
+        backbuffer,
+        view_desc.as_ref().map(|desc| desc as *const _),
+        Some(&mut render_view),
+    )
+    .context("Failed to create render target view")?;
+
+    render_view.context("Failed to create render target view")
+}
+
+/// Looks at the view the game has bound, if it points at the backbuffer its format tells
+/// whether the game renders through an `_SRGB` view.
+unsafe fn game_view_srgb(ctx: &ID3D11DeviceContext, backbuffer: usize) -> Option<bool> {
+    let mut views = [None];
+    ctx.OMGetRenderTargets(Some(&mut views), None);
+    let view = views[0].take()?;
+
+    let texture: ID3D11Texture2D = view.GetResource().ok()?.cast().ok()?;
+    if texture.as_raw() as usize != backbuffer {
+        return None;
+    }
+
+    let mut desc = D3D11_RENDER_TARGET_VIEW_DESC::default();
+    view.GetDesc(&mut desc);
+    Some(is_srgb(desc.Format))
+}
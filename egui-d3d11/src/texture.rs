@@ -1,118 +1,877 @@
-use egui::{Color32, ImageData, TextureId, TexturesDelta};
-use std::{collections::HashMap, mem::size_of, slice::from_raw_parts_mut};
+use crate::{
+    config::{AddressMode, BudgetAction, TextureSampling},
+    dds::DdsImage,
+    error::{Error, ErrorContext},
+    format::bytes_per_pixel,
+    BACKBUFFER_TEXTURE_ID,
+};
+use egui::{Color32, ImageData, TextureId, TextureOptions, TexturesDelta};
+use std::{
+    collections::{HashMap, VecDeque},
+    mem::size_of,
+    slice::from_raw_parts_mut,
+};
 use windows::Win32::Graphics::{
-    Direct3D::D3D11_SRV_DIMENSION_TEXTURE2D,
+    Direct3D::{
+        D3D11_SRV_DIMENSION_TEXTURE2D, D3D_FEATURE_LEVEL_10_0, D3D_FEATURE_LEVEL_11_0,
+        D3D_FEATURE_LEVEL_9_3, D3D_FL9_1_REQ_TEXTURE2D_U_OR_V_DIMENSION,
+        D3D_FL9_3_REQ_TEXTURE2D_U_OR_V_DIMENSION,
+    },
     Direct3D11::{
-        ID3D11Device, ID3D11DeviceContext, ID3D11ShaderResourceView, ID3D11Texture2D,
-        D3D11_BIND_SHADER_RESOURCE, D3D11_CPU_ACCESS_WRITE, D3D11_MAP_WRITE_DISCARD,
-        D3D11_SHADER_RESOURCE_VIEW_DESC, D3D11_SHADER_RESOURCE_VIEW_DESC_0, D3D11_SUBRESOURCE_DATA,
-        D3D11_TEX2D_SRV, D3D11_TEXTURE2D_DESC, D3D11_USAGE_DYNAMIC,
+        ID3D11CommandList, ID3D11Device, ID3D11DeviceContext, ID3D11ShaderResourceView,
+        ID3D11Texture2D, D3D11_BIND_SHADER_RESOURCE, D3D11_BOX, D3D11_CPU_ACCESS_FLAG,
+        D3D11_CPU_ACCESS_WRITE, D3D11_FORMAT_SUPPORT_TEXTURE2D, D3D11_MAP_WRITE_DISCARD,
+        D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION, D3D11_SHADER_RESOURCE_VIEW_DESC,
+        D3D11_SHADER_RESOURCE_VIEW_DESC_0, D3D11_SUBRESOURCE_DATA, D3D11_TEX2D_SRV,
+        D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT, D3D11_USAGE_DYNAMIC, D3D11_USAGE_IMMUTABLE,
+        D3D11_USAGE_STAGING,
     },
-    Dxgi::Common::{DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_SAMPLE_DESC},
+    Dxgi::Common::{DXGI_FORMAT, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_SAMPLE_DESC},
 };
 
+/// Textures with at least this many pixels are updated through staging copies.
+const STAGED_UPLOAD_PIXELS: usize = 512 * 512;
+
 struct ManagedTexture {
     resource: ID3D11ShaderResourceView,
     texture: ID3D11Texture2D,
     pixels: Vec<Color32>,
     width: usize,
+    staged: bool,
+    bytes: usize,
+    // frame the texture was last drawn in
+    last_used: u64,
+    sampler: SamplerOverride,
+}
+
+/// Sampler options a texture sets itself, the ones left at `None` come from the config.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SamplerOverride {
+    pub options: Option<TextureOptions>,
+    pub address: Option<AddressMode>,
+    pub sampling: Option<TextureSampling>,
+}
+
+/// GPU memory the UI allocated, see [`crate::DirectX11App::gpu_memory_usage`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GpuMemoryUsage {
+    /// Bytes of all textures, including egui's font atlas.
+    pub textures: usize,
+    /// Bytes of the vertex, index and constant buffers.
+    pub buffers: usize,
+}
+
+impl GpuMemoryUsage {
+    pub fn total(&self) -> usize {
+        self.textures + self.buffers
+    }
+}
+
+/// State of egui's font atlas, see [`crate::DirectX11App::font_atlas_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FontAtlasStats {
+    /// Width and height of the atlas in pixels.
+    pub size: [usize; 2],
+    /// Part of the atlas that holds glyphs, from 0 to 1. egui rebuilds it once it's full.
+    pub fill_ratio: f32,
+    /// Times the whole atlas was uploaded since init, the first upload included.
+    pub uploads: usize,
+    /// Whether a rebuilt atlas is still being copied, see [`crate::Config::staged_atlas`].
+    pub staging: bool,
+}
+
+/// Description of an allocated texture, see [`TextureAllocator::textures`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureInfo {
+    pub id: TextureId,
+    pub size: [usize; 2],
+    pub format: DXGI_FORMAT,
+    pub mip_levels: u32,
+    /// GPU memory the texture takes.
+    pub bytes: usize,
+    /// Number of frames since the texture was last drawn.
+    pub idle_frames: u64,
+}
+
+/// Changed region of a staged texture waiting to be copied, see [`TextureAllocator::flush_uploads`].
+struct PendingUpload {
+    tid: TextureId,
+    staging: ID3D11Texture2D,
+    pos: [u32; 2],
+    size: [u32; 2],
+    // rows that are already copied
+    copied: u32,
+}
+
+/// egui's rebuilt font atlas, copied into a new texture over several frames while the old one
+/// is still drawn. See [`crate::Config::staged_atlas`].
+struct AtlasReplacement {
+    tid: TextureId,
+    texture: ManagedTexture,
+    staging: ID3D11Texture2D,
+    height: u32,
+    // rows that are already copied
+    copied: u32,
+    // updates egui made to the new atlas meanwhile, applied once it's complete
+    updates: Vec<(ImageData, [usize; 2])>,
 }
 
+/// Owns every texture the UI can draw, egui's own and the ones registered by the user.
+/// See [`crate::DirectX11App::lock_textures`].
 #[derive(Default)]
 pub struct TextureAllocator {
     allocated: HashMap<TextureId, ManagedTexture>,
+    pending: VecDeque<PendingUpload>,
+    next_user_id: u64,
+    frame: u64,
+    over_budget: bool,
+    // big textures keep their pixels too, see `Config::retain_textures`
+    retain_pixels: bool,
+    atlas: Option<AtlasReplacement>,
+    // bytes of the replacement copied per frame, see `Config::staged_atlas`
+    atlas_budget: Option<usize>,
+    // whole uploads of the font atlas
+    atlas_uploads: usize,
 }
 
 impl TextureAllocator {
-    pub fn process_deltas(
+    pub(crate) fn process_deltas(
         &mut self,
         dev: &ID3D11Device,
         ctx: &ID3D11DeviceContext,
         delta: TexturesDelta,
-    ) {
+    ) -> Result<(), Error> {
         for (tid, delta) in delta.set {
             if delta.is_whole() {
-                self.allocate_new(dev, tid, delta.image);
+                if tid == TextureId::default() {
+                    self.atlas_uploads += 1;
+                }
+                // the default options are what egui uses when nothing was asked for
+                let options = (delta.options != TextureOptions::default()).then_some(delta.options);
+                if self.stages_replacement(tid) {
+                    self.replace_atlas(dev, tid, delta.image, options)?;
+                } else {
+                    self.allocate_new(dev, tid, delta.image, options)?;
+                }
+            } else if let Some(atlas) = self.atlas.as_mut().filter(|atlas| atlas.tid == tid) {
+                atlas.updates.push((delta.image, delta.pos.unwrap()));
             } else {
-                self.update_partial(ctx, tid, delta.image, delta.pos.unwrap());
+                self.update_partial(dev, ctx, tid, delta.image, delta.pos.unwrap())?;
             }
         }
 
         for tid in delta.free {
             self.free(tid);
         }
+
+        Ok(())
+    }
+
+    /// Copies queued regions into their textures, at most `budget` bytes per call
+    /// but always at least one row so uploads can't stall. `None` copies everything.
+    /// Returns `false` if there was nothing to copy.
+    /// Uploads of earlier frames that didn't fit into the budget yet.
+    pub(crate) fn has_pending_uploads(&self) -> bool {
+        !self.pending.is_empty() || self.atlas.is_some()
+    }
+
+    /// Checks if a rebuilt font atlas is still being copied, the frames drawn meanwhile have
+    /// to use primitives made for the old one.
+    pub(crate) fn is_staging_atlas(&self) -> bool {
+        self.atlas.is_some()
+    }
+
+    /// Copies the next rows of a rebuilt font atlas and swaps it in once it's complete, along
+    /// with the updates egui made to it meanwhile. Returns `false` if there was nothing to copy.
+    pub(crate) fn flush_atlas(
+        &mut self,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+    ) -> Result<bool, Error> {
+        let Some(atlas) = self.atlas.as_mut() else {
+            return Ok(false);
+        };
+
+        let width = atlas.texture.width as u32;
+        let row_size = atlas.texture.width * size_of::<Color32>();
+        let budget = self.atlas_budget.unwrap_or(usize::MAX);
+        let rows = (budget / row_size).clamp(1, (atlas.height - atlas.copied) as usize) as u32;
+
+        let src = D3D11_BOX {
+            left: 0,
+            top: atlas.copied,
+            front: 0,
+            right: width,
+            bottom: atlas.copied + rows,
+            back: 1,
+        };
+
+        unsafe {
+            ctx.CopySubresourceRegion(
+                &atlas.texture.texture,
+                0,
+                0,
+                atlas.copied,
+                0,
+                &atlas.staging,
+                0,
+                Some(&src),
+            );
+        }
+
+        atlas.copied += rows;
+        if atlas.copied < atlas.height {
+            return Ok(true);
+        }
+
+        let AtlasReplacement {
+            tid,
+            mut texture,
+            updates,
+            ..
+        } = self.atlas.take().unwrap();
+        log_msg!(debug, "Swapping in the new atlas of {:?}", tid);
+
+        texture.last_used = self.frame;
+        self.allocated.insert(tid, texture);
+        self.pending.retain(|upload| upload.tid != tid);
+        for (image, pos) in updates {
+            self.update_partial(dev, ctx, tid, image, pos)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Copies rebuilds of egui's font atlas over several frames, at most `budget` bytes
+    /// per frame. See [`crate::Config::staged_atlas`].
+    pub(crate) fn atlas_uploads(&self) -> usize {
+        self.atlas_uploads
+    }
+
+    pub(crate) fn set_atlas_budget(&mut self, budget: Option<usize>) {
+        self.atlas_budget = budget;
+    }
+
+    pub(crate) fn flush_uploads(
+        &mut self,
+        ctx: &ID3D11DeviceContext,
+        budget: Option<usize>,
+    ) -> bool {
+        if self.pending.is_empty() {
+            return false;
+        }
+
+        let mut budget = budget.unwrap_or(usize::MAX);
+
+        while let Some(upload) = self.pending.front_mut() {
+            let Some(tex) = self.allocated.get(&upload.tid) else {
+                self.pending.pop_front();
+                continue;
+            };
+
+            let row_size = upload.size[0] as usize * size_of::<Color32>();
+            let left = (upload.size[1] - upload.copied) as usize;
+            let rows = (budget / row_size).clamp(1, left) as u32;
+
+            let src = D3D11_BOX {
+                left: 0,
+                top: upload.copied,
+                front: 0,
+                right: upload.size[0],
+                bottom: upload.copied + rows,
+                back: 1,
+            };
+
+            unsafe {
+                ctx.CopySubresourceRegion(
+                    &tex.texture,
+                    0,
+                    upload.pos[0],
+                    upload.pos[1] + upload.copied,
+                    0,
+                    &upload.staging,
+                    0,
+                    Some(&src),
+                );
+            }
+
+            upload.copied += rows;
+            if upload.copied == upload.size[1] {
+                self.pending.pop_front();
+            }
+
+            budget = budget.saturating_sub(rows as usize * row_size);
+            if budget == 0 {
+                break;
+            }
+        }
+
+        true
     }
 
-    pub fn get_by_id(&self, tid: TextureId) -> Option<ID3D11ShaderResourceView> {
-        self.allocated.get(&tid).map(|t| t.resource.clone())
+    /// Keeps the pixels of big textures allocated from now on.
+    /// See [`crate::Config::retain_textures`].
+    pub(crate) fn set_retain_pixels(&mut self, retain: bool) {
+        self.retain_pixels = retain;
+    }
+
+    /// Moves every texture to `dev` after the game recreated its device, from the pixels it
+    /// retained or through `restore` for textures without a copy. Textures that can't be
+    /// restored are freed, their ids are returned.
+    pub(crate) fn recreate(
+        &mut self,
+        dev: &ID3D11Device,
+        mut restore: impl FnMut(TextureId) -> Option<(ID3D11Texture2D, ID3D11ShaderResourceView)>,
+    ) -> Vec<TextureId> {
+        // the staging copies belong to the old device, a new atlas is restored from its pixels
+        // like any other texture
+        self.pending.clear();
+        if let Some(atlas) = self.atlas.take() {
+            self.allocated.insert(atlas.tid, atlas.texture);
+        }
+        // the backbuffer copy is created again by its next update
+        self.allocated.remove(&BACKBUFFER_TEXTURE_ID);
+
+        let mut lost = vec![];
+        for (tid, old) in std::mem::take(&mut self.allocated) {
+            let new = if old.pixels.is_empty() {
+                // egui can't hand out the images of its own textures again
+                let restored = match tid {
+                    TextureId::User(_) => restore(tid),
+                    TextureId::Managed(_) => None,
+                };
+                restored.map(|(texture, resource)| ManagedTexture {
+                    resource,
+                    texture,
+                    pixels: vec![],
+                    ..old
+                })
+            } else {
+                let image = ImageData::Color(egui::ColorImage {
+                    size: [old.width, old.pixels.len() / old.width],
+                    pixels: old.pixels,
+                });
+                match Self::allocate_texture(dev, image, true) {
+                    Ok(new) => Some(ManagedTexture {
+                        last_used: old.last_used,
+                        sampler: old.sampler,
+                        ..new
+                    }),
+                    Err(_e) => {
+                        log_msg!(warn, "Failed to restore texture {:?}: {}", tid, _e);
+                        None
+                    }
+                }
+            };
+
+            match new {
+                Some(new) => {
+                    self.allocated.insert(tid, new);
+                }
+                None => lost.push(tid),
+            }
+        }
+
+        log_msg!(
+            info,
+            "Restored {} textures on the new device, {} lost",
+            self.allocated.len(),
+            lost.len()
+        );
+        lost
+    }
+
+    /// Lists every allocated texture, in no particular order.
+    pub fn textures(&self) -> Vec<TextureInfo> {
+        self.allocated
+            .keys()
+            .filter_map(|tid| self.info(*tid))
+            .collect()
+    }
+
+    pub fn info(&self, tid: TextureId) -> Option<TextureInfo> {
+        let tex = self.allocated.get(&tid)?;
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { tex.texture.GetDesc(&mut desc) };
+
+        Some(TextureInfo {
+            id: tid,
+            size: [desc.Width as _, desc.Height as _],
+            format: desc.Format,
+            mip_levels: desc.MipLevels,
+            bytes: tex.bytes,
+            idle_frames: self.frame - tex.last_used.min(self.frame),
+        })
+    }
+
+    /// View the texture is sampled through, e.g. to draw it with your own pipeline.
+    /// Unlike drawing it with egui, this doesn't count as a use for the memory budget.
+    pub fn resource(&self, tid: TextureId) -> Option<ID3D11ShaderResourceView> {
+        self.allocated.get(&tid).map(|tex| tex.resource.clone())
+    }
+
+    /// Makes a texture created by the user drawable by egui under a new [`TextureId::User`].
+    /// The allocator keeps a reference until the texture is freed.
+    pub fn register(
+        &mut self,
+        texture: ID3D11Texture2D,
+        resource: ID3D11ShaderResourceView,
+    ) -> TextureId {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { texture.GetDesc(&mut desc) };
+
+        let tid = self.next_user_id();
+        log_msg!(
+            debug,
+            "Registered user texture {:?} of {}x{}",
+            tid,
+            desc.Width,
+            desc.Height
+        );
+
+        let bytes = (desc.Width * desc.Height) as usize * bytes_per_pixel(desc.Format);
+        self.register_native(tid, texture, resource, desc.Width as _, bytes);
+        tid
+    }
+
+    /// Also marks the texture as used in this frame.
+    pub(crate) fn get_by_id(
+        &mut self,
+        tid: TextureId,
+    ) -> Option<(ID3D11ShaderResourceView, SamplerOverride)> {
+        let frame = self.frame;
+
+        self.allocated.get_mut(&tid).map(|t| {
+            t.last_used = frame;
+            (t.resource.clone(), t.sampler)
+        })
+    }
+
+    /// Addressing `tid` is sampled with, `None` uses the config's.
+    /// Survives egui reallocating the texture, returns `false` if it isn't allocated.
+    pub fn set_address(&mut self, tid: TextureId, address: Option<AddressMode>) -> bool {
+        match self.allocated.get_mut(&tid) {
+            Some(tex) => {
+                tex.sampler.address = address;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mip range and anisotropy `tid` is sampled with, `None` samples only the first mip.
+    /// Survives egui reallocating the texture, returns `false` if it isn't allocated.
+    pub fn set_sampling(&mut self, tid: TextureId, sampling: Option<TextureSampling>) -> bool {
+        match self.allocated.get_mut(&tid) {
+            Some(tex) => {
+                tex.sampler.sampling = sampling;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reads every texture back into a delta that recreates it, see [`crate::replay`].
+    /// Textures of formats that can't be read back are left out.
+    #[cfg(feature = "replay")]
+    pub(crate) fn snapshot(
+        &mut self,
+        dev: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+    ) -> TexturesDelta {
+        self.flush_uploads(ctx, None);
+
+        let mut delta = TexturesDelta::default();
+        for (tid, tex) in &self.allocated {
+            if *tid == BACKBUFFER_TEXTURE_ID {
+                continue;
+            }
+
+            match unsafe { crate::capture::read_texture(dev, ctx, &tex.texture) } {
+                Ok(frame) => {
+                    let image = egui::ColorImage {
+                        size: [frame.width, frame.height],
+                        pixels: frame
+                            .pixels
+                            .chunks_exact(4)
+                            .map(|p| Color32::from_rgba_premultiplied(p[0], p[1], p[2], p[3]))
+                            .collect(),
+                    };
+                    let options = tex.sampler.options.unwrap_or_default();
+                    delta
+                        .set
+                        .push((*tid, egui::epaint::ImageDelta::full(image, options)));
+                }
+                Err(_e) => {
+                    log_msg!(warn, "Texture {:?} can't be recorded: {}", tid, _e);
+                }
+            }
+        }
+
+        delta
+    }
+
+    /// Bytes of all allocated textures.
+    pub fn bytes(&self) -> usize {
+        self.allocated.values().map(|t| t.bytes).sum()
+    }
+
+    /// Checks the textures and `other` allocations against `budget`, returns the evicted textures.
+    /// Has to be called once per frame, after drawing.
+    pub(crate) fn enforce_budget(
+        &mut self,
+        (budget, action): (usize, BudgetAction),
+        other: usize,
+    ) -> Vec<TextureId> {
+        let mut evicted = vec![];
+        let mut used = self.bytes() + other;
+
+        if used > budget && action == BudgetAction::EvictUserTextures {
+            let mut candidates: Vec<_> = self
+                .allocated
+                .iter()
+                .filter(|(tid, _)| {
+                    matches!(tid, TextureId::User(_)) && **tid != BACKBUFFER_TEXTURE_ID
+                })
+                .map(|(tid, t)| (t.last_used, *tid))
+                .collect();
+            candidates.sort_unstable_by_key(|(last_used, _)| *last_used);
+
+            for (_, tid) in candidates {
+                if used <= budget {
+                    break;
+                }
+
+                used -= self.allocated[&tid].bytes;
+                self.free(tid);
+                evicted.push(tid);
+            }
+        }
+
+        match (used > budget, self.over_budget) {
+            (true, false) => {
+                log_msg!(
+                    warn,
+                    "UI uses {} bytes of GPU memory, budget is {}",
+                    used,
+                    budget
+                );
+            }
+            (false, true) => {
+                log_msg!(info, "UI is back within its GPU memory budget");
+            }
+            _ => {}
+        }
+        self.over_budget = used > budget;
+        self.frame += 1;
+
+        evicted
+    }
+
+    /// Registers a texture that was created outside of egui's texture deltas.
+    /// Replaces the previous texture if `tid` is already taken.
+    pub(crate) fn register_native(
+        &mut self,
+        tid: TextureId,
+        texture: ID3D11Texture2D,
+        resource: ID3D11ShaderResourceView,
+        width: usize,
+        bytes: usize,
+    ) {
+        let kept = self.kept_sampler(tid);
+
+        self.allocated.insert(
+            tid,
+            ManagedTexture {
+                pixels: vec![],
+                resource,
+                texture,
+                width,
+                staged: false,
+                bytes,
+                last_used: self.frame,
+                sampler: SamplerOverride {
+                    options: None,
+                    ..kept
+                },
+            },
+        );
+    }
+
+    /// Uploads a block-compressed image as is, the GPU decodes it when sampling.
+    /// Returns a new [`TextureId::User`] the image can be drawn with.
+    pub(crate) fn register_compressed(
+        &mut self,
+        dev: &ID3D11Device,
+        image: &DdsImage,
+    ) -> Result<TextureId, Error> {
+        let max_side = max_texture_side(dev);
+        if image.width > max_side || image.height > max_side {
+            return Err(Error::TextureTooLarge(
+                [image.width, image.height],
+                max_side,
+            ));
+        }
+
+        // BC6H and BC7 need feature level 11
+        let support = unsafe { dev.CheckFormatSupport(image.format) }.unwrap_or(0);
+        if support & D3D11_FORMAT_SUPPORT_TEXTURE2D.0 as u32 == 0 {
+            return Err(Error::UnsupportedFormat(image.format));
+        }
+
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: image.width as _,
+            Height: image.height as _,
+            MipLevels: image.mips.len() as _,
+            ArraySize: 1,
+            Format: image.format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_IMMUTABLE,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            ..Default::default()
+        };
+
+        let data: Vec<_> = image
+            .mips
+            .iter()
+            .map(|(mip, pitch)| D3D11_SUBRESOURCE_DATA {
+                pSysMem: mip.as_ptr() as _,
+                SysMemPitch: *pitch as _,
+                SysMemSlicePitch: 0,
+            })
+            .collect();
+
+        let (texture, resource) = unsafe {
+            let mut texture: Option<ID3D11Texture2D> = None;
+
+            dev.CreateTexture2D(&desc, Some(data.as_ptr()), Some(&mut texture))
+                .context("Failed to create a compressed texture")?;
+
+            let texture = texture.context("Failed to create a compressed texture")?;
+
+            let mut resource: Option<ID3D11ShaderResourceView> = None;
+
+            dev.CreateShaderResourceView(&texture, None, Some(&mut resource))
+                .context("Failed to create shader resource view")?;
+
+            (
+                texture,
+                resource.context("Failed to create shader resource view")?,
+            )
+        };
+
+        let tid = self.next_user_id();
+
+        log_msg!(
+            debug,
+            "Registered {:?} texture {:?} of {}x{}",
+            image.format,
+            tid,
+            image.width,
+            image.height
+        );
+
+        let bytes = image.mips.iter().map(|(mip, _)| mip.len()).sum();
+        self.register_native(tid, texture, resource, image.width, bytes);
+        Ok(tid)
+    }
+
+    pub fn free(&mut self, tid: TextureId) -> bool {
+        log_msg!(debug, "Freeing texture {:?}", tid);
+        self.pending.retain(|upload| upload.tid != tid);
+        if self.atlas.as_ref().is_some_and(|atlas| atlas.tid == tid) {
+            self.atlas = None;
+        }
+        self.allocated.remove(&tid).is_some()
     }
 }
 
 impl TextureAllocator {
-    fn allocate_new(&mut self, dev: &ID3D11Device, tid: TextureId, image: ImageData) {
-        let tex = Self::allocate_texture(dev, image);
+    fn next_user_id(&mut self) -> TextureId {
+        // `u64::MAX` is taken by the backbuffer copy
+        let tid = TextureId::User(self.next_user_id);
+        self.next_user_id += 1;
+        tid
+    }
+
+    /// Sampler options the app set on `tid`, kept when the texture gets replaced.
+    fn kept_sampler(&self, tid: TextureId) -> SamplerOverride {
+        self.allocated
+            .get(&tid)
+            .map(|old| old.sampler)
+            .unwrap_or_default()
+    }
+
+    fn allocate_new(
+        &mut self,
+        dev: &ID3D11Device,
+        tid: TextureId,
+        image: ImageData,
+        options: Option<TextureOptions>,
+    ) -> Result<(), Error> {
+        log_msg!(
+            debug,
+            "Allocating texture {:?} of {}x{}",
+            tid,
+            image.width(),
+            image.height()
+        );
+
+        let mut tex = Self::allocate_texture(dev, image, self.retain_pixels)?;
+        tex.last_used = self.frame;
+        if self.atlas.as_ref().is_some_and(|atlas| atlas.tid == tid) {
+            self.atlas = None;
+        }
+        tex.sampler = SamplerOverride {
+            options,
+            ..self.kept_sampler(tid)
+        };
         self.allocated.insert(tid, tex);
+        // copies queued for the old texture would only overwrite the new contents
+        self.pending.retain(|upload| upload.tid != tid);
+
+        Ok(())
     }
 
-    fn free(&mut self, tid: TextureId) -> bool {
-        self.allocated.remove(&tid).is_some()
+    /// Only egui's font atlas is staged, and only once there is an old one to draw meanwhile.
+    fn stages_replacement(&self, tid: TextureId) -> bool {
+        // `TextureId::default()` is the font atlas
+        self.atlas_budget.is_some()
+            && tid == TextureId::default()
+            && self.allocated.contains_key(&tid)
+    }
+
+    /// Starts copying `image` into a new texture that replaces `tid` once it's complete,
+    /// see [`Self::flush_atlas`]. A replacement that is still being copied is dropped.
+    fn replace_atlas(
+        &mut self,
+        dev: &ID3D11Device,
+        tid: TextureId,
+        image: ImageData,
+        options: Option<TextureOptions>,
+    ) -> Result<(), Error> {
+        let [width, height] = image.size();
+        log_msg!(
+            debug,
+            "Staging the new atlas of {:?} of {}x{}",
+            tid,
+            width,
+            height
+        );
+
+        let pixels = image_pixels(image);
+        let staging = Self::create_staging(dev, &pixels, width, height)?;
+        let (texture, resource) = Self::create_texture(dev, [width, height], true, None)?;
+
+        self.atlas = Some(AtlasReplacement {
+            tid,
+            texture: ManagedTexture {
+                resource,
+                texture,
+                pixels: if self.retain_pixels { pixels } else { vec![] },
+                width,
+                staged: true,
+                bytes: width * height * size_of::<Color32>(),
+                last_used: self.frame,
+                sampler: SamplerOverride {
+                    options,
+                    ..self.kept_sampler(tid)
+                },
+            },
+            staging,
+            height: height as _,
+            copied: 0,
+            updates: vec![],
+        });
+
+        Ok(())
     }
 
     fn update_partial(
         &mut self,
+        dev: &ID3D11Device,
         ctx: &ID3D11DeviceContext,
         tid: TextureId,
         image: ImageData,
         [nx, ny]: [usize; 2],
-    ) -> bool {
-        if let Some(old) = self.allocated.get_mut(&tid) {
-            let mut subr = unsafe { std::mem::zeroed() };
+    ) -> Result<bool, Error> {
+        log_msg!(
+            trace,
+            "Updating texture {:?} at {:?} with {}x{}",
+            tid,
+            [nx, ny],
+            image.width(),
+            image.height()
+        );
 
-            unsafe {
-                expect!(
-                    ctx.Map(&old.texture, 0, D3D11_MAP_WRITE_DISCARD, 0, Some(&mut subr)),
-                    "Failed to map subresource"
-                );
-            }
+        let Some(old) = self.allocated.get_mut(&tid) else {
+            return Ok(false);
+        };
 
-            match image {
-                ImageData::Font(f) => unsafe {
-                    let data = from_raw_parts_mut(subr.pData as *mut Color32, old.pixels.len());
-                    data.as_mut_ptr()
-                        .copy_from_nonoverlapping(old.pixels.as_ptr(), old.pixels.len());
-
-                    let new: Vec<Color32> = f
-                        .pixels
-                        .iter()
-                        .map(|a| Color32::from_rgba_premultiplied(255, 255, 255, (a * 255.) as u8))
-                        .collect();
-
-                    for y in 0..f.height() {
-                        for x in 0..f.width() {
-                            let whole = (ny + y) * old.width + nx + x;
-                            let frac = y * f.width() + x;
-                            old.pixels[whole] = new[frac];
-                            data[whole] = new[frac];
-                        }
-                    }
-                },
-                _ => unreachable!(),
+        let [width, height] = image.size();
+        let new = image_pixels(image);
+
+        // only retained big textures have a copy to keep in sync
+        if !old.pixels.is_empty() {
+            for (y, row) in new.chunks_exact(width).enumerate() {
+                let whole = (ny + y) * old.width + nx;
+                old.pixels[whole..whole + width].copy_from_slice(row);
             }
+        }
+
+        if old.staged {
+            let staging = Self::create_staging(dev, &new, width, height)?;
+            self.pending.push_back(PendingUpload {
+                tid,
+                staging,
+                pos: [nx as _, ny as _],
+                size: [width as _, height as _],
+                copied: 0,
+            });
+
+            return Ok(true);
+        }
+
+        let mut subr = unsafe { std::mem::zeroed() };
+
+        unsafe {
+            ctx.Map(&old.texture, 0, D3D11_MAP_WRITE_DISCARD, 0, Some(&mut subr))
+                .context("Failed to map subresource")?;
+        }
 
+        // Discarding drops the old contents, so the whole texture is written again.
+        // Rows of big atlases are usually padded, hence the copy row by row.
+        for (y, row) in old.pixels.chunks_exact(old.width).enumerate() {
             unsafe {
-                ctx.Unmap(&old.texture, 0);
+                let data = from_raw_parts_mut(
+                    (subr.pData as *mut u8).add(y * subr.RowPitch as usize) as *mut Color32,
+                    row.len(),
+                );
+                data.copy_from_slice(row);
             }
+        }
 
-            true
-        } else {
-            false
+        unsafe {
+            ctx.Unmap(&old.texture, 0);
         }
+
+        Ok(true)
     }
 
-    fn allocate_texture(dev: &ID3D11Device, image: ImageData) -> ManagedTexture {
+    /// Staging copy of a changed region, filled on creation and copied to the GPU later.
+    /// Doesn't need a context, so it also works while recording on a deferred one.
+    fn create_staging(
+        dev: &ID3D11Device,
+        pixels: &[Color32],
+        width: usize,
+        height: usize,
+    ) -> Result<ID3D11Texture2D, Error> {
         let desc = D3D11_TEXTURE2D_DESC {
-            Width: image.width() as _,
-            Height: image.height() as _,
+            Width: width as _,
+            Height: height as _,
             MipLevels: 1,
             ArraySize: 1,
             Format: DXGI_FORMAT_R8G8B8A8_UNORM,
@@ -120,38 +879,102 @@ impl TextureAllocator {
                 Count: 1,
                 Quality: 0,
             },
-            Usage: D3D11_USAGE_DYNAMIC,
-            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            Usage: D3D11_USAGE_STAGING,
             CPUAccessFlags: D3D11_CPU_ACCESS_WRITE,
             ..Default::default()
         };
 
-        // rust is cringe sometimes
-        let width = image.width();
-        let pixels = match image {
-            ImageData::Color(c) => c.pixels,
-            ImageData::Font(f) => f
-                .pixels
-                .iter()
-                .map(|a| Color32::from_rgba_premultiplied(255, 255, 255, (a * 255.) as u8))
-                .collect(),
+        let data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: pixels.as_ptr() as _,
+            SysMemPitch: (width * size_of::<Color32>()) as u32,
+            SysMemSlicePitch: 0,
         };
 
+        unsafe {
+            let mut staging: Option<ID3D11Texture2D> = None;
+
+            dev.CreateTexture2D(&desc, Some(&data), Some(&mut staging))
+                .context("Failed to create a staging texture")?;
+
+            staging.context("Failed to create a staging texture")
+        }
+    }
+
+    /// `retain` keeps the pixels of big textures as well, small ones always keep them.
+    fn allocate_texture(
+        dev: &ID3D11Device,
+        image: ImageData,
+        retain: bool,
+    ) -> Result<ManagedTexture, Error> {
+        let [width, height] = image.size();
+        // Rewriting a big atlas through a mapped dynamic texture stalls the frame,
+        // those are updated region by region through staging copies instead.
+        let staged = width * height >= STAGED_UPLOAD_PIXELS;
+        let pixels = image_pixels(image);
+
         let data = D3D11_SUBRESOURCE_DATA {
             pSysMem: pixels.as_ptr() as _,
             SysMemPitch: (width * size_of::<Color32>()) as u32,
             SysMemSlicePitch: 0,
         };
+        let (texture, resource) = Self::create_texture(dev, [width, height], staged, Some(&data))?;
+
+        Ok(ManagedTexture {
+            width,
+            resource,
+            // staged textures are updated on the GPU, the copy is only needed to restore them
+            pixels: if staged && !retain { vec![] } else { pixels },
+            texture,
+            staged,
+            bytes: width * height * size_of::<Color32>(),
+            last_used: 0,
+            sampler: SamplerOverride::default(),
+        })
+    }
+
+    /// Texture and view of egui's format, filled with `data` or left undefined.
+    fn create_texture(
+        dev: &ID3D11Device,
+        [width, height]: [usize; 2],
+        staged: bool,
+        data: Option<&D3D11_SUBRESOURCE_DATA>,
+    ) -> Result<(ID3D11Texture2D, ID3D11ShaderResourceView), Error> {
+        let max_side = max_texture_side(dev);
+        if width > max_side || height > max_side {
+            return Err(Error::TextureTooLarge([width, height], max_side));
+        }
+
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width as _,
+            Height: height as _,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: if staged {
+                D3D11_USAGE_DEFAULT
+            } else {
+                D3D11_USAGE_DYNAMIC
+            },
+            BindFlags: D3D11_BIND_SHADER_RESOURCE,
+            CPUAccessFlags: if staged {
+                D3D11_CPU_ACCESS_FLAG(0)
+            } else {
+                D3D11_CPU_ACCESS_WRITE
+            },
+            ..Default::default()
+        };
 
         unsafe {
             let mut texture: Option<ID3D11Texture2D> = None;
 
-            expect!(
-                dev.CreateTexture2D(&desc, Some(&data), Some(&mut texture)),
-                "Failed to create a texture"
-            );
+            dev.CreateTexture2D(&desc, data.map(|data| data as *const _), Some(&mut texture))
+                .context("Failed to create a texture")?;
 
-            let texture = expect!(texture, "Failed to create a texture");
+            let texture = texture.context("Failed to create a texture")?;
 
             let desc = D3D11_SHADER_RESOURCE_VIEW_DESC {
                 Format: DXGI_FORMAT_R8G8B8A8_UNORM,
@@ -166,19 +989,94 @@ impl TextureAllocator {
 
             let mut resource: Option<ID3D11ShaderResourceView> = None;
 
-            expect!(
-                dev.CreateShaderResourceView(&texture, Some(&desc), Some(&mut resource)),
-                "Failed to create shader resource view"
-            );
+            dev.CreateShaderResourceView(&texture, Some(&desc), Some(&mut resource))
+                .context("Failed to create shader resource view")?;
 
-            let resource = expect!(resource, "Failed to create shader resource view");
+            let resource = resource.context("Failed to create shader resource view")?;
 
-            ManagedTexture {
-                width,
-                resource,
-                pixels,
-                texture,
+            Ok((texture, resource))
+        }
+    }
+}
+
+fn image_pixels(image: ImageData) -> Vec<Color32> {
+    match image {
+        ImageData::Color(c) => c.pixels,
+        ImageData::Font(f) => f
+            .pixels
+            .iter()
+            .map(|a| Color32::from_rgba_premultiplied(255, 255, 255, (a * 255.) as u8))
+            .collect(),
+    }
+}
+
+/// Records texture updates on a deferred context, the recorded commands are executed
+/// at the start of the next frame instead of in the middle of the one that changed them.
+pub struct DeferredUploads {
+    pub context: ID3D11DeviceContext,
+    recorded: Option<ID3D11CommandList>,
+}
+
+impl DeferredUploads {
+    pub fn new(dev: &ID3D11Device) -> Result<Self, Error> {
+        unsafe {
+            let mut context = None;
+            dev.CreateDeferredContext(0, Some(&mut context))
+                .context("Failed to create deferred context")?;
+
+            Ok(Self {
+                context: context.context("Failed to create deferred context")?,
+                recorded: None,
+            })
+        }
+    }
+
+    /// A command list is waiting to be executed.
+    pub fn is_recorded(&self) -> bool {
+        self.recorded.is_some()
+    }
+
+    /// Closes the commands recorded so far into a command list.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        // The previous list wasn't executed yet, the new commands stay on the context
+        // and end up in the next list so the order is kept.
+        if self.recorded.is_some() {
+            return Ok(());
+        }
+
+        unsafe {
+            let mut list = None;
+            self.context
+                .FinishCommandList(false, Some(&mut list))
+                .context("Failed to finish command list")?;
+            self.recorded = Some(list.context("Failed to finish command list")?);
+        }
+
+        Ok(())
+    }
+
+    /// Executes the recorded updates on `ctx`, leaving its state untouched.
+    pub fn execute(&mut self, ctx: &ID3D11DeviceContext) {
+        if let Some(list) = self.recorded.take() {
+            unsafe {
+                ctx.ExecuteCommandList(&list, true);
             }
         }
     }
 }
+
+/// Biggest texture the device can create, passed to egui so its font atlas never outgrows it.
+pub fn max_texture_side(dev: &ID3D11Device) -> usize {
+    let level = unsafe { dev.GetFeatureLevel() };
+
+    (if level.0 >= D3D_FEATURE_LEVEL_11_0.0 {
+        D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION
+    } else if level.0 >= D3D_FEATURE_LEVEL_10_0.0 {
+        // D3D10_REQ_TEXTURE2D_U_OR_V_DIMENSION, lives in the Direct3D10 bindings
+        8192
+    } else if level.0 >= D3D_FEATURE_LEVEL_9_3.0 {
+        D3D_FL9_3_REQ_TEXTURE2D_U_OR_V_DIMENSION
+    } else {
+        D3D_FL9_1_REQ_TEXTURE2D_U_OR_V_DIMENSION
+    }) as usize
+}
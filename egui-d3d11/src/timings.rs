@@ -0,0 +1,229 @@
+use egui::{pos2, vec2, Color32, Context, Grid, Rect, Sense, Window};
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const STAGE_COUNT: usize = 6;
+
+/// Parts of `present` whose wall-clock time is measured, see [`FrameTimings`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameStage {
+    /// Collecting the window's messages into egui's input.
+    Input,
+    /// The UI closure and the layers.
+    Ui,
+    /// Turning egui's shapes into meshes.
+    Tessellate,
+    /// Uploading textures, vertices and indices.
+    Upload,
+    /// Recording the draw calls, including draw hooks and the composite pass.
+    Draw,
+    /// Saving and restoring the game's pipeline state.
+    Backup,
+}
+
+impl FrameStage {
+    pub const ALL: [Self; STAGE_COUNT] = [
+        Self::Input,
+        Self::Ui,
+        Self::Tessellate,
+        Self::Upload,
+        Self::Draw,
+        Self::Backup,
+    ];
+
+    fn color(self) -> Color32 {
+        match self {
+            Self::Input => Color32::from_rgb(120, 120, 120),
+            Self::Ui => Color32::from_rgb(90, 170, 250),
+            Self::Tessellate => Color32::from_rgb(250, 200, 60),
+            Self::Upload => Color32::from_rgb(230, 110, 60),
+            Self::Draw => Color32::from_rgb(110, 210, 110),
+            Self::Backup => Color32::from_rgb(200, 110, 220),
+        }
+    }
+}
+
+/// Wall-clock time one `present` took, split into its stages. Whatever isn't part of a stage,
+/// e.g. converting meshes or acquiring the backbuffer, is in [`Self::other`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameTimings {
+    pub total: Duration,
+    pub stages: [Duration; STAGE_COUNT],
+}
+
+impl FrameTimings {
+    pub fn stage(&self, stage: FrameStage) -> Duration {
+        self.stages[stage as usize]
+    }
+
+    pub fn other(&self) -> Duration {
+        self.total.saturating_sub(self.stages.iter().sum())
+    }
+}
+
+thread_local! {
+    // stages of the frame the current thread is presenting
+    static CURRENT: Cell<[Duration; STAGE_COUNT]> = Cell::new(Default::default());
+}
+
+/// Adds the time until it's dropped to a stage of the current frame.
+pub(crate) struct StageTimer(FrameStage, Instant);
+
+impl StageTimer {
+    #[inline]
+    pub fn enter(stage: FrameStage) -> Self {
+        Self(stage, Instant::now())
+    }
+}
+
+impl Drop for StageTimer {
+    #[inline]
+    fn drop(&mut self) {
+        let elapsed = self.1.elapsed();
+        CURRENT.with(|current| {
+            let mut stages = current.get();
+            stages[self.0 as usize] += elapsed;
+            current.set(stages);
+        });
+    }
+}
+
+/// Start of a frame, the stages measured on this thread until [`Self::finish`] belong to it.
+pub(crate) struct FrameClock(Instant);
+
+impl FrameClock {
+    pub fn start() -> Self {
+        CURRENT.with(|current| current.set(Default::default()));
+        Self(Instant::now())
+    }
+
+    pub fn finish(self) -> FrameTimings {
+        FrameTimings {
+            total: self.0.elapsed(),
+            stages: CURRENT.with(Cell::get),
+        }
+    }
+}
+
+/// Timings of the last frames the UI was drawn in, see [`crate::Config::frame_timings`].
+#[derive(Default)]
+pub struct TimingHistory {
+    frames: VecDeque<FrameTimings>,
+    slow_frames: usize,
+}
+
+impl TimingHistory {
+    /// Keeps `frame` if the UI ran in it, dropping the oldest ones beyond `capacity`.
+    pub fn push(&mut self, frame: FrameTimings, capacity: usize, slow: Option<Duration>) {
+        if frame.stage(FrameStage::Ui).is_zero() && frame.stage(FrameStage::Draw).is_zero() {
+            return;
+        }
+
+        if slow.is_some_and(|threshold| frame.total >= threshold) {
+            self.slow_frames += 1;
+            log_msg!(
+                warn,
+                "Slow frame of {:?}: input {:?}, ui {:?}, tessellate {:?}, upload {:?}, \
+                 draw {:?}, backup {:?}",
+                frame.total,
+                frame.stage(FrameStage::Input),
+                frame.stage(FrameStage::Ui),
+                frame.stage(FrameStage::Tessellate),
+                frame.stage(FrameStage::Upload),
+                frame.stage(FrameStage::Draw),
+                frame.stage(FrameStage::Backup)
+            );
+        }
+
+        self.frames.push_back(frame);
+        while self.frames.len() > capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = &FrameTimings> {
+        self.frames.iter()
+    }
+
+    pub fn slow_frames(&self) -> usize {
+        self.slow_frames
+    }
+
+    /// Window with the stages of the last frame and a bar per frame, scaled to 60 FPS or the
+    /// slowest frame, whichever is longer.
+    pub fn show(&self, ctx: &Context) {
+        const BAR_WIDTH: f32 = 3.;
+        const HEIGHT: f32 = 60.;
+
+        Window::new("egui-d3d11 frame time").show(ctx, |ui| {
+            let last = self.frames.back().copied().unwrap_or_default();
+            Grid::new("egui-d3d11 stages").show(ui, |ui| {
+                for stage in FrameStage::ALL {
+                    ui.colored_label(stage.color(), format!("{stage:?}"));
+                    ui.label(format!("{:.3} ms", last.stage(stage).as_secs_f64() * 1e3));
+                    ui.end_row();
+                }
+                ui.label("Total");
+                ui.label(format!("{:.3} ms", last.total.as_secs_f64() * 1e3));
+                ui.end_row();
+            });
+
+            let scale = self
+                .frames
+                .iter()
+                .map(|frame| frame.total)
+                .max()
+                .unwrap_or_default()
+                .max(Duration::from_micros(16_667))
+                .as_secs_f32();
+            let width = BAR_WIDTH * self.frames.len().max(1) as f32;
+            let (rect, _) = ui.allocate_exact_size(vec2(width, HEIGHT), Sense::hover());
+            let painter = ui.painter_at(rect);
+
+            for (i, frame) in self.frames.iter().enumerate() {
+                let left = rect.left() + i as f32 * BAR_WIDTH;
+                let mut bottom = rect.bottom();
+                for stage in FrameStage::ALL {
+                    let height = frame.stage(stage).as_secs_f32() / scale * HEIGHT;
+                    let bar = Rect::from_min_max(
+                        pos2(left, bottom - height),
+                        pos2(left + BAR_WIDTH - 1., bottom),
+                    );
+                    painter.rect_filled(bar, 0., stage.color());
+                    bottom -= height;
+                }
+            }
+
+            ui.label(format!("Slow frames: {}", self.slow_frames));
+        });
+    }
+}
+
+#[test]
+fn test_timing_history() {
+    let mut history = TimingHistory::default();
+    let frame = |ui_ms| {
+        let mut frame = FrameTimings {
+            total: Duration::from_millis(ui_ms + 1),
+            ..Default::default()
+        };
+        frame.stages[FrameStage::Ui as usize] = Duration::from_millis(ui_ms);
+        frame
+    };
+
+    // hidden frames don't run the UI
+    history.push(FrameTimings::default(), 2, None);
+    for ui_ms in [1, 2, 20] {
+        history.push(frame(ui_ms), 2, Some(Duration::from_millis(10)));
+    }
+
+    assert_eq!(history.frames().count(), 2);
+    assert_eq!(history.slow_frames(), 1);
+    assert_eq!(
+        history.frames().next().map(FrameTimings::other),
+        Some(Duration::from_millis(1))
+    );
+}
@@ -0,0 +1,398 @@
+//! Headless setup for integration tests: a WARP device rendering into the swapchain of a hidden window,
+//! so the whole init/present/resize path runs without a GPU or a hooked game.
+
+use crate::{DirectX11App, RawMutex};
+#[cfg(test)]
+use egui::{Color32, Rgba};
+use windows::{
+    core::HRESULT,
+    w,
+    Win32::{
+        Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM},
+        Graphics::{
+            Direct3D::D3D_DRIVER_TYPE_WARP,
+            Direct3D11::{
+                D3D11CreateDeviceAndSwapChain, ID3D11DeviceContext, D3D11_CREATE_DEVICE_FLAG,
+                D3D11_SDK_VERSION, D3D11_VIEWPORT,
+            },
+            Dxgi::{
+                Common::{
+                    DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_UNKNOWN, DXGI_MODE_DESC,
+                    DXGI_SAMPLE_DESC,
+                },
+                IDXGISwapChain, DXGI_SWAP_CHAIN_DESC, DXGI_SWAP_EFFECT_DISCARD,
+                DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            },
+        },
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DestroyWindow, RegisterClassW, HMENU, WNDCLASSW,
+            WS_POPUP,
+        },
+    },
+};
+
+pub struct WarpTarget {
+    pub hwnd: HWND,
+    pub swap: IDXGISwapChain,
+    pub context: ID3D11DeviceContext,
+}
+
+impl WarpTarget {
+    pub fn new(width: u32, height: u32) -> Self {
+        unsafe {
+            let class = w!("egui-d3d11-warp");
+
+            // Fails for every target but the first one, the class stays registered.
+            RegisterClassW(&WNDCLASSW {
+                lpfnWndProc: Some(wnd_proc),
+                lpszClassName: class,
+                ..Default::default()
+            });
+
+            // Never shown, popups have no borders, so the client area matches the backbuffer.
+            let hwnd = CreateWindowExW(
+                Default::default(),
+                class,
+                w!(""),
+                WS_POPUP,
+                0,
+                0,
+                width as _,
+                height as _,
+                HWND::default(),
+                HMENU::default(),
+                HINSTANCE::default(),
+                None,
+            );
+            assert_ne!(hwnd, HWND::default(), "Failed to create window");
+
+            let desc = DXGI_SWAP_CHAIN_DESC {
+                BufferDesc: DXGI_MODE_DESC {
+                    Width: width,
+                    Height: height,
+                    Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                    ..Default::default()
+                },
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
+                BufferCount: 1,
+                OutputWindow: hwnd,
+                Windowed: true.into(),
+                SwapEffect: DXGI_SWAP_EFFECT_DISCARD,
+                ..Default::default()
+            };
+
+            let mut swap = None;
+            let mut context = None;
+
+            D3D11CreateDeviceAndSwapChain(
+                None,
+                D3D_DRIVER_TYPE_WARP,
+                HINSTANCE::default(),
+                D3D11_CREATE_DEVICE_FLAG::default(),
+                None,
+                D3D11_SDK_VERSION,
+                Some(&desc),
+                Some(&mut swap),
+                None,
+                None,
+                Some(&mut context),
+            )
+            .expect("Failed to create WARP device");
+
+            Self {
+                hwnd,
+                swap: swap.unwrap(),
+                context: context.unwrap(),
+            }
+        }
+    }
+
+    pub fn resize<T, R: RawMutex>(
+        &self,
+        app: &DirectX11App<T, R>,
+        width: u32,
+        height: u32,
+    ) -> HRESULT {
+        app.resize_buffers(&self.swap, || unsafe {
+            match self
+                .swap
+                .ResizeBuffers(0, width, height, DXGI_FORMAT_UNKNOWN, 0)
+            {
+                Ok(()) => Default::default(),
+                Err(e) => e.code(),
+            }
+        })
+    }
+
+    /// Every viewport bound to the context.
+    pub fn viewports(&self) -> Vec<(f32, f32, f32, f32)> {
+        let mut viewports = [D3D11_VIEWPORT::default(); 16];
+        let mut count = viewports.len() as u32;
+        unsafe {
+            self.context
+                .RSGetViewports(&mut count, Some(viewports.as_mut_ptr()));
+        }
+        viewports[..count as usize]
+            .iter()
+            .map(|v| (v.TopLeftX, v.TopLeftY, v.Width, v.Height))
+            .collect()
+    }
+}
+
+impl Drop for WarpTarget {
+    fn drop(&mut self) {
+        unsafe {
+            DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+unsafe extern "system" fn wnd_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+#[cfg(test)]
+fn draw_window(ctx: &egui::Context, _: &mut ()) {
+    egui::Window::new("warp")
+        .fixed_pos([10., 10.])
+        .show(ctx, |ui| ui.label("Hello from WARP"));
+}
+
+#[test]
+fn test_warp_present() {
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+
+    app.init_default(&target.swap, draw_window);
+    app.set_clear_color(Some(Rgba::BLACK));
+
+    // The font atlas is uploaded with the first frame and patched by the following ones.
+    for _ in 0..3 {
+        app.present(&target.swap);
+    }
+
+    let frame = app.capture_frame(&target.swap).unwrap();
+    assert_eq!((frame.width, frame.height), (320, 240));
+    assert_eq!(
+        frame.pixels[frame.pixels.len() - 4..],
+        Color32::BLACK.to_array()
+    );
+    assert!(frame
+        .pixels
+        .chunks_exact(4)
+        .any(|px| px != Color32::BLACK.to_array()));
+}
+
+#[test]
+fn test_warp_try_init() {
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+
+    assert!(app.try_init_with_state(&target.swap, draw_window, ()).is_ok());
+    assert!(app.is_ready());
+    assert!(matches!(
+        app.try_init_with_state(&target.swap, draw_window, ()),
+        Err(crate::InitError::AlreadyInitialized)
+    ));
+    app.present(&target.swap);
+}
+
+#[test]
+fn test_warp_resize() {
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+
+    app.init_default(&target.swap, draw_window);
+    app.present(&target.swap);
+
+    assert!(target.resize(&app, 640, 480).is_ok());
+    app.present(&target.swap);
+
+    let frame = app.capture_frame(&target.swap).unwrap();
+    assert_eq!((frame.width, frame.height), (640, 480));
+}
+
+#[test]
+fn test_warp_max_ui_fps() {
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<u32>::new();
+
+    app.init_default(&target.swap, |ctx, runs| {
+        *runs += 1;
+        draw_window(ctx, &mut ());
+    });
+    app.set_max_ui_fps(1);
+
+    // egui repaints a few times while it settles, after that the frames are reused
+    for _ in 0..10 {
+        app.present(&target.swap);
+    }
+    assert!(*app.lock_state() < 10);
+
+    // a repaint request runs it on the next frame
+    let runs = *app.lock_state();
+    app.context().request_repaint();
+    app.present(&target.swap);
+    assert!(*app.lock_state() > runs);
+}
+
+#[test]
+fn test_warp_backup() {
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+
+    app.init_default(&target.swap, draw_window);
+
+    // split screen, every viewport has to come back and not just the first
+    let viewport = |x, y| D3D11_VIEWPORT {
+        TopLeftX: x,
+        TopLeftY: y,
+        Width: 100.,
+        Height: 50.,
+        MinDepth: 0.,
+        MaxDepth: 1.,
+    };
+    unsafe {
+        target
+            .context
+            .RSSetViewports(Some(&[viewport(5., 7.), viewport(160., 7.)]));
+    }
+
+    app.present(&target.swap);
+
+    assert_eq!(
+        target.viewports(),
+        [(5., 7., 100., 50.), (160., 7., 100., 50.)]
+    );
+}
+
+#[cfg(feature = "replay")]
+#[test]
+fn test_warp_replay() {
+    let path = std::env::temp_dir().join("egui-d3d11-replay.ron");
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+
+    app.init_default(&target.swap, draw_window);
+    app.set_clear_color(Some(Rgba::BLACK));
+
+    // The atlas exists before the recording starts, so it has to be read back.
+    app.present(&target.swap);
+    app.capture_frames(1, path.clone());
+    app.present(&target.swap);
+    let expected = app.capture_frame(&target.swap).unwrap();
+
+    let frames = crate::replay::load_recording(&path).unwrap();
+    assert_eq!(frames.len(), 1);
+
+    let replay_target = WarpTarget::new(320, 240);
+    let replayed = DirectX11App::<()>::new();
+
+    replayed.init_default(&replay_target.swap, |_, _| {});
+    replayed.set_clear_color(Some(Rgba::BLACK));
+    replayed.replay(frames);
+    replayed.present(&replay_target.swap);
+
+    let frame = replayed.capture_frame(&replay_target.swap).unwrap();
+    assert!(frame.pixels == expected.pixels);
+}
+
+#[test]
+fn test_warp_textures() {
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+
+    app.init_default(&target.swap, draw_window);
+    app.present(&target.swap);
+
+    let textures = app.lock_textures();
+    let atlas = textures.info(egui::TextureId::Managed(0)).unwrap();
+    assert_eq!(atlas.format, DXGI_FORMAT_R8G8B8A8_UNORM);
+    assert_eq!(atlas.idle_frames, 0);
+    assert!(textures.resource(atlas.id).is_some());
+    assert!(textures.textures().contains(&atlas));
+}
+
+#[test]
+fn test_warp_device_change() {
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+
+    app.init_default(&target.swap, draw_window);
+    app.set_clear_color(Some(Rgba::BLACK));
+    app.present(&target.swap);
+    let atlas = app.lock_textures().info(egui::TextureId::Managed(0)).unwrap();
+
+    // the game recreated its device and swapchain, egui doesn't send the atlas again
+    let recreated = WarpTarget::new(320, 240);
+    app.present(&recreated.swap);
+
+    let textures = app.lock_textures();
+    assert_eq!(textures.info(atlas.id).unwrap().size, atlas.size);
+    drop(textures);
+    assert!(app.take_evicted_textures().is_empty());
+
+    let frame = app.capture_frame(&recreated.swap).unwrap();
+    assert!(frame
+        .pixels
+        .chunks_exact(4)
+        .any(|px| px != Color32::BLACK.to_array()));
+}
+
+#[test]
+fn test_warp_paint_to() {
+    use windows::Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11Texture2D};
+
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+
+    unsafe {
+        let dev: ID3D11Device = target.swap.GetDevice().unwrap();
+        let backbuffer: ID3D11Texture2D = target.swap.GetBuffer(0).unwrap();
+        let mut view = None;
+        dev.CreateRenderTargetView(&backbuffer, None, Some(&mut view))
+            .unwrap();
+
+        let config = crate::Config::default().clear_color(Some(Rgba::BLACK));
+        app.init_with_device(&dev, target.hwnd, draw_window, (), config);
+        for _ in 0..3 {
+            app.paint_to(view.as_ref().unwrap(), (320, 240));
+        }
+    }
+
+    let frame = app.capture_frame(&target.swap).unwrap();
+    assert_eq!(
+        frame.pixels[frame.pixels.len() - 4..],
+        Color32::BLACK.to_array()
+    );
+    assert!(frame
+        .pixels
+        .chunks_exact(4)
+        .any(|px| px != Color32::BLACK.to_array()));
+}
+
+#[test]
+fn test_warp_capabilities() {
+    use windows::Win32::Graphics::Direct3D::D3D_FEATURE_LEVEL_11_0;
+
+    let target = WarpTarget::new(320, 240);
+    let app = DirectX11App::<()>::new();
+    app.init_default(&target.swap, draw_window);
+
+    let caps = app.capabilities();
+    assert_eq!(caps.backbuffer_format, Some(DXGI_FORMAT_R8G8B8A8_UNORM));
+    assert!(!caps.flip_model && !caps.d3d12);
+    assert!(caps.feature_level.0 >= D3D_FEATURE_LEVEL_11_0.0);
+    assert_eq!(caps.max_texture_side, 16384);
+    assert_eq!(caps.color_space, crate::ColorSpace::Srgb);
+    assert_eq!(caps.samples, 1);
+}
@@ -0,0 +1,90 @@
+use lock_api::{Mutex, MutexGuard, RawMutex};
+use std::{
+    ops::Deref,
+    panic::Location,
+    ptr::null_mut,
+    sync::atomic::{AtomicPtr, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Lock that remembers where it was last taken, the holder when it's locked.
+pub struct Watched<R, T> {
+    name: &'static str,
+    mutex: Mutex<R, T>,
+    holder: AtomicPtr<Location<'static>>,
+}
+
+impl<R: RawMutex, T> Watched<R, T> {
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Self {
+            name,
+            mutex: Mutex::const_new(R::INIT, value),
+            holder: AtomicPtr::new(null_mut()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Where the lock was taken last, if it went through [`Self::taken_at`].
+    pub fn holder(&self) -> String {
+        let holder = self.holder.load(Ordering::Relaxed);
+
+        match unsafe { holder.as_ref() } {
+            Some(location) => location.to_string(),
+            None => "unknown".into(),
+        }
+    }
+
+    /// Records `location` as the holder of the guard just taken.
+    pub fn taken_at<'a>(
+        &self,
+        guard: MutexGuard<'a, R, T>,
+        location: &'static Location<'static>,
+    ) -> MutexGuard<'a, R, T> {
+        self.holder
+            .store(location as *const _ as *mut _, Ordering::Relaxed);
+        guard
+    }
+}
+
+impl<R, T> Deref for Watched<R, T> {
+    type Target = Mutex<R, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mutex
+    }
+}
+
+/// Waits up to `timeout` for `mutex`. Raw mutexes in general have no timed locking, it's
+/// polled, yielding in between.
+pub fn lock_timeout<R: RawMutex, T>(
+    mutex: &Mutex<R, T>,
+    timeout: Duration,
+) -> Option<MutexGuard<'_, R, T>> {
+    let start = Instant::now();
+    loop {
+        if let Some(guard) = mutex.try_lock() {
+            return Some(guard);
+        }
+        if start.elapsed() >= timeout {
+            return None;
+        }
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn test_lock_timeout() {
+    let lock = Watched::<crate::DefaultRawMutex, _>::new("test", 0);
+    assert_eq!(lock.holder(), "unknown");
+
+    let guard = lock.taken_at(lock.lock(), Location::caller());
+    assert!(lock_timeout(&lock, Duration::from_millis(1)).is_none());
+    assert!(lock.holder().contains("watchdog.rs"));
+
+    drop(guard);
+    assert!(lock_timeout(&lock, Duration::from_millis(1)).is_some());
+}
@@ -4,20 +4,12 @@ use egui::{
     Modifiers, Pos2, Rect, RichText, ScrollArea, Slider, Stroke, TextureId, TextureOptions, Vec2,
     Widget,
 };
-use egui_d3d11::DirectX11App;
+use egui_d3d11::entry::{FnPresent, FnResizeBuffers};
 use faithe::{internal::alloc_console, pattern::Pattern};
 use std::{
     intrinsics::transmute,
     sync::{Arc, Once},
 };
-use windows::{
-    core::HRESULT,
-    Win32::{
-        Foundation::{HWND, LPARAM, LRESULT, WPARAM},
-        Graphics::Dxgi::{Common::DXGI_FORMAT, IDXGISwapChain},
-        UI::WindowsAndMessaging::{CallWindowProcW, SetWindowLongPtrA, GWLP_WNDPROC, WNDPROC},
-    },
-};
 
 #[no_mangle]
 extern "stdcall" fn DllMain(hinst: usize, reason: u32) -> i32 {
@@ -28,78 +20,7 @@ extern "stdcall" fn DllMain(hinst: usize, reason: u32) -> i32 {
     1
 }
 
-static mut APP: DirectX11App<i32> = DirectX11App::new();
-static mut OLD_WND_PROC: Option<WNDPROC> = None;
-
-type FnPresent = unsafe extern "stdcall" fn(IDXGISwapChain, u32, u32) -> HRESULT;
-static mut O_PRESENT: Option<FnPresent> = None;
-
-type FnResizeBuffers =
-    unsafe extern "stdcall" fn(IDXGISwapChain, u32, u32, u32, DXGI_FORMAT, u32) -> HRESULT;
-static mut O_RESIZE_BUFFERS: Option<FnResizeBuffers> = None;
-
-unsafe extern "stdcall" fn hk_present(
-    swap_chain: IDXGISwapChain,
-    sync_interval: u32,
-    flags: u32,
-) -> HRESULT {
-    static INIT: Once = Once::new();
-
-    INIT.call_once(|| {
-        APP.init_default(&swap_chain, ui);
-
-        let mut desc = unsafe { std::mem::zeroed() };
-
-        swap_chain.GetDesc(&mut desc).unwrap();
-
-        if desc.OutputWindow.0 == -1 {
-            panic!("Invalid window handle");
-        }
-
-        OLD_WND_PROC = Some(transmute(SetWindowLongPtrA(
-            desc.OutputWindow,
-            GWLP_WNDPROC,
-            hk_wnd_proc as usize as _,
-        )));
-    });
-
-    APP.present(&swap_chain);
-
-    O_PRESENT.as_ref().unwrap()(swap_chain, sync_interval, flags)
-}
-
-unsafe extern "stdcall" fn hk_resize_buffers(
-    swap_chain: IDXGISwapChain,
-    buffer_count: u32,
-    width: u32,
-    height: u32,
-    new_format: DXGI_FORMAT,
-    swap_chain_flags: u32,
-) -> HRESULT {
-    eprintln!("Resizing buffers");
-
-    APP.resize_buffers(&swap_chain, || {
-        O_RESIZE_BUFFERS.as_ref().unwrap()(
-            swap_chain.clone(),
-            buffer_count,
-            width,
-            height,
-            new_format,
-            swap_chain_flags,
-        )
-    })
-}
-
-unsafe extern "stdcall" fn hk_wnd_proc(
-    hwnd: HWND,
-    msg: u32,
-    wparam: WPARAM,
-    lparam: LPARAM,
-) -> LRESULT {
-    APP.wnd_proc(msg, wparam, lparam);
-
-    CallWindowProcW(OLD_WND_PROC.unwrap(), hwnd, msg, wparam, lparam)
-}
+egui_d3d11::hook_entry!(APP: i32, ui);
 
 static mut FRAME: i32 = 0;
 fn ui(ctx: &Context, i: &mut i32) {